@@ -0,0 +1,2 @@
+-- Drop Tasks Table
+DROP TABLE IF EXISTS ProjectTasks;
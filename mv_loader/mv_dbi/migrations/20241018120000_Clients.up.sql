@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Create Clients Table
+CREATE TABLE IF NOT EXISTS Clients (
+  ClientId    GUID NOT NULL UNIQUE,
+  ClientName  VARCHAR(255) NOT NULL UNIQUE,
+  PayRate     REAL,    -- The rate for projects that do not set their own
+  CONSTRAINT pk_Clients PRIMARY KEY(ClientId)
+);
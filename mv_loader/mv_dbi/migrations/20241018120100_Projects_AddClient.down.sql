@@ -0,0 +1,19 @@
+-- Unlink Projects from Clients. SQLite cannot drop a column that has a
+-- foreign key, so Projects is rebuilt without ClientId, with foreign keys
+-- off while the migration runs so ProjectTasks keeps its rows.
+CREATE TABLE Projects_New (
+  ProjectId       GUID NOT NULL UNIQUE,
+  ProjectName     VARCHAR(255) NOT NULL,
+  ProjectDate     DATE NOT NULL,
+  PayRate         REAL NOT NULL,
+  ProjectDuration INTEGER NOT NULL,    -- The duration in milliseconds
+  TotalPay        REAL NOT NULL,
+  CONSTRAINT pk_Projects PRIMARY KEY(ProjectID)
+);
+
+INSERT INTO Projects_New (ProjectId, ProjectName, ProjectDate, PayRate, ProjectDuration, TotalPay)
+SELECT ProjectId, ProjectName, ProjectDate, PayRate, ProjectDuration, TotalPay
+FROM Projects;
+
+DROP TABLE Projects;
+ALTER TABLE Projects_New RENAME TO Projects;
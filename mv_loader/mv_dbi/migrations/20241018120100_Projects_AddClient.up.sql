@@ -0,0 +1,4 @@
+-- Add migration script here
+-- Link Projects to Clients
+ALTER TABLE Projects
+ADD ClientId    GUID REFERENCES Clients(ClientId) ON DELETE SET NULL;
@@ -0,0 +1,19 @@
+-- Store money as REAL again and drop the project currency. Each money
+-- column is renamed, re-added as REAL, copied and dropped.
+ALTER TABLE Clients RENAME COLUMN PayRate TO PayRateText;
+ALTER TABLE Clients ADD PayRate REAL;    -- The rate for projects that do not set their own
+UPDATE Clients SET PayRate = CAST(PayRateText AS REAL)
+WHERE PayRateText IS NOT NULL;
+ALTER TABLE Clients DROP COLUMN PayRateText;
+
+ALTER TABLE Projects DROP COLUMN Currency;
+
+ALTER TABLE Projects RENAME COLUMN TotalPay TO TotalPayText;
+ALTER TABLE Projects ADD TotalPay REAL NOT NULL DEFAULT 0;
+UPDATE Projects SET TotalPay = CAST(TotalPayText AS REAL);
+ALTER TABLE Projects DROP COLUMN TotalPayText;
+
+ALTER TABLE Projects RENAME COLUMN PayRate TO PayRateText;
+ALTER TABLE Projects ADD PayRate REAL NOT NULL DEFAULT 0;
+UPDATE Projects SET PayRate = CAST(PayRateText AS REAL);
+ALTER TABLE Projects DROP COLUMN PayRateText;
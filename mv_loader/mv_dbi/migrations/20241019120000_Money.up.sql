@@ -0,0 +1,22 @@
+-- Add migration script here
+-- Store money as exact decimal text rather than REAL, and give each
+-- project a currency. SQLite cannot change a column's type, so each
+-- money column is renamed, re-added as TEXT, copied and dropped.
+-- Rates keep up to 4 places, TotalPay is rounded to cents.
+ALTER TABLE Projects RENAME COLUMN PayRate TO PayRateReal;
+ALTER TABLE Projects ADD PayRate TEXT NOT NULL DEFAULT '0';
+UPDATE Projects SET PayRate = CAST(ROUND(PayRateReal, 4) AS TEXT);
+ALTER TABLE Projects DROP COLUMN PayRateReal;
+
+ALTER TABLE Projects RENAME COLUMN TotalPay TO TotalPayReal;
+ALTER TABLE Projects ADD TotalPay TEXT NOT NULL DEFAULT '0.00';
+UPDATE Projects SET TotalPay = printf('%.2f', ROUND(TotalPayReal, 2));
+ALTER TABLE Projects DROP COLUMN TotalPayReal;
+
+ALTER TABLE Projects ADD Currency CHAR(3) NOT NULL DEFAULT 'USD';
+
+ALTER TABLE Clients RENAME COLUMN PayRate TO PayRateReal;
+ALTER TABLE Clients ADD PayRate TEXT;    -- The rate for projects that do not set their own
+UPDATE Clients SET PayRate = CAST(ROUND(PayRateReal, 4) AS TEXT)
+WHERE PayRateReal IS NOT NULL;
+ALTER TABLE Clients DROP COLUMN PayRateReal;
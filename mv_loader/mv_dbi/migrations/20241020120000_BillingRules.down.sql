@@ -0,0 +1,3 @@
+-- Drop TaskRates and BillingRules Tables
+DROP TABLE IF EXISTS TaskRates;
+DROP TABLE IF EXISTS BillingRules;
@@ -0,0 +1,28 @@
+-- Add migration script here
+-- Create BillingRules Table. A rule belongs to one project or one client;
+-- a project's own rule is used before its client's. Times are in minutes
+-- and 0 turns a rule off.
+CREATE TABLE IF NOT EXISTS BillingRules (
+  RuleId            GUID NOT NULL UNIQUE,
+  ProjectId         GUID UNIQUE REFERENCES Projects(ProjectId) ON DELETE CASCADE,
+  ClientId          GUID UNIQUE REFERENCES Clients(ClientId) ON DELETE CASCADE,
+  TimeIncrement     INTEGER NOT NULL DEFAULT 0,    -- Round each TaskTime to this
+  TaskIncrement     INTEGER NOT NULL DEFAULT 0,    -- Round each task's total to this
+  IncrementRounding VARCHAR(16) NOT NULL DEFAULT 'up',
+  DailyMinimum      INTEGER NOT NULL DEFAULT 0,
+  DailyOvertime     INTEGER NOT NULL DEFAULT 0,    -- Overtime starts after this much a day
+  WeeklyOvertime    INTEGER NOT NULL DEFAULT 0,    -- or this much in an ISO week
+  OvertimePercent   INTEGER NOT NULL DEFAULT 150,  -- The rate paid for overtime
+  CONSTRAINT pk_BillingRules PRIMARY KEY(RuleId),
+  CONSTRAINT ck_BillingRules_Owner CHECK ((ProjectId IS NULL) <> (ClientId IS NULL))
+);
+
+-- Create TaskRates Table, the rates a rule pays for tasks with a given name
+CREATE TABLE IF NOT EXISTS TaskRates (
+  TaskRateId  GUID NOT NULL UNIQUE,
+  RuleId      GUID NOT NULL REFERENCES BillingRules(RuleId) ON DELETE CASCADE,
+  TaskName    VARCHAR(255) NOT NULL,
+  PayRate     TEXT NOT NULL,
+  CONSTRAINT pk_TaskRates PRIMARY KEY(TaskRateId),
+  CONSTRAINT uq_TaskRates UNIQUE(RuleId, TaskName)
+);
@@ -0,0 +1,35 @@
+-- Drop Invoices and InvoiceLines Tables, and unlink TaskTimes from them.
+-- SQLite cannot drop a column that has a foreign key, so TaskTimes is
+-- rebuilt without InvoiceId, with foreign keys off while the migration
+-- runs.
+DROP TRIGGER IF EXISTS TaskTimes_BilledNoDelete;
+DROP TRIGGER IF EXISTS TaskTimes_BilledNoUpdate;
+DROP TRIGGER IF EXISTS InvoiceLines_NoDelete;
+DROP TRIGGER IF EXISTS InvoiceLines_NoUpdate;
+DROP TRIGGER IF EXISTS Invoices_NoDelete;
+DROP TRIGGER IF EXISTS Invoices_NoUpdate;
+
+CREATE TABLE TaskTimes_New (
+  TaskTimeId INTEGER NOT NULL UNIQUE,
+  TaskId     GUID NOT NULL,
+  StartTime  TIMESTAMP NOT NULL,
+  EndTime    TIMESTAMP NOT NULL,
+  CONSTRAINT pk_TaskTimes  PRIMARY KEY("TaskTimeId" AUTOINCREMENT),
+  CONSTRAINT fk_TimesTasks FOREIGN KEY(TaskID)
+    REFERENCES ProjectTasks(TaskId)  ON DELETE CASCADE
+);
+
+INSERT INTO TaskTimes_New (TaskTimeId, TaskId, StartTime, EndTime)
+SELECT TaskTimeId, TaskId, StartTime, EndTime
+FROM TaskTimes;
+
+-- Keep the AUTOINCREMENT counter, so the TaskTimeIds of deleted rows are
+-- not handed out again
+DELETE FROM sqlite_sequence WHERE name = 'TaskTimes_New';
+UPDATE sqlite_sequence SET name = 'TaskTimes_New' WHERE name = 'TaskTimes';
+
+DROP TABLE TaskTimes;
+ALTER TABLE TaskTimes_New RENAME TO TaskTimes;
+
+DROP TABLE IF EXISTS InvoiceLines;
+DROP TABLE IF EXISTS Invoices;
@@ -0,0 +1,67 @@
+-- Add migration script here
+-- Create Invoices and InvoiceLines Tables. An invoice is numbered in
+-- sequence and, like its lines, never changes once written.
+CREATE TABLE IF NOT EXISTS Invoices (
+  InvoiceId     GUID NOT NULL UNIQUE,
+  InvoiceNumber INTEGER NOT NULL UNIQUE,
+  ClientId      GUID NOT NULL REFERENCES Clients(ClientId),
+  IssueDate     DATE NOT NULL,
+  PeriodStart   DATE,
+  PeriodEnd     DATE,
+  Currency      CHAR(3) NOT NULL,
+  Total         TEXT NOT NULL,
+  CONSTRAINT pk_Invoices PRIMARY KEY(InvoiceId)
+);
+
+CREATE TABLE IF NOT EXISTS InvoiceLines (
+  InvoiceLineId GUID NOT NULL UNIQUE,
+  InvoiceId     GUID NOT NULL REFERENCES Invoices(InvoiceId),
+  LineNumber    INTEGER NOT NULL,
+  LineDate      DATE NOT NULL,
+  ProjectName   VARCHAR(255) NOT NULL,
+  TaskName      VARCHAR(255) NOT NULL,
+  Kind          VARCHAR(16) NOT NULL,
+  Minutes       INTEGER NOT NULL,
+  PayRate       TEXT NOT NULL,
+  Percent       INTEGER NOT NULL,
+  Amount        TEXT NOT NULL,
+  Description   TEXT NOT NULL,
+  CONSTRAINT pk_InvoiceLines PRIMARY KEY(InvoiceLineId),
+  CONSTRAINT uq_InvoiceLines UNIQUE(InvoiceId, LineNumber)
+);
+
+-- The invoice a TaskTime was billed on, NULL until it is billed
+ALTER TABLE TaskTimes
+ADD InvoiceId GUID REFERENCES Invoices(InvoiceId);
+
+CREATE TRIGGER IF NOT EXISTS Invoices_NoUpdate BEFORE UPDATE ON Invoices
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be changed');
+END;
+
+CREATE TRIGGER IF NOT EXISTS Invoices_NoDelete BEFORE DELETE ON Invoices
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be deleted');
+END;
+
+CREATE TRIGGER IF NOT EXISTS InvoiceLines_NoUpdate BEFORE UPDATE ON InvoiceLines
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be changed');
+END;
+
+CREATE TRIGGER IF NOT EXISTS InvoiceLines_NoDelete BEFORE DELETE ON InvoiceLines
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be deleted');
+END;
+
+CREATE TRIGGER IF NOT EXISTS TaskTimes_BilledNoUpdate BEFORE UPDATE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
+
+CREATE TRIGGER IF NOT EXISTS TaskTimes_BilledNoDelete BEFORE DELETE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
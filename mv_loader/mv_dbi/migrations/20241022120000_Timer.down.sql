@@ -0,0 +1,42 @@
+-- Make EndTime required again. A timer still running is stopped at the
+-- time it started. SQLite cannot add a NOT NULL rule to a column, so the
+-- table is rebuilt with it, with foreign keys off while the migration
+-- runs. Dropping the old table drops ix_TaskTimes_Running and the
+-- triggers, and the triggers are put back.
+UPDATE TaskTimes SET EndTime = StartTime
+WHERE EndTime IS NULL;
+
+CREATE TABLE TaskTimes_New (
+  TaskTimeId INTEGER NOT NULL UNIQUE,
+  TaskId     GUID NOT NULL,
+  StartTime  TIMESTAMP NOT NULL,
+  EndTime    TIMESTAMP NOT NULL,
+  InvoiceId  GUID REFERENCES Invoices(InvoiceId),    -- NULL until it is billed
+  CONSTRAINT pk_TaskTimes  PRIMARY KEY("TaskTimeId" AUTOINCREMENT),
+  CONSTRAINT fk_TimesTasks FOREIGN KEY(TaskID)
+    REFERENCES ProjectTasks(TaskId)  ON DELETE CASCADE
+);
+
+INSERT INTO TaskTimes_New (TaskTimeId, TaskId, StartTime, EndTime, InvoiceId)
+SELECT TaskTimeId, TaskId, StartTime, EndTime, InvoiceId
+FROM TaskTimes;
+
+-- Keep the AUTOINCREMENT counter, so the TaskTimeIds of deleted rows are
+-- not handed out again
+DELETE FROM sqlite_sequence WHERE name = 'TaskTimes_New';
+UPDATE sqlite_sequence SET name = 'TaskTimes_New' WHERE name = 'TaskTimes';
+
+DROP TABLE TaskTimes;
+ALTER TABLE TaskTimes_New RENAME TO TaskTimes;
+
+CREATE TRIGGER IF NOT EXISTS TaskTimes_BilledNoUpdate BEFORE UPDATE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
+
+CREATE TRIGGER IF NOT EXISTS TaskTimes_BilledNoDelete BEFORE DELETE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
@@ -0,0 +1,20 @@
+-- Add migration script here
+-- Let EndTime be NULL while a timer is running. SQLite cannot drop a NOT
+-- NULL rule, so the column is renamed, re-added, copied and dropped. The
+-- invoiced TaskTimes are unlocked while it is copied.
+DROP TRIGGER IF EXISTS TaskTimes_BilledNoUpdate;
+
+ALTER TABLE TaskTimes RENAME COLUMN EndTime TO EndTimeRequired;
+ALTER TABLE TaskTimes ADD EndTime TIMESTAMP;    -- NULL while the timer runs
+UPDATE TaskTimes SET EndTime = EndTimeRequired;
+ALTER TABLE TaskTimes DROP COLUMN EndTimeRequired;
+
+CREATE TRIGGER IF NOT EXISTS TaskTimes_BilledNoUpdate BEFORE UPDATE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
+
+-- Only one timer may run at a time
+CREATE UNIQUE INDEX IF NOT EXISTS ix_TaskTimes_Running
+ON TaskTimes ((EndTime IS NULL)) WHERE EndTime IS NULL;
@@ -0,0 +1,16 @@
+-- Add migration script here
+-- Create Tags Table. Names are kept trimmed and in lower case.
+CREATE TABLE IF NOT EXISTS Tags (
+  TagId    GUID NOT NULL UNIQUE,
+  TagName  VARCHAR(64) NOT NULL UNIQUE,
+  CONSTRAINT pk_Tags PRIMARY KEY(TagId)
+);
+
+-- Create TaskTags Table, the tags on each ProjectTask
+CREATE TABLE IF NOT EXISTS TaskTags (
+  TaskId  GUID NOT NULL REFERENCES ProjectTasks(TaskId) ON DELETE CASCADE,
+  TagId   GUID NOT NULL REFERENCES Tags(TagId) ON DELETE CASCADE,
+  CONSTRAINT pk_TaskTags PRIMARY KEY(TaskId, TagId)
+);
+
+CREATE INDEX IF NOT EXISTS ix_TaskTags_TagId ON TaskTags(TagId);
@@ -0,0 +1,12 @@
+-- Drop the full-text SearchIndex and the task notes
+DROP TRIGGER IF EXISTS ProjectTasks_SearchDelete;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchUpdate;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchInsert;
+DROP TRIGGER IF EXISTS Projects_SearchDelete;
+DROP TRIGGER IF EXISTS Projects_SearchUpdate;
+DROP TRIGGER IF EXISTS Projects_SearchInsert;
+
+DROP TABLE IF EXISTS SearchIndex;
+
+ALTER TABLE ProjectTasks
+DROP COLUMN Notes;
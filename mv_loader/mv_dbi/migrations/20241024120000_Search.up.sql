@@ -0,0 +1,54 @@
+-- Free-form notes on a task
+ALTER TABLE ProjectTasks
+ADD Notes TEXT;
+
+-- Create the full-text SearchIndex. Each Project has a row with its name
+-- and a NULL TaskId; each ProjectTask has a row with its name and notes.
+-- The triggers below keep it in step with Projects and ProjectTasks.
+CREATE VIRTUAL TABLE IF NOT EXISTS SearchIndex USING fts5(
+  ProjectId UNINDEXED,
+  TaskId UNINDEXED,
+  Name,
+  Notes,
+  tokenize = 'porter unicode61'
+);
+
+INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+SELECT ProjectId, NULL, ProjectName, NULL FROM Projects;
+
+INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+SELECT ProjectId, TaskId, TaskName, Notes FROM ProjectTasks;
+
+CREATE TRIGGER IF NOT EXISTS Projects_SearchInsert AFTER INSERT ON Projects
+BEGIN
+  INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+  VALUES (new.ProjectId, NULL, new.ProjectName, NULL);
+END;
+
+CREATE TRIGGER IF NOT EXISTS Projects_SearchUpdate AFTER UPDATE OF ProjectName ON Projects
+BEGIN
+  UPDATE SearchIndex SET Name = new.ProjectName
+  WHERE ProjectId = old.ProjectId AND TaskId IS NULL;
+END;
+
+CREATE TRIGGER IF NOT EXISTS Projects_SearchDelete AFTER DELETE ON Projects
+BEGIN
+  DELETE FROM SearchIndex WHERE ProjectId = old.ProjectId;
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchInsert AFTER INSERT ON ProjectTasks
+BEGIN
+  INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+  VALUES (new.ProjectId, new.TaskId, new.TaskName, new.Notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchUpdate AFTER UPDATE OF TaskName, Notes ON ProjectTasks
+BEGIN
+  UPDATE SearchIndex SET Name = new.TaskName, Notes = new.Notes
+  WHERE TaskId = old.TaskId;
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchDelete AFTER DELETE ON ProjectTasks
+BEGIN
+  DELETE FROM SearchIndex WHERE TaskId = old.TaskId;
+END;
@@ -0,0 +1,39 @@
+-- Let TaskDateTime be NULL again. The table is rebuilt without the NOT
+-- NULL rule, with foreign keys off while the migration runs so TaskTimes
+-- and TaskTags keep their rows.
+CREATE TABLE ProjectTasks_New (
+  TaskId       GUID NOT NULL,
+  ProjectId    GUID NOT NULL,
+  TaskName     VARCHAR(255) NOT NULL,
+  TaskDuration INTEGER NOT NULL,    -- The duration in milliseconds
+  TaskDateTime DATETIME,
+  Notes        TEXT,
+  CONSTRAINT pk_Tasks PRIMARY KEY(TaskID),
+  CONSTRAINT fk_TasksProjects FOREIGN KEY(ProjectID)
+    REFERENCES Projects(ProjectID) ON DELETE CASCADE
+);
+
+INSERT INTO ProjectTasks_New (TaskId, ProjectId, TaskName, TaskDuration, TaskDateTime, Notes)
+SELECT TaskId, ProjectId, TaskName, TaskDuration, TaskDateTime, Notes
+FROM ProjectTasks;
+
+DROP TABLE ProjectTasks;
+ALTER TABLE ProjectTasks_New RENAME TO ProjectTasks;
+
+-- Dropping the old table dropped its triggers
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchInsert AFTER INSERT ON ProjectTasks
+BEGIN
+  INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+  VALUES (new.ProjectId, new.TaskId, new.TaskName, new.Notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchUpdate AFTER UPDATE OF TaskName, Notes ON ProjectTasks
+BEGIN
+  UPDATE SearchIndex SET Name = new.TaskName, Notes = new.Notes
+  WHERE TaskId = old.TaskId;
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchDelete AFTER DELETE ON ProjectTasks
+BEGIN
+  DELETE FROM SearchIndex WHERE TaskId = old.TaskId;
+END;
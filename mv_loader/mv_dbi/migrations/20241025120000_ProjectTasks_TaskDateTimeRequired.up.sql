@@ -0,0 +1,57 @@
+-- TaskDateTime was added without a NOT NULL rule, though every task has
+-- one. A task saved without it is given its first TaskTime's start, or
+-- the start of its project's day. SQLite cannot add a NOT NULL rule to a
+-- column, so the table is rebuilt with it, with foreign keys off while
+-- the migration runs so TaskTimes and TaskTags keep their rows.
+UPDATE ProjectTasks
+SET TaskDateTime = COALESCE(
+  (SELECT MIN(StartTime) FROM TaskTimes WHERE TaskTimes.TaskId = ProjectTasks.TaskId),
+  (SELECT ProjectDate || ' 00:00:00' FROM Projects WHERE Projects.ProjectId = ProjectTasks.ProjectId))
+WHERE TaskDateTime IS NULL;
+
+-- A task with neither stops the migration, as copying it would fail
+CREATE TEMP TABLE TaskDateTimeMissing (TaskId GUID);
+CREATE TEMP TRIGGER TaskDateTimeMissing_Stop BEFORE INSERT ON TaskDateTimeMissing
+BEGIN
+  SELECT RAISE(ABORT, 'Some ProjectTasks have no TaskDateTime, no TaskTimes and no project to take one from. Give them a TaskDateTime and migrate again.');
+END;
+INSERT INTO TaskDateTimeMissing
+SELECT TaskId FROM ProjectTasks WHERE TaskDateTime IS NULL;
+DROP TABLE TaskDateTimeMissing;
+
+CREATE TABLE ProjectTasks_New (
+  TaskId       GUID NOT NULL,
+  ProjectId    GUID NOT NULL,
+  TaskName     VARCHAR(255) NOT NULL,
+  TaskDuration INTEGER NOT NULL,    -- The duration in milliseconds
+  TaskDateTime DATETIME NOT NULL,
+  Notes        TEXT,
+  CONSTRAINT pk_Tasks PRIMARY KEY(TaskID),
+  CONSTRAINT fk_TasksProjects FOREIGN KEY(ProjectID)
+    REFERENCES Projects(ProjectID) ON DELETE CASCADE
+);
+
+INSERT INTO ProjectTasks_New (TaskId, ProjectId, TaskName, TaskDuration, TaskDateTime, Notes)
+SELECT TaskId, ProjectId, TaskName, TaskDuration, TaskDateTime, Notes
+FROM ProjectTasks;
+
+DROP TABLE ProjectTasks;
+ALTER TABLE ProjectTasks_New RENAME TO ProjectTasks;
+
+-- Dropping the old table dropped its triggers
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchInsert AFTER INSERT ON ProjectTasks
+BEGIN
+  INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+  VALUES (new.ProjectId, new.TaskId, new.TaskName, new.Notes);
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchUpdate AFTER UPDATE OF TaskName, Notes ON ProjectTasks
+BEGIN
+  UPDATE SearchIndex SET Name = new.TaskName, Notes = new.Notes
+  WHERE TaskId = old.TaskId;
+END;
+
+CREATE TRIGGER IF NOT EXISTS ProjectTasks_SearchDelete AFTER DELETE ON ProjectTasks
+BEGIN
+  DELETE FROM SearchIndex WHERE TaskId = old.TaskId;
+END;
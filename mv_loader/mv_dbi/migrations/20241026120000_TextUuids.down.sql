@@ -0,0 +1,86 @@
+-- Store the keys as 16 byte BLOBs again
+PRAGMA defer_foreign_keys = ON;
+
+DROP TRIGGER Invoices_NoUpdate;
+DROP TRIGGER InvoiceLines_NoUpdate;
+DROP TRIGGER TaskTimes_BilledNoUpdate;
+
+UPDATE Clients
+SET ClientId = unhex(replace(ClientId, '-', ''))
+WHERE typeof(ClientId) = 'text';
+UPDATE Projects
+SET ProjectId = unhex(replace(ProjectId, '-', ''))
+WHERE typeof(ProjectId) = 'text';
+UPDATE Projects
+SET ClientId = unhex(replace(ClientId, '-', ''))
+WHERE typeof(ClientId) = 'text';
+UPDATE ProjectTasks
+SET TaskId = unhex(replace(TaskId, '-', ''))
+WHERE typeof(TaskId) = 'text';
+UPDATE ProjectTasks
+SET ProjectId = unhex(replace(ProjectId, '-', ''))
+WHERE typeof(ProjectId) = 'text';
+UPDATE Invoices
+SET InvoiceId = unhex(replace(InvoiceId, '-', ''))
+WHERE typeof(InvoiceId) = 'text';
+UPDATE Invoices
+SET ClientId = unhex(replace(ClientId, '-', ''))
+WHERE typeof(ClientId) = 'text';
+UPDATE InvoiceLines
+SET InvoiceLineId = unhex(replace(InvoiceLineId, '-', ''))
+WHERE typeof(InvoiceLineId) = 'text';
+UPDATE InvoiceLines
+SET InvoiceId = unhex(replace(InvoiceId, '-', ''))
+WHERE typeof(InvoiceId) = 'text';
+UPDATE TaskTimes
+SET TaskId = unhex(replace(TaskId, '-', ''))
+WHERE typeof(TaskId) = 'text';
+UPDATE TaskTimes
+SET InvoiceId = unhex(replace(InvoiceId, '-', ''))
+WHERE typeof(InvoiceId) = 'text';
+UPDATE BillingRules
+SET RuleId = unhex(replace(RuleId, '-', ''))
+WHERE typeof(RuleId) = 'text';
+UPDATE BillingRules
+SET ProjectId = unhex(replace(ProjectId, '-', ''))
+WHERE typeof(ProjectId) = 'text';
+UPDATE BillingRules
+SET ClientId = unhex(replace(ClientId, '-', ''))
+WHERE typeof(ClientId) = 'text';
+UPDATE TaskRates
+SET TaskRateId = unhex(replace(TaskRateId, '-', ''))
+WHERE typeof(TaskRateId) = 'text';
+UPDATE TaskRates
+SET RuleId = unhex(replace(RuleId, '-', ''))
+WHERE typeof(RuleId) = 'text';
+UPDATE Tags
+SET TagId = unhex(replace(TagId, '-', ''))
+WHERE typeof(TagId) = 'text';
+UPDATE TaskTags
+SET TaskId = unhex(replace(TaskId, '-', ''))
+WHERE typeof(TaskId) = 'text';
+UPDATE TaskTags
+SET TagId = unhex(replace(TagId, '-', ''))
+WHERE typeof(TagId) = 'text';
+UPDATE SearchIndex
+SET ProjectId = unhex(replace(ProjectId, '-', ''))
+WHERE typeof(ProjectId) = 'text';
+UPDATE SearchIndex
+SET TaskId = unhex(replace(TaskId, '-', ''))
+WHERE typeof(TaskId) = 'text';
+
+CREATE TRIGGER Invoices_NoUpdate BEFORE UPDATE ON Invoices
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be changed');
+END;
+
+CREATE TRIGGER InvoiceLines_NoUpdate BEFORE UPDATE ON InvoiceLines
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be changed');
+END;
+
+CREATE TRIGGER TaskTimes_BilledNoUpdate BEFORE UPDATE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
@@ -0,0 +1,131 @@
+-- Store every key as hyphenated text instead of a 16 byte BLOB, so the
+-- keys can be read and typed in the sqlite3 shell. The checks on foreign
+-- keys wait for the commit, when parents and children match again, and
+-- the triggers that keep invoices from changing are put back afterwards.
+PRAGMA defer_foreign_keys = ON;
+
+DROP TRIGGER Invoices_NoUpdate;
+DROP TRIGGER InvoiceLines_NoUpdate;
+DROP TRIGGER TaskTimes_BilledNoUpdate;
+
+UPDATE Clients
+SET ClientId = lower(substr(hex(ClientId), 1, 8) || '-' || substr(hex(ClientId), 9, 4)
+  || '-' || substr(hex(ClientId), 13, 4) || '-' || substr(hex(ClientId), 17, 4)
+  || '-' || substr(hex(ClientId), 21, 12))
+WHERE typeof(ClientId) = 'blob';
+UPDATE Projects
+SET ProjectId = lower(substr(hex(ProjectId), 1, 8) || '-' || substr(hex(ProjectId), 9, 4)
+  || '-' || substr(hex(ProjectId), 13, 4) || '-' || substr(hex(ProjectId), 17, 4)
+  || '-' || substr(hex(ProjectId), 21, 12))
+WHERE typeof(ProjectId) = 'blob';
+UPDATE Projects
+SET ClientId = lower(substr(hex(ClientId), 1, 8) || '-' || substr(hex(ClientId), 9, 4)
+  || '-' || substr(hex(ClientId), 13, 4) || '-' || substr(hex(ClientId), 17, 4)
+  || '-' || substr(hex(ClientId), 21, 12))
+WHERE typeof(ClientId) = 'blob';
+UPDATE ProjectTasks
+SET TaskId = lower(substr(hex(TaskId), 1, 8) || '-' || substr(hex(TaskId), 9, 4)
+  || '-' || substr(hex(TaskId), 13, 4) || '-' || substr(hex(TaskId), 17, 4)
+  || '-' || substr(hex(TaskId), 21, 12))
+WHERE typeof(TaskId) = 'blob';
+UPDATE ProjectTasks
+SET ProjectId = lower(substr(hex(ProjectId), 1, 8) || '-' || substr(hex(ProjectId), 9, 4)
+  || '-' || substr(hex(ProjectId), 13, 4) || '-' || substr(hex(ProjectId), 17, 4)
+  || '-' || substr(hex(ProjectId), 21, 12))
+WHERE typeof(ProjectId) = 'blob';
+UPDATE Invoices
+SET InvoiceId = lower(substr(hex(InvoiceId), 1, 8) || '-' || substr(hex(InvoiceId), 9, 4)
+  || '-' || substr(hex(InvoiceId), 13, 4) || '-' || substr(hex(InvoiceId), 17, 4)
+  || '-' || substr(hex(InvoiceId), 21, 12))
+WHERE typeof(InvoiceId) = 'blob';
+UPDATE Invoices
+SET ClientId = lower(substr(hex(ClientId), 1, 8) || '-' || substr(hex(ClientId), 9, 4)
+  || '-' || substr(hex(ClientId), 13, 4) || '-' || substr(hex(ClientId), 17, 4)
+  || '-' || substr(hex(ClientId), 21, 12))
+WHERE typeof(ClientId) = 'blob';
+UPDATE InvoiceLines
+SET InvoiceLineId = lower(substr(hex(InvoiceLineId), 1, 8) || '-' || substr(hex(InvoiceLineId), 9, 4)
+  || '-' || substr(hex(InvoiceLineId), 13, 4) || '-' || substr(hex(InvoiceLineId), 17, 4)
+  || '-' || substr(hex(InvoiceLineId), 21, 12))
+WHERE typeof(InvoiceLineId) = 'blob';
+UPDATE InvoiceLines
+SET InvoiceId = lower(substr(hex(InvoiceId), 1, 8) || '-' || substr(hex(InvoiceId), 9, 4)
+  || '-' || substr(hex(InvoiceId), 13, 4) || '-' || substr(hex(InvoiceId), 17, 4)
+  || '-' || substr(hex(InvoiceId), 21, 12))
+WHERE typeof(InvoiceId) = 'blob';
+UPDATE TaskTimes
+SET TaskId = lower(substr(hex(TaskId), 1, 8) || '-' || substr(hex(TaskId), 9, 4)
+  || '-' || substr(hex(TaskId), 13, 4) || '-' || substr(hex(TaskId), 17, 4)
+  || '-' || substr(hex(TaskId), 21, 12))
+WHERE typeof(TaskId) = 'blob';
+UPDATE TaskTimes
+SET InvoiceId = lower(substr(hex(InvoiceId), 1, 8) || '-' || substr(hex(InvoiceId), 9, 4)
+  || '-' || substr(hex(InvoiceId), 13, 4) || '-' || substr(hex(InvoiceId), 17, 4)
+  || '-' || substr(hex(InvoiceId), 21, 12))
+WHERE typeof(InvoiceId) = 'blob';
+UPDATE BillingRules
+SET RuleId = lower(substr(hex(RuleId), 1, 8) || '-' || substr(hex(RuleId), 9, 4)
+  || '-' || substr(hex(RuleId), 13, 4) || '-' || substr(hex(RuleId), 17, 4)
+  || '-' || substr(hex(RuleId), 21, 12))
+WHERE typeof(RuleId) = 'blob';
+UPDATE BillingRules
+SET ProjectId = lower(substr(hex(ProjectId), 1, 8) || '-' || substr(hex(ProjectId), 9, 4)
+  || '-' || substr(hex(ProjectId), 13, 4) || '-' || substr(hex(ProjectId), 17, 4)
+  || '-' || substr(hex(ProjectId), 21, 12))
+WHERE typeof(ProjectId) = 'blob';
+UPDATE BillingRules
+SET ClientId = lower(substr(hex(ClientId), 1, 8) || '-' || substr(hex(ClientId), 9, 4)
+  || '-' || substr(hex(ClientId), 13, 4) || '-' || substr(hex(ClientId), 17, 4)
+  || '-' || substr(hex(ClientId), 21, 12))
+WHERE typeof(ClientId) = 'blob';
+UPDATE TaskRates
+SET TaskRateId = lower(substr(hex(TaskRateId), 1, 8) || '-' || substr(hex(TaskRateId), 9, 4)
+  || '-' || substr(hex(TaskRateId), 13, 4) || '-' || substr(hex(TaskRateId), 17, 4)
+  || '-' || substr(hex(TaskRateId), 21, 12))
+WHERE typeof(TaskRateId) = 'blob';
+UPDATE TaskRates
+SET RuleId = lower(substr(hex(RuleId), 1, 8) || '-' || substr(hex(RuleId), 9, 4)
+  || '-' || substr(hex(RuleId), 13, 4) || '-' || substr(hex(RuleId), 17, 4)
+  || '-' || substr(hex(RuleId), 21, 12))
+WHERE typeof(RuleId) = 'blob';
+UPDATE Tags
+SET TagId = lower(substr(hex(TagId), 1, 8) || '-' || substr(hex(TagId), 9, 4)
+  || '-' || substr(hex(TagId), 13, 4) || '-' || substr(hex(TagId), 17, 4)
+  || '-' || substr(hex(TagId), 21, 12))
+WHERE typeof(TagId) = 'blob';
+UPDATE TaskTags
+SET TaskId = lower(substr(hex(TaskId), 1, 8) || '-' || substr(hex(TaskId), 9, 4)
+  || '-' || substr(hex(TaskId), 13, 4) || '-' || substr(hex(TaskId), 17, 4)
+  || '-' || substr(hex(TaskId), 21, 12))
+WHERE typeof(TaskId) = 'blob';
+UPDATE TaskTags
+SET TagId = lower(substr(hex(TagId), 1, 8) || '-' || substr(hex(TagId), 9, 4)
+  || '-' || substr(hex(TagId), 13, 4) || '-' || substr(hex(TagId), 17, 4)
+  || '-' || substr(hex(TagId), 21, 12))
+WHERE typeof(TagId) = 'blob';
+UPDATE SearchIndex
+SET ProjectId = lower(substr(hex(ProjectId), 1, 8) || '-' || substr(hex(ProjectId), 9, 4)
+  || '-' || substr(hex(ProjectId), 13, 4) || '-' || substr(hex(ProjectId), 17, 4)
+  || '-' || substr(hex(ProjectId), 21, 12))
+WHERE typeof(ProjectId) = 'blob';
+UPDATE SearchIndex
+SET TaskId = lower(substr(hex(TaskId), 1, 8) || '-' || substr(hex(TaskId), 9, 4)
+  || '-' || substr(hex(TaskId), 13, 4) || '-' || substr(hex(TaskId), 17, 4)
+  || '-' || substr(hex(TaskId), 21, 12))
+WHERE typeof(TaskId) = 'blob';
+
+CREATE TRIGGER Invoices_NoUpdate BEFORE UPDATE ON Invoices
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be changed');
+END;
+
+CREATE TRIGGER InvoiceLines_NoUpdate BEFORE UPDATE ON InvoiceLines
+BEGIN
+  SELECT RAISE(ABORT, 'an invoice cannot be changed');
+END;
+
+CREATE TRIGGER TaskTimes_BilledNoUpdate BEFORE UPDATE ON TaskTimes
+WHEN OLD.InvoiceId IS NOT NULL
+BEGIN
+  SELECT RAISE(ABORT, 'it has been invoiced');
+END;
@@ -0,0 +1,4 @@
+-- Drop the views for querying by hand
+DROP VIEW IF EXISTS v_TaskTimes;
+DROP VIEW IF EXISTS v_ProjectTasks;
+DROP VIEW IF EXISTS v_Projects;
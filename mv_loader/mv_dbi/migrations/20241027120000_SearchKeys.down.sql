@@ -0,0 +1,43 @@
+-- Go back to finding SearchIndex rows by their ProjectId and TaskId
+DROP TRIGGER IF EXISTS ProjectTasks_SearchDelete;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchUpdate;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchInsert;
+DROP TRIGGER IF EXISTS Projects_SearchDelete;
+DROP TRIGGER IF EXISTS Projects_SearchUpdate;
+DROP TRIGGER IF EXISTS Projects_SearchInsert;
+
+CREATE TRIGGER Projects_SearchInsert AFTER INSERT ON Projects
+BEGIN
+  INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+  VALUES (new.ProjectId, NULL, new.ProjectName, NULL);
+END;
+
+CREATE TRIGGER Projects_SearchUpdate AFTER UPDATE OF ProjectName ON Projects
+BEGIN
+  UPDATE SearchIndex SET Name = new.ProjectName
+  WHERE ProjectId = old.ProjectId AND TaskId IS NULL;
+END;
+
+CREATE TRIGGER Projects_SearchDelete AFTER DELETE ON Projects
+BEGIN
+  DELETE FROM SearchIndex WHERE ProjectId = old.ProjectId;
+END;
+
+CREATE TRIGGER ProjectTasks_SearchInsert AFTER INSERT ON ProjectTasks
+BEGIN
+  INSERT INTO SearchIndex (ProjectId, TaskId, Name, Notes)
+  VALUES (new.ProjectId, new.TaskId, new.TaskName, new.Notes);
+END;
+
+CREATE TRIGGER ProjectTasks_SearchUpdate AFTER UPDATE OF TaskName, Notes ON ProjectTasks
+BEGIN
+  UPDATE SearchIndex SET Name = new.TaskName, Notes = new.Notes
+  WHERE TaskId = old.TaskId;
+END;
+
+CREATE TRIGGER ProjectTasks_SearchDelete AFTER DELETE ON ProjectTasks
+BEGIN
+  DELETE FROM SearchIndex WHERE TaskId = old.TaskId;
+END;
+
+DROP TABLE IF EXISTS SearchKeys;
@@ -0,0 +1,64 @@
+-- Find SearchIndex rows by rowid. ProjectId and TaskId are UNINDEXED
+-- columns of the FTS5 table, so a trigger that looks rows up by them reads
+-- the whole index. SearchKeys holds each row's rowid under its keys
+-- instead. The rowids of Projects and ProjectTasks are not used, as VACUUM
+-- may renumber them.
+CREATE TABLE IF NOT EXISTS SearchKeys (
+  SearchRowid INTEGER PRIMARY KEY,
+  ProjectId   NOT NULL,
+  TaskId
+);
+
+CREATE INDEX IF NOT EXISTS ix_SearchKeys_ProjectId ON SearchKeys(ProjectId, TaskId);
+CREATE INDEX IF NOT EXISTS ix_SearchKeys_TaskId ON SearchKeys(TaskId);
+
+INSERT INTO SearchKeys (SearchRowid, ProjectId, TaskId)
+SELECT rowid, ProjectId, TaskId FROM SearchIndex;
+
+DROP TRIGGER IF EXISTS Projects_SearchInsert;
+DROP TRIGGER IF EXISTS Projects_SearchUpdate;
+DROP TRIGGER IF EXISTS Projects_SearchDelete;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchInsert;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchUpdate;
+DROP TRIGGER IF EXISTS ProjectTasks_SearchDelete;
+
+CREATE TRIGGER Projects_SearchInsert AFTER INSERT ON Projects
+BEGIN
+  INSERT INTO SearchKeys (ProjectId, TaskId) VALUES (new.ProjectId, NULL);
+  INSERT INTO SearchIndex (rowid, ProjectId, TaskId, Name, Notes)
+  VALUES (last_insert_rowid(), new.ProjectId, NULL, new.ProjectName, NULL);
+END;
+
+CREATE TRIGGER Projects_SearchUpdate AFTER UPDATE OF ProjectName ON Projects
+BEGIN
+  UPDATE SearchIndex SET Name = new.ProjectName
+  WHERE rowid = (SELECT SearchRowid FROM SearchKeys
+    WHERE ProjectId = old.ProjectId AND TaskId IS NULL);
+END;
+
+CREATE TRIGGER Projects_SearchDelete AFTER DELETE ON Projects
+BEGIN
+  DELETE FROM SearchIndex WHERE rowid IN (SELECT SearchRowid FROM SearchKeys
+    WHERE ProjectId = old.ProjectId);
+  DELETE FROM SearchKeys WHERE ProjectId = old.ProjectId;
+END;
+
+CREATE TRIGGER ProjectTasks_SearchInsert AFTER INSERT ON ProjectTasks
+BEGIN
+  INSERT INTO SearchKeys (ProjectId, TaskId) VALUES (new.ProjectId, new.TaskId);
+  INSERT INTO SearchIndex (rowid, ProjectId, TaskId, Name, Notes)
+  VALUES (last_insert_rowid(), new.ProjectId, new.TaskId, new.TaskName, new.Notes);
+END;
+
+CREATE TRIGGER ProjectTasks_SearchUpdate AFTER UPDATE OF TaskName, Notes ON ProjectTasks
+BEGIN
+  UPDATE SearchIndex SET Name = new.TaskName, Notes = new.Notes
+  WHERE rowid = (SELECT SearchRowid FROM SearchKeys WHERE TaskId = old.TaskId);
+END;
+
+CREATE TRIGGER ProjectTasks_SearchDelete AFTER DELETE ON ProjectTasks
+BEGIN
+  DELETE FROM SearchIndex WHERE rowid IN (SELECT SearchRowid FROM SearchKeys
+    WHERE TaskId = old.TaskId);
+  DELETE FROM SearchKeys WHERE TaskId = old.TaskId;
+END;
@@ -0,0 +1,53 @@
+-- Put back the views that show keys as they are stored
+DROP VIEW IF EXISTS v_TaskTimes;
+DROP VIEW IF EXISTS v_ProjectTasks;
+DROP VIEW IF EXISTS v_Projects;
+
+CREATE VIEW IF NOT EXISTS v_Projects AS
+SELECT
+  p.ProjectId,
+  p.ProjectName,
+  p.ProjectDate,
+  printf('%02d:%02d:%02d', p.ProjectDuration / 3600000,
+    p.ProjectDuration / 60000 % 60, p.ProjectDuration / 1000 % 60) AS Duration,
+  p.PayRate,
+  p.TotalPay,
+  p.Currency,
+  p.ClientId,
+  c.ClientName
+FROM Projects p
+LEFT JOIN Clients c ON c.ClientId = p.ClientId;
+
+CREATE VIEW IF NOT EXISTS v_ProjectTasks AS
+SELECT
+  t.TaskId,
+  t.ProjectId,
+  p.ProjectName,
+  t.TaskName,
+  t.TaskDateTime,
+  printf('%02d:%02d:%02d', t.TaskDuration / 3600000,
+    t.TaskDuration / 60000 % 60, t.TaskDuration / 1000 % 60) AS Duration,
+  t.Notes
+FROM ProjectTasks t
+JOIN Projects p ON p.ProjectId = t.ProjectId;
+
+-- A running timer has no EndTime, so no Duration
+CREATE VIEW IF NOT EXISTS v_TaskTimes AS
+SELECT
+  TaskTimeId,
+  TaskId,
+  ProjectName,
+  TaskName,
+  StartTime,
+  EndTime,
+  CASE WHEN Seconds IS NOT NULL
+    THEN printf('%02d:%02d:%02d', Seconds / 3600, Seconds / 60 % 60, Seconds % 60)
+  END AS Duration,
+  InvoiceId
+FROM (
+  SELECT tt.*, p.ProjectName, t.TaskName,
+    CAST(round((julianday(tt.EndTime) - julianday(tt.StartTime)) * 86400) AS INTEGER) AS Seconds
+  FROM TaskTimes tt
+  JOIN ProjectTasks t ON t.TaskId = tt.TaskId
+  JOIN Projects p ON p.ProjectId = t.ProjectId
+);
@@ -0,0 +1,72 @@
+-- Show keys hyphenated in the views whether the database keeps them as
+-- text or, with uuid_storage = "blob", as 16 byte BLOBs.
+DROP VIEW IF EXISTS v_TaskTimes;
+DROP VIEW IF EXISTS v_ProjectTasks;
+DROP VIEW IF EXISTS v_Projects;
+
+CREATE VIEW v_Projects AS
+SELECT
+  CASE typeof(p.ProjectId) WHEN 'blob' THEN lower(substr(hex(p.ProjectId), 1, 8) || '-'
+    || substr(hex(p.ProjectId), 9, 4) || '-' || substr(hex(p.ProjectId), 13, 4) || '-'
+    || substr(hex(p.ProjectId), 17, 4) || '-' || substr(hex(p.ProjectId), 21, 12))
+    ELSE p.ProjectId END AS ProjectId,
+  p.ProjectName,
+  p.ProjectDate,
+  printf('%02d:%02d:%02d', p.ProjectDuration / 3600000,
+    p.ProjectDuration / 60000 % 60, p.ProjectDuration / 1000 % 60) AS Duration,
+  p.PayRate,
+  p.TotalPay,
+  p.Currency,
+  CASE typeof(p.ClientId) WHEN 'blob' THEN lower(substr(hex(p.ClientId), 1, 8) || '-'
+    || substr(hex(p.ClientId), 9, 4) || '-' || substr(hex(p.ClientId), 13, 4) || '-'
+    || substr(hex(p.ClientId), 17, 4) || '-' || substr(hex(p.ClientId), 21, 12))
+    ELSE p.ClientId END AS ClientId,
+  c.ClientName
+FROM Projects p
+LEFT JOIN Clients c ON c.ClientId = p.ClientId;
+
+CREATE VIEW v_ProjectTasks AS
+SELECT
+  CASE typeof(t.TaskId) WHEN 'blob' THEN lower(substr(hex(t.TaskId), 1, 8) || '-'
+    || substr(hex(t.TaskId), 9, 4) || '-' || substr(hex(t.TaskId), 13, 4) || '-'
+    || substr(hex(t.TaskId), 17, 4) || '-' || substr(hex(t.TaskId), 21, 12))
+    ELSE t.TaskId END AS TaskId,
+  CASE typeof(t.ProjectId) WHEN 'blob' THEN lower(substr(hex(t.ProjectId), 1, 8) || '-'
+    || substr(hex(t.ProjectId), 9, 4) || '-' || substr(hex(t.ProjectId), 13, 4) || '-'
+    || substr(hex(t.ProjectId), 17, 4) || '-' || substr(hex(t.ProjectId), 21, 12))
+    ELSE t.ProjectId END AS ProjectId,
+  p.ProjectName,
+  t.TaskName,
+  t.TaskDateTime,
+  printf('%02d:%02d:%02d', t.TaskDuration / 3600000,
+    t.TaskDuration / 60000 % 60, t.TaskDuration / 1000 % 60) AS Duration,
+  t.Notes
+FROM ProjectTasks t
+JOIN Projects p ON p.ProjectId = t.ProjectId;
+
+-- A running timer has no EndTime, so no Duration
+CREATE VIEW v_TaskTimes AS
+SELECT
+  TaskTimeId,
+  CASE typeof(TaskId) WHEN 'blob' THEN lower(substr(hex(TaskId), 1, 8) || '-'
+    || substr(hex(TaskId), 9, 4) || '-' || substr(hex(TaskId), 13, 4) || '-'
+    || substr(hex(TaskId), 17, 4) || '-' || substr(hex(TaskId), 21, 12))
+    ELSE TaskId END AS TaskId,
+  ProjectName,
+  TaskName,
+  StartTime,
+  EndTime,
+  CASE WHEN Seconds IS NOT NULL
+    THEN printf('%02d:%02d:%02d', Seconds / 3600, Seconds / 60 % 60, Seconds % 60)
+  END AS Duration,
+  CASE typeof(InvoiceId) WHEN 'blob' THEN lower(substr(hex(InvoiceId), 1, 8) || '-'
+    || substr(hex(InvoiceId), 9, 4) || '-' || substr(hex(InvoiceId), 13, 4) || '-'
+    || substr(hex(InvoiceId), 17, 4) || '-' || substr(hex(InvoiceId), 21, 12))
+    ELSE InvoiceId END AS InvoiceId
+FROM (
+  SELECT tt.*, p.ProjectName, t.TaskName,
+    CAST(round((julianday(tt.EndTime) - julianday(tt.StartTime)) * 86400) AS INTEGER) AS Seconds
+  FROM TaskTimes tt
+  JOIN ProjectTasks t ON t.TaskId = tt.TaskId
+  JOIN Projects p ON p.ProjectId = t.ProjectId
+);
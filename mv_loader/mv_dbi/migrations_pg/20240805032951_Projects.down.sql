@@ -0,0 +1,2 @@
+-- Drop Projects Table
+DROP TABLE IF EXISTS Projects;
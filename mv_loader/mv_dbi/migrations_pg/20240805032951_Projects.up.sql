@@ -0,0 +1,10 @@
+-- Create Projects Table
+CREATE TABLE IF NOT EXISTS Projects (
+  "ProjectId"       UUID NOT NULL UNIQUE,
+  "ProjectName"     VARCHAR(255) NOT NULL,
+  "ProjectDate"     DATE NOT NULL,
+  "PayRate"         DOUBLE PRECISION NOT NULL,
+  "ProjectDuration" BIGINT NOT NULL,    -- The duration in milliseconds
+  "TotalPay"        DOUBLE PRECISION NOT NULL,
+  CONSTRAINT pk_Projects PRIMARY KEY("ProjectId")
+);
@@ -0,0 +1,10 @@
+-- Create Tasks Table
+CREATE TABLE IF NOT EXISTS ProjectTasks (
+  "TaskId"       UUID NOT NULL,
+  "ProjectId"    UUID NOT NULL,
+  "TaskName"     VARCHAR(255) NOT NULL,
+  "TaskDuration" BIGINT NOT NULL,    -- The duration in milliseconds
+  CONSTRAINT pk_Tasks PRIMARY KEY("TaskId"),
+  CONSTRAINT fk_TasksProjects FOREIGN KEY("ProjectId")
+    REFERENCES Projects("ProjectId") ON DELETE CASCADE
+);
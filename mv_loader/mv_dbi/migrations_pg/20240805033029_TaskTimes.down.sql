@@ -0,0 +1,2 @@
+-- Drop TaskTimes Table
+DROP TABLE IF EXISTS TaskTimes;
@@ -0,0 +1,10 @@
+-- Create TaskTimes Table
+CREATE TABLE IF NOT EXISTS TaskTimes (
+  "TaskTimeId" BIGINT GENERATED BY DEFAULT AS IDENTITY,
+  "TaskId"     UUID NOT NULL,
+  "StartTime"  TIMESTAMP NOT NULL,
+  "EndTime"    TIMESTAMP NOT NULL,
+  CONSTRAINT pk_TaskTimes  PRIMARY KEY("TaskTimeId"),
+  CONSTRAINT fk_TimesTasks FOREIGN KEY("TaskId")
+    REFERENCES ProjectTasks("TaskId")  ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Drop the task start time
+ALTER TABLE ProjectTasks
+DROP COLUMN "TaskDateTime";
@@ -0,0 +1,3 @@
+-- Add the task start time
+ALTER TABLE ProjectTasks
+ADD "TaskDateTime"    TIMESTAMP;
@@ -0,0 +1,2 @@
+-- Drop Clients Table
+DROP TABLE IF EXISTS Clients;
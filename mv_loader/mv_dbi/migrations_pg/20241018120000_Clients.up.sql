@@ -0,0 +1,7 @@
+-- Create Clients Table
+CREATE TABLE IF NOT EXISTS Clients (
+  "ClientId"    UUID NOT NULL UNIQUE,
+  "ClientName"  VARCHAR(255) NOT NULL UNIQUE,
+  "PayRate"     DOUBLE PRECISION,    -- The rate for projects that do not set their own
+  CONSTRAINT pk_Clients PRIMARY KEY("ClientId")
+);
@@ -0,0 +1,3 @@
+-- Unlink Projects from Clients
+ALTER TABLE Projects
+DROP COLUMN "ClientId";
@@ -0,0 +1,3 @@
+-- Link Projects to Clients
+ALTER TABLE Projects
+ADD "ClientId"    UUID REFERENCES Clients("ClientId") ON DELETE SET NULL;
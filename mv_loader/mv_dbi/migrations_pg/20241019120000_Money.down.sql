@@ -0,0 +1,8 @@
+-- Store money as DOUBLE PRECISION again and drop the project currency
+ALTER TABLE Clients
+ALTER COLUMN "PayRate" TYPE DOUBLE PRECISION USING "PayRate"::DOUBLE PRECISION;
+
+ALTER TABLE Projects
+DROP COLUMN "Currency",
+ALTER COLUMN "TotalPay" TYPE DOUBLE PRECISION USING "TotalPay"::DOUBLE PRECISION,
+ALTER COLUMN "PayRate" TYPE DOUBLE PRECISION USING "PayRate"::DOUBLE PRECISION;
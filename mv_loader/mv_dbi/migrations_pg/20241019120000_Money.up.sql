@@ -0,0 +1,10 @@
+-- Store money as exact decimals rather than DOUBLE PRECISION, and give
+-- each project a currency. Rates keep up to 4 places, TotalPay is
+-- rounded to cents.
+ALTER TABLE Projects
+ALTER COLUMN "PayRate" TYPE NUMERIC(12,4) USING ROUND("PayRate"::NUMERIC, 4),
+ALTER COLUMN "TotalPay" TYPE NUMERIC(14,2) USING ROUND("TotalPay"::NUMERIC, 2),
+ADD "Currency"    CHAR(3) NOT NULL DEFAULT 'USD';
+
+ALTER TABLE Clients
+ALTER COLUMN "PayRate" TYPE NUMERIC(12,4) USING ROUND("PayRate"::NUMERIC, 4);
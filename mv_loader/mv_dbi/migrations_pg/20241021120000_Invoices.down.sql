@@ -0,0 +1,11 @@
+-- Drop Invoices and InvoiceLines Tables, and unlink TaskTimes from them
+DROP TRIGGER IF EXISTS TaskTimes_BilledNoChange ON TaskTimes;
+DROP TRIGGER IF EXISTS InvoiceLines_NoChange ON InvoiceLines;
+DROP TRIGGER IF EXISTS Invoices_NoChange ON Invoices;
+DROP FUNCTION IF EXISTS mv_reject_change();
+
+ALTER TABLE TaskTimes
+DROP COLUMN "InvoiceId";
+
+DROP TABLE IF EXISTS InvoiceLines;
+DROP TABLE IF EXISTS Invoices;
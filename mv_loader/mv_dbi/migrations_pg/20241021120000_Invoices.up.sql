@@ -0,0 +1,52 @@
+-- Create Invoices and InvoiceLines Tables. An invoice is numbered in
+-- sequence and, like its lines, never changes once written.
+CREATE TABLE IF NOT EXISTS Invoices (
+  "InvoiceId"     UUID NOT NULL UNIQUE,
+  "InvoiceNumber" BIGINT NOT NULL UNIQUE,
+  "ClientId"      UUID NOT NULL REFERENCES Clients("ClientId"),
+  "IssueDate"     DATE NOT NULL,
+  "PeriodStart"   DATE,
+  "PeriodEnd"     DATE,
+  "Currency"      CHAR(3) NOT NULL,
+  "Total"         NUMERIC(14,2) NOT NULL,
+  CONSTRAINT pk_Invoices PRIMARY KEY("InvoiceId")
+);
+
+CREATE TABLE IF NOT EXISTS InvoiceLines (
+  "InvoiceLineId" UUID NOT NULL UNIQUE,
+  "InvoiceId"     UUID NOT NULL REFERENCES Invoices("InvoiceId"),
+  "LineNumber"    INTEGER NOT NULL,
+  "LineDate"      DATE NOT NULL,
+  "ProjectName"   VARCHAR(255) NOT NULL,
+  "TaskName"      VARCHAR(255) NOT NULL,
+  "Kind"          VARCHAR(16) NOT NULL,
+  "Minutes"       BIGINT NOT NULL,
+  "PayRate"       NUMERIC(12,4) NOT NULL,
+  "Percent"       INTEGER NOT NULL,
+  "Amount"        NUMERIC(14,2) NOT NULL,
+  "Description"   TEXT NOT NULL,
+  CONSTRAINT pk_InvoiceLines PRIMARY KEY("InvoiceLineId"),
+  CONSTRAINT uq_InvoiceLines UNIQUE("InvoiceId", "LineNumber")
+);
+
+-- The invoice a TaskTime was billed on, NULL until it is billed
+ALTER TABLE TaskTimes
+ADD "InvoiceId" UUID REFERENCES Invoices("InvoiceId");
+
+-- Refuse the change with the message given to the trigger. The code is
+-- restrict_violation, which mv_dbi reports as Error::Locked.
+CREATE OR REPLACE FUNCTION mv_reject_change() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION '%', TG_ARGV[0] USING ERRCODE = 'restrict_violation';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER Invoices_NoChange BEFORE UPDATE OR DELETE ON Invoices
+FOR EACH ROW EXECUTE FUNCTION mv_reject_change('an invoice cannot be changed');
+
+CREATE TRIGGER InvoiceLines_NoChange BEFORE UPDATE OR DELETE ON InvoiceLines
+FOR EACH ROW EXECUTE FUNCTION mv_reject_change('an invoice cannot be changed');
+
+CREATE TRIGGER TaskTimes_BilledNoChange BEFORE UPDATE OR DELETE ON TaskTimes
+FOR EACH ROW WHEN (OLD."InvoiceId" IS NOT NULL)
+EXECUTE FUNCTION mv_reject_change('it has been invoiced');
@@ -0,0 +1,9 @@
+-- Make EndTime required again. A timer still running is stopped at the
+-- time it started.
+DROP INDEX IF EXISTS ix_TaskTimes_Running;
+
+UPDATE TaskTimes SET "EndTime" = "StartTime"
+WHERE "EndTime" IS NULL;
+
+ALTER TABLE TaskTimes
+ALTER COLUMN "EndTime" SET NOT NULL;
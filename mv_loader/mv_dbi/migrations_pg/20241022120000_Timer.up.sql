@@ -0,0 +1,7 @@
+-- Let EndTime be NULL while a timer is running
+ALTER TABLE TaskTimes
+ALTER COLUMN "EndTime" DROP NOT NULL;
+
+-- Only one timer may run at a time
+CREATE UNIQUE INDEX IF NOT EXISTS ix_TaskTimes_Running
+ON TaskTimes (("EndTime" IS NULL)) WHERE "EndTime" IS NULL;
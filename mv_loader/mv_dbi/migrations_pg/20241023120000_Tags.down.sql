@@ -0,0 +1,4 @@
+-- Drop TaskTags and Tags Tables
+DROP INDEX IF EXISTS ix_TaskTags_TagId;
+DROP TABLE IF EXISTS TaskTags;
+DROP TABLE IF EXISTS Tags;
@@ -0,0 +1,15 @@
+-- Create Tags Table. Names are kept trimmed and in lower case.
+CREATE TABLE IF NOT EXISTS Tags (
+  "TagId"    UUID NOT NULL UNIQUE,
+  "TagName"  VARCHAR(64) NOT NULL UNIQUE,
+  CONSTRAINT pk_Tags PRIMARY KEY("TagId")
+);
+
+-- Create TaskTags Table, the tags on each ProjectTask
+CREATE TABLE IF NOT EXISTS TaskTags (
+  "TaskId"  UUID NOT NULL REFERENCES ProjectTasks("TaskId") ON DELETE CASCADE,
+  "TagId"   UUID NOT NULL REFERENCES Tags("TagId") ON DELETE CASCADE,
+  CONSTRAINT pk_TaskTags PRIMARY KEY("TaskId", "TagId")
+);
+
+CREATE INDEX IF NOT EXISTS ix_TaskTags_TagId ON TaskTags("TagId");
@@ -0,0 +1,10 @@
+-- Drop the full-text SearchIndex and the task notes
+DROP TRIGGER IF EXISTS ProjectTasks_Search ON ProjectTasks;
+DROP TRIGGER IF EXISTS Projects_Search ON Projects;
+DROP FUNCTION IF EXISTS mv_index_task();
+DROP FUNCTION IF EXISTS mv_index_project();
+
+DROP TABLE IF EXISTS SearchIndex;
+
+ALTER TABLE ProjectTasks
+DROP COLUMN "Notes";
@@ -0,0 +1,61 @@
+-- Free-form notes on a task
+ALTER TABLE ProjectTasks
+ADD "Notes" TEXT;
+
+-- Create the full-text SearchIndex. Each Project has a row with its name
+-- and a NULL TaskId; each ProjectTask has a row with its name and notes.
+-- The triggers below keep it in step with Projects and ProjectTasks.
+CREATE TABLE IF NOT EXISTS SearchIndex (
+  "ProjectId" UUID NOT NULL,
+  "TaskId"    UUID,
+  "Name"      VARCHAR(255) NOT NULL,
+  "Notes"     TEXT,
+  "Document"  TSVECTOR GENERATED ALWAYS AS
+    (to_tsvector('english', "Name" || ' ' || COALESCE("Notes", ''))) STORED
+);
+
+CREATE INDEX IF NOT EXISTS ix_SearchIndex_Document ON SearchIndex USING GIN("Document");
+CREATE INDEX IF NOT EXISTS ix_SearchIndex_ProjectId ON SearchIndex("ProjectId");
+CREATE INDEX IF NOT EXISTS ix_SearchIndex_TaskId ON SearchIndex("TaskId");
+
+INSERT INTO SearchIndex ("ProjectId", "TaskId", "Name", "Notes")
+SELECT "ProjectId", NULL, "ProjectName", NULL FROM Projects;
+
+INSERT INTO SearchIndex ("ProjectId", "TaskId", "Name", "Notes")
+SELECT "ProjectId", "TaskId", "TaskName", "Notes" FROM ProjectTasks;
+
+CREATE OR REPLACE FUNCTION mv_index_project() RETURNS trigger AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    INSERT INTO SearchIndex ("ProjectId", "TaskId", "Name", "Notes")
+    VALUES (NEW."ProjectId", NULL, NEW."ProjectName", NULL);
+  ELSIF TG_OP = 'UPDATE' THEN
+    UPDATE SearchIndex SET "Name" = NEW."ProjectName"
+    WHERE "ProjectId" = OLD."ProjectId" AND "TaskId" IS NULL;
+  ELSE
+    DELETE FROM SearchIndex WHERE "ProjectId" = OLD."ProjectId";
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION mv_index_task() RETURNS trigger AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    INSERT INTO SearchIndex ("ProjectId", "TaskId", "Name", "Notes")
+    VALUES (NEW."ProjectId", NEW."TaskId", NEW."TaskName", NEW."Notes");
+  ELSIF TG_OP = 'UPDATE' THEN
+    UPDATE SearchIndex SET "Name" = NEW."TaskName", "Notes" = NEW."Notes"
+    WHERE "TaskId" = OLD."TaskId";
+  ELSE
+    DELETE FROM SearchIndex WHERE "TaskId" = OLD."TaskId";
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER Projects_Search AFTER INSERT OR UPDATE OF "ProjectName" OR DELETE ON Projects
+FOR EACH ROW EXECUTE FUNCTION mv_index_project();
+
+CREATE TRIGGER ProjectTasks_Search AFTER INSERT OR UPDATE OF "TaskName", "Notes" OR DELETE ON ProjectTasks
+FOR EACH ROW EXECUTE FUNCTION mv_index_task();
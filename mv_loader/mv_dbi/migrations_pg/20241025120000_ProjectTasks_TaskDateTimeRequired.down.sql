@@ -0,0 +1,3 @@
+-- Let TaskDateTime be NULL again
+ALTER TABLE ProjectTasks
+ALTER COLUMN "TaskDateTime" DROP NOT NULL;
@@ -0,0 +1,11 @@
+-- TaskDateTime was added without a NOT NULL rule, though every task has
+-- one. A task saved without it is given its first TaskTime's start, or
+-- the start of its project's day.
+UPDATE ProjectTasks t
+SET "TaskDateTime" = COALESCE(
+  (SELECT MIN(tt."StartTime") FROM TaskTimes tt WHERE tt."TaskId" = t."TaskId"),
+  (SELECT p."ProjectDate"::TIMESTAMP FROM Projects p WHERE p."ProjectId" = t."ProjectId"))
+WHERE t."TaskDateTime" IS NULL;
+
+ALTER TABLE ProjectTasks
+ALTER COLUMN "TaskDateTime" SET NOT NULL;
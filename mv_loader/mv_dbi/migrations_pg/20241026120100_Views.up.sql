@@ -0,0 +1,45 @@
+-- Views for querying by hand in psql. Durations are shown as
+-- hours:minutes:seconds, and each row carries the names of the project
+-- and task it belongs to.
+CREATE OR REPLACE VIEW v_Projects AS
+SELECT
+  p."ProjectId",
+  p."ProjectName",
+  p."ProjectDate",
+  to_char(p."ProjectDuration" * INTERVAL '1 millisecond', 'HH24:MI:SS') AS "Duration",
+  p."PayRate",
+  p."TotalPay",
+  p."Currency",
+  p."ClientId",
+  c."ClientName"
+FROM Projects p
+LEFT JOIN Clients c ON c."ClientId" = p."ClientId";
+
+CREATE OR REPLACE VIEW v_ProjectTasks AS
+SELECT
+  t."TaskId",
+  t."ProjectId",
+  p."ProjectName",
+  t."TaskName",
+  t."TaskDateTime",
+  to_char(t."TaskDuration" * INTERVAL '1 millisecond', 'HH24:MI:SS') AS "Duration",
+  t."Notes"
+FROM ProjectTasks t
+JOIN Projects p ON p."ProjectId" = t."ProjectId";
+
+-- A running timer has no EndTime, so no Duration. The difference is
+-- taken in seconds so a span over midnight is not shown as "1 day".
+CREATE OR REPLACE VIEW v_TaskTimes AS
+SELECT
+  tt."TaskTimeId",
+  tt."TaskId",
+  p."ProjectName",
+  t."TaskName",
+  tt."StartTime",
+  tt."EndTime",
+  to_char(EXTRACT(EPOCH FROM tt."EndTime" - tt."StartTime") * INTERVAL '1 second',
+    'HH24:MI:SS') AS "Duration",
+  tt."InvoiceId"
+FROM TaskTimes tt
+JOIN ProjectTasks t ON t."TaskId" = tt."TaskId"
+JOIN Projects p ON p."ProjectId" = t."ProjectId";
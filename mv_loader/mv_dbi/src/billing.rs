@@ -0,0 +1,661 @@
+// billing.rs
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::text_type::text_type;
+use crate::error::Error;
+use crate::guid::{Guid, OptionalGuid};
+use crate::model::billing_rule::BillingRule;
+use crate::model::task_rate::TaskRate;
+use crate::money::{Money, RoundingMode};
+
+///
+/// One TaskTime to bill, with the task and project it belongs to.
+/// `pay_rate` is the project's rate, or its client's when it has none,
+/// in the project's `currency`.
+///
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct WorkEntry {
+    pub task_time_id: i64,
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub project_name: String,
+    #[sqlx(try_from = "OptionalGuid")]
+    pub client_id: Option<Uuid>,
+    #[sqlx(try_from = "Guid")]
+    pub task_id: Uuid,
+    pub task_name: String,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub pay_rate: Money,
+    pub currency: String,
+}
+
+/// What a line of a charge is for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChargeKind {
+    /// Time billed at the normal rate
+    #[default]
+    Regular,
+    /// Time past a daily or weekly limit, billed at the overtime percent
+    Overtime,
+    /// Time added to bring a day up to the daily minimum
+    Minimum,
+}
+
+impl ChargeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChargeKind::Regular => "regular",
+            ChargeKind::Overtime => "overtime",
+            ChargeKind::Minimum => "minimum",
+        }
+    }
+}
+
+impl FromStr for ChargeKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "regular" => Ok(ChargeKind::Regular),
+            "overtime" => Ok(ChargeKind::Overtime),
+            "minimum" => Ok(ChargeKind::Minimum),
+            _ => Err(Error::Validation {
+                entity: "InvoiceLine",
+                id: String::new(),
+                message: format!("Unknown charge kind '{s}'"),
+            }),
+        }
+    }
+}
+
+text_type!(ChargeKind);
+
+///
+/// One line of a charge: some minutes of a task, or a day's minimum top
+/// up, at a rate. `reason` says how the minutes were arrived at.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChargeLine {
+    pub date: NaiveDate,
+    pub project_id: Uuid,
+    pub project_name: String,
+    /// Empty for a minimum line
+    pub task_name: String,
+    pub kind: ChargeKind,
+    pub minutes: i64,
+    pub pay_rate: Money,
+    pub percent: i32,
+    pub amount: Money,
+    pub reason: String,
+}
+
+impl fmt::Display for ChargeLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}: {}m at {}/h",
+            self.date,
+            self.project_name,
+            self.task_name,
+            self.kind.as_str(),
+            self.minutes,
+            self.pay_rate
+        )?;
+        if self.percent != 100 {
+            write!(f, " x {}%", self.percent)?;
+        }
+        write!(f, " = {} ({})", self.amount, self.reason)
+    }
+}
+
+///
+/// The lines billed under one rule, in the order the work was done.
+/// `rule_id` is None for projects that have no rule and are billed at
+/// their rate for the whole minutes of each task.
+///
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Charge {
+    pub rule_id: Option<Uuid>,
+    pub lines: Vec<ChargeLine>,
+}
+
+impl Charge {
+    pub fn total(&self) -> Money {
+        self.lines.iter().map(|l| l.amount).sum()
+    }
+
+    /// The part of the total for one project
+    pub fn project_total(&self, project_id: Uuid) -> Money {
+        self.lines
+            .iter()
+            .filter(|l| l.project_id == project_id)
+            .map(|l| l.amount)
+            .sum()
+    }
+}
+
+impl fmt::Display for Charge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        write!(f, "Total {}", self.total())
+    }
+}
+
+///
+/// The stored billing rules and task rates, for finding the rule each
+/// project is billed by.
+///
+#[derive(Debug, Clone, Default)]
+pub struct RuleBook {
+    rules: Vec<BillingRule>,
+    task_rates: Vec<TaskRate>,
+}
+
+impl RuleBook {
+    pub fn new(rules: Vec<BillingRule>, task_rates: Vec<TaskRate>) -> Self {
+        Self { rules, task_rates }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The project's own rule, or else its client's
+    pub fn rule_for(&self, project_id: Uuid, client_id: Option<Uuid>) -> Option<&BillingRule> {
+        self.rules
+            .iter()
+            .find(|r| r.project_id == Some(project_id))
+            .or_else(|| {
+                client_id.and_then(|id| self.rules.iter().find(|r| r.client_id == Some(id)))
+            })
+    }
+
+    pub fn task_rates(&self, rule_id: Uuid) -> Vec<TaskRate> {
+        self.task_rates
+            .iter()
+            .filter(|r| r.rule_id == rule_id)
+            .cloned()
+            .collect()
+    }
+
+    ///
+    /// Bill the entries, one charge for each rule. Projects without a rule
+    /// get a charge each. Daily minimums and overtime are counted across
+    /// all the entries under a rule, so a client's rule counts the time on
+    /// all of its projects together.
+    ///
+    pub fn charges(&self, entries: &[WorkEntry], mode: RoundingMode) -> Vec<Charge> {
+        let mut groups: Vec<(Option<&BillingRule>, Uuid, Vec<WorkEntry>)> = Vec::new();
+        for entry in entries {
+            let rule = self.rule_for(entry.project_id, entry.client_id);
+            // Without a rule each project is its own group
+            let key = rule.map_or(entry.project_id, |r| r.rule_id);
+            match groups.iter_mut().find(|(_, k, _)| *k == key) {
+                Some((_, _, group)) => group.push(entry.clone()),
+                None => groups.push((rule, key, vec![entry.clone()])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(rule, _, group)| match rule {
+                Some(rule) => charge(rule, &self.task_rates(rule.rule_id), &group, mode),
+                None => charge(&BillingRule::default(), &[], &group, mode),
+            })
+            .collect()
+    }
+}
+
+// The billed minutes of a task before overtime is split off
+struct TaskMinutes {
+    date: NaiveDate,
+    project_id: Uuid,
+    project_name: String,
+    task_name: String,
+    minutes: i64,
+    pay_rate: Money,
+    reason: String,
+}
+
+///
+/// Bill the entries under one rule. The parts of the rule are applied in
+/// this order:
+///
+/// 1. Each TaskTime is rounded to `time_increment`, then each task's
+///    total to `task_increment`. With neither, a task is billed for its
+///    whole minutes. A task is billed on the day it starts.
+/// 2. A task named in `task_rates` is billed at that rate, the rest at
+///    their project's rate.
+/// 3. Going through the tasks in order, minutes past `daily_overtime` in
+///    the day or `weekly_overtime` in the ISO week are split into an
+///    overtime line at `overtime_percent` of the rate.
+/// 4. A day billed for less than `daily_minimum` gets a minimum line for
+///    the difference at the rate of the day's first project.
+///
+/// Each line's amount is rounded to cents with `mode`.
+///
+pub fn charge(
+    rule: &BillingRule,
+    task_rates: &[TaskRate],
+    entries: &[WorkEntry],
+    mode: RoundingMode,
+) -> Charge {
+    let mut entries: Vec<&WorkEntry> = entries.iter().collect();
+    entries.sort_by_key(|e| e.start_time);
+
+    // The tasks in the order they were started
+    let mut tasks: Vec<(Uuid, Vec<&WorkEntry>)> = Vec::new();
+    for entry in entries {
+        match tasks.iter_mut().find(|(id, _)| *id == entry.task_id) {
+            Some((_, times)) => times.push(entry),
+            None => tasks.push((entry.task_id, vec![entry])),
+        }
+    }
+    let tasks: Vec<TaskMinutes> = tasks
+        .iter()
+        .map(|(_, times)| task_minutes(rule, task_rates, times))
+        .collect();
+
+    let mut lines = Vec::with_capacity(tasks.len());
+    let mut day_minutes: HashMap<NaiveDate, i64> = HashMap::new();
+    let mut week_minutes: HashMap<(i32, u32), i64> = HashMap::new();
+    for task in tasks {
+        let week = task.date.iso_week();
+        let week = (week.year(), week.week());
+        let day_used = day_minutes.entry(task.date).or_default();
+        let week_used = week_minutes.entry(week).or_default();
+
+        let mut regular = task.minutes;
+        let mut over = Vec::new();
+        if rule.daily_overtime > 0 {
+            let left = (rule.daily_overtime as i64 - *day_used).max(0);
+            if task.minutes > left {
+                over.push(format!("past {}m a day", rule.daily_overtime));
+            }
+            regular = regular.min(left);
+        }
+        if rule.weekly_overtime > 0 {
+            let left = (rule.weekly_overtime as i64 - *week_used).max(0);
+            if task.minutes > left {
+                over.push(format!("past {}m a week", rule.weekly_overtime));
+            }
+            regular = regular.min(left);
+        }
+        *day_used += task.minutes;
+        *week_used += task.minutes;
+
+        let line = |kind, minutes, percent, reason| ChargeLine {
+            date: task.date,
+            project_id: task.project_id,
+            project_name: task.project_name.clone(),
+            task_name: task.task_name.clone(),
+            kind,
+            minutes,
+            pay_rate: task.pay_rate,
+            percent,
+            amount: amount(minutes, task.pay_rate, percent, mode),
+            reason,
+        };
+        if regular > 0 || task.minutes == 0 {
+            lines.push(line(ChargeKind::Regular, regular, 100, task.reason.clone()));
+        }
+        if task.minutes > regular {
+            let reason = format!("{}; {}", task.reason, over.join(" and "));
+            lines.push(line(
+                ChargeKind::Overtime,
+                task.minutes - regular,
+                rule.overtime_percent,
+                reason,
+            ));
+        }
+    }
+
+    if rule.daily_minimum > 0 {
+        let mut index = 0;
+        while index < lines.len() {
+            let date = lines[index].date;
+            let end = index + lines[index..].iter().take_while(|l| l.date == date).count();
+            let billed = day_minutes.get(&date).copied().unwrap_or_default();
+            let short = rule.daily_minimum as i64 - billed;
+            if short > 0 {
+                let first = &lines[index];
+                let pay_rate = first.pay_rate;
+                lines.insert(
+                    end,
+                    ChargeLine {
+                        date,
+                        project_id: first.project_id,
+                        project_name: first.project_name.clone(),
+                        task_name: String::new(),
+                        kind: ChargeKind::Minimum,
+                        minutes: short,
+                        pay_rate,
+                        percent: 100,
+                        amount: amount(short, pay_rate, 100, mode),
+                        reason: format!(
+                            "{}m billed, less than the {}m daily minimum",
+                            billed, rule.daily_minimum
+                        ),
+                    },
+                );
+                index = end + 1;
+            } else {
+                index = end;
+            }
+        }
+    }
+
+    Charge {
+        rule_id: (!rule.rule_id.is_nil()).then_some(rule.rule_id),
+        lines,
+    }
+}
+
+// Round a task's times and total as the rule says, and find its rate
+fn task_minutes(rule: &BillingRule, task_rates: &[TaskRate], times: &[&WorkEntry]) -> TaskMinutes {
+    let first = times[0];
+    let seconds: Vec<i64> = times
+        .iter()
+        .map(|t| (t.end_time - t.start_time).num_seconds())
+        .collect();
+    let worked: i64 = seconds.iter().sum();
+    let mut reason = format!("{} worked", format_seconds(worked));
+
+    let mut billed_seconds = worked;
+    if rule.time_increment > 0 {
+        billed_seconds = seconds
+            .iter()
+            .map(|s| round_minutes(*s, rule.time_increment, rule.increment_rounding) * 60)
+            .sum();
+        reason.push_str(&format!(
+            ", each TaskTime rounded {} to {}m",
+            rule.increment_rounding, rule.time_increment
+        ));
+    }
+    let minutes = if rule.task_increment > 0 {
+        reason.push_str(&format!(
+            ", the task rounded {} to {}m",
+            rule.increment_rounding, rule.task_increment
+        ));
+        round_minutes(billed_seconds, rule.task_increment, rule.increment_rounding)
+    } else {
+        billed_seconds / 60
+    };
+
+    let pay_rate = match task_rates.iter().find(|r| r.task_name == first.task_name) {
+        Some(rate) => {
+            reason.push_str(&format!(", task rate for {}", rate.task_name));
+            rate.pay_rate
+        }
+        None => first.pay_rate,
+    };
+
+    TaskMinutes {
+        date: first.start_time.date(),
+        project_id: first.project_id,
+        project_name: first.project_name.clone(),
+        task_name: first.task_name.clone(),
+        minutes,
+        pay_rate,
+        reason,
+    }
+}
+
+// Whole increments of `increment` minutes, given back in minutes
+fn round_minutes(seconds: i64, increment: i32, mode: RoundingMode) -> i64 {
+    let steps = Decimal::from(seconds) / Decimal::from(increment as i64 * 60);
+    let steps = steps.round_dp_with_strategy(0, mode.strategy());
+    i64::try_from(steps).unwrap_or_default() * increment as i64
+}
+
+fn amount(minutes: i64, pay_rate: Money, percent: i32, mode: RoundingMode) -> Money {
+    let hours = Decimal::from(minutes) / Decimal::from(60);
+    Money(hours * pay_rate.0 * Decimal::from(percent) / Decimal::from(100)).round(mode)
+}
+
+// "52m" or "52m 30s"
+fn format_seconds(seconds: i64) -> String {
+    match seconds % 60 {
+        0 => format!("{}m", seconds / 60),
+        s => format!("{}m {}s", seconds / 60, s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::database::report::ReportRange;
+    use crate::utils::make_uuid;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn entry(task_name: &str, start: NaiveDateTime, seconds: i64) -> WorkEntry {
+        WorkEntry {
+            task_time_id: 0,
+            project_id: make_uuid(&"Billing".to_string()),
+            project_name: "Billing".to_string(),
+            client_id: None,
+            task_id: make_uuid(&format!("{}{}", task_name, start.date())),
+            task_name: task_name.to_string(),
+            start_time: start,
+            end_time: start + TimeDelta::seconds(seconds),
+            pay_rate: Money::from(60),
+            currency: "USD".to_string(),
+        }
+    }
+
+    fn summary(charge: &Charge) -> Vec<(ChargeKind, i64, Money)> {
+        charge
+            .lines
+            .iter()
+            .map(|l| (l.kind, l.minutes, l.amount))
+            .collect()
+    }
+
+    #[test]
+    fn test_charge_without_rules() {
+        // Seconds past the minute are not billed
+        let entries = vec![entry("Design", at(5, 9, 0), 52 * 60 + 40)];
+        let charge = charge(&BillingRule::default(), &[], &entries, RoundingMode::HalfUp);
+        assert_eq!(
+            summary(&charge),
+            vec![(ChargeKind::Regular, 52, Money::from(52))]
+        );
+        assert_eq!(charge.rule_id, None);
+        assert_eq!(
+            charge.lines[0].to_string(),
+            "2024-08-05 Billing Design regular: 52m at 60.00/h = 52.00 (52m 40s worked)"
+        );
+    }
+
+    #[test]
+    fn test_charge_increments() {
+        let entries = vec![
+            entry("Design", at(5, 9, 0), 7 * 60),
+            entry("Design", at(5, 10, 0), 16 * 60),
+        ];
+        let rule = BillingRule::for_project(entries[0].project_id).time_increment(15);
+        let by_time = charge(&rule, &[], &entries, RoundingMode::HalfUp);
+        assert_eq!(by_time.lines[0].minutes, 45);
+
+        let rule = rule.time_increment(0).task_increment(6);
+        let by_task = charge(&rule, &[], &entries, RoundingMode::HalfUp);
+        assert_eq!(by_task.lines[0].minutes, 24);
+        assert_eq!(
+            by_task.lines[0].reason,
+            "23m worked, the task rounded up to 6m"
+        );
+
+        let rule = rule.increment_rounding(RoundingMode::HalfUp);
+        let nearest = charge(&rule, &[], &entries, RoundingMode::HalfUp);
+        assert_eq!(nearest.lines[0].minutes, 24);
+        let rule = rule.increment_rounding(RoundingMode::Down);
+        let down = charge(&rule, &[], &entries, RoundingMode::HalfUp);
+        assert_eq!(down.lines[0].minutes, 18);
+    }
+
+    #[test]
+    fn test_charge_overtime_and_task_rates() {
+        let entries = vec![
+            entry("Design", at(5, 8, 0), 6 * 3600),
+            entry("Review", at(5, 15, 0), 3 * 3600),
+            entry("Design", at(6, 8, 0), 2 * 3600),
+        ];
+        let rule = BillingRule::for_project(entries[0].project_id)
+            .daily_overtime(8 * 60)
+            .weekly_overtime(10 * 60);
+        let rates = vec![TaskRate::new(rule.rule_id, "Review", Money::from(90))];
+        let charge = charge(&rule, &rates, &entries, RoundingMode::HalfUp);
+        assert_eq!(
+            summary(&charge),
+            vec![
+                (ChargeKind::Regular, 360, Money::from(360)),
+                (ChargeKind::Regular, 120, Money::from(180)),
+                (ChargeKind::Overtime, 60, Money(dec!(135))),
+                (ChargeKind::Regular, 60, Money::from(60)),
+                (ChargeKind::Overtime, 60, Money::from(90)),
+            ]
+        );
+        assert_eq!(
+            charge.lines[2].reason,
+            "180m worked, task rate for Review; past 480m a day"
+        );
+        assert_eq!(charge.lines[4].reason, "120m worked; past 600m a week");
+        assert_eq!(charge.total(), Money::from(825));
+    }
+
+    #[test]
+    fn test_charge_daily_minimum() {
+        let entries = vec![
+            entry("Design", at(5, 9, 0), 30 * 60),
+            entry("Design", at(6, 9, 0), 3 * 3600),
+        ];
+        let rule = BillingRule::for_project(entries[0].project_id).daily_minimum(120);
+        let charge = charge(&rule, &[], &entries, RoundingMode::HalfUp);
+        assert_eq!(
+            summary(&charge),
+            vec![
+                (ChargeKind::Regular, 30, Money::from(30)),
+                (ChargeKind::Minimum, 90, Money::from(90)),
+                (ChargeKind::Regular, 180, Money::from(180)),
+            ]
+        );
+        assert_eq!(
+            charge.lines[1].reason,
+            "30m billed, less than the 120m daily minimum"
+        );
+        assert!(charge.to_string().ends_with("Total 300.00"));
+    }
+
+    #[tokio::test]
+    async fn test_bill_projects() -> Result<(), crate::Error> {
+        use crate::model::client::Client;
+        use crate::model::project_task::ProjectTask;
+        use crate::model::task_time::TaskTime;
+        use crate::{DbConfig, DbiDatabase, Project};
+
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        let client = Client {
+            client_id: make_uuid(&"Acme".to_string()),
+            client_name: "Acme".to_string(),
+            pay_rate: None,
+        };
+        db.insert(&client).await?;
+        db.insert(
+            &BillingRule::for_client(client.client_id)
+                .time_increment(15)
+                .daily_minimum(60),
+        )
+        .await?;
+
+        // 20 minutes on a project of Acme's and on one without a client
+        let duration = TimeDelta::minutes(20).num_milliseconds();
+        for (name, client_id) in [("Shed", Some(client.client_id)), ("Garden", None)] {
+            let project = Project {
+                project_id: make_uuid(&name.to_string()),
+                project_name: name.to_string(),
+                project_date: at(5, 9, 0).date(),
+                pay_rate: Money::from(40),
+                project_duration: duration,
+                total_pay: crate::utils::total_pay(duration, Money::from(40), RoundingMode::HalfUp),
+                client_id,
+                ..Default::default()
+            };
+            db.insert(&project).await?;
+            let task = ProjectTask {
+                task_id: make_uuid(&format!("{} Task", name)),
+                project_id: project.project_id,
+                task_name: "Task".to_string(),
+                task_duration: duration,
+                task_date_time: at(5, 9, 0),
+                notes: None,
+            };
+            db.insert(&task).await?;
+            db.insert(&TaskTime {
+                task_id: task.task_id,
+                start_time: at(5, 9, 0),
+                end_time: Some(at(5, 9, 20)),
+                ..Default::default()
+            })
+            .await?;
+        }
+
+        // The rule rounds Shed up to 30 minutes and then to the hour minimum
+        let report = db.verify().await?;
+        assert_eq!(report.projects.len(), 1);
+        assert_eq!(report.projects[0].project_name, "Shed");
+        assert_eq!(db.bill().await?, 1);
+        assert_eq!(db.bill().await?, 0);
+        assert!(db.verify().await?.is_empty());
+
+        let charges = db.charges(&ReportRange::all()).await?;
+        let totals: Vec<(Option<Uuid>, Money)> =
+            charges.iter().map(|c| (c.rule_id, c.total())).collect();
+        let rule_id = BillingRule::for_client(client.client_id).rule_id;
+        assert_eq!(
+            totals,
+            vec![(Some(rule_id), Money::from(40)), (None, Money(dec!(13.33)))]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_book_prefers_project_rule() {
+        let project_id = make_uuid(&"Billing".to_string());
+        let client_id = make_uuid(&"Acme".to_string());
+        let client_rule = BillingRule::for_client(client_id).time_increment(15);
+        let book = RuleBook::new(vec![client_rule.clone()], vec![]);
+        assert_eq!(
+            book.rule_for(project_id, Some(client_id)),
+            Some(&client_rule)
+        );
+        assert_eq!(book.rule_for(project_id, None), None);
+
+        let project_rule = BillingRule::for_project(project_id);
+        let book = RuleBook::new(vec![client_rule, project_rule.clone()], vec![]);
+        assert_eq!(
+            book.rule_for(project_id, Some(client_id)),
+            Some(&project_rule)
+        );
+    }
+}
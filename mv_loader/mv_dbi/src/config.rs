@@ -0,0 +1,529 @@
+// config.rs
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteJournalMode, SqliteSynchronous};
+
+use crate::error::Error;
+use crate::money::RoundingMode;
+
+const MEMORY_URL: &str = "sqlite::memory:";
+
+///
+/// The database servers mv_dbi can store its data in
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+///
+/// The SQLite journal mode. WAL lets readers carry on while a writer is
+/// busy, DELETE keeps everything in the one database file.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    #[default]
+    Delete,
+    Wal,
+}
+
+impl FromStr for JournalMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "delete" => Ok(JournalMode::Delete),
+            "wal" => Ok(JournalMode::Wal),
+            _ => Err(Error::Configuration(format!(
+                "Unknown journal mode '{s}', expected 'delete' or 'wal'"
+            ))),
+        }
+    }
+}
+
+impl From<JournalMode> for SqliteJournalMode {
+    fn from(mode: JournalMode) -> Self {
+        match mode {
+            JournalMode::Delete => SqliteJournalMode::Delete,
+            JournalMode::Wal => SqliteJournalMode::Wal,
+        }
+    }
+}
+
+///
+/// The SQLite `synchronous` level, trading durability for write speed.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    Normal,
+    #[default]
+    Full,
+    Extra,
+}
+
+impl FromStr for Synchronous {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Synchronous::Off),
+            "normal" => Ok(Synchronous::Normal),
+            "full" => Ok(Synchronous::Full),
+            "extra" => Ok(Synchronous::Extra),
+            _ => Err(Error::Configuration(format!(
+                "Unknown synchronous level '{s}', expected 'off', 'normal', 'full' or 'extra'"
+            ))),
+        }
+    }
+}
+
+impl From<Synchronous> for SqliteSynchronous {
+    fn from(level: Synchronous) -> Self {
+        match level {
+            Synchronous::Off => SqliteSynchronous::Off,
+            Synchronous::Normal => SqliteSynchronous::Normal,
+            Synchronous::Full => SqliteSynchronous::Full,
+            Synchronous::Extra => SqliteSynchronous::Extra,
+        }
+    }
+}
+
+///
+/// How SQLite stores the UUID keys of rows. TEXT keeps them hyphenated,
+/// as in `67e55044-10b1-426f-9247-bb680e5fe0c8`, so they can be read and
+/// typed in the sqlite3 shell; BLOB keeps them as 16 bytes, the way
+/// versions before the TextUuids migration did. PostgreSQL always uses
+/// its native UUID type.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UuidStorage {
+    #[default]
+    Text,
+    Blob,
+}
+
+impl FromStr for UuidStorage {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(UuidStorage::Text),
+            "blob" => Ok(UuidStorage::Blob),
+            _ => Err(Error::Configuration(format!(
+                "Unknown UUID storage '{s}', expected 'text' or 'blob'"
+            ))),
+        }
+    }
+}
+
+///
+/// Which stored TaskTimes a new or changed TaskTime may not overlap.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapScope {
+    /// Overlaps are allowed
+    Off,
+    /// Times in the same task may not overlap
+    #[default]
+    Task,
+    /// No two times may overlap, whatever task they are in
+    Global,
+}
+
+impl FromStr for OverlapScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(OverlapScope::Off),
+            "task" => Ok(OverlapScope::Task),
+            "global" => Ok(OverlapScope::Global),
+            _ => Err(Error::Configuration(format!(
+                "Unknown overlap scope '{s}', expected 'off', 'task' or 'global'"
+            ))),
+        }
+    }
+}
+
+///
+/// The checks `DbiDatabase` makes before it writes a TaskTime, read from
+/// the `[database.validation]` table of a config file.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ValidationRules {
+    pub overlap: OverlapScope,
+    /// The longest a single TaskTime may be, 0 for no limit
+    pub max_task_minutes: u32,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self {
+            overlap: OverlapScope::Task,
+            max_task_minutes: 24 * 60,
+        }
+    }
+}
+
+impl ValidationRules {
+    pub fn overlap(mut self, scope: OverlapScope) -> Self {
+        self.overlap = scope;
+        self
+    }
+
+    pub fn max_task_minutes(mut self, minutes: u32) -> Self {
+        self.max_task_minutes = minutes;
+        self
+    }
+
+    pub fn max_task_duration(&self) -> Option<TimeDelta> {
+        (self.max_task_minutes > 0).then(|| TimeDelta::minutes(self.max_task_minutes as i64))
+    }
+}
+
+///
+/// How `DbiDatabase::new` opens the database.
+///
+/// `journal_mode`, `synchronous`, `busy_timeout_ms` and `uuid_storage`
+/// only apply to SQLite. A database opened `read_only` or with
+/// `skip_migrations` is not migrated. Once its schema is current, one
+/// opened with a `uuid_storage` other than the one its keys are kept in
+/// has them converted, or is refused if it is read-only. `rounding` is
+/// how pay is rounded to cents when totals are worked out.
+///
+/// A config can be built in code, read from the `[database]` table of a
+/// TOML file, taken from `MV_DB_*` environment variables, or any mix of
+/// these with `DbConfig::load`:
+///
+/// ```toml
+/// [database]
+/// url = "sqlite://projects.db3"
+/// max_connections = 4
+/// journal_mode = "wal"
+/// busy_timeout_ms = 10000
+/// synchronous = "normal"
+/// uuid_storage = "text"
+/// rounding = "half_even"
+///
+/// [database.validation]
+/// overlap = "global"
+/// max_task_minutes = 720
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DbConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub journal_mode: JournalMode,
+    pub busy_timeout_ms: u64,
+    pub synchronous: Synchronous,
+    pub read_only: bool,
+    pub skip_migrations: bool,
+    pub uuid_storage: UuidStorage,
+    pub validation: ValidationRules,
+    pub rounding: RoundingMode,
+}
+
+impl Default for DbConfig {
+    fn default() -> Self {
+        Self {
+            url: MEMORY_URL.to_string(),
+            max_connections: 10,
+            min_connections: 0,
+            journal_mode: JournalMode::Delete,
+            busy_timeout_ms: 5000,
+            synchronous: Synchronous::Full,
+            read_only: false,
+            skip_migrations: false,
+            uuid_storage: UuidStorage::Text,
+            validation: ValidationRules::default(),
+            rounding: RoundingMode::HalfUp,
+        }
+    }
+}
+
+// The file layout, so the database settings can share a file with others
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    database: DbConfig,
+}
+
+impl DbConfig {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    pub fn min_connections(mut self, min: u32) -> Self {
+        self.min_connections = min;
+        self
+    }
+
+    pub fn journal_mode(mut self, mode: JournalMode) -> Self {
+        self.journal_mode = mode;
+        self
+    }
+
+    pub fn busy_timeout(mut self, timeout: Duration) -> Self {
+        self.busy_timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    pub fn synchronous(mut self, level: Synchronous) -> Self {
+        self.synchronous = level;
+        self
+    }
+
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn skip_migrations(mut self, skip: bool) -> Self {
+        self.skip_migrations = skip;
+        self
+    }
+
+    pub fn uuid_storage(mut self, storage: UuidStorage) -> Self {
+        self.uuid_storage = storage;
+        self
+    }
+
+    pub fn validation(mut self, rules: ValidationRules) -> Self {
+        self.validation = rules;
+        self
+    }
+
+    pub fn rounding(mut self, mode: RoundingMode) -> Self {
+        self.rounding = mode;
+        self
+    }
+
+    ///
+    /// The backend named by the URL scheme. `postgres://` and
+    /// `postgresql://` select PostgreSQL, anything else is SQLite.
+    ///
+    pub fn backend(&self) -> Backend {
+        if self.url.starts_with("postgres://") || self.url.starts_with("postgresql://") {
+            Backend::Postgres
+        } else {
+            Backend::Sqlite
+        }
+    }
+
+    /// Whether migrations should run when the database is opened
+    pub fn runs_migrations(&self) -> bool {
+        !(self.skip_migrations || self.read_only)
+    }
+
+    ///
+    /// Read the `[database]` table of a TOML document. Missing keys keep
+    /// their defaults.
+    ///
+    pub fn from_toml_str(text: &str) -> Result<Self, Error> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|e| Error::Configuration(e.to_string()))?;
+        Ok(file.database)
+    }
+
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| {
+            Error::Configuration(format!("Could not read {}: {}", path.display(), e))
+        })?;
+        Self::from_toml_str(&text)
+    }
+
+    ///
+    /// The defaults, overridden by whatever is set in the environment.
+    ///
+    pub fn from_env() -> Result<Self, Error> {
+        Self::default().with_env()
+    }
+
+    ///
+    /// Start from the TOML file when one is given, then let environment
+    /// variables override it. Load a .env file first to have it included.
+    ///
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let config = match path {
+            Some(path) => Self::from_toml_file(path)?,
+            None => Self::default(),
+        };
+        config.with_env()
+    }
+
+    ///
+    /// Override settings from the environment. The URL comes from
+    /// `MV_DB_URL`, or from `DATABASE_URL` when nothing else has named a
+    /// database; the rest from
+    /// `MV_DB_MAX_CONNECTIONS`, `MV_DB_MIN_CONNECTIONS`,
+    /// `MV_DB_JOURNAL_MODE`, `MV_DB_BUSY_TIMEOUT_MS`, `MV_DB_SYNCHRONOUS`,
+    /// `MV_DB_READ_ONLY`, `MV_DB_SKIP_MIGRATIONS`, `MV_DB_UUID_STORAGE`,
+    /// `MV_DB_OVERLAP_SCOPE`, `MV_DB_MAX_TASK_MINUTES` and `MV_DB_ROUNDING`.
+    ///
+    pub fn with_env(self) -> Result<Self, Error> {
+        self.with_vars(|name| env::var(name).ok())
+    }
+
+    fn with_vars(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        if let Some(url) = var("MV_DB_URL") {
+            self.url = url;
+        } else if self.url == MEMORY_URL {
+            if let Some(url) = var("DATABASE_URL") {
+                self.url = url;
+            }
+        }
+        if let Some(value) = var("MV_DB_MAX_CONNECTIONS") {
+            self.max_connections = parse_var("MV_DB_MAX_CONNECTIONS", &value)?;
+        }
+        if let Some(value) = var("MV_DB_MIN_CONNECTIONS") {
+            self.min_connections = parse_var("MV_DB_MIN_CONNECTIONS", &value)?;
+        }
+        if let Some(value) = var("MV_DB_JOURNAL_MODE") {
+            self.journal_mode = value.parse()?;
+        }
+        if let Some(value) = var("MV_DB_BUSY_TIMEOUT_MS") {
+            self.busy_timeout_ms = parse_var("MV_DB_BUSY_TIMEOUT_MS", &value)?;
+        }
+        if let Some(value) = var("MV_DB_SYNCHRONOUS") {
+            self.synchronous = value.parse()?;
+        }
+        if let Some(value) = var("MV_DB_READ_ONLY") {
+            self.read_only = parse_var("MV_DB_READ_ONLY", &value)?;
+        }
+        if let Some(value) = var("MV_DB_SKIP_MIGRATIONS") {
+            self.skip_migrations = parse_var("MV_DB_SKIP_MIGRATIONS", &value)?;
+        }
+        if let Some(value) = var("MV_DB_UUID_STORAGE") {
+            self.uuid_storage = value.parse()?;
+        }
+        if let Some(value) = var("MV_DB_OVERLAP_SCOPE") {
+            self.validation.overlap = value.parse()?;
+        }
+        if let Some(value) = var("MV_DB_MAX_TASK_MINUTES") {
+            self.validation.max_task_minutes = parse_var("MV_DB_MAX_TASK_MINUTES", &value)?;
+        }
+        if let Some(value) = var("MV_DB_ROUNDING") {
+            self.rounding = value.parse()?;
+        }
+        Ok(self)
+    }
+}
+
+fn parse_var<T: FromStr>(name: &str, value: &str) -> Result<T, Error> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::Configuration(format!("{name} has an invalid value '{value}'")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_config_backend() {
+        assert_eq!(DbConfig::new("sqlite::memory:").backend(), Backend::Sqlite);
+        assert_eq!(DbConfig::new("sqlite://mv.db").backend(), Backend::Sqlite);
+        assert_eq!(
+            DbConfig::new("postgres://localhost/mv").backend(),
+            Backend::Postgres
+        );
+        assert_eq!(
+            DbConfig::new("postgresql://user@host/mv").backend(),
+            Backend::Postgres
+        );
+    }
+
+    #[test]
+    fn test_config_from_toml() -> Result<(), Error> {
+        let config = DbConfig::from_toml_str(
+            r#"
+            [loader]
+            atomic = "file"
+
+            [database]
+            url = "sqlite://projects.db3"
+            max_connections = 4
+            journal_mode = "wal"
+            synchronous = "normal"
+            read_only = true
+            uuid_storage = "blob"
+            rounding = "half_even"
+
+            [database.validation]
+            overlap = "global"
+            "#,
+        )?;
+        let expected = DbConfig::new("sqlite://projects.db3")
+            .max_connections(4)
+            .journal_mode(JournalMode::Wal)
+            .synchronous(Synchronous::Normal)
+            .read_only(true)
+            .uuid_storage(UuidStorage::Blob)
+            .rounding(RoundingMode::HalfEven)
+            .validation(ValidationRules::default().overlap(OverlapScope::Global));
+        assert_eq!(config, expected);
+        assert!(!config.runs_migrations());
+
+        let result = DbConfig::from_toml_str("[database]\njournal_mode = \"memory\"");
+        assert!(matches!(result, Err(Error::Configuration(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_from_vars() -> Result<(), Error> {
+        let vars: HashMap<&str, &str> = HashMap::from([
+            ("DATABASE_URL", "sqlite://fallback.db3"),
+            ("MV_DB_URL", "postgres://localhost/mv"),
+            ("MV_DB_MIN_CONNECTIONS", "2"),
+            ("MV_DB_BUSY_TIMEOUT_MS", "250"),
+            ("MV_DB_JOURNAL_MODE", "WAL"),
+            ("MV_DB_SKIP_MIGRATIONS", "true"),
+            ("MV_DB_UUID_STORAGE", "Text"),
+            ("MV_DB_MAX_TASK_MINUTES", "0"),
+            ("MV_DB_ROUNDING", "down"),
+        ]);
+        let config = DbConfig::default().with_vars(|name| vars.get(name).map(|v| v.to_string()))?;
+        let expected = DbConfig::new("postgres://localhost/mv")
+            .min_connections(2)
+            .busy_timeout(Duration::from_millis(250))
+            .journal_mode(JournalMode::Wal)
+            .skip_migrations(true)
+            .rounding(RoundingMode::Down)
+            .validation(ValidationRules::default().max_task_minutes(0));
+        assert_eq!(config, expected);
+        assert_eq!(config.validation.max_task_duration(), None);
+
+        let result = DbConfig::default()
+            .with_vars(|name| (name == "MV_DB_MAX_CONNECTIONS").then(|| "lots".to_string()));
+        assert!(matches!(result, Err(Error::Configuration(_))));
+        Ok(())
+    }
+}
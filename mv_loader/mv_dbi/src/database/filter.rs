@@ -0,0 +1,280 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use sqlx::{Database, Encode, QueryBuilder, Type};
+use uuid::Uuid;
+
+use crate::guid::Guid;
+use crate::model::tag::Tag;
+use crate::money::Money;
+
+///
+/// Sort direction applied to a model's natural ordering column
+/// (ProjectDate, TaskDateTime or StartTime).
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Ascending => "ASC",
+            SortOrder::Descending => "DESC",
+        }
+    }
+}
+
+///
+/// The table a filter is being applied to. Each target knows which
+/// columns the filter fields map onto.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FilterTarget {
+    Projects,
+    ProjectTasks,
+    TaskTimes,
+}
+
+///
+/// A typed filter for the model retrieve queries.
+///
+/// All conditions are optional and are combined with AND. The date range
+/// is inclusive at both ends and applies to ProjectDate for Projects and
+/// ProjectTasks, and to StartTime for TaskTimes.
+///
+/// ```ignore
+/// let july = QueryFilter::new()
+///     .project_id(project_id)
+///     .date_range(
+///         NaiveDate::from_ymd_opt(2024, 7, 1).unwrap(),
+///         NaiveDate::from_ymd_opt(2024, 7, 31).unwrap(),
+///     );
+/// let times: Vec<TaskTime> = db.fetch_filtered(&july).await?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryFilter {
+    pub date_from: Option<NaiveDate>,
+    pub date_to: Option<NaiveDate>,
+    pub project_id: Option<Uuid>,
+    pub task_id: Option<Uuid>,
+    pub project_name: Option<String>,
+    pub task_name: Option<String>,
+    pub tag_id: Option<Uuid>,
+    pub min_pay_rate: Option<Money>,
+    pub max_pay_rate: Option<Money>,
+    pub order: SortOrder,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl QueryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn date_from(mut self, date: NaiveDate) -> Self {
+        self.date_from = Some(date);
+        self
+    }
+
+    pub fn date_to(mut self, date: NaiveDate) -> Self {
+        self.date_to = Some(date);
+        self
+    }
+
+    pub fn date_range(self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.date_from(from).date_to(to)
+    }
+
+    pub fn project_id(mut self, project_id: Uuid) -> Self {
+        self.project_id = Some(project_id);
+        self
+    }
+
+    pub fn task_id(mut self, task_id: Uuid) -> Self {
+        self.task_id = Some(task_id);
+        self
+    }
+
+    /// Match project names with an SQL LIKE pattern, e.g. `"Dia%"`
+    pub fn project_name(mut self, pattern: &str) -> Self {
+        self.project_name = Some(pattern.to_owned());
+        self
+    }
+
+    /// Match task names with an SQL LIKE pattern, e.g. `"Task%"`
+    pub fn task_name(mut self, pattern: &str) -> Self {
+        self.task_name = Some(pattern.to_owned());
+        self
+    }
+
+    /// Only tasks with the tag, or projects with such a task
+    pub fn tag(mut self, name: &str) -> Self {
+        self.tag_id = Some(Tag::id_for(name));
+        self
+    }
+
+    pub fn min_pay_rate(mut self, rate: Money) -> Self {
+        self.min_pay_rate = Some(rate);
+        self
+    }
+
+    pub fn max_pay_rate(mut self, rate: Money) -> Self {
+        self.max_pay_rate = Some(rate);
+        self
+    }
+
+    pub fn order(mut self, order: SortOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: i64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    ///
+    /// Append the FROM/JOIN, WHERE, ORDER BY and LIMIT clauses for the
+    /// target to a query that has already pushed its SELECT column list.
+    ///
+    /// The column list must use the aliases `p` (Projects), `t`
+    /// (ProjectTasks) and `tt` (TaskTimes). Project and task name patterns
+    /// use LIKE, which ignores ASCII case on SQLite but not on PostgreSQL.
+    ///
+    pub(crate) fn push_clauses<'args, DB>(
+        &self,
+        qb: &mut QueryBuilder<'args, DB>,
+        target: FilterTarget,
+    ) where
+        DB: Database,
+        NaiveDate: Encode<'args, DB> + Type<DB>,
+        NaiveDateTime: Encode<'args, DB> + Type<DB>,
+        Guid: Encode<'args, DB> + Type<DB>,
+        String: Encode<'args, DB> + Type<DB>,
+        Money: Encode<'args, DB> + Type<DB>,
+        i64: Encode<'args, DB> + Type<DB>,
+    {
+        let (from, order_column) = match target {
+            FilterTarget::Projects => (" FROM Projects p", r#"p."ProjectDate""#),
+            FilterTarget::ProjectTasks => (
+                r#" FROM ProjectTasks t JOIN Projects p ON p."ProjectId" = t."ProjectId""#,
+                r#"t."TaskDateTime""#,
+            ),
+            FilterTarget::TaskTimes => (
+                r#" FROM TaskTimes tt
+                JOIN ProjectTasks t ON t."TaskId" = tt."TaskId"
+                JOIN Projects p ON p."ProjectId" = t."ProjectId""#,
+                r#"tt."StartTime""#,
+            ),
+        };
+        qb.push(from);
+        qb.push(" WHERE 1 = 1");
+
+        if let Some(date) = self.date_from {
+            match target {
+                FilterTarget::TaskTimes => {
+                    qb.push(r#" AND tt."StartTime" >= "#);
+                    qb.push_bind(NaiveDateTime::new(date, NaiveTime::MIN));
+                }
+                _ => {
+                    qb.push(r#" AND p."ProjectDate" >= "#);
+                    qb.push_bind(date);
+                }
+            }
+        }
+        if let Some(date) = self.date_to {
+            match target {
+                FilterTarget::TaskTimes => {
+                    let next_day = date + TimeDelta::days(1);
+                    qb.push(r#" AND tt."StartTime" < "#);
+                    qb.push_bind(NaiveDateTime::new(next_day, NaiveTime::MIN));
+                }
+                _ => {
+                    qb.push(r#" AND p."ProjectDate" <= "#);
+                    qb.push_bind(date);
+                }
+            }
+        }
+        if let Some(project_id) = self.project_id {
+            qb.push(r#" AND p."ProjectId" = "#);
+            qb.push_bind(Guid::from(project_id));
+        }
+        if let Some(task_id) = self.task_id {
+            match target {
+                FilterTarget::Projects => {
+                    qb.push(r#" AND EXISTS (SELECT 1 FROM ProjectTasks x WHERE x."ProjectId" = p."ProjectId" AND x."TaskId" = "#);
+                    qb.push_bind(Guid::from(task_id));
+                    qb.push(")");
+                }
+                _ => {
+                    qb.push(r#" AND t."TaskId" = "#);
+                    qb.push_bind(Guid::from(task_id));
+                }
+            }
+        }
+        if let Some(pattern) = &self.project_name {
+            qb.push(r#" AND p."ProjectName" LIKE "#);
+            qb.push_bind(pattern.clone());
+        }
+        if let Some(pattern) = &self.task_name {
+            match target {
+                FilterTarget::Projects => {
+                    qb.push(r#" AND EXISTS (SELECT 1 FROM ProjectTasks x WHERE x."ProjectId" = p."ProjectId" AND x."TaskName" LIKE "#);
+                    qb.push_bind(pattern.clone());
+                    qb.push(")");
+                }
+                _ => {
+                    qb.push(r#" AND t."TaskName" LIKE "#);
+                    qb.push_bind(pattern.clone());
+                }
+            }
+        }
+        if let Some(tag_id) = self.tag_id {
+            match target {
+                FilterTarget::Projects => {
+                    qb.push(r#" AND EXISTS (SELECT 1 FROM ProjectTasks x JOIN TaskTags g ON g."TaskId" = x."TaskId" WHERE x."ProjectId" = p."ProjectId" AND g."TagId" = "#);
+                    qb.push_bind(Guid::from(tag_id));
+                    qb.push(")");
+                }
+                _ => {
+                    qb.push(r#" AND EXISTS (SELECT 1 FROM TaskTags x WHERE x."TaskId" = t."TaskId" AND x."TagId" = "#);
+                    qb.push_bind(Guid::from(tag_id));
+                    qb.push(")");
+                }
+            }
+        }
+        if let Some(rate) = self.min_pay_rate {
+            qb.push(r#" AND CAST(p."PayRate" AS NUMERIC) >= CAST("#);
+            qb.push_bind(rate);
+            qb.push(" AS NUMERIC)");
+        }
+        if let Some(rate) = self.max_pay_rate {
+            qb.push(r#" AND CAST(p."PayRate" AS NUMERIC) <= CAST("#);
+            qb.push_bind(rate);
+            qb.push(" AS NUMERIC)");
+        }
+
+        qb.push(format!(
+            " ORDER BY {} {}",
+            order_column,
+            self.order.as_sql()
+        ));
+
+        // OFFSET needs a LIMIT on SQLite, and the backends disagree on how
+        // to spell "no limit", so use the largest one both understand
+        if self.limit.is_some() || self.offset.is_some() {
+            qb.push(" LIMIT ");
+            qb.push_bind(self.limit.unwrap_or(i64::MAX));
+            qb.push(" OFFSET ");
+            qb.push_bind(self.offset.unwrap_or(0));
+        }
+    }
+}
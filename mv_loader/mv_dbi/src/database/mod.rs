@@ -1,2 +1,7 @@
 // database/mod.rs
+pub mod filter;
 pub mod query;
+pub mod reconcile;
+pub mod report;
+pub(crate) mod text_type;
+pub mod validate;
@@ -1,14 +1,112 @@
-use sqlx::Error;
+use crate::database::filter::QueryFilter;
+use crate::database::validate::Validate;
+use crate::error::Error;
+use futures::stream::BoxStream;
 use sqlx::Pool;
+use sqlx::Postgres;
+use sqlx::Sqlite;
 use uuid::Uuid;
 
+pub use mv_dbi_derive::DbObject;
+
+///
+/// The outcome of a `delete_one` call.
+///
+/// `rows` is the number of rows removed from the object's own table, and
+/// `children` is the number of dependent rows removed along with it by the
+/// `ON DELETE CASCADE` foreign keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeleteCount {
+    pub rows: u64,
+    pub children: u64,
+}
+
+///
+/// The primary key of the last row on a page, used to fetch the next one.
+///
+/// Projects and ProjectTasks are keyed by their UUID and TaskTimes by
+/// their TaskTimeId. Pages are always walked in key order.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageKey {
+    Uuid(Uuid),
+    Id(u64),
+}
+
+impl PageKey {
+    pub(crate) fn uuid(&self, entity: &'static str) -> Result<Uuid, Error> {
+        match self {
+            PageKey::Uuid(uuid) => Ok(*uuid),
+            PageKey::Id(id) => Err(Error::Validation {
+                entity,
+                id: id.to_string(),
+                message: "Pages are keyed by UUID".to_string(),
+            }),
+        }
+    }
+
+    pub(crate) fn id(&self, entity: &'static str) -> Result<i64, Error> {
+        match self {
+            PageKey::Id(id) => Ok(*id as i64),
+            PageKey::Uuid(uuid) => Err(Error::Validation {
+                entity,
+                id: uuid.to_string(),
+                message: "Pages are keyed by id".to_string(),
+            }),
+        }
+    }
+}
+
 #[allow(dead_code, async_fn_in_trait)]
 pub trait DbObject<DB, T>
 where
     DB: sqlx::Database,
 {
     async fn insert_one(pool: &Pool<DB>, dbo: &T) -> Result<u64, Error>;
+    async fn insert_in_tx(conn: &mut DB::Connection, dbo: &T) -> Result<u64, Error>;
+    async fn update_one(pool: &Pool<DB>, dbo: &T) -> Result<u64, Error>;
+    async fn upsert_one(pool: &Pool<DB>, dbo: &T) -> Result<u64, Error>;
+    async fn upsert_in_tx(conn: &mut DB::Connection, dbo: &T) -> Result<u64, Error>;
+    async fn delete_one(pool: &Pool<DB>, dbo: &T) -> Result<DeleteCount, Error>;
     async fn retrieve_all(pool: &Pool<DB>) -> Result<Vec<T>, Error>;
+    fn retrieve_stream(pool: &Pool<DB>) -> BoxStream<'_, Result<T, Error>>;
+    async fn retrieve_page(
+        pool: &Pool<DB>,
+        after: Option<&PageKey>,
+        limit: i64,
+    ) -> Result<Vec<T>, Error>;
     async fn retrieve_some(pool: &Pool<DB>, uuid: &Uuid) -> Result<Vec<T>, Error>;
+    async fn retrieve_filtered(pool: &Pool<DB>, filter: &QueryFilter) -> Result<Vec<T>, Error>;
     async fn retrieve_one(&mut self, pool: &Pool<DB>) -> Result<(), Error>;
 }
+
+///
+/// Where a model is stored, as generated by `derive(DbObject)`.
+///
+pub trait DbTable {
+    const TABLE: &'static str;
+    /// The primary key column
+    const KEY: &'static str;
+    /// The column holding the key of the row that owns this one
+    const PARENT: Option<&'static str>;
+
+    ///
+    /// Queries counting the rows that would be removed by `ON DELETE
+    /// CASCADE` along with the rows whose keys are selected by `keys`.
+    ///
+    fn dependents_sql(keys: &str) -> Vec<String>;
+
+    /// This row's key, for `DbiDatabase::fetch_page`
+    fn page_key(&self) -> PageKey;
+}
+
+///
+/// A model `DbiDatabase` can store on either backend. Everything that
+/// derives `DbObject` is one.
+///
+pub trait DbModel:
+    DbObject<Sqlite, Self> + DbObject<Postgres, Self> + DbTable + Validate + Sized
+{
+}
+
+impl<T> DbModel for T where T: DbObject<Sqlite, T> + DbObject<Postgres, T> + DbTable + Validate {}
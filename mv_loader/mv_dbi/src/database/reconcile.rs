@@ -0,0 +1,231 @@
+// database/reconcile.rs
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::report::{ReportDialect, PAY_RATE};
+use crate::guid::Guid;
+use crate::money::{Money, RoundingMode};
+use crate::utils::total_pay;
+
+///
+/// A project's stored ProjectDuration and TotalPay next to the values
+/// its TaskTimes give. `pay_rate` is the project's, or its client's when
+/// the project has none. `billed_pay` is what the project's billing rule
+/// charges, when it has one.
+///
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct ProjectTotals {
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub pay_rate: Money,
+    pub stored_duration: i64,
+    pub stored_pay: Money,
+    pub actual_duration: i64,
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub billed_pay: Option<Money>,
+}
+
+impl ProjectTotals {
+    ///
+    /// The pay the billing rule charges, or else the pay for the actual
+    /// duration worked out the way mv_load_csv does without rules
+    ///
+    pub fn actual_pay(&self, mode: RoundingMode) -> Money {
+        self.billed_pay
+            .unwrap_or_else(|| total_pay(self.actual_duration, self.pay_rate, mode))
+    }
+
+    pub fn is_consistent(&self, mode: RoundingMode) -> bool {
+        self.stored_duration == self.actual_duration && self.stored_pay == self.actual_pay(mode)
+    }
+}
+
+///
+/// A task's stored TaskDuration next to the sum of its TaskTimes.
+///
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct TaskTotals {
+    #[sqlx(try_from = "Guid")]
+    pub task_id: Uuid,
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub task_name: String,
+    pub stored_duration: i64,
+    pub actual_duration: i64,
+}
+
+impl TaskTotals {
+    pub fn is_consistent(&self) -> bool {
+        self.stored_duration == self.actual_duration
+    }
+}
+
+///
+/// The projects and tasks whose stored totals disagree with their
+/// TaskTimes, as found by `DbiDatabase::verify` or fixed by
+/// `DbiDatabase::reconcile`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TotalsReport {
+    pub projects: Vec<ProjectTotals>,
+    pub tasks: Vec<TaskTotals>,
+}
+
+impl TotalsReport {
+    /// Keep only the rows that disagree
+    pub(crate) fn mismatches(
+        projects: Vec<ProjectTotals>,
+        tasks: Vec<TaskTotals>,
+        mode: RoundingMode,
+    ) -> Self {
+        Self {
+            projects: projects
+                .into_iter()
+                .filter(|p| !p.is_consistent(mode))
+                .collect(),
+            tasks: tasks.into_iter().filter(|t| !t.is_consistent()).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty() && self.tasks.is_empty()
+    }
+}
+
+pub(crate) fn project_totals_sql<DB: ReportDialect>() -> String {
+    format!(
+        r#"SELECT p."ProjectId", p."ProjectName", {rate} AS "PayRate",
+        p."ProjectDuration" AS "StoredDuration", p."TotalPay" AS "StoredPay",
+        CAST(COALESCE(SUM({ms}), 0) AS BIGINT) AS "ActualDuration"
+        FROM Projects p
+        LEFT JOIN Clients c ON c."ClientId" = p."ClientId"
+        LEFT JOIN ProjectTasks t ON t."ProjectId" = p."ProjectId"
+        LEFT JOIN TaskTimes tt ON tt."TaskId" = t."TaskId"
+        GROUP BY p."ProjectId", p."ProjectName", p."ProjectDate", p."PayRate", c."PayRate",
+            p."ProjectDuration", p."TotalPay"
+        ORDER BY p."ProjectDate", p."ProjectName""#,
+        rate = PAY_RATE,
+        ms = DB::MILLISECONDS
+    )
+}
+
+pub(crate) fn task_totals_sql<DB: ReportDialect>() -> String {
+    format!(
+        r#"SELECT t."TaskId", t."ProjectId", t."TaskName",
+        t."TaskDuration" AS "StoredDuration",
+        CAST(COALESCE(SUM({}), 0) AS BIGINT) AS "ActualDuration"
+        FROM ProjectTasks t
+        LEFT JOIN TaskTimes tt ON tt."TaskId" = t."TaskId"
+        GROUP BY t."TaskId", t."ProjectId", t."TaskName", t."TaskDuration"
+        ORDER BY t."ProjectId", t."TaskName""#,
+        DB::MILLISECONDS
+    )
+}
+
+pub(crate) const UPDATE_PROJECT_SQL: &str =
+    r#"UPDATE Projects SET "ProjectDuration" = $1, "TotalPay" = $2 WHERE "ProjectId" = $3"#;
+
+pub(crate) const UPDATE_PAY_SQL: &str =
+    r#"UPDATE Projects SET "TotalPay" = $1 WHERE "ProjectId" = $2"#;
+
+pub(crate) const UPDATE_TASK_SQL: &str =
+    r#"UPDATE ProjectTasks SET "TaskDuration" = $1 WHERE "TaskId" = $2"#;
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeDelta};
+
+    use super::*;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::task_time::TaskTime;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Error, Project};
+
+    // A project with one task holding two TaskTimes of 45 and 50 minutes,
+    // stored with the given totals
+    async fn setup(project_duration: i64, task_duration: i64) -> Result<DbiDatabase, Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        let mut db = DbiDatabase::new(config).await?;
+        let start = NaiveDate::from_ymd_opt(2024, 8, 10)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let project = Project {
+            project_id: make_uuid(&"Reconcile".to_string()),
+            project_name: "Reconcile".to_string(),
+            project_date: start.date(),
+            pay_rate: Money::from(60),
+            project_duration,
+            total_pay: total_pay(project_duration, Money::from(60), RoundingMode::HalfUp),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        let task = ProjectTask {
+            task_id: make_uuid(&"Reconcile Task".to_string()),
+            project_id: project.project_id,
+            task_name: "Task".to_string(),
+            task_duration,
+            task_date_time: start,
+            notes: None,
+        };
+        db.insert(&task).await?;
+        for (offset, minutes) in [(0, 45), (60, 50)] {
+            let start_time = start + TimeDelta::minutes(offset);
+            let task_time = TaskTime {
+                task_id: task.task_id,
+                start_time,
+                end_time: Some(start_time + TimeDelta::minutes(minutes)),
+                ..Default::default()
+            };
+            db.insert(&task_time).await?;
+        }
+        Ok(db)
+    }
+
+    #[tokio::test]
+    async fn test_verify_consistent_totals() -> Result<(), Error> {
+        let duration = TimeDelta::minutes(95).num_milliseconds();
+        let mut db = setup(duration, duration).await?;
+        assert!(db.verify().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_reconcile() -> Result<(), Error> {
+        let duration = TimeDelta::minutes(95).num_milliseconds();
+        let mut db = setup(duration, duration).await?;
+
+        // Edit a TaskTime so the stored totals are stale
+        let mut times = db.fetch_all::<TaskTime>().await?;
+        times[1].end_time = times[1].end_time.map(|end| end + TimeDelta::minutes(10));
+        db.update(&times[1]).await?;
+
+        let report = db.verify().await?;
+        assert_eq!(report.projects.len(), 1);
+        assert_eq!(report.tasks.len(), 1);
+        let project = &report.projects[0];
+        assert_eq!(project.stored_duration, duration);
+        assert_eq!(
+            project.actual_duration,
+            TimeDelta::minutes(105).num_milliseconds()
+        );
+        assert_eq!(project.stored_pay, Money::from(95));
+        assert_eq!(project.actual_pay(RoundingMode::HalfUp), Money::from(105));
+
+        let fixed = db.reconcile().await?;
+        assert_eq!(fixed, report);
+        assert!(db.verify().await?.is_empty());
+
+        let projects = db.fetch_all::<Project>().await?;
+        assert_eq!(projects[0].project_duration, project.actual_duration);
+        assert_eq!(projects[0].total_pay, Money::from(105));
+        let tasks = db.fetch_all::<ProjectTask>().await?;
+        assert_eq!(tasks[0].task_duration, project.actual_duration);
+        Ok(())
+    }
+}
@@ -0,0 +1,747 @@
+// database/report.rs
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Encode, FromRow, Postgres, QueryBuilder, Sqlite, Type};
+use uuid::Uuid;
+
+use crate::guid::{Guid, OptionalGuid};
+use crate::model::tag::Tag;
+use crate::money::{Money, RoundingMode};
+
+///
+/// The length of the periods a report is grouped into. Weeks are ISO
+/// weeks starting on Monday.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+impl Period {
+    ///
+    /// A label for the period starting on `start`: "2024-08-10" for a
+    /// day, "2024-W32" for an ISO week and "2024-08" for a month.
+    ///
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.format("%Y-%m-%d").to_string(),
+            Period::Week => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Period::Month => start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+///
+/// The dates a report covers, inclusive at both ends. A missing end
+/// leaves the range open on that side. With a tag, only the times of
+/// tasks that have the tag are counted.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReportRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub tag_id: Option<Uuid>,
+}
+
+impl ReportRange {
+    /// Every TaskTime in the database
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn new(from: NaiveDate, to: NaiveDate) -> Self {
+        Self {
+            from: Some(from),
+            to: Some(to),
+            tag_id: None,
+        }
+    }
+
+    /// The calendar month, or None when the month is not valid
+    pub fn month(year: i32, month: u32) -> Option<Self> {
+        let from = NaiveDate::from_ymd_opt(year, month, 1)?;
+        let to = from
+            .checked_add_months(chrono::Months::new(1))?
+            .pred_opt()?;
+        Some(Self::new(from, to))
+    }
+
+    /// Only the tasks tagged with the name
+    pub fn tag(mut self, name: &str) -> Self {
+        self.tag_id = Some(Tag::id_for(name));
+        self
+    }
+}
+
+/// Hours worked and pay earned on one project in one period
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct ProjectHours {
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub period_start: NaiveDate,
+    pub hours: f64,
+    pub pay: Money,
+}
+
+/// Hours worked and pay earned across all projects in one period
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct PeriodEarnings {
+    pub period_start: NaiveDate,
+    pub hours: f64,
+    pub pay: Money,
+}
+
+/// Hours worked on every task with the same name, whichever project it is in
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct TaskNameHours {
+    pub task_name: String,
+    pub project_count: i64,
+    pub hours: f64,
+    pub pay: Money,
+}
+
+/// Hours and pay for one client's projects. Projects without a client
+/// are summed in a row whose client_id and client_name are None.
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct ClientEarnings {
+    #[sqlx(try_from = "OptionalGuid")]
+    pub client_id: Option<Uuid>,
+    pub client_name: Option<String>,
+    pub hours: f64,
+    pub pay: Money,
+}
+
+/// Hours and pay for the tasks with one tag
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct TagHours {
+    pub tag_name: String,
+    pub task_count: i64,
+    pub hours: f64,
+    pub pay: Money,
+}
+
+/// A project's hours and the pay they earned
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct ProjectRevenue {
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub project_name: String,
+    pub hours: f64,
+    pub revenue: Money,
+}
+
+///
+/// The SQL that differs between the backends. Both expressions work on
+/// the aliases `p` (Projects) and `tt` (TaskTimes).
+///
+pub(crate) trait ReportDialect {
+    /// The length of a TaskTime in milliseconds
+    const MILLISECONDS: &'static str;
+
+    /// The first day of the period holding the TaskTime's StartTime
+    fn period_start(period: Period) -> &'static str;
+
+    /// The exact sum over the group of each TaskTime's milliseconds times
+    /// its rate, as a column that decodes as `Money`
+    fn rate_milliseconds() -> String;
+
+    /// ORDER BY terms that put the group paid the most first
+    fn most_pay_first() -> String;
+}
+
+impl ReportDialect for Sqlite {
+    const MILLISECONDS: &'static str =
+        r#"ROUND((julianday(tt."EndTime") - julianday(tt."StartTime")) * 86400000.0)"#;
+
+    fn period_start(period: Period) -> &'static str {
+        match period {
+            Period::Day => r#"date(tt."StartTime")"#,
+            Period::Week => r#"date(tt."StartTime", '-6 days', 'weekday 1')"#,
+            Period::Month => r#"date(tt."StartTime", 'start of month')"#,
+        }
+    }
+
+    // The whole part plus the millionths, written out as a decimal
+    fn rate_milliseconds() -> String {
+        let (whole, millionths) = sqlite_rate_sums();
+        format!(
+            "({whole} + {millionths} / 1000000) || '.' || substr('00000' || ({millionths} % 1000000), -6)"
+        )
+    }
+
+    fn most_pay_first() -> String {
+        let (whole, millionths) = sqlite_rate_sums();
+        format!("{whole} + {millionths} / 1000000 DESC, {millionths} % 1000000 DESC")
+    }
+}
+
+impl ReportDialect for Postgres {
+    const MILLISECONDS: &'static str =
+        r#"EXTRACT(EPOCH FROM (tt."EndTime" - tt."StartTime")) * 1000"#;
+
+    fn period_start(period: Period) -> &'static str {
+        match period {
+            Period::Day => r#"CAST(date_trunc('day', tt."StartTime") AS DATE)"#,
+            Period::Week => r#"CAST(date_trunc('week', tt."StartTime") AS DATE)"#,
+            Period::Month => r#"CAST(date_trunc('month', tt."StartTime") AS DATE)"#,
+        }
+    }
+
+    fn rate_milliseconds() -> String {
+        format!(
+            "SUM(CAST({} AS NUMERIC) * {})",
+            Self::MILLISECONDS,
+            PAY_RATE
+        )
+    }
+
+    fn most_pay_first() -> String {
+        format!("{} DESC", Self::rate_milliseconds())
+    }
+}
+
+// PAY_RATE as SQLite stores it, in TEXT
+const SQLITE_PAY_RATE: &str = r#"CASE WHEN CAST(p."PayRate" AS NUMERIC) <> 0 THEN p."PayRate"
+    ELSE COALESCE(c."PayRate", '0') END"#;
+
+// SQLite has no decimal arithmetic, and multiplying by a rate as a REAL
+// can land a half cent on the wrong side. So the rate's whole part and its
+// first six decimal places, in millionths, are each multiplied by the
+// milliseconds and summed as exact INTEGERs.
+fn sqlite_rate_sums() -> (String, String) {
+    let ms = format!("CAST({} AS INTEGER)", Sqlite::MILLISECONDS);
+    let rate = SQLITE_PAY_RATE;
+    let whole = format!("SUM({ms} * CAST({rate} AS INTEGER))");
+    let millionths = format!(
+        "SUM({ms} * CAST(substr(substr({rate}, instr({rate} || '.', '.') + 1) || '000000', 1, 6) AS INTEGER))"
+    );
+    (whole, millionths)
+}
+
+// The "Hours" column, and the pay column holding the group's milliseconds
+// times their rates. `round_pay` turns that into the pay.
+fn totals<DB: ReportDialect>(pay_alias: &str) -> String {
+    format!(
+        r#"CAST(SUM({ms}) / 3600000.0 AS DOUBLE PRECISION) AS "Hours",
+        {rate_ms} AS "{pay_alias}""#,
+        ms = DB::MILLISECONDS,
+        rate_ms = DB::rate_milliseconds()
+    )
+}
+
+///
+/// The rate a project is paid at: its own, or its client's when the
+/// project's is 0. Needs the aliases `p` (Projects) and `c` (Clients).
+/// SQLite stores money as TEXT, so the rates are cast to compare and
+/// multiply them as numbers.
+///
+pub(crate) const PAY_RATE: &str =
+    r#"COALESCE(NULLIF(CAST(p."PayRate" AS NUMERIC), 0), CAST(c."PayRate" AS NUMERIC), 0)"#;
+
+///
+/// Turn the milliseconds times rate in each report row into pay, rounded
+/// to cents.
+///
+pub(crate) fn round_pay<T>(rows: &mut [T], mode: RoundingMode, pay: impl Fn(&mut T) -> &mut Money) {
+    for row in rows {
+        let amount = pay(row);
+        *amount = Money(amount.0 / Decimal::from(3_600_000)).round(mode);
+    }
+}
+
+const FROM_TASK_TIMES: &str = r#" FROM TaskTimes tt
+    JOIN ProjectTasks t ON t."TaskId" = tt."TaskId"
+    JOIN Projects p ON p."ProjectId" = t."ProjectId"
+    LEFT JOIN Clients c ON c."ClientId" = p."ClientId"
+    WHERE tt."EndTime" IS NOT NULL"#;
+
+// Keep the TaskTimes that start inside the range, of tasks with its tag
+fn push_range<'args, DB>(qb: &mut QueryBuilder<'args, DB>, range: &ReportRange)
+where
+    DB: Database,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    if let Some(tag_id) = range.tag_id {
+        qb.push(
+            r#" AND EXISTS (SELECT 1 FROM TaskTags x WHERE x."TaskId" = t."TaskId" AND x."TagId" = "#,
+        );
+        qb.push_bind(Guid::from(tag_id));
+        qb.push(")");
+    }
+    if let Some(date) = range.from {
+        qb.push(r#" AND tt."StartTime" >= "#);
+        qb.push_bind(NaiveDateTime::new(date, NaiveTime::MIN));
+    }
+    if let Some(date) = range.to {
+        qb.push(r#" AND tt."StartTime" < "#);
+        qb.push_bind(NaiveDateTime::new(
+            date + TimeDelta::days(1),
+            NaiveTime::MIN,
+        ));
+    }
+}
+
+pub(crate) fn project_hours_query<'args, DB>(
+    period: Period,
+    range: &ReportRange,
+) -> QueryBuilder<'args, DB>
+where
+    DB: Database + ReportDialect,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT p."ProjectId", p."ProjectName", {} AS "PeriodStart", {}"#,
+        DB::period_start(period),
+        totals::<DB>("Pay")
+    ));
+    qb.push(FROM_TASK_TIMES);
+    push_range(&mut qb, range);
+    qb.push(r#" GROUP BY p."ProjectId", p."ProjectName", "PeriodStart""#);
+    qb.push(r#" ORDER BY "PeriodStart", p."ProjectName""#);
+    qb
+}
+
+pub(crate) fn period_earnings_query<'args, DB>(
+    period: Period,
+    range: &ReportRange,
+) -> QueryBuilder<'args, DB>
+where
+    DB: Database + ReportDialect,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT {} AS "PeriodStart", {}"#,
+        DB::period_start(period),
+        totals::<DB>("Pay")
+    ));
+    qb.push(FROM_TASK_TIMES);
+    push_range(&mut qb, range);
+    qb.push(r#" GROUP BY "PeriodStart" ORDER BY "PeriodStart""#);
+    qb
+}
+
+pub(crate) fn task_name_hours_query<'args, DB>(range: &ReportRange) -> QueryBuilder<'args, DB>
+where
+    DB: Database + ReportDialect,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT t."TaskName", COUNT(DISTINCT p."ProjectId") AS "ProjectCount", {}"#,
+        totals::<DB>("Pay")
+    ));
+    qb.push(FROM_TASK_TIMES);
+    push_range(&mut qb, range);
+    qb.push(r#" GROUP BY t."TaskName" ORDER BY "Hours" DESC, t."TaskName""#);
+    qb
+}
+
+pub(crate) fn client_earnings_query<'args, DB>(range: &ReportRange) -> QueryBuilder<'args, DB>
+where
+    DB: Database + ReportDialect,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT c."ClientId", c."ClientName", {}"#,
+        totals::<DB>("Pay")
+    ));
+    qb.push(FROM_TASK_TIMES);
+    push_range(&mut qb, range);
+    qb.push(r#" GROUP BY c."ClientId", c."ClientName""#);
+    qb.push(format!(
+        r#" ORDER BY {}, c."ClientName""#,
+        DB::most_pay_first()
+    ));
+    qb
+}
+
+///
+/// Every TaskTime in the range with what `billing::charge` needs to know
+/// about its task and project, oldest first. With `unbilled_for`, only the
+/// client's TaskTimes that are not yet on an invoice.
+///
+pub(crate) fn work_entries_query<'args, DB>(
+    range: &ReportRange,
+    unbilled_for: Option<Uuid>,
+) -> QueryBuilder<'args, DB>
+where
+    DB: Database,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT tt."TaskTimeId", p."ProjectId", p."ProjectName", p."ClientId",
+        t."TaskId", t."TaskName", tt."StartTime", tt."EndTime", {} AS "PayRate",
+        p."Currency""#,
+        PAY_RATE
+    ));
+    qb.push(FROM_TASK_TIMES);
+    push_range(&mut qb, range);
+    if let Some(client_id) = unbilled_for {
+        qb.push(r#" AND tt."InvoiceId" IS NULL AND p."ClientId" = "#);
+        qb.push_bind(Guid::from(client_id));
+    }
+    qb.push(r#" ORDER BY tt."StartTime", tt."TaskTimeId""#);
+    qb
+}
+
+pub(crate) fn tag_hours_query<'args, DB>(range: &ReportRange) -> QueryBuilder<'args, DB>
+where
+    DB: Database + ReportDialect,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT g."TagName", COUNT(DISTINCT t."TaskId") AS "TaskCount", {}
+        FROM TaskTimes tt
+        JOIN ProjectTasks t ON t."TaskId" = tt."TaskId"
+        JOIN TaskTags x ON x."TaskId" = t."TaskId"
+        JOIN Tags g ON g."TagId" = x."TagId"
+        JOIN Projects p ON p."ProjectId" = t."ProjectId"
+        LEFT JOIN Clients c ON c."ClientId" = p."ClientId"
+        WHERE tt."EndTime" IS NOT NULL"#,
+        totals::<DB>("Pay")
+    ));
+    push_range(&mut qb, range);
+    qb.push(r#" GROUP BY g."TagName" ORDER BY "Hours" DESC, g."TagName""#);
+    qb
+}
+
+pub(crate) fn top_projects_query<'args, DB>(
+    limit: i64,
+    range: &ReportRange,
+) -> QueryBuilder<'args, DB>
+where
+    DB: Database + ReportDialect,
+    NaiveDateTime: Encode<'args, DB> + Type<DB>,
+    Guid: Encode<'args, DB> + Type<DB>,
+    i64: Encode<'args, DB> + Type<DB>,
+{
+    let mut qb = QueryBuilder::new(format!(
+        r#"SELECT p."ProjectId", p."ProjectName", {}"#,
+        totals::<DB>("Revenue")
+    ));
+    qb.push(FROM_TASK_TIMES);
+    push_range(&mut qb, range);
+    qb.push(r#" GROUP BY p."ProjectId", p."ProjectName""#);
+    qb.push(format!(
+        r#" ORDER BY {}, p."ProjectName" LIMIT "#,
+        DB::most_pay_first()
+    ));
+    qb.push_bind(limit);
+    qb
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+    use rust_decimal_macros::dec;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::model::client::Client;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::task_time::TaskTime;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Error, Project};
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    async fn add_project(
+        db: &mut DbiDatabase,
+        name: &str,
+        pay_rate: i64,
+        task_name: &str,
+        times: &[(NaiveDateTime, i64)],
+    ) -> Result<Uuid, Error> {
+        let project = Project {
+            project_id: make_uuid(&name.to_string()),
+            project_name: name.to_string(),
+            project_date: times[0].0.date(),
+            pay_rate: Money::from(pay_rate),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        let task = ProjectTask {
+            task_id: make_uuid(&format!("{}{}", name, task_name)),
+            project_id: project.project_id,
+            task_name: task_name.to_string(),
+            task_date_time: times[0].0,
+            ..Default::default()
+        };
+        db.insert(&task).await?;
+        for (start_time, minutes) in times {
+            let task_time = TaskTime {
+                task_id: task.task_id,
+                start_time: *start_time,
+                end_time: Some(*start_time + TimeDelta::minutes(*minutes)),
+                ..Default::default()
+            };
+            db.insert(&task_time).await?;
+        }
+        Ok(project.project_id)
+    }
+
+    // Two projects, one task each, spread over July and August 2024
+    async fn setup() -> Result<DbiDatabase, Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        let mut db = DbiDatabase::new(config).await?;
+        let at = |month, day, hour| date(month, day).and_hms_opt(hour, 0, 0).unwrap();
+        add_project(
+            &mut db,
+            "Diary",
+            40,
+            "Design",
+            &[(at(7, 31, 9), 90), (at(8, 5, 9), 60), (at(8, 6, 9), 30)],
+        )
+        .await?;
+        add_project(&mut db, "Garden", 20, "Design", &[(at(8, 5, 14), 120)]).await?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_period_label() {
+        assert_eq!(Period::Day.label(date(8, 10)), "2024-08-10");
+        assert_eq!(Period::Week.label(date(8, 5)), "2024-W32");
+        assert_eq!(Period::Month.label(date(8, 1)), "2024-08");
+        assert_eq!(ReportRange::month(2024, 2).unwrap().to, Some(date(2, 29)));
+        assert_eq!(ReportRange::month(2024, 13), None);
+    }
+
+    #[tokio::test]
+    async fn test_project_hours_by_week() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let rows = db.project_hours(Period::Week, &ReportRange::all()).await?;
+        let actual: Vec<(String, String, f64, Money)> = rows
+            .iter()
+            .map(|r| {
+                (
+                    Period::Week.label(r.period_start),
+                    r.project_name.clone(),
+                    r.hours,
+                    r.pay,
+                )
+            })
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                (
+                    "2024-W31".to_string(),
+                    "Diary".to_string(),
+                    1.5,
+                    Money::from(60)
+                ),
+                (
+                    "2024-W32".to_string(),
+                    "Diary".to_string(),
+                    1.5,
+                    Money::from(60)
+                ),
+                (
+                    "2024-W32".to_string(),
+                    "Garden".to_string(),
+                    2.0,
+                    Money::from(40)
+                ),
+            ]
+        );
+        assert_eq!(rows[0].period_start, date(7, 29));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_period_earnings() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let rows = db
+            .period_earnings(Period::Month, &ReportRange::all())
+            .await?;
+        assert_eq!(
+            rows,
+            vec![
+                PeriodEarnings {
+                    period_start: date(7, 1),
+                    hours: 1.5,
+                    pay: Money::from(60)
+                },
+                PeriodEarnings {
+                    period_start: date(8, 1),
+                    hours: 3.5,
+                    pay: Money::from(100)
+                },
+            ]
+        );
+
+        let august = ReportRange::month(2024, 8).unwrap();
+        let rows = db.period_earnings(Period::Day, &august).await?;
+        let days: Vec<NaiveDate> = rows.iter().map(|r| r.period_start).collect();
+        assert_eq!(days, vec![date(8, 5), date(8, 6)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_name_hours_and_top_projects() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let rows = db.task_name_hours(&ReportRange::all()).await?;
+        assert_eq!(
+            rows,
+            vec![TaskNameHours {
+                task_name: "Design".to_string(),
+                project_count: 2,
+                hours: 5.0,
+                pay: Money::from(160)
+            }]
+        );
+
+        let rows = db.top_projects(1, &ReportRange::all()).await?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].project_name, "Diary");
+        assert_eq!(rows[0].revenue, Money::from(120));
+
+        let august = ReportRange::month(2024, 8).unwrap();
+        let rows = db.top_projects(5, &august).await?;
+        let names: Vec<&str> = rows.iter().map(|r| r.project_name.as_str()).collect();
+        assert_eq!(names, vec!["Diary", "Garden"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pay_is_rounded_from_exact_cents() -> Result<(), Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        let mut db = DbiDatabase::new(config).await?;
+        let at = date(8, 1).and_hms_opt(9, 0, 0).unwrap();
+        let project_id = add_project(&mut db, "Fence", 0, "Paint", &[(at, 90)]).await?;
+        let mut project = Project {
+            project_id,
+            ..Default::default()
+        };
+        db.fetch_one(&mut project).await?;
+        project.pay_rate = Money(dec!(20.15));
+        db.update(&project).await?;
+
+        // 1.5 hours at 20.15 is 30.225, which floating point makes 30.2249...
+        let rows = db.project_hours(Period::Month, &ReportRange::all()).await?;
+        assert_eq!(rows[0].pay, Money(dec!(30.23)));
+        let rows = db.top_projects(1, &ReportRange::all()).await?;
+        assert_eq!(rows[0].revenue, Money(dec!(30.23)));
+
+        // And from a client's rate, 0.525
+        let client = Client {
+            client_id: make_uuid(&"Neighbour".to_string()),
+            client_name: "Neighbour".to_string(),
+            pay_rate: Some(Money(dec!(0.35))),
+        };
+        db.insert(&client).await?;
+        project.pay_rate = Money::ZERO;
+        project.client_id = Some(client.client_id);
+        db.update(&project).await?;
+        let rows = db.client_earnings(&ReportRange::all()).await?;
+        assert_eq!(rows[0].pay, Money(dec!(0.53)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_earnings() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let client = Client {
+            client_id: make_uuid(&"Acme".to_string()),
+            client_name: "Acme".to_string(),
+            pay_rate: Some(Money::from(30)),
+        };
+        db.insert(&client).await?;
+        let at = date(8, 7).and_hms_opt(9, 0, 0).unwrap();
+        let project_id = add_project(&mut db, "Shed", 0, "Build", &[(at, 60)]).await?;
+        let mut project = Project {
+            project_id,
+            ..Default::default()
+        };
+        db.fetch_one(&mut project).await?;
+        project.client_id = Some(client.client_id);
+        db.update(&project).await?;
+
+        let rows = db.client_earnings(&ReportRange::all()).await?;
+        assert_eq!(
+            rows,
+            vec![
+                ClientEarnings {
+                    client_id: None,
+                    client_name: None,
+                    hours: 5.0,
+                    pay: Money::from(160)
+                },
+                ClientEarnings {
+                    client_id: Some(client.client_id),
+                    client_name: Some("Acme".to_string()),
+                    hours: 1.0,
+                    pay: Money::from(30)
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tagged_hours() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let diary_task = make_uuid(&"DiaryDesign".to_string());
+        let garden_task = make_uuid(&"GardenDesign".to_string());
+        db.tag_task(diary_task, &["Meetings"]).await?;
+        db.tag_task(garden_task, &["travel"]).await?;
+
+        // All hours tagged meetings in August
+        let august = ReportRange::month(2024, 8).unwrap().tag("meetings");
+        let rows = db.project_hours(Period::Month, &august).await?;
+        let actual: Vec<(&str, f64, Money)> = rows
+            .iter()
+            .map(|r| (r.project_name.as_str(), r.hours, r.pay))
+            .collect();
+        assert_eq!(actual, vec![("Diary", 1.5, Money::from(60))]);
+        let all = ReportRange::all().tag("Meetings");
+        assert_eq!(db.task_name_hours(&all).await?[0].hours, 3.0);
+        assert!(db
+            .period_earnings(Period::Month, &ReportRange::all().tag("none"))
+            .await?
+            .is_empty());
+
+        let rows = db.tag_hours(&ReportRange::month(2024, 8).unwrap()).await?;
+        assert_eq!(
+            rows,
+            vec![
+                TagHours {
+                    tag_name: "travel".to_string(),
+                    task_count: 1,
+                    hours: 2.0,
+                    pay: Money::from(40)
+                },
+                TagHours {
+                    tag_name: "meetings".to_string(),
+                    task_count: 1,
+                    hours: 1.5,
+                    pay: Money::from(60)
+                },
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+// database/text_type.rs
+
+///
+/// Store a type as the text of its `as_str`, reading it back with its
+/// `FromStr`. Used for the enums kept in VARCHAR columns.
+///
+macro_rules! text_type {
+    ($ty:ty) => {
+        impl ::sqlx::Type<::sqlx::Sqlite> for $ty {
+            fn type_info() -> ::sqlx::sqlite::SqliteTypeInfo {
+                <String as ::sqlx::Type<::sqlx::Sqlite>>::type_info()
+            }
+        }
+
+        impl<'q> ::sqlx::Encode<'q, ::sqlx::Sqlite> for $ty {
+            fn encode_by_ref(
+                &self,
+                args: &mut Vec<::sqlx::sqlite::SqliteArgumentValue<'q>>,
+            ) -> Result<::sqlx::encode::IsNull, ::sqlx::error::BoxDynError> {
+                <&str as ::sqlx::Encode<::sqlx::Sqlite>>::encode(self.as_str(), args)
+            }
+        }
+
+        impl<'r> ::sqlx::Decode<'r, ::sqlx::Sqlite> for $ty {
+            fn decode(
+                value: ::sqlx::sqlite::SqliteValueRef<'r>,
+            ) -> Result<Self, ::sqlx::error::BoxDynError> {
+                Ok(<&str as ::sqlx::Decode<::sqlx::Sqlite>>::decode(value)?.parse()?)
+            }
+        }
+
+        impl ::sqlx::Type<::sqlx::Postgres> for $ty {
+            fn type_info() -> ::sqlx::postgres::PgTypeInfo {
+                <String as ::sqlx::Type<::sqlx::Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &::sqlx::postgres::PgTypeInfo) -> bool {
+                <String as ::sqlx::Type<::sqlx::Postgres>>::compatible(ty)
+            }
+        }
+
+        impl ::sqlx::Encode<'_, ::sqlx::Postgres> for $ty {
+            fn encode_by_ref(
+                &self,
+                buf: &mut ::sqlx::postgres::PgArgumentBuffer,
+            ) -> Result<::sqlx::encode::IsNull, ::sqlx::error::BoxDynError> {
+                <&str as ::sqlx::Encode<::sqlx::Postgres>>::encode(self.as_str(), buf)
+            }
+        }
+
+        impl<'r> ::sqlx::Decode<'r, ::sqlx::Postgres> for $ty {
+            fn decode(
+                value: ::sqlx::postgres::PgValueRef<'r>,
+            ) -> Result<Self, ::sqlx::error::BoxDynError> {
+                Ok(<&str as ::sqlx::Decode<::sqlx::Postgres>>::decode(value)?.parse()?)
+            }
+        }
+    };
+}
+
+pub(crate) use text_type;
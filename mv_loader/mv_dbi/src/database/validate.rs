@@ -0,0 +1,131 @@
+// database/validate.rs
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use sqlx::{ColumnIndex, Database, Decode, Encode, Executor, IntoArguments, Type};
+use uuid::Uuid;
+
+use crate::config::{OverlapScope, ValidationRules};
+use crate::database::query::{DbTable, PageKey};
+use crate::error::Error;
+use crate::guid::Guid;
+
+///
+/// The end of the span of a row that has no end yet, such as a running
+/// timer. It overlaps everything that starts after it.
+///
+pub const OPEN_END: NaiveDateTime = match NaiveDate::from_ymd_opt(9999, 12, 31) {
+    Some(date) => date.and_time(NaiveTime::MIN),
+    None => panic!("Invalid OPEN_END"),
+};
+
+///
+/// The time a row covers. `group` is the parent key the row is compared
+/// within when overlaps are checked per task.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSpan {
+    /// The model and key the row is named by in errors
+    pub entity: &'static str,
+    pub id: String,
+    pub group: Uuid,
+    pub start_column: &'static str,
+    pub end_column: &'static str,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+///
+/// Checks `DbiDatabase` runs before a row is inserted or updated.
+///
+/// derive(DbObject) implements it with no checks unless the model is
+/// marked `#[dbobject(validate)]`, in which case the model implements it.
+///
+pub trait Validate {
+    /// Check the row on its own, returning a Validation error
+    fn validate(&self, _rules: &ValidationRules) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The time the row covers, for models whose rows may not overlap
+    fn time_span(&self) -> Option<TimeSpan> {
+        None
+    }
+}
+
+///
+/// The query for the keys of the stored rows that overlap a span.
+///
+pub(crate) struct OverlapQuery {
+    sql: String,
+    by_group: bool,
+}
+
+impl OverlapQuery {
+    ///
+    /// `$1` and `$2` are the span's end and start, `$3` the row's own key
+    /// and `$4` its group. A stored row with no end runs on forever. None
+    /// when overlaps are allowed.
+    ///
+    pub(crate) fn new<T: DbTable>(span: &TimeSpan, scope: OverlapScope) -> Option<Self> {
+        let by_group = match (scope, T::PARENT) {
+            (OverlapScope::Off, _) => return None,
+            (OverlapScope::Task, Some(_)) => true,
+            (OverlapScope::Task, None) | (OverlapScope::Global, _) => false,
+        };
+        let mut sql = format!(
+            r#"SELECT "{key}" FROM {table} WHERE "{start}" < $1 AND ("{end}" > $2 OR "{end}" IS NULL) AND "{key}" <> $3"#,
+            key = T::KEY,
+            table = T::TABLE,
+            start = span.start_column,
+            end = span.end_column,
+        );
+        if let (true, Some(parent)) = (by_group, T::PARENT) {
+            sql.push_str(&format!(r#" AND "{}" = $4"#, parent));
+        }
+        sql.push_str(&format!(r#" ORDER BY "{}""#, T::KEY));
+        Some(Self { sql, by_group })
+    }
+
+    ///
+    /// Run the query, returning the keys of the overlapping rows.
+    ///
+    pub(crate) async fn find<'e, DB, E>(
+        &'e self,
+        executor: E,
+        span: &TimeSpan,
+        key: PageKey,
+    ) -> Result<Vec<String>, sqlx::Error>
+    where
+        DB: Database,
+        E: Executor<'e, Database = DB>,
+        for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+        for<'q> NaiveDateTime: Encode<'q, DB> + Type<DB>,
+        for<'q> Guid: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+        for<'q> i64: Encode<'q, DB> + Decode<'q, DB> + Type<DB>,
+        usize: ColumnIndex<DB::Row>,
+    {
+        match key {
+            PageKey::Id(id) => {
+                let mut query = sqlx::query_scalar::<DB, i64>(&self.sql)
+                    .bind(span.end)
+                    .bind(span.start)
+                    .bind(id as i64);
+                if self.by_group {
+                    query = query.bind(Guid::from(span.group));
+                }
+                let keys = query.fetch_all(executor).await?;
+                Ok(keys.iter().map(i64::to_string).collect())
+            }
+            PageKey::Uuid(id) => {
+                let mut query = sqlx::query_scalar::<DB, Guid>(&self.sql)
+                    .bind(span.end)
+                    .bind(span.start)
+                    .bind(Guid::from(id));
+                if self.by_group {
+                    query = query.bind(Guid::from(span.group));
+                }
+                let keys = query.fetch_all(executor).await?;
+                Ok(keys.iter().map(Guid::to_string).collect())
+            }
+        }
+    }
+}
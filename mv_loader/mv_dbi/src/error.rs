@@ -0,0 +1,364 @@
+// error.rs
+use std::fmt;
+
+use sqlx::error::{DatabaseError, ErrorKind};
+use sqlx::migrate::MigrateError;
+use sqlx::postgres::PgDatabaseError;
+
+///
+/// Everything the server said about a failed statement, so callers can
+/// print it without digging through `sqlx::Error`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DbDetails {
+    pub code: Option<String>,
+    pub message: String,
+    pub table: Option<String>,
+    pub constraint: Option<String>,
+    /// PostgreSQL only
+    pub detail: Option<String>,
+    /// PostgreSQL only
+    pub hint: Option<String>,
+    /// PostgreSQL only
+    pub column: Option<String>,
+}
+
+impl DbDetails {
+    fn from_database_error(e: &dyn DatabaseError) -> Self {
+        let mut details = DbDetails {
+            code: e.code().map(|c| c.into_owned()),
+            message: e.message().to_owned(),
+            table: e.table().map(str::to_owned),
+            constraint: e.constraint().map(str::to_owned),
+            ..Default::default()
+        };
+        if let Some(pgerr) = e.try_downcast_ref::<PgDatabaseError>() {
+            details.detail = pgerr.detail().map(str::to_owned);
+            details.hint = pgerr.hint().map(str::to_owned);
+            details.column = pgerr.column().map(str::to_owned);
+        }
+        details
+    }
+}
+
+impl fmt::Display for DbDetails {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Database Error {{")?;
+        writeln!(f, "    Code       : {:?}", self.code)?;
+        writeln!(f, "    Message    : {}", self.message)?;
+        writeln!(f, "    Table      : {:?}", self.table)?;
+        writeln!(f, "    Constraint : {:?}", self.constraint)?;
+        if self.detail.is_some() || self.hint.is_some() || self.column.is_some() {
+            writeln!(f, "    Details    : {:?}", self.detail)?;
+            writeln!(f, "    Hint       : {:?}", self.hint)?;
+            writeln!(f, "    Column     : {:?}", self.column)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+// The codes the invoice triggers fail with: SQLITE_CONSTRAINT_TRIGGER
+// from RAISE(ABORT) and PostgreSQL's restrict_violation
+const LOCKED_CODES: [&str; 2] = ["1811", "23001"];
+
+///
+/// The errors returned by mv_dbi.
+///
+/// `entity` is the model involved ("Project", "ProjectTask", "TaskTime")
+/// and `id` is the key of the row being written or read. Both are empty
+/// when the failure did not involve a particular row.
+///
+#[derive(Debug)]
+pub enum Error {
+    /// The row to read does not exist
+    NotFound { entity: &'static str, id: String },
+    /// A row with the same key is already stored
+    Duplicate {
+        entity: &'static str,
+        id: String,
+        details: Box<DbDetails>,
+    },
+    /// The row refers to a parent that is not stored
+    ForeignKey {
+        entity: &'static str,
+        id: String,
+        details: Box<DbDetails>,
+    },
+    /// The row breaks a NOT NULL or CHECK rule, or a rule mv_dbi enforces
+    Validation {
+        entity: &'static str,
+        id: String,
+        message: String,
+    },
+    /// The row's time overlaps the stored rows with the keys in `others`
+    Overlap {
+        entity: &'static str,
+        id: String,
+        others: Vec<String>,
+    },
+    /// The row has been invoiced, or is part of an invoice, and cannot be
+    /// changed or deleted
+    Locked {
+        entity: &'static str,
+        id: String,
+        message: String,
+    },
+    /// The schema could not be brought up to date
+    Migration(MigrateError),
+    /// The database was migrated by a newer build of mv_dbi, whose schema
+    /// this one does not know
+    SchemaTooNew { version: i64, latest: i64 },
+    /// A setting in the DbConfig, its file or the environment is wrong
+    Configuration(String),
+    /// An export could not be written, or a file being imported could not
+    /// be read or is from a newer schema version
+    Export(String),
+    /// A backup, restore or integrity check could not be done, or the
+    /// backup being restored is damaged
+    Maintenance(String),
+    /// The database could not be opened or the connection was lost
+    Connection(sqlx::Error),
+    /// Any other failure reported by the database or the driver
+    Database {
+        entity: &'static str,
+        id: String,
+        source: sqlx::Error,
+    },
+}
+
+impl Error {
+    ///
+    /// Sort an sqlx error into one of the variants, recording the model
+    /// and key that were being worked on.
+    ///
+    pub fn classify(error: sqlx::Error, entity: &'static str, id: String) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => Error::NotFound { entity, id },
+            sqlx::Error::Migrate(e) => Error::Migration(*e),
+            sqlx::Error::Configuration(_)
+            | sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => Error::Connection(error),
+            sqlx::Error::Database(ref e) => {
+                let details = Box::new(DbDetails::from_database_error(e.as_ref()));
+                if e.code()
+                    .is_some_and(|code| LOCKED_CODES.contains(&code.as_ref()))
+                {
+                    return Error::Locked {
+                        entity,
+                        id,
+                        message: details.message,
+                    };
+                }
+                match e.kind() {
+                    ErrorKind::UniqueViolation => Error::Duplicate {
+                        entity,
+                        id,
+                        details,
+                    },
+                    ErrorKind::ForeignKeyViolation => Error::ForeignKey {
+                        entity,
+                        id,
+                        details,
+                    },
+                    ErrorKind::NotNullViolation | ErrorKind::CheckViolation => Error::Validation {
+                        entity,
+                        id,
+                        message: details.message,
+                    },
+                    _ => Error::Database {
+                        entity,
+                        id,
+                        source: error,
+                    },
+                }
+            }
+            _ => Error::Database {
+                entity,
+                id,
+                source: error,
+            },
+        }
+    }
+
+    /// The model the error was raised for, if any
+    pub fn entity(&self) -> Option<&'static str> {
+        match self {
+            Error::NotFound { entity, .. }
+            | Error::Duplicate { entity, .. }
+            | Error::ForeignKey { entity, .. }
+            | Error::Validation { entity, .. }
+            | Error::Overlap { entity, .. }
+            | Error::Locked { entity, .. }
+            | Error::Database { entity, .. } => (!entity.is_empty()).then_some(*entity),
+            Error::Migration(_)
+            | Error::SchemaTooNew { .. }
+            | Error::Configuration(_)
+            | Error::Export(_)
+            | Error::Maintenance(_)
+            | Error::Connection(_) => None,
+        }
+    }
+
+    /// The key of the row the error was raised for, if any
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Error::NotFound { id, .. }
+            | Error::Duplicate { id, .. }
+            | Error::ForeignKey { id, .. }
+            | Error::Validation { id, .. }
+            | Error::Overlap { id, .. }
+            | Error::Locked { id, .. }
+            | Error::Database { id, .. } => (!id.is_empty()).then_some(id.as_str()),
+            Error::Migration(_)
+            | Error::SchemaTooNew { .. }
+            | Error::Configuration(_)
+            | Error::Export(_)
+            | Error::Maintenance(_)
+            | Error::Connection(_) => None,
+        }
+    }
+}
+
+// "Project 1234" or just "Project" when there is no key
+fn write_subject(f: &mut fmt::Formatter, entity: &str, id: &str) -> fmt::Result {
+    match (entity.is_empty(), id.is_empty()) {
+        (true, _) => write!(f, "Row"),
+        (false, true) => write!(f, "{}", entity),
+        (false, false) => write!(f, "{} {}", entity, id),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFound { entity, id } => {
+                write_subject(f, entity, id)?;
+                write!(f, " was not found")
+            }
+            Error::Duplicate {
+                entity,
+                id,
+                details,
+            } => {
+                write_subject(f, entity, id)?;
+                write!(f, " already exists\n{}", details)
+            }
+            Error::ForeignKey {
+                entity,
+                id,
+                details,
+            } => {
+                write_subject(f, entity, id)?;
+                write!(f, " refers to a row that does not exist\n{}", details)
+            }
+            Error::Validation {
+                entity,
+                id,
+                message,
+            } => {
+                write_subject(f, entity, id)?;
+                write!(f, " is not valid: {}", message)
+            }
+            Error::Overlap { entity, id, others } => {
+                write_subject(f, entity, id)?;
+                write!(f, " overlaps {} {}", entity, others.join(", "))
+            }
+            Error::Locked {
+                entity,
+                id,
+                message,
+            } => {
+                write_subject(f, entity, id)?;
+                write!(f, " is locked: {}", message)
+            }
+            Error::Migration(e) => write!(f, "Migration failed: {}", e),
+            Error::SchemaTooNew { version, latest } => write!(
+                f,
+                "The database is at schema version {}, newer than {}, the latest this build knows",
+                version, latest
+            ),
+            Error::Configuration(message) => write!(f, "Bad database configuration: {}", message),
+            Error::Export(message) => write!(f, "Export failed: {}", message),
+            Error::Maintenance(message) => write!(f, "Maintenance failed: {}", message),
+            Error::Connection(e) => write!(f, "Database connection failed: {}", e),
+            Error::Database { entity, id, source } => {
+                write!(f, "Database error on ")?;
+                write_subject(f, entity, id)?;
+                match source {
+                    sqlx::Error::Database(e) => {
+                        write!(f, "\n{}", DbDetails::from_database_error(e.as_ref()))
+                    }
+                    _ => write!(f, ": {}", source),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Migration(e) => Some(e),
+            Error::Connection(e) | Error::Database { source: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Error::classify(error, "", String::new())
+    }
+}
+
+impl From<MigrateError> for Error {
+    fn from(error: MigrateError) -> Self {
+        Error::Migration(error)
+    }
+}
+
+///
+/// Attach the model and key being worked on to an sqlx result.
+///
+pub(crate) trait DbContext<T> {
+    fn context(self, entity: &'static str, id: impl fmt::Display) -> Result<T, Error>;
+}
+
+impl<T> DbContext<T> for Result<T, sqlx::Error> {
+    fn context(self, entity: &'static str, id: impl fmt::Display) -> Result<T, Error> {
+        self.map_err(|e| Error::classify(e, entity, id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_display() {
+        let error = Error::NotFound {
+            entity: "Project",
+            id: "42".to_string(),
+        };
+        assert_eq!(error.to_string(), "Project 42 was not found");
+        assert_eq!(error.entity(), Some("Project"));
+        assert_eq!(error.id(), Some("42"));
+
+        let error = Error::from(sqlx::Error::RowNotFound);
+        assert_eq!(error.to_string(), "Row was not found");
+        assert_eq!(error.entity(), None);
+
+        let error = Error::Overlap {
+            entity: "TaskTime",
+            id: "7".to_string(),
+            others: vec!["3".to_string(), "4".to_string()],
+        };
+        assert_eq!(error.to_string(), "TaskTime 7 overlaps TaskTime 3, 4");
+
+        let error = Error::from(sqlx::Error::PoolClosed);
+        assert!(matches!(error, Error::Connection(_)));
+    }
+}
@@ -0,0 +1,388 @@
+// export.rs
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::model::client::Client;
+use crate::model::invoice::Invoice;
+use crate::model::invoice_line::InvoiceLine;
+use crate::model::project_tree::ProjectTree;
+
+/// The version of the export layout written by this build. Files with a
+/// higher version cannot be imported.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How many projects of an NDJSON import are saved in each transaction
+pub const IMPORT_BATCH_SIZE: usize = 100;
+
+// How many projects an export reads at a time
+pub(crate) const EXPORT_PAGE_SIZE: u32 = 100;
+
+///
+/// How an export is written. JSON is one document holding everything;
+/// NDJSON has one record per line, so it can be written and read a
+/// project at a time.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+
+    /// The format a file's extension names: .ndjson or .jsonl for NDJSON,
+    /// anything else for JSON
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext)
+                if ext.eq_ignore_ascii_case("ndjson") || ext.eq_ignore_ascii_case("jsonl") =>
+            {
+                ExportFormat::Ndjson
+            }
+            _ => ExportFormat::Json,
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            _ => Err(Error::Configuration(format!(
+                "Unknown export format '{}', expected json or ndjson",
+                s
+            ))),
+        }
+    }
+}
+
+///
+/// Which layout an export was written with, and when.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExportHeader {
+    pub schema_version: u32,
+    pub exported_at: NaiveDateTime,
+}
+
+impl ExportHeader {
+    pub fn new(exported_at: NaiveDateTime) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            exported_at,
+        }
+    }
+
+    /// Fail when the file was written by a newer version of mv_dbi
+    pub fn check(&self) -> Result<(), Error> {
+        if self.schema_version > SCHEMA_VERSION {
+            return Err(Error::Export(format!(
+                "the file has schema version {}, this build reads up to {}",
+                self.schema_version, SCHEMA_VERSION
+            )));
+        }
+        Ok(())
+    }
+}
+
+///
+/// An Invoice with its lines.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct InvoiceTree {
+    pub invoice: Invoice,
+    pub lines: Vec<InvoiceLine>,
+}
+
+///
+/// A database as one JSON document: the project trees, with the clients
+/// the projects belong to and the invoices their times were billed on.
+/// Keys, including TaskTimeIds, are kept as they were. Billing rules are
+/// settings, not records, and are not exported.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Export {
+    pub header: ExportHeader,
+    #[serde(default)]
+    pub clients: Vec<Client>,
+    #[serde(default)]
+    pub invoices: Vec<InvoiceTree>,
+    #[serde(default)]
+    pub projects: Vec<ProjectTree>,
+}
+
+///
+/// One line of an NDJSON export, such as `{"project": {...}}`. The
+/// header comes first, then the clients, the invoices and the projects.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportRecord {
+    Header(ExportHeader),
+    Client(Client),
+    Invoice(InvoiceTree),
+    Project(ProjectTree),
+}
+
+impl ExportRecord {
+    /// Parse the line numbered `number`, counting from 1
+    pub fn from_line(line: &str, number: usize) -> Result<Self, Error> {
+        serde_json::from_str(line).map_err(|e| Error::Export(format!("line {}: {}", number, e)))
+    }
+
+    /// Write the record and a newline
+    pub fn write_line(&self, writer: &mut impl Write) -> Result<(), Error> {
+        serde_json::to_writer(&mut *writer, self).map_err(|e| Error::Export(e.to_string()))?;
+        writeln!(writer).map_err(|e| Error::Export(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::{NaiveDate, TimeDelta};
+
+    use super::*;
+    use crate::database::report::ReportRange;
+    use crate::model::project::Project;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::project_tree::SaveMode;
+    use crate::model::tag::Tag;
+    use crate::model::task_time::TaskTime;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Money};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 8, day).unwrap()
+    }
+
+    async fn empty() -> Result<DbiDatabase, Error> {
+        DbiDatabase::new(DbConfig::new("sqlite::memory:")).await
+    }
+
+    // A client with an invoiced project, a project without a client, tags,
+    // notes and a gap in the TaskTimeIds
+    async fn setup() -> Result<DbiDatabase, Error> {
+        let mut db = empty().await?;
+        let client = Client {
+            client_id: make_uuid(&"Acme".to_string()),
+            client_name: "Acme".to_string(),
+            pay_rate: Some(Money::from(50)),
+        };
+        db.insert(&client).await?;
+        for (name, client_id) in [("Diamond", Some(client.client_id)), ("Ruby", None)] {
+            let project = Project {
+                project_id: make_uuid(&name.to_string()),
+                project_name: name.to_string(),
+                project_date: date(5),
+                client_id,
+                ..Default::default()
+            };
+            db.insert(&project).await?;
+            let task = ProjectTask {
+                task_id: make_uuid(&format!("{}Task", name)),
+                project_id: project.project_id,
+                task_name: "Collate".to_string(),
+                task_date_time: date(5).and_hms_opt(9, 0, 0).unwrap(),
+                notes: Some(format!("Collated the {} files", name)),
+                ..Default::default()
+            };
+            db.insert(&task).await?;
+            db.tag_task(task.task_id, &["meetings", name]).await?;
+            for hour in [9, 11, 13] {
+                let start_time = date(5).and_hms_opt(hour, 0, 0).unwrap();
+                let task_time = TaskTime {
+                    task_id: task.task_id,
+                    start_time,
+                    end_time: Some(start_time + TimeDelta::minutes(90)),
+                    ..Default::default()
+                };
+                db.insert(&task_time).await?;
+            }
+        }
+        let times = db.fetch_all::<TaskTime>().await?;
+        db.delete(&times[1]).await?;
+        db.generate_invoice(client.client_id, &ReportRange::all(), date(31))
+            .await?;
+        Ok(db)
+    }
+
+    // Everything an export should carry, read back from the database
+    async fn contents(
+        db: &mut DbiDatabase,
+    ) -> Result<
+        (
+            Vec<Client>,
+            Vec<Invoice>,
+            Vec<InvoiceLine>,
+            Vec<ProjectTree>,
+        ),
+        Error,
+    > {
+        let mut trees = Vec::new();
+        for project in db.fetch_all::<Project>().await? {
+            trees.push(db.fetch_project_tree(project).await?);
+        }
+        trees.sort_by_key(|tree| tree.project.project_id);
+        Ok((
+            db.fetch_all().await?,
+            db.fetch_all().await?,
+            db.fetch_all().await?,
+            trees,
+        ))
+    }
+
+    async fn round_trip(format: ExportFormat) -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut file = Vec::new();
+        assert_eq!(db.export(format, &mut file).await?, 2);
+
+        let mut copy = empty().await?;
+        let report = copy
+            .import(format, Cursor::new(&file), SaveMode::Insert)
+            .await?;
+        assert_eq!(report.saved, 2);
+        let expected = contents(&mut db).await?;
+        assert_eq!(contents(&mut copy).await?, expected);
+        assert_eq!(expected.1.len(), 1);
+        let ids: Vec<u64> = copy
+            .fetch_all::<TaskTime>()
+            .await?
+            .iter()
+            .map(|t| t.task_time_id)
+            .collect();
+        assert_eq!(ids, vec![1, 3, 4, 5, 6]);
+        assert_eq!(copy.fetch_all::<Tag>().await?.len(), 3);
+        assert_eq!(copy.search("collate diamond").await?.len(), 1);
+
+        // New times follow on from the imported ids
+        let task_time = TaskTime {
+            task_id: make_uuid(&"RubyTask".to_string()),
+            start_time: date(6).and_hms_opt(9, 0, 0).unwrap(),
+            end_time: Some(date(6).and_hms_opt(10, 0, 0).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(copy.insert(&task_time).await?, 7);
+
+        // The rows are stored already. Replacing them keeps the invoiced
+        // times and their ids, and drops the one the file does not have.
+        let result = copy
+            .import(format, Cursor::new(&file), SaveMode::Insert)
+            .await;
+        assert!(matches!(result, Err(Error::Duplicate { .. })));
+        let report = copy
+            .import(format, Cursor::new(&file), SaveMode::Replace)
+            .await?;
+        assert_eq!(report.saved, 2);
+        assert!(report.locked.is_empty());
+        assert_eq!(contents(&mut copy).await?, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_json_round_trip() -> Result<(), Error> {
+        round_trip(ExportFormat::Json).await
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_round_trip() -> Result<(), Error> {
+        round_trip(ExportFormat::Ndjson).await
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_layout() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut file = Vec::new();
+        db.export(ExportFormat::Ndjson, &mut file).await?;
+        let text = String::from_utf8(file).unwrap();
+        let kinds: Vec<&str> = text
+            .lines()
+            .map(|line| line.split('"').nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec!["header", "client", "invoice", "project", "project"]
+        );
+        let first = ExportRecord::from_line(text.lines().next().unwrap(), 1)?;
+        assert!(matches!(
+            first,
+            ExportRecord::Header(ExportHeader {
+                schema_version: SCHEMA_VERSION,
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_errors() -> Result<(), Error> {
+        let mut db = empty().await?;
+        let newer = ExportHeader {
+            schema_version: SCHEMA_VERSION + 1,
+            exported_at: date(1).and_hms_opt(0, 0, 0).unwrap(),
+        };
+        let mut file = Vec::new();
+        ExportRecord::Header(newer.clone()).write_line(&mut file)?;
+        let result = db
+            .import(ExportFormat::Ndjson, Cursor::new(&file), SaveMode::Insert)
+            .await;
+        assert!(matches!(result, Err(Error::Export(_))));
+
+        let json = serde_json::to_vec(&Export {
+            header: newer,
+            clients: Vec::new(),
+            invoices: Vec::new(),
+            projects: Vec::new(),
+        })
+        .unwrap();
+        let result = db
+            .import(ExportFormat::Json, Cursor::new(&json), SaveMode::Insert)
+            .await;
+        assert!(matches!(result, Err(Error::Export(_))));
+
+        for text in ["", "{\"client\": {}}\n", "not json\n"] {
+            let result = db
+                .import(ExportFormat::Ndjson, Cursor::new(text), SaveMode::Insert)
+                .await;
+            assert!(matches!(result, Err(Error::Export(_))), "{:?}", text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_format() {
+        assert_eq!(
+            ExportFormat::for_path(Path::new("a/b.NDJSON")),
+            ExportFormat::Ndjson
+        );
+        assert_eq!(
+            ExportFormat::for_path(Path::new("b.jsonl")),
+            ExportFormat::Ndjson
+        );
+        assert_eq!(
+            ExportFormat::for_path(Path::new("b.json")),
+            ExportFormat::Json
+        );
+        assert_eq!(
+            "ndjson".parse::<ExportFormat>().unwrap(),
+            ExportFormat::Ndjson
+        );
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,355 @@
+// guid.rs
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, Weak};
+
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
+use sqlx::sqlite::{SqliteArgumentValue, SqliteConnectOptions, SqliteTypeInfo, SqliteValueRef};
+use sqlx::{Database, Decode, Encode, Pool, Postgres, Sqlite, Type, TypeInfo, ValueRef};
+use uuid::Uuid;
+
+use crate::config::UuidStorage;
+
+tokio::task_local! {
+    static KEY_STORAGE: UuidStorage;
+}
+
+// The uuid_storage of each SQLite pool DbiDatabase opened. A pool is
+// known by its connect options, which live as long as it does.
+static POOL_STORAGE: Mutex<Vec<(Weak<SqliteConnectOptions>, UuidStorage)>> = Mutex::new(Vec::new());
+
+///
+/// Run `future` with Guids bound for SQLite in `storage`. `DbiDatabase`
+/// runs each query this way so the keys it binds match the ones stored,
+/// as `Encode` is not told which database it is writing to. Outside of
+/// it, and of `with_pool_storage`, keys are bound as text.
+///
+pub(crate) async fn with_storage<F: Future>(storage: UuidStorage, future: F) -> F::Output {
+    KEY_STORAGE.scope(storage, future).await
+}
+
+/// Bind keys in `storage` whenever a query is run on the pool itself
+pub(crate) fn register_pool(pool: &Pool<Sqlite>, storage: UuidStorage) {
+    let options = Arc::downgrade(&pool.connect_options());
+    let mut pools = POOL_STORAGE.lock().unwrap();
+    pools.retain(|(options, _)| options.strong_count() > 0);
+    pools.push((options, storage));
+}
+
+///
+/// Run `future`, which queries `pool`, with keys bound in the storage the
+/// pool was opened with. The `DbObject` methods run this way, so they bind
+/// the same keys whether `DbiDatabase` calls them or not. A pool it did
+/// not open binds them as text.
+///
+pub async fn with_pool_storage<DB: Database, F: Future>(pool: &Pool<DB>, future: F) -> F::Output {
+    // PostgreSQL keys are always native UUIDs
+    let Some(pool) = (pool as &dyn Any).downcast_ref::<Pool<Sqlite>>() else {
+        return future.await;
+    };
+    if KEY_STORAGE.try_with(|_| ()).is_ok() {
+        return future.await;
+    }
+    let options = Arc::as_ptr(&pool.connect_options());
+    let storage = POOL_STORAGE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(known, _)| known.as_ptr() == options)
+        .map(|(_, storage)| *storage);
+    match storage {
+        Some(storage) => with_storage(storage, future).await,
+        None => future.await,
+    }
+}
+
+fn key_storage() -> UuidStorage {
+    KEY_STORAGE.try_with(|storage| *storage).unwrap_or_default()
+}
+
+///
+/// The way the models' `Uuid` keys are bound and read. SQLite keeps them
+/// as hyphenated text, such as `67e55044-10b1-426f-9247-bb680e5fe0c8`, or
+/// as 16 byte BLOBs, as `DbConfig::uuid_storage` says; PostgreSQL keeps
+/// them as native UUIDs. Both SQLite forms can always be read.
+///
+/// Model fields stay `Uuid` and are decoded through this with
+/// `#[sqlx(try_from = "Guid")]`.
+///
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct Guid(Uuid);
+
+impl Guid {
+    /// The all zero key, used before a row is given one
+    pub const fn nil() -> Self {
+        Guid(Uuid::nil())
+    }
+
+    /// A new random key
+    pub fn new_v4() -> Self {
+        Guid(Uuid::new_v4())
+    }
+
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+
+    pub fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl From<Uuid> for Guid {
+    fn from(uuid: Uuid) -> Self {
+        Guid(uuid)
+    }
+}
+
+impl From<Guid> for Uuid {
+    fn from(guid: Guid) -> Self {
+        guid.0
+    }
+}
+
+impl PartialEq<Uuid> for Guid {
+    fn eq(&self, other: &Uuid) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.hyphenated())
+    }
+}
+
+impl FromStr for Guid {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Guid(Uuid::parse_str(s)?))
+    }
+}
+
+impl Type<Sqlite> for Guid {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    // BLOBs are the keys of a database that has not been migrated yet
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as Type<Sqlite>>::compatible(ty) || <Uuid as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for Guid {
+    fn encode_by_ref(
+        &self,
+        args: &mut Vec<SqliteArgumentValue<'q>>,
+    ) -> Result<IsNull, BoxDynError> {
+        match key_storage() {
+            UuidStorage::Text => <String as Encode<Sqlite>>::encode(self.to_string(), args),
+            UuidStorage::Blob => <Uuid as Encode<Sqlite>>::encode_by_ref(&self.0, args),
+        }
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for Guid {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        if value.type_info().name() == "BLOB" {
+            return Ok(Guid(<Uuid as Decode<Sqlite>>::decode(value)?));
+        }
+        Ok(<&str as Decode<Sqlite>>::decode(value)?.parse()?)
+    }
+}
+
+impl Type<Postgres> for Guid {
+    fn type_info() -> PgTypeInfo {
+        <Uuid as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <Uuid as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Guid {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <Uuid as Encode<Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Guid {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(Guid(<Uuid as Decode<Postgres>>::decode(value)?))
+    }
+}
+
+///
+/// A key column that may be NULL, for `Option<Uuid>` model fields marked
+/// `#[sqlx(try_from = "OptionalGuid")]`.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptionalGuid(pub Option<Guid>);
+
+impl From<OptionalGuid> for Option<Uuid> {
+    fn from(guid: OptionalGuid) -> Self {
+        guid.0.map(Uuid::from)
+    }
+}
+
+impl Type<Sqlite> for OptionalGuid {
+    fn type_info() -> SqliteTypeInfo {
+        <Guid as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <Guid as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for OptionalGuid {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        if value.is_null() {
+            return Ok(OptionalGuid(None));
+        }
+        Ok(OptionalGuid(Some(<Guid as Decode<Sqlite>>::decode(value)?)))
+    }
+}
+
+impl Type<Postgres> for OptionalGuid {
+    fn type_info() -> PgTypeInfo {
+        <Guid as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <Guid as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for OptionalGuid {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        if value.is_null() {
+            return Ok(OptionalGuid(None));
+        }
+        Ok(OptionalGuid(Some(<Guid as Decode<Postgres>>::decode(
+            value,
+        )?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::migrate::MigrateDatabase;
+    use sqlx::SqlitePool;
+
+    use super::*;
+    use crate::database::query::DbObject;
+    use crate::{DbConfig, DbiDatabase, Project};
+
+    #[test]
+    fn test_guid_text() {
+        let text = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let guid: Guid = text.parse().unwrap();
+        assert_eq!(guid.to_string(), text);
+        assert_eq!(guid, Uuid::parse_str(text).unwrap());
+        assert_eq!(
+            serde_json::to_string(&guid).unwrap(),
+            format!("\"{}\"", text)
+        );
+        assert!("not a key".parse::<Guid>().is_err());
+        assert!(Guid::nil().is_nil());
+    }
+
+    #[tokio::test]
+    async fn test_guid_stored_as_text_and_read_from_blob() -> Result<(), sqlx::Error> {
+        let pool = SqlitePool::connect("sqlite::memory:").await?;
+        let guid = Guid::new_v4();
+
+        let (kind, stored): (String, Guid) = sqlx::query_as("SELECT typeof($1), $1")
+            .bind(guid)
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(kind, "text");
+        assert_eq!(stored, guid);
+
+        let stored: Guid = sqlx::query_scalar("SELECT $1")
+            .bind(*guid.as_uuid())
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(stored, guid);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_guid_stored_as_blob() -> Result<(), sqlx::Error> {
+        let pool = SqlitePool::connect("sqlite::memory:").await?;
+        let guid = Guid::new_v4();
+
+        // Keys are encoded when they are bound, so that has to be in scope
+        let query = async {
+            sqlx::query_as("SELECT typeof($1), $1, NULL")
+                .bind(guid)
+                .fetch_one(&pool)
+                .await
+        };
+        let (kind, stored, missing): (String, Guid, OptionalGuid) =
+            with_storage(UuidStorage::Blob, query).await?;
+        assert_eq!(kind, "blob");
+        assert_eq!(stored, guid);
+        assert_eq!(missing, OptionalGuid(None));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool_binds_keys_as_configured() -> Result<(), crate::Error> {
+        let config = DbConfig::new("sqlite::memory:").uuid_storage(UuidStorage::Blob);
+        let db = DbiDatabase::new(config).await?;
+        let pool = db.pool.sqlite();
+        let project = Project {
+            project_id: Uuid::new_v4(),
+            project_name: "Blob keys".to_string(),
+            ..Default::default()
+        };
+
+        // Called on the pool, not through DbiDatabase
+        Project::insert_one(pool, &project).await?;
+        let kind: String = sqlx::query_scalar(r#"SELECT typeof("ProjectId") FROM Projects"#)
+            .fetch_one(pool)
+            .await?;
+        assert_eq!(kind, "blob");
+        let mut found = Project {
+            project_id: project.project_id,
+            ..Default::default()
+        };
+        found.retrieve_one(pool).await?;
+        assert_eq!(found, project);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a PostgreSQL server in TEST_POSTGRES_URL"]
+    async fn test_guid_postgres() -> Result<(), sqlx::Error> {
+        let url = crate::test_postgres_url();
+        if !Postgres::database_exists(&url).await? {
+            Postgres::create_database(&url).await?;
+        }
+        let pool = sqlx::PgPool::connect(&url).await?;
+        let guid = Guid::new_v4();
+        let (kind, stored): (String, Guid) = sqlx::query_as("SELECT pg_typeof($1)::TEXT, $1")
+            .bind(guid)
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(kind, "uuid");
+        assert_eq!(stored, guid);
+        Ok(())
+    }
+}
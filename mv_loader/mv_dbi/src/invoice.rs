@@ -0,0 +1,530 @@
+// invoice.rs
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::model::client::Client;
+use crate::model::invoice::Invoice;
+use crate::model::invoice_line::InvoiceLine;
+
+/// The formats an invoice can be rendered in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceFormat {
+    #[default]
+    Markdown,
+    Html,
+    Text,
+}
+
+impl InvoiceFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceFormat::Markdown => "markdown",
+            InvoiceFormat::Html => "html",
+            InvoiceFormat::Text => "text",
+        }
+    }
+}
+
+impl FromStr for InvoiceFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Ok(InvoiceFormat::Markdown),
+            "html" => Ok(InvoiceFormat::Html),
+            "text" | "txt" => Ok(InvoiceFormat::Text),
+            _ => Err(Error::Configuration(format!(
+                "Unknown invoice format '{s}', expected 'markdown', 'html' or 'text'"
+            ))),
+        }
+    }
+}
+
+///
+/// An invoice with its client and lines, as needed to render it.
+///
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct InvoiceDoc {
+    pub invoice: Invoice,
+    pub client: Client,
+    pub lines: Vec<InvoiceLine>,
+}
+
+const HEADINGS: [&str; 8] = [
+    "#", "Date", "Project", "Task", "Time", "Rate", "Amount", "Notes",
+];
+
+impl InvoiceDoc {
+    pub fn render(&self, format: InvoiceFormat) -> String {
+        match format {
+            InvoiceFormat::Markdown => self.markdown(),
+            InvoiceFormat::Html => self.html(),
+            InvoiceFormat::Text => self.text(),
+        }
+    }
+
+    fn title(&self) -> String {
+        format!("Invoice {}", self.invoice.invoice_number)
+    }
+
+    fn period(&self) -> Option<String> {
+        match (self.invoice.period_start, self.invoice.period_end) {
+            (None, None) => None,
+            (start, end) => Some(format!(
+                "{} to {}",
+                start.map_or("the start".to_string(), |d| d.to_string()),
+                end.map_or("the end".to_string(), |d| d.to_string())
+            )),
+        }
+    }
+
+    fn total(&self) -> String {
+        format!("{} {}", self.invoice.currency, self.invoice.total)
+    }
+
+    // The cells of each line, in the order of HEADINGS
+    fn rows(&self) -> Vec<[String; 8]> {
+        self.lines
+            .iter()
+            .map(|line| {
+                let mut rate = line.pay_rate.to_string();
+                if line.percent != 100 {
+                    rate.push_str(&format!(" x {}%", line.percent));
+                }
+                let task = match line.task_name.as_str() {
+                    "" => line.kind.as_str().to_string(),
+                    name => name.to_string(),
+                };
+                [
+                    line.line_number.to_string(),
+                    line.line_date.to_string(),
+                    line.project_name.clone(),
+                    task,
+                    format!("{}:{:02}", line.minutes / 60, line.minutes % 60),
+                    rate,
+                    line.amount.to_string(),
+                    line.description.clone(),
+                ]
+            })
+            .collect()
+    }
+
+    fn markdown(&self) -> String {
+        let cell = |s: &str| s.replace('\\', "\\\\").replace('|', "\\|");
+        let mut out = String::new();
+        writeln!(out, "# {}", self.title()).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "**Client:** {}  ", cell(&self.client.client_name)).unwrap();
+        writeln!(out, "**Issued:** {}  ", self.invoice.issue_date).unwrap();
+        if let Some(period) = self.period() {
+            writeln!(out, "**Period:** {}  ", period).unwrap();
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "| {} |", HEADINGS.join(" | ")).unwrap();
+        writeln!(out, "|---:|---|---|---|---:|---:|---:|---|").unwrap();
+        for row in self.rows() {
+            let cells: Vec<String> = row.iter().map(|c| cell(c)).collect();
+            writeln!(out, "| {} |", cells.join(" | ")).unwrap();
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "**Total: {}**", self.total()).unwrap();
+        out
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "<!DOCTYPE html>").unwrap();
+        writeln!(out, "<html>").unwrap();
+        writeln!(out, "<head><title>{}</title></head>", self.title()).unwrap();
+        writeln!(out, "<body>").unwrap();
+        writeln!(out, "<h1>{}</h1>", self.title()).unwrap();
+        writeln!(out, "<dl>").unwrap();
+        writeln!(
+            out,
+            "<dt>Client</dt><dd>{}</dd>",
+            escape_html(&self.client.client_name)
+        )
+        .unwrap();
+        writeln!(out, "<dt>Issued</dt><dd>{}</dd>", self.invoice.issue_date).unwrap();
+        if let Some(period) = self.period() {
+            writeln!(out, "<dt>Period</dt><dd>{}</dd>", period).unwrap();
+        }
+        writeln!(out, "</dl>").unwrap();
+        writeln!(out, "<table>").unwrap();
+        let headings: String = HEADINGS
+            .iter()
+            .map(|h| format!("<th>{}</th>", escape_html(h)))
+            .collect();
+        writeln!(out, "<thead><tr>{}</tr></thead>", headings).unwrap();
+        writeln!(out, "<tbody>").unwrap();
+        for row in self.rows() {
+            let cells: String = row
+                .iter()
+                .map(|c| format!("<td>{}</td>", escape_html(c)))
+                .collect();
+            writeln!(out, "<tr>{}</tr>", cells).unwrap();
+        }
+        writeln!(out, "</tbody>").unwrap();
+        writeln!(
+            out,
+            "<tfoot><tr><th colspan=\"6\">Total</th><th>{}</th><th></th></tr></tfoot>",
+            escape_html(&self.total())
+        )
+        .unwrap();
+        writeln!(out, "</table>").unwrap();
+        writeln!(out, "</body>").unwrap();
+        writeln!(out, "</html>").unwrap();
+        out
+    }
+
+    fn text(&self) -> String {
+        let rows = self.rows();
+        let mut widths = HEADINGS.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        // Numbers line up on the right, words on the left
+        let line = |cells: &[String]| {
+            let mut out = String::new();
+            for (i, (cell, width)) in cells.iter().zip(widths).enumerate() {
+                if i > 0 {
+                    out.push_str("  ");
+                }
+                match i {
+                    0 | 4 | 5 | 6 => write!(out, "{:>width$}", cell).unwrap(),
+                    _ => write!(out, "{:<width$}", cell).unwrap(),
+                }
+            }
+            out.trim_end().to_string()
+        };
+
+        let mut out = String::new();
+        writeln!(out, "{}", self.title()).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "Client: {}", self.client.client_name).unwrap();
+        writeln!(out, "Issued: {}", self.invoice.issue_date).unwrap();
+        if let Some(period) = self.period() {
+            writeln!(out, "Period: {}", period).unwrap();
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "{}", line(&HEADINGS.map(String::from))).unwrap();
+        let rule: usize = widths.iter().sum::<usize>() + 2 * (widths.len() - 1);
+        writeln!(out, "{}", "-".repeat(rule)).unwrap();
+        for row in &rows {
+            writeln!(out, "{}", line(row)).unwrap();
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "Total: {}", self.total()).unwrap();
+        out
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::billing::ChargeKind;
+    use crate::database::report::ReportRange;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::task_time::TaskTime;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Money, Project};
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn august() -> ReportRange {
+        ReportRange::month(2024, 8).unwrap()
+    }
+
+    fn issued() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 9, 1).unwrap()
+    }
+
+    // Acme, billed 60.00 an hour, with one project and task
+    async fn setup() -> Result<(DbiDatabase, Client, ProjectTask), Error> {
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        let client = Client {
+            client_id: make_uuid(&"Acme".to_string()),
+            client_name: "Acme".to_string(),
+            pay_rate: Some(Money::from(60)),
+        };
+        db.insert(&client).await?;
+        let project = Project {
+            project_id: make_uuid(&"Shed".to_string()),
+            project_name: "Shed".to_string(),
+            project_date: at(5, 9).date(),
+            client_id: Some(client.client_id),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        let task = ProjectTask {
+            task_id: make_uuid(&"Shed Roof".to_string()),
+            project_id: project.project_id,
+            task_name: "Roof".to_string(),
+            task_duration: 0,
+            task_date_time: at(5, 9),
+            notes: None,
+        };
+        db.insert(&task).await?;
+        Ok((db, client, task))
+    }
+
+    async fn add_time(
+        db: &mut DbiDatabase,
+        task: &ProjectTask,
+        start: NaiveDateTime,
+        minutes: i64,
+    ) -> Result<TaskTime, Error> {
+        let mut task_time = TaskTime {
+            task_id: task.task_id,
+            start_time: start,
+            end_time: Some(start + TimeDelta::minutes(minutes)),
+            ..Default::default()
+        };
+        task_time.task_time_id = db.insert(&task_time).await?;
+        Ok(task_time)
+    }
+
+    #[tokio::test]
+    async fn test_generate_invoices_in_sequence() -> Result<(), Error> {
+        let (mut db, client, task) = setup().await?;
+        let walls = ProjectTask {
+            task_id: make_uuid(&"Shed Walls".to_string()),
+            task_name: "Walls".to_string(),
+            task_date_time: at(6, 9),
+            ..task.clone()
+        };
+        db.insert(&walls).await?;
+        add_time(&mut db, &task, at(5, 9), 60).await?;
+        add_time(&mut db, &walls, at(6, 9), 30).await?;
+
+        let doc = db
+            .generate_invoice(client.client_id, &august(), issued())
+            .await?
+            .expect("Expected an invoice");
+        assert_eq!(doc.invoice.invoice_number, 1);
+        assert_eq!(doc.invoice.period_start, august().from);
+        assert_eq!(doc.invoice.currency, "USD");
+        assert_eq!(doc.invoice.total, Money::from(90));
+        let lines: Vec<(i32, i64, Money)> = doc
+            .lines
+            .iter()
+            .map(|l| (l.line_number, l.minutes, l.amount))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![(1, 60, Money::from(60)), (2, 30, Money::from(30))]
+        );
+        assert_eq!(db.invoice(1).await?, doc);
+
+        // The same times are never billed twice
+        let again = db
+            .generate_invoice(client.client_id, &august(), issued())
+            .await?;
+        assert!(again.is_none());
+
+        add_time(&mut db, &task, at(20, 9), 15).await?;
+        let doc = db
+            .generate_invoice(client.client_id, &august(), issued())
+            .await?
+            .expect("Expected an invoice");
+        assert_eq!(doc.invoice.invoice_number, 2);
+        assert_eq!(doc.invoice.total, Money::from(15));
+        assert_eq!(doc.lines.len(), 1);
+
+        let times = db.fetch_all::<TaskTime>().await?;
+        assert!(times.iter().all(|t| t.invoice_id.is_some()));
+        assert!(matches!(
+            db.invoice(3).await,
+            Err(Error::NotFound {
+                entity: "Invoice",
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoiced_rows_are_locked() -> Result<(), Error> {
+        let (mut db, client, task) = setup().await?;
+        let mut billed = add_time(&mut db, &task, at(5, 9), 60).await?;
+        let mut doc = db
+            .generate_invoice(client.client_id, &august(), issued())
+            .await?
+            .expect("Expected an invoice");
+
+        billed.invoice_id = Some(doc.invoice.invoice_id);
+        billed.end_time = billed.end_time.map(|end| end + TimeDelta::minutes(30));
+        let result = db.update(&billed).await;
+        assert!(
+            matches!(result, Err(Error::Locked { entity: "TaskTime", ref message, .. })
+                if message.contains("invoiced")),
+            "{:?}",
+            result
+        );
+        assert!(matches!(
+            db.delete(&billed).await,
+            Err(Error::Locked { .. })
+        ));
+
+        doc.invoice.total = Money::from(1);
+        assert!(matches!(
+            db.update(&doc.invoice).await,
+            Err(Error::Locked { .. })
+        ));
+        assert!(matches!(
+            db.delete(&doc.lines[0]).await,
+            Err(Error::Locked { .. })
+        ));
+        assert!(matches!(
+            db.delete(&doc.invoice).await,
+            Err(Error::Locked { .. })
+        ));
+        assert_eq!(db.invoice(1).await?.invoice.total, Money::from(60));
+
+        // Times not yet invoiced can still change
+        let mut unbilled = add_time(&mut db, &task, at(6, 9), 60).await?;
+        unbilled.end_time = unbilled.end_time.map(|end| end + TimeDelta::minutes(30));
+        assert_eq!(db.update(&unbilled).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invoice_mixed_currencies() -> Result<(), Error> {
+        let (mut db, client, task) = setup().await?;
+        add_time(&mut db, &task, at(5, 9), 60).await?;
+        let other = Project {
+            project_id: make_uuid(&"Abroad".to_string()),
+            project_name: "Abroad".to_string(),
+            project_date: at(5, 9).date(),
+            client_id: Some(client.client_id),
+            currency: "EUR".to_string(),
+            ..Default::default()
+        };
+        db.insert(&other).await?;
+        let other_task = ProjectTask {
+            task_id: make_uuid(&"Abroad Task".to_string()),
+            project_id: other.project_id,
+            task_name: "Task".to_string(),
+            task_duration: 0,
+            task_date_time: at(5, 12),
+            notes: None,
+        };
+        db.insert(&other_task).await?;
+        add_time(&mut db, &other_task, at(5, 12), 60).await?;
+
+        let result = db
+            .generate_invoice(client.client_id, &august(), issued())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::Validation {
+                entity: "Invoice",
+                ..
+            })
+        ));
+        // Nothing was written
+        assert!(db.fetch_all::<Invoice>().await?.is_empty());
+        let times = db.fetch_all::<TaskTime>().await?;
+        assert!(times.iter().all(|t| t.invoice_id.is_none()));
+        Ok(())
+    }
+
+    fn make_doc() -> InvoiceDoc {
+        let client = Client {
+            client_id: Uuid::nil(),
+            client_name: "Smith & <Sons>".to_string(),
+            pay_rate: None,
+        };
+        let mut invoice = Invoice::new(7, client.client_id, issued());
+        invoice.period_start = august().from;
+        invoice.period_end = august().to;
+        invoice.currency = "USD".to_string();
+        invoice.total = Money::from(150);
+        let line =
+            |number: i32, kind: ChargeKind, task: &str, percent: i32, amount: i64| InvoiceLine {
+                invoice_line_id: Uuid::nil(),
+                invoice_id: invoice.invoice_id,
+                line_number: number,
+                line_date: at(5, 9).date(),
+                project_name: "Shed | Garden".to_string(),
+                task_name: task.to_string(),
+                kind,
+                minutes: 60,
+                pay_rate: Money::from(60),
+                percent,
+                amount: Money::from(amount),
+                description: "1:00 worked".to_string(),
+            };
+        InvoiceDoc {
+            lines: vec![
+                line(1, ChargeKind::Regular, "Roof", 100, 60),
+                line(2, ChargeKind::Overtime, "Roof", 150, 90),
+            ],
+            invoice,
+            client,
+        }
+    }
+
+    #[test]
+    fn test_render_invoice() {
+        let doc = make_doc();
+
+        let markdown = doc.render(InvoiceFormat::Markdown);
+        assert!(markdown.starts_with("# Invoice 7\n"));
+        assert!(markdown.contains("**Period:** 2024-08-01 to 2024-08-31"));
+        assert!(markdown.contains(
+            "| 2 | 2024-08-05 | Shed \\| Garden | Roof | 1:00 | 60.00 x 150% | 90.00 | 1:00 worked |"
+        ));
+        assert!(markdown.ends_with("**Total: USD 150.00**\n"));
+
+        let html = doc.render(InvoiceFormat::Html);
+        assert!(html.contains("<dd>Smith &amp; &lt;Sons&gt;</dd>"));
+        assert!(html.contains("<td>60.00 x 150%</td>"));
+        assert!(!html.contains("<Sons>"));
+
+        let text = doc.render(InvoiceFormat::Text);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "Invoice 7");
+        assert_eq!(lines[2], "Client: Smith & <Sons>");
+        assert_eq!(
+            lines[8],
+            "1  2024-08-05  Shed | Garden  Roof  1:00         60.00   60.00  1:00 worked"
+        );
+        assert_eq!(lines.last(), Some(&"Total: USD 150.00"));
+
+        assert_eq!(
+            "HTML".parse::<InvoiceFormat>().unwrap(),
+            InvoiceFormat::Html
+        );
+        assert!("pdf".parse::<InvoiceFormat>().is_err());
+    }
+}
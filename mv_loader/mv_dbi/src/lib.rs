@@ -1,122 +1,1778 @@
 #![allow(unused)]
 
+// Lets the code generated by derive(DbObject) name this crate as mv_dbi
+extern crate self as mv_dbi;
+
+pub mod billing;
+pub mod config;
 pub mod database;
+pub mod error;
+pub mod export;
+pub mod guid;
+pub mod invoice;
+pub mod maintenance;
 pub mod model;
+pub mod money;
+pub mod schema;
+pub mod search;
+pub mod sync;
+pub mod timer;
 pub mod utils;
 
-use database::query::DbObject;
+use database::filter::QueryFilter;
+use database::query::{DbModel, DbObject, DbTable, DeleteCount, PageKey};
+use database::reconcile::{self, ProjectTotals, TaskTotals, TotalsReport};
+use database::report::{
+    self, ClientEarnings, Period, PeriodEarnings, ProjectHours, ProjectRevenue, ReportRange,
+    TagHours, TaskNameHours,
+};
+use error::DbContext;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 use model::project_task::ProjectTask;
-use model::task_time::TaskTime;
+use model::task_time::{self, TaskTime};
 use sqlx::migrate::MigrateDatabase;
+use uuid::Uuid;
+
+use __private::finish_tx;
+use config::ValidationRules;
+pub use config::{Backend, DbConfig};
+use database::validate::{OverlapQuery, TimeSpan, Validate};
+pub use error::Error;
+pub use guid::Guid;
+pub use money::{Money, RoundingMode};
+
+// Dependencies and helpers used by the code derive(DbObject) generates
+#[doc(hidden)]
+pub mod __private {
+    pub use futures;
+    pub use sqlx;
+    pub use uuid;
 
+    pub use crate::guid::with_pool_storage;
+    use crate::Error;
+
+    // Commit the transaction if the work in it succeeded, otherwise roll it
+    // back before returning the error. A transaction that is only dropped is
+    // rolled back whenever the pool gets to it, and until then SQLite holds
+    // the write lock, so the next write fails with SQLITE_BUSY.
+    pub async fn finish_tx<DB: sqlx::Database, T>(
+        tx: sqlx::Transaction<'_, DB>,
+        result: Result<T, Error>,
+    ) -> Result<T, Error> {
+        match result {
+            Ok(value) => {
+                tx.commit().await?;
+                Ok(value)
+            }
+            Err(error) => {
+                // The error that failed the work says more than one from the
+                // rollback would
+                let _ = tx.rollback().await;
+                Err(error)
+            }
+        }
+    }
+}
+use billing::{Charge, ChargeLine, RuleBook, WorkEntry};
+use chrono::{Local, NaiveDate, NaiveDateTime, Timelike};
+use export::{
+    Export, ExportFormat, ExportHeader, ExportRecord, InvoiceTree, EXPORT_PAGE_SIZE,
+    IMPORT_BATCH_SIZE,
+};
+use invoice::InvoiceDoc;
+use maintenance::IntegrityReport;
+use model::billing_rule::BillingRule;
+use model::client::Client;
+use model::invoice::Invoice;
+use model::invoice_line::InvoiceLine;
 use model::project;
 use model::project::Project;
-use sqlx::Error;
+use model::project_tree::{self, ProjectTree, ReplacePlan, SaveMode, SaveReport, TaskTree};
+use model::tag::{self, tag_in_tx, Tag};
+use model::task_rate::TaskRate;
+use schema::SchemaStatus;
+use search::{SearchDialect, SearchHit};
+use sqlx::pool::PoolOptions;
+use sqlx::postgres::PgConnectOptions;
+use sqlx::sqlite::SqliteConnectOptions;
 use sqlx::Pool;
+use sqlx::Postgres;
 use sqlx::Row;
 use sqlx::Sqlite;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use sync::{ConflictPolicy, MergePlan, MergeReport, Snapshot, SyncAction, SyncReport, SyncStatus};
+use timer::RunningTimer;
+
+///
+/// One page of a keyset walk through a table. `next` is the key to pass
+/// to `DbiDatabase::fetch_page` for the following page, and is None once
+/// the last page has been read.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<PageKey>,
+}
 
-pub enum DataCollection {
-    Projects(Vec<Project>),
-    ProjectTasks(Vec<ProjectTask>),
-    TaskTimes(Vec<TaskTime>),
-    #[cfg(test)]
-    MyTables(Vec<tests::MyTable>),
+pub(crate) enum DbPool {
+    Sqlite(Pool<Sqlite>),
+    Postgres(Pool<Postgres>),
 }
 
-pub enum DataObject {
-    Project(Project),
-    ProjectTask(ProjectTask),
-    TaskTime(TaskTime),
-    #[cfg(test)]
-    MyTable(tests::MyTable),
+///
+/// Run the same body against whichever pool the database was opened with.
+/// `$db` is a type alias for the backend, for calls that cannot infer it.
+/// SQLite bodies run with keys bound in the configured `uuid_storage`.
+///
+macro_rules! with_pool {
+    ($dbi:expr, $p:ident, $db:ident => $body:expr) => {
+        match &$dbi.pool {
+            DbPool::Sqlite($p) => {
+                type $db = Sqlite;
+                guid::with_storage($dbi.config.uuid_storage, async { $body }).await
+            }
+            DbPool::Postgres($p) => {
+                type $db = Postgres;
+                $body
+            }
+        }
+    };
 }
 
-#[derive(Debug)]
-pub struct DbConfig {
-    url: String,
+///
+/// The server the PostgreSQL tests run against. They are ignored unless
+/// asked for, as in
+/// `TEST_POSTGRES_URL=postgres://postgres@localhost/mv_test cargo test -p mv_dbi --lib -- --ignored`.
+///
+#[cfg(test)]
+pub(crate) fn test_postgres_url() -> String {
+    std::env::var("TEST_POSTGRES_URL")
+        .expect("TEST_POSTGRES_URL should name the server the PostgreSQL tests use")
 }
 
-impl DbConfig {
-    pub fn new(url: &str) -> Self {
-        Self {
-            url: url.to_owned(),
+#[cfg(test)]
+impl DbPool {
+    pub(crate) fn sqlite(&self) -> &Pool<Sqlite> {
+        match self {
+            DbPool::Sqlite(pool) => pool,
+            DbPool::Postgres(_) => panic!("Expected an SQLite pool"),
         }
     }
 }
 
 pub struct DbiDatabase {
-    pool: Pool<Sqlite>,
+    pool: DbPool,
+    // Kept so the pool can be reopened after a restore
+    config: DbConfig,
 }
 
-const MEMORY_DB: &str = "sqlite::memory:";
-
 impl DbiDatabase {
     pub async fn new(config: DbConfig) -> Result<Self, Error> {
-        Self::check_and_create_database_file(&config.url).await?;
-        let pool = Pool::connect(&config.url).await?;
+        let pool = match config.backend() {
+            Backend::Sqlite => DbPool::Sqlite(Self::open_sqlite(&config).await?),
+            Backend::Postgres => DbPool::Postgres(Self::open_postgres(&config).await?),
+        };
+        Ok(Self { pool, config })
+    }
 
-        let result = sqlx::query("PRAGMA journal_mode = DELETE;")
-            .execute(&pool)
+    async fn open_sqlite(config: &DbConfig) -> Result<Pool<Sqlite>, Error> {
+        let mut options = SqliteConnectOptions::from_str(&config.url)?
+            .create_if_missing(!config.read_only)
+            .read_only(config.read_only)
+            .foreign_keys(true)
+            .synchronous(config.synchronous.into())
+            .busy_timeout(Duration::from_millis(config.busy_timeout_ms));
+        // Changing the journal mode writes to the file, so a read-only
+        // connection keeps whatever mode the database already has
+        if !config.read_only {
+            options = options.journal_mode(config.journal_mode.into());
+        }
+        let pool = PoolOptions::<Sqlite>::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .connect_with(options)
             .await?;
 
-        let result = sqlx::query("PRAGMA foreign_keys = ON;")
-            .execute(&pool)
+        let migrator = schema::migrator(Backend::Sqlite);
+        schema::ensure_supported(&pool, migrator).await?;
+        if config.runs_migrations() {
+            schema::run(&pool, migrator).await?;
+        }
+        // Only the latest schema has every key column, so one opened
+        // without its migrations is left as it is
+        if schema::status(&pool, migrator).await?.is_current() {
+            Self::check_key_storage(&pool, config).await?;
+        }
+        guid::register_pool(&pool, config.uuid_storage);
+        Ok(pool)
+    }
+
+    // Bring the keys of a current schema into the configured storage. A
+    // read-only database cannot be converted, and keys bound the other way
+    // would match nothing, so it is refused instead.
+    async fn check_key_storage(pool: &Pool<Sqlite>, config: &DbConfig) -> Result<(), Error> {
+        if !config.read_only {
+            schema::convert_keys(pool, config.uuid_storage).await?;
+        } else if schema::keys_need_converting(pool, config.uuid_storage).await? {
+            return Err(Error::Configuration(format!(
+                "The keys of {} are not stored as {:?}, and a read-only database cannot be converted",
+                config.url, config.uuid_storage
+            )));
+        }
+        Ok(())
+    }
+
+    async fn open_postgres(config: &DbConfig) -> Result<Pool<Postgres>, Error> {
+        let mut options = PgConnectOptions::from_str(&config.url)?;
+        if config.read_only {
+            options = options.options([("default_transaction_read_only", "on")]);
+        } else if !Postgres::database_exists(&config.url).await? {
+            Postgres::create_database(&config.url).await?
+        }
+        let pool = PoolOptions::<Postgres>::new()
+            .max_connections(config.max_connections)
+            .min_connections(config.min_connections)
+            .connect_with(options)
             .await?;
-        sqlx::migrate!("../migrations").run(&pool).await?;
-        Ok(Self { pool })
+
+        let migrator = schema::migrator(Backend::Postgres);
+        schema::ensure_supported(&pool, migrator).await?;
+        if config.runs_migrations() {
+            schema::run(&pool, migrator).await?;
+        }
+        Ok(pool)
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.pool {
+            DbPool::Sqlite(_) => Backend::Sqlite,
+            DbPool::Postgres(_) => Backend::Postgres,
+        }
+    }
+
+    ///
+    /// The schema version the database is at, and the migrations this
+    /// build knows that it has and has not had applied. Open with
+    /// `skip_migrations` to see the status before anything is applied.
+    ///
+    pub async fn schema_status(&mut self) -> Result<SchemaStatus, Error> {
+        let migrator = schema::migrator(self.backend());
+        with_pool!(self, pool, _DB => schema::status(pool, migrator).await)
     }
 
-    async fn check_and_create_database_file(db_url: &str) -> Result<(), sqlx::Error> {
-        if !db_url.eq(MEMORY_DB) {
-            if !Sqlite::database_exists(&db_url).await.unwrap_or(false) {
-                Sqlite::create_database(&db_url).await?
+    ///
+    /// Apply or roll back migrations until the schema is at `version`,
+    /// which is 0 or one of the versions in `schema_status`. Rolling back
+    /// drops the tables and columns later migrations added, with the rows
+    /// in them.
+    ///
+    pub async fn migrate_to(&mut self, version: i64) -> Result<SchemaStatus, Error> {
+        let migrator = schema::migrator(self.backend());
+        with_pool!(self, pool, _DB => schema::migrate_to(pool, migrator, version).await)?;
+        let status = self.schema_status().await?;
+        // The TextUuids migration turns every key into text, so once the
+        // schema is current again the keys go back to the configured storage
+        if let DbPool::Sqlite(pool) = &self.pool {
+            if status.is_current() {
+                Self::check_key_storage(pool, &self.config).await?;
             }
         }
-        Ok(())
+        Ok(status)
+    }
+
+    ///
+    /// Save complete Project -> ProjectTasks -> TaskTimes hierarchies in a
+    /// single transaction. If any row fails to write, nothing from any of
+    /// the trees is kept.
+    ///
+    /// With `SaveMode::Insert` a project that is already stored is an error.
+    /// With `SaveMode::Replace` the stored project is updated and its tasks
+    /// and times brought into line with the ones in the tree, so saving the
+    /// same trees again leaves the database unchanged. TaskTimes are matched
+    /// on `TaskTime::stable_id`; invoiced ones are left as they are.
+    ///
+    /// TaskTimes with a TaskTimeId keep it, as they do when an export is
+    /// imported; those with 0 are given a new one.
+    ///
+    /// Returns the number of projects saved.
+    ///
+    pub async fn save_project_trees(
+        &mut self,
+        trees: &[ProjectTree],
+        mode: SaveMode,
+    ) -> Result<u64, Error> {
+        Ok(self
+            .save_project_trees_with_report(trees, mode)
+            .await?
+            .saved)
     }
 
-    pub async fn do_insert(&mut self, data_object: &DataObject) -> Result<u64, Error> {
-        match data_object {
-            DataObject::Project(project) => <Project>::insert_one(&mut self.pool, project).await,
-            DataObject::ProjectTask(task) => <ProjectTask>::insert_one(&mut self.pool, task).await,
-            DataObject::TaskTime(task_time) => <TaskTime>::insert_one(&mut self.pool, task_time).await,
-            #[cfg(test)]
-            DataObject::MyTable(value) => <tests::MyTable>::insert_one(&mut self.pool, value).await,
+    ///
+    /// `save_project_trees`, also returning the invoiced TaskTimes that
+    /// `SaveMode::Replace` kept though the trees change them.
+    ///
+    pub async fn save_project_trees_with_report(
+        &mut self,
+        trees: &[ProjectTree],
+        mode: SaveMode,
+    ) -> Result<SaveReport, Error> {
+        self.save_records(&[], &[], trees, mode).await
+    }
+
+    // Save clients, invoices and project trees, in that order so the rows
+    // they refer to are stored first, in one transaction. Invoices never
+    // change, so with SaveMode::Replace a stored one is left as it is.
+    async fn save_records(
+        &mut self,
+        clients: &[Client],
+        invoices: &[InvoiceTree],
+        trees: &[ProjectTree],
+        mode: SaveMode,
+    ) -> Result<SaveReport, Error> {
+        let backend = self.backend();
+        let kept_ids = trees
+            .iter()
+            .flat_map(|tree| &tree.tasks)
+            .flat_map(|task_tree| &task_tree.task_times)
+            .any(|task_time| task_time.task_time_id != 0);
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                let mut report = SaveReport::default();
+
+                for client in clients {
+                    client.validate(&self.config.validation)?;
+                    match mode {
+                        SaveMode::Insert => {
+                            <Client as DbObject<DB, _>>::insert_in_tx(&mut tx, client).await?;
+                        }
+                        SaveMode::Replace => {
+                            <Client as DbObject<DB, _>>::upsert_in_tx(&mut tx, client).await?;
+                        }
+                    }
+                }
+                for invoice_tree in invoices {
+                    let invoice = &invoice_tree.invoice;
+                    if mode == SaveMode::Replace {
+                        let stored = sqlx::query(r#"SELECT 1 FROM Invoices WHERE "InvoiceId" = $1"#)
+                            .bind(Guid::from(invoice.invoice_id))
+                            .fetch_optional(&mut *tx)
+                            .await
+                            .context("Invoice", invoice.invoice_id)?;
+                        if stored.is_some() {
+                            continue;
+                        }
+                    }
+                    <Invoice as DbObject<DB, _>>::insert_in_tx(&mut tx, invoice).await?;
+                    for line in &invoice_tree.lines {
+                        <InvoiceLine as DbObject<DB, _>>::insert_in_tx(&mut tx, line).await?;
+                    }
+                }
+
+                for tree in trees {
+                    let project_id = tree.project.project_id;
+                    let times = tree.tasks.iter().flat_map(|task_tree| &task_tree.task_times);
+                    let plan = match mode {
+                        SaveMode::Insert => {
+                            report.saved +=
+                                <Project as DbObject<DB, _>>::insert_in_tx(&mut tx, &tree.project)
+                                    .await?;
+                            ReplacePlan {
+                                insert: times.collect(),
+                                ..ReplacePlan::default()
+                            }
+                        }
+                        SaveMode::Replace => {
+                            report.saved +=
+                                <Project as DbObject<DB, _>>::upsert_in_tx(&mut tx, &tree.project)
+                                    .await?;
+                            let stored: Vec<TaskTime> = sqlx::query_as(task_time::PROJECT_TIMES_SQL)
+                                .bind(Guid::from(project_id))
+                                .fetch_all(&mut *tx)
+                                .await
+                                .context("Project", project_id)?;
+                            let plan = ReplacePlan::new(stored, times);
+                            for task_time_id in &plan.delete {
+                                sqlx::query(task_time::DELETE_BY_ID_SQL)
+                                    .bind(*task_time_id as i64)
+                                    .execute(&mut *tx)
+                                    .await
+                                    .context("TaskTime", task_time_id)?;
+                            }
+                            let stored_tasks: Vec<Uuid> =
+                                sqlx::query_scalar::<DB, Guid>(project_tree::PROJECT_TASK_IDS_SQL)
+                                    .bind(Guid::from(project_id))
+                                    .fetch_all(&mut *tx)
+                                    .await
+                                    .context("Project", project_id)?
+                                    .into_iter()
+                                    .map(Uuid::from)
+                                    .collect();
+                            for task_id in ReplacePlan::removed_tasks(&stored_tasks, tree) {
+                                sqlx::query(project_tree::DELETE_EMPTY_TASK_SQL)
+                                    .bind(Guid::from(task_id))
+                                    .execute(&mut *tx)
+                                    .await
+                                    .context("ProjectTask", task_id)?;
+                            }
+                            plan
+                        }
+                    };
+                    for task_tree in &tree.tasks {
+                        let task_id = task_tree.task.task_id;
+                        let tags: Vec<Tag> = task_tree.tags.iter().map(|name| Tag::new(name)).collect();
+                        for tag in &tags {
+                            tag.validate(&self.config.validation)?;
+                        }
+                        match mode {
+                            SaveMode::Insert => {
+                                <ProjectTask as DbObject<DB, _>>::insert_in_tx(
+                                    &mut tx,
+                                    &task_tree.task,
+                                )
+                                .await?;
+                            }
+                            SaveMode::Replace => {
+                                <ProjectTask as DbObject<DB, _>>::upsert_in_tx(
+                                    &mut tx,
+                                    &task_tree.task,
+                                )
+                                .await?;
+                                sqlx::query(tag::DELETE_TASK_TAGS_SQL)
+                                    .bind(Guid::from(task_id))
+                                    .execute(&mut *tx)
+                                    .await
+                                    .context("ProjectTask", task_id)?;
+                            }
+                        }
+                        tag_in_tx::<DB>(&mut tx, task_id, &tags).await?;
+                    }
+                    // Updates first, so they are not mistaken for overlaps
+                    let updates = plan.update.iter().map(|(id, task_time)| (Some(*id), *task_time));
+                    let inserts = plan.insert.iter().map(|task_time| (None, *task_time));
+                    for (stored_id, task_time) in updates.chain(inserts) {
+                        let task_time = match stored_id {
+                            Some(task_time_id) => TaskTime { task_time_id, ..task_time.clone() },
+                            None => task_time.clone(),
+                        };
+                        task_time.validate(&self.config.validation)?;
+                        if let Some(span) = task_time.time_span() {
+                            if let Some(query) = OverlapQuery::new::<TaskTime>(&span, self.config.validation.overlap) {
+                                let others = query
+                                    .find(&mut *tx, &span, task_time.page_key())
+                                    .await
+                                    .context(span.entity, &span.id)?;
+                                overlap_error(span, others)?;
+                            }
+                        }
+                        if stored_id.is_some() {
+                            sqlx::query(task_time::UPDATE_SYNCED_SQL)
+                                .bind(task_time.end_time)
+                                .bind(task_time.invoice_id.map(Guid::from))
+                                .bind(task_time.task_time_id as i64)
+                                .execute(&mut *tx)
+                                .await
+                                .context("TaskTime", task_time.task_time_id)?;
+                        } else if task_time.task_time_id == 0 {
+                            <TaskTime as DbObject<DB, _>>::insert_in_tx(&mut tx, &task_time).await?;
+                        } else {
+                            sqlx::query(task_time::INSERT_WITH_ID_SQL)
+                                .bind(task_time.task_time_id as i64)
+                                .bind(Guid::from(task_time.task_id))
+                                .bind(task_time.start_time)
+                                .bind(task_time.end_time)
+                                .bind(task_time.invoice_id.map(Guid::from))
+                                .execute(&mut *tx)
+                                .await
+                                .context("TaskTime", task_time.task_time_id)?;
+                        }
+                    }
+                    report.locked.extend(plan.locked);
+                }
+                if kept_ids && backend == Backend::Postgres {
+                    sqlx::query(task_time::PG_RESTART_IDS_SQL)
+                        .execute(&mut *tx)
+                        .await
+                        .context("TaskTime", "")?;
+                }
+
+                Ok::<_, Error>(report)
+            }
+            .await;
+            finish_tx(tx, result).await
+        })
+    }
+
+    ///
+    /// The stored project with its tasks, their times and tags.
+    ///
+    pub async fn fetch_project_tree(&mut self, project: Project) -> Result<ProjectTree, Error> {
+        let tasks = self.fetch_some::<ProjectTask>(&project.project_id).await?;
+        let mut task_trees = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            let task_times = self.fetch_some::<TaskTime>(&task.task_id).await?;
+            let tags = self
+                .task_tags(task.task_id)
+                .await?
+                .into_iter()
+                .map(|tag| tag.tag_name)
+                .collect();
+            task_trees.push(TaskTree {
+                task,
+                task_times,
+                tags,
+            });
+        }
+        Ok(ProjectTree {
+            project,
+            tasks: task_trees,
+        })
+    }
+
+    ///
+    /// Write every client, invoice and project tree to `writer`. NDJSON is
+    /// written a project at a time; JSON is gathered into one `Export`
+    /// first. Returns the number of projects written.
+    ///
+    /// ```ignore
+    /// let file = std::io::BufWriter::new(std::fs::File::create("backup.ndjson")?);
+    /// db.export(ExportFormat::Ndjson, file).await?;
+    /// ```
+    pub async fn export(
+        &mut self,
+        format: ExportFormat,
+        mut writer: impl Write,
+    ) -> Result<u64, Error> {
+        let header = ExportHeader::new(now());
+        let clients = self.fetch_all::<Client>().await?;
+        let invoices = self.fetch_invoice_trees().await?;
+
+        let mut document = Export {
+            header: header.clone(),
+            clients: Vec::new(),
+            invoices: Vec::new(),
+            projects: Vec::new(),
+        };
+        match format {
+            ExportFormat::Json => {
+                document.clients = clients;
+                document.invoices = invoices;
+            }
+            ExportFormat::Ndjson => {
+                ExportRecord::Header(header).write_line(&mut writer)?;
+                for client in clients {
+                    ExportRecord::Client(client).write_line(&mut writer)?;
+                }
+                for invoice in invoices {
+                    ExportRecord::Invoice(invoice).write_line(&mut writer)?;
+                }
+            }
+        }
+
+        let mut written = 0;
+        let mut after = None;
+        loop {
+            let page: Page<Project> = self.fetch_page(after, EXPORT_PAGE_SIZE).await?;
+            for project in page.items {
+                let tree = self.fetch_project_tree(project).await?;
+                match format {
+                    ExportFormat::Json => document.projects.push(tree),
+                    ExportFormat::Ndjson => ExportRecord::Project(tree).write_line(&mut writer)?,
+                }
+                written += 1;
+            }
+            match page.next {
+                Some(key) => after = Some(key),
+                None => break,
+            }
+        }
+
+        if format == ExportFormat::Json {
+            serde_json::to_writer_pretty(&mut writer, &document)
+                .map_err(|e| Error::Export(e.to_string()))?;
+        }
+        writer.flush().map_err(|e| Error::Export(e.to_string()))?;
+        Ok(written)
+    }
+
+    ///
+    /// Read an export written by `export` and save what it holds, keeping
+    /// its keys. `mode` is applied as in `save_project_trees`. A JSON file
+    /// is saved in one transaction; an NDJSON file in batches of
+    /// `IMPORT_BATCH_SIZE` projects, each batch in its own transaction.
+    /// Returns the number of projects saved and the invoiced TaskTimes a
+    /// replace kept.
+    ///
+    pub async fn import(
+        &mut self,
+        format: ExportFormat,
+        reader: impl BufRead,
+        mode: SaveMode,
+    ) -> Result<SaveReport, Error> {
+        if format == ExportFormat::Json {
+            let document: Export =
+                serde_json::from_reader(reader).map_err(|e| Error::Export(e.to_string()))?;
+            document.header.check()?;
+            return self
+                .save_records(
+                    &document.clients,
+                    &document.invoices,
+                    &document.projects,
+                    mode,
+                )
+                .await;
+        }
+
+        let mut report = SaveReport::default();
+        let mut header_read = false;
+        let mut clients = Vec::new();
+        let mut invoices = Vec::new();
+        let mut trees = Vec::new();
+        for (n, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| Error::Export(e.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = ExportRecord::from_line(&line, n + 1)?;
+            match record {
+                ExportRecord::Header(header) if !header_read => {
+                    header.check()?;
+                    header_read = true;
+                    continue;
+                }
+                _ if !header_read => {
+                    return Err(Error::Export(format!(
+                        "line {}: the file does not start with a header",
+                        n + 1
+                    )))
+                }
+                ExportRecord::Header(_) => {
+                    return Err(Error::Export(format!("line {}: a second header", n + 1)))
+                }
+                ExportRecord::Client(client) => clients.push(client),
+                ExportRecord::Invoice(invoice) => invoices.push(invoice),
+                ExportRecord::Project(tree) => trees.push(tree),
+            }
+            if trees.len() == IMPORT_BATCH_SIZE {
+                report.add(self.save_records(&clients, &invoices, &trees, mode).await?);
+                clients.clear();
+                invoices.clear();
+                trees.clear();
+            }
+        }
+        if !header_read {
+            return Err(Error::Export("the file is empty".to_string()));
         }
+        report.add(self.save_records(&clients, &invoices, &trees, mode).await?);
+        Ok(report)
+    }
+
+    // Every invoice with its lines
+    async fn fetch_invoice_trees(&mut self) -> Result<Vec<InvoiceTree>, Error> {
+        let mut invoices = Vec::new();
+        for invoice in self.fetch_all::<Invoice>().await? {
+            let lines = self.fetch_some::<InvoiceLine>(&invoice.invoice_id).await?;
+            invoices.push(InvoiceTree { invoice, lines });
+        }
+        Ok(invoices)
+    }
+
+    async fn snapshot(&mut self) -> Result<Snapshot, Error> {
+        let clients = self.fetch_all::<Client>().await?;
+        let invoices = self.fetch_invoice_trees().await?;
+        let mut projects = Vec::new();
+        for project in self.fetch_all::<Project>().await? {
+            projects.push(self.fetch_project_tree(project).await?);
+        }
+        Ok(Snapshot {
+            clients,
+            invoices,
+            projects,
+        })
+    }
+
+    ///
+    /// Copy the rows of `other` that this database does not have, and
+    /// settle the rows both have but store differently by `policy`, in
+    /// one transaction. `other` is only read. Invoices and invoiced
+    /// TaskTimes are never changed, whatever the policy. The stored
+    /// totals are reconciled afterwards.
+    ///
+    /// Returns a report of every row of `other` and what was done with it.
+    /// With `ConflictPolicy::Fail` nothing is written if any row conflicts,
+    /// and the report's `applied` is false.
+    ///
+    /// ```ignore
+    /// let report = desktop.merge_from(&mut laptop, ConflictPolicy::Ours).await?;
+    /// print!("{}", report);
+    /// ```
+    pub async fn merge_from(
+        &mut self,
+        other: &mut DbiDatabase,
+        policy: ConflictPolicy,
+    ) -> Result<MergeReport, Error> {
+        let ours = self.snapshot().await?;
+        let theirs = other.snapshot().await?;
+        let mut plan = sync::plan(&ours, &theirs, policy);
+        self.apply_merge(&mut plan).await?;
+        if plan.report.written() > 0 {
+            self.reconcile().await?;
+        }
+        Ok(plan.report)
+    }
+
+    ///
+    /// Merge `other` into this database by `policy`, then merge the result
+    /// back into `other`, so both end up with every row. A conflict is
+    /// settled once, on the way in, and the winning row is then written
+    /// to both.
+    ///
+    pub async fn sync_with(
+        &mut self,
+        other: &mut DbiDatabase,
+        policy: ConflictPolicy,
+    ) -> Result<SyncReport, Error> {
+        let pulled = self.merge_from(other, policy).await?;
+        let pushed = if pulled.applied {
+            other.merge_from(self, ConflictPolicy::Theirs).await?
+        } else {
+            MergeReport::new(ConflictPolicy::Theirs)
+        };
+        Ok(SyncReport { pulled, pushed })
+    }
+
+    // Write a merge plan in one transaction, setting the report's
+    // `applied` once it is committed
+    async fn apply_merge(&mut self, plan: &mut MergePlan) -> Result<(), Error> {
+        let fail = plan.report.policy == ConflictPolicy::Fail;
+        if fail && plan.report.has_conflicts() {
+            return Ok(());
+        }
+        let rules = self.config.validation;
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            // Whether to keep what was written
+            let result = async {
+                for client in &plan.clients {
+                    client.validate(&rules)?;
+                    <Client as DbObject<DB, _>>::upsert_in_tx(&mut tx, client).await?;
+                }
+                for invoice_tree in &plan.invoices {
+                    <Invoice as DbObject<DB, _>>::insert_in_tx(&mut tx, &invoice_tree.invoice).await?;
+                    for line in &invoice_tree.lines {
+                        <InvoiceLine as DbObject<DB, _>>::insert_in_tx(&mut tx, line).await?;
+                    }
+                }
+                for project in &plan.projects {
+                    <Project as DbObject<DB, _>>::upsert_in_tx(&mut tx, project).await?;
+                }
+                for task_tree in &plan.tasks {
+                    let task_id = task_tree.task.task_id;
+                    <ProjectTask as DbObject<DB, _>>::upsert_in_tx(&mut tx, &task_tree.task).await?;
+                    let tags: Vec<Tag> = task_tree.tags.iter().map(|name| Tag::new(name)).collect();
+                    for tag in &tags {
+                        tag.validate(&rules)?;
+                    }
+                    sqlx::query(tag::DELETE_TASK_TAGS_SQL)
+                        .bind(Guid::from(task_id))
+                        .execute(&mut *tx)
+                        .await
+                        .context("ProjectTask", task_id)?;
+                    tag_in_tx::<DB>(&mut tx, task_id, &tags).await?;
+                }
+                for task_time in &plan.changed_times {
+                    task_time.validate(&rules)?;
+                    sqlx::query(task_time::UPDATE_SYNCED_SQL)
+                        .bind(task_time.end_time)
+                        .bind(task_time.invoice_id.map(Guid::from))
+                        .bind(task_time.task_time_id as i64)
+                        .execute(&mut *tx)
+                        .await
+                        .context("TaskTime", task_time.task_time_id)?;
+                }
+                for (index, task_time) in &plan.new_times {
+                    task_time.validate(&rules)?;
+                    if let Some(span) = task_time.time_span() {
+                        if let Some(query) = OverlapQuery::new::<TaskTime>(&span, rules.overlap) {
+                            let others = query
+                                .find(&mut *tx, &span, task_time.page_key())
+                                .await
+                                .context(span.entity, &span.id)?;
+                            // Two different times for the same work; neither
+                            // database's is dropped
+                            if !others.is_empty() {
+                                let item = &mut plan.report.items[*index];
+                                item.status = SyncStatus::Conflict;
+                                item.action = SyncAction::Kept;
+                                item.reason = Some(format!("overlaps TaskTime {} here", others.join(", ")));
+                                if fail {
+                                    return Ok(false);
+                                }
+                                continue;
+                            }
+                        }
+                    }
+                    <TaskTime as DbObject<DB, _>>::insert_in_tx(&mut tx, task_time).await?;
+                }
+                Ok::<_, Error>(true)
+            }
+            .await;
+            if let Ok(false) = result {
+                tx.rollback().await?;
+                return Ok(());
+            }
+            finish_tx(tx, result).await?;
+            plan.report.applied = true;
+            Ok(())
+        })
+    }
+
+    ///
+    /// Run the model's own checks, then look for stored rows its time
+    /// overlaps under the configured `OverlapScope`.
+    ///
+    pub async fn validate<T: DbModel>(&self, dbo: &T) -> Result<(), Error> {
+        dbo.validate(&self.config.validation)?;
+        let Some(span) = dbo.time_span() else {
+            return Ok(());
+        };
+        let Some(query) = OverlapQuery::new::<T>(&span, self.config.validation.overlap) else {
+            return Ok(());
+        };
+        let others = with_pool!(self, pool, DB => {
+            query.find(pool, &span, dbo.page_key()).await
+        })
+        .context(span.entity, &span.id)?;
+        overlap_error(span, others)
+    }
+
+    ///
+    /// Insert a row for any model. Returns the new key for models with a
+    /// database assigned key, otherwise the number of rows inserted.
+    ///
+    /// ```ignore
+    /// db.insert(&project).await?;
+    /// let projects: Vec<Project> = db.fetch_all().await?;
+    /// ```
+    pub async fn insert<T: DbModel>(&mut self, dbo: &T) -> Result<u64, Error> {
+        self.validate(dbo).await?;
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::insert_one(pool, dbo).await)
+    }
+
+    pub async fn update<T: DbModel>(&mut self, dbo: &T) -> Result<u64, Error> {
+        self.validate(dbo).await?;
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::update_one(pool, dbo).await)
+    }
+
+    ///
+    /// Insert the object, or overwrite the existing row with the same key.
+    ///
+    pub async fn upsert<T: DbModel>(&mut self, dbo: &T) -> Result<u64, Error> {
+        self.validate(dbo).await?;
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::upsert_one(pool, dbo).await)
+    }
+
+    ///
+    /// Delete the object's row. Dependent rows are removed by the
+    /// ON DELETE CASCADE foreign keys and reported in `DeleteCount::children`.
+    ///
+    pub async fn delete<T: DbModel>(&mut self, dbo: &T) -> Result<DeleteCount, Error> {
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::delete_one(pool, dbo).await)
+    }
+
+    pub async fn fetch_all<T: DbModel>(&mut self) -> Result<Vec<T>, Error> {
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::retrieve_all(pool).await)
+    }
+
+    ///
+    /// Fetch the rows owned by the parent with the given key, or for a
+    /// model without a parent, the row with that key.
+    ///
+    pub async fn fetch_some<T: DbModel>(&mut self, uuid: &Uuid) -> Result<Vec<T>, Error> {
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::retrieve_some(pool, uuid).await)
+    }
+
+    ///
+    /// Stream every row of the model's table in primary key order,
+    /// holding only one row in memory at a time.
+    ///
+    pub fn fetch_stream<T: DbModel>(&self) -> BoxStream<'_, Result<T, Error>> {
+        // Nothing is bound, so the stream can be read whatever the key storage
+        match &self.pool {
+            DbPool::Sqlite(pool) => <T as DbObject<Sqlite, T>>::retrieve_stream(pool),
+            DbPool::Postgres(pool) => <T as DbObject<Postgres, T>>::retrieve_stream(pool),
+        }
+    }
+
+    ///
+    /// Fetch up to `limit` rows of the model's table whose primary key
+    /// comes after `after`, or the first page when `after` is None.
+    ///
+    /// ```ignore
+    /// let mut after = None;
+    /// loop {
+    ///     let page: Page<TaskTime> = db.fetch_page(after, 500).await?;
+    ///     // ... use page.items
+    ///     match page.next {
+    ///         Some(key) => after = Some(key),
+    ///         None => break,
+    ///     }
+    /// }
+    /// ```
+    pub async fn fetch_page<T: DbModel>(
+        &mut self,
+        after: Option<PageKey>,
+        limit: u32,
+    ) -> Result<Page<T>, Error> {
+        let limit = limit as i64;
+        let items = with_pool!(self, pool, DB => {
+            <T as DbObject<DB, T>>::retrieve_page(pool, after.as_ref(), limit).await
+        })?;
+        // A short page is the last one, so there is nothing after it
+        let next = match items.last() {
+            Some(last) if items.len() as i64 == limit => Some(last.page_key()),
+            _ => None,
+        };
+        Ok(Page { items, next })
+    }
+
+    ///
+    /// Fetch the rows of the model's table that match the filter.
+    ///
+    pub async fn fetch_filtered<T: DbModel>(
+        &mut self,
+        filter: &QueryFilter,
+    ) -> Result<Vec<T>, Error> {
+        with_pool!(self, pool, DB => {
+            <T as DbObject<DB, T>>::retrieve_filtered(pool, filter).await
+        })
+    }
+
+    ///
+    /// Fill in the object from the row with the same key.
+    ///
+    pub async fn fetch_one<T: DbModel>(&mut self, dbo: &mut T) -> Result<(), Error> {
+        with_pool!(self, pool, DB => <T as DbObject<DB, T>>::retrieve_one(dbo, pool).await)
+    }
+
+    ///
+    /// The rate the project is paid at, falling back to its client's
+    /// rate when the project's is 0.
+    ///
+    pub async fn pay_rate(&mut self, project: &Project) -> Result<Money, Error> {
+        let client = match project.client_id {
+            Some(client_id) if project.pay_rate <= Money::ZERO => {
+                let mut client = Client {
+                    client_id,
+                    ..Default::default()
+                };
+                self.fetch_one(&mut client).await?;
+                Some(client)
+            }
+            _ => None,
+        };
+        Ok(project.pay_rate_for(client.as_ref()))
+    }
+
+    ///
+    /// List every project and task whose stored ProjectDuration, TotalPay
+    /// or TaskDuration disagrees with its TaskTimes. Nothing is changed.
+    ///
+    pub async fn verify(&mut self) -> Result<TotalsReport, Error> {
+        self.check_totals(false).await
+    }
+
+    ///
+    /// Rewrite the stored totals that disagree with the TaskTimes, in one
+    /// transaction, and return the rows that were changed with their old
+    /// values.
+    ///
+    pub async fn reconcile(&mut self) -> Result<TotalsReport, Error> {
+        self.check_totals(true).await
+    }
+
+    async fn check_totals(&mut self, fix: bool) -> Result<TotalsReport, Error> {
+        let billed = self.billed_pay().await?;
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                let mut projects: Vec<ProjectTotals> =
+                    sqlx::query_as(&reconcile::project_totals_sql::<DB>())
+                        .fetch_all(&mut *tx)
+                        .await
+                        .context("Project", "")?;
+                let tasks: Vec<TaskTotals> = sqlx::query_as(&reconcile::task_totals_sql::<DB>())
+                    .fetch_all(&mut *tx)
+                    .await
+                    .context("ProjectTask", "")?;
+                for project in &mut projects {
+                    project.billed_pay = billed.get(&project.project_id).copied();
+                }
+                let report = TotalsReport::mismatches(projects, tasks, self.config.rounding);
+                if !fix {
+                    return Ok(report);
+                }
+
+                for task in &report.tasks {
+                    sqlx::query(reconcile::UPDATE_TASK_SQL)
+                        .bind(task.actual_duration)
+                        .bind(Guid::from(task.task_id))
+                        .execute(&mut *tx)
+                        .await
+                        .context("ProjectTask", task.task_id)?;
+                }
+                for project in &report.projects {
+                    sqlx::query(reconcile::UPDATE_PROJECT_SQL)
+                        .bind(project.actual_duration)
+                        .bind(project.actual_pay(self.config.rounding))
+                        .bind(Guid::from(project.project_id))
+                        .execute(&mut *tx)
+                        .await
+                        .context("Project", project.project_id)?;
+                }
+                Ok::<_, Error>(report)
+            }
+            .await;
+            finish_tx(tx, result).await
+        })
+    }
+
+    ///
+    /// Hours and pay for each project in each period, from the TaskTimes
+    /// that start inside the range.
+    ///
+    /// ```ignore
+    /// let july = ReportRange::month(2024, 7).unwrap();
+    /// for row in db.project_hours(Period::Week, &july).await? {
+    ///     println!("{} {} {:.2}", Period::Week.label(row.period_start), row.project_name, row.hours);
+    /// }
+    /// ```
+    pub async fn project_hours(
+        &mut self,
+        period: Period,
+        range: &ReportRange,
+    ) -> Result<Vec<ProjectHours>, Error> {
+        let mut rows: Vec<ProjectHours> = with_pool!(self, pool, DB => {
+            let mut qb = report::project_hours_query::<DB>(period, range);
+            qb.build_query_as().fetch_all(pool).await.context("ProjectHours", "")
+        })?;
+        report::round_pay(&mut rows, self.config.rounding, |r| &mut r.pay);
+        Ok(rows)
+    }
+
+    ///
+    /// Hours and pay across all projects for each period.
+    ///
+    pub async fn period_earnings(
+        &mut self,
+        period: Period,
+        range: &ReportRange,
+    ) -> Result<Vec<PeriodEarnings>, Error> {
+        let mut rows: Vec<PeriodEarnings> = with_pool!(self, pool, DB => {
+            let mut qb = report::period_earnings_query::<DB>(period, range);
+            qb.build_query_as().fetch_all(pool).await.context("PeriodEarnings", "")
+        })?;
+        report::round_pay(&mut rows, self.config.rounding, |r| &mut r.pay);
+        Ok(rows)
+    }
+
+    ///
+    /// Hours and pay for each task name, summed over every project that
+    /// has a task with that name. Longest first.
+    ///
+    pub async fn task_name_hours(
+        &mut self,
+        range: &ReportRange,
+    ) -> Result<Vec<TaskNameHours>, Error> {
+        let mut rows: Vec<TaskNameHours> = with_pool!(self, pool, DB => {
+            let mut qb = report::task_name_hours_query::<DB>(range);
+            qb.build_query_as().fetch_all(pool).await.context("TaskNameHours", "")
+        })?;
+        report::round_pay(&mut rows, self.config.rounding, |r| &mut r.pay);
+        Ok(rows)
+    }
+
+    ///
+    /// Hours and pay for each client, most paid first.
+    ///
+    pub async fn client_earnings(
+        &mut self,
+        range: &ReportRange,
+    ) -> Result<Vec<ClientEarnings>, Error> {
+        let mut rows: Vec<ClientEarnings> = with_pool!(self, pool, DB => {
+            let mut qb = report::client_earnings_query::<DB>(range);
+            qb.build_query_as().fetch_all(pool).await.context("ClientEarnings", "")
+        })?;
+        report::round_pay(&mut rows, self.config.rounding, |r| &mut r.pay);
+        Ok(rows)
+    }
+
+    ///
+    /// The `limit` projects that earned the most in the range.
+    ///
+    pub async fn top_projects(
+        &mut self,
+        limit: u32,
+        range: &ReportRange,
+    ) -> Result<Vec<ProjectRevenue>, Error> {
+        let mut rows: Vec<ProjectRevenue> = with_pool!(self, pool, DB => {
+            let mut qb = report::top_projects_query::<DB>(limit as i64, range);
+            qb.build_query_as().fetch_all(pool).await.context("ProjectRevenue", "")
+        })?;
+        report::round_pay(&mut rows, self.config.rounding, |r| &mut r.revenue);
+        Ok(rows)
+    }
+
+    ///
+    /// The stored billing rules with their task rates.
+    ///
+    pub async fn rule_book(&mut self) -> Result<RuleBook, Error> {
+        let rules = self.fetch_all::<BillingRule>().await?;
+        let task_rates = self.fetch_all::<TaskRate>().await?;
+        Ok(RuleBook::new(rules, task_rates))
+    }
+
+    ///
+    /// Bill the TaskTimes that start inside the range, one charge for each
+    /// billing rule and one for each project without a rule. Print a
+    /// charge to see how every line was worked out.
+    ///
+    /// ```ignore
+    /// for charge in db.charges(&ReportRange::month(2024, 8).unwrap()).await? {
+    ///     println!("{}", charge);
+    /// }
+    /// ```
+    pub async fn charges(&mut self, range: &ReportRange) -> Result<Vec<Charge>, Error> {
+        let book = self.rule_book().await?;
+        let entries: Vec<WorkEntry> = with_pool!(self, pool, DB => {
+            let mut qb = report::work_entries_query::<DB>(range, None);
+            qb.build_query_as().fetch_all(pool).await.context("TaskTime", "")
+        })?;
+        Ok(book.charges(&entries, self.config.rounding))
+    }
+
+    ///
+    /// Set the TotalPay of every project that has a billing rule, its own
+    /// or its client's, to what the rule charges for all its TaskTimes.
+    /// Returns how many projects changed.
+    ///
+    pub async fn bill(&mut self) -> Result<u64, Error> {
+        let billed = self.billed_pay().await?;
+        let changed: Vec<(Uuid, Money)> = self
+            .fetch_all::<Project>()
+            .await?
+            .into_iter()
+            .filter_map(|p| match billed.get(&p.project_id) {
+                Some(pay) if *pay != p.total_pay => Some((p.project_id, *pay)),
+                _ => None,
+            })
+            .collect();
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                for (project_id, pay) in &changed {
+                    sqlx::query(reconcile::UPDATE_PAY_SQL)
+                        .bind(pay)
+                        .bind(Guid::from(*project_id))
+                        .execute(&mut *tx)
+                        .await
+                        .context("Project", project_id)?;
+                }
+                Ok::<_, Error>(changed.len() as u64)
+            }
+            .await;
+            finish_tx(tx, result).await
+        })
+    }
+
+    ///
+    /// Invoice a client for its TaskTimes in the range that are not yet on
+    /// an invoice, billed by their rules as `charges` does. The invoice
+    /// takes the next number, and the TaskTimes are marked with it so they
+    /// are never billed again. Returns None when there is nothing to bill.
+    ///
+    /// ```ignore
+    /// let range = ReportRange::month(2024, 8).unwrap();
+    /// if let Some(doc) = db.generate_invoice(client_id, &range, today).await? {
+    ///     println!("{}", doc.render(InvoiceFormat::Markdown));
+    /// }
+    /// ```
+    ///
+    pub async fn generate_invoice(
+        &mut self,
+        client_id: Uuid,
+        range: &ReportRange,
+        issue_date: NaiveDate,
+    ) -> Result<Option<InvoiceDoc>, Error> {
+        let mut client = Client {
+            client_id,
+            ..Default::default()
+        };
+        self.fetch_one(&mut client).await?;
+        let book = self.rule_book().await?;
+        let rounding = self.config.rounding;
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                let entries: Vec<WorkEntry> = report::work_entries_query::<DB>(range, Some(client_id))
+                    .build_query_as()
+                    .fetch_all(&mut *tx)
+                    .await
+                    .context("TaskTime", "")?;
+                let Some(first) = entries.first() else {
+                    return Ok(None);
+                };
+                if let Some(other) = entries.iter().find(|e| e.currency != first.currency) {
+                    return Err(Error::Validation {
+                        entity: "Invoice",
+                        id: client_id.to_string(),
+                        message: format!(
+                            "Projects billed in both {} and {} cannot share an invoice",
+                            first.currency, other.currency
+                        ),
+                    });
+                }
+
+                let mut charged: Vec<ChargeLine> = book
+                    .charges(&entries, rounding)
+                    .into_iter()
+                    .flat_map(|c| c.lines)
+                    .collect();
+                charged.sort_by_key(|l| l.date);
+
+                let number: i64 = sqlx::query_scalar(
+                    r#"SELECT COALESCE(MAX("InvoiceNumber"), 0) + 1 FROM Invoices"#,
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .context("Invoice", "")?;
+                let mut invoice = Invoice::new(number, client_id, issue_date);
+                invoice.period_start = range.from;
+                invoice.period_end = range.to;
+                invoice.currency = first.currency.clone();
+                invoice.total = charged.iter().map(|l| l.amount).sum();
+                <Invoice as DbObject<DB, _>>::insert_in_tx(&mut tx, &invoice).await?;
+
+                let mut lines = Vec::with_capacity(charged.len());
+                for (i, line) in charged.iter().enumerate() {
+                    let line = InvoiceLine::new(invoice.invoice_id, i as i32 + 1, line);
+                    <InvoiceLine as DbObject<DB, _>>::insert_in_tx(&mut tx, &line).await?;
+                    lines.push(line);
+                }
+                for entry in &entries {
+                    sqlx::query(r#"UPDATE TaskTimes SET "InvoiceId" = $1 WHERE "TaskTimeId" = $2"#)
+                        .bind(Guid::from(invoice.invoice_id))
+                        .bind(entry.task_time_id)
+                        .execute(&mut *tx)
+                        .await
+                        .context("TaskTime", entry.task_time_id.to_string())?;
+                }
+                Ok::<_, Error>(Some(InvoiceDoc {
+                    invoice,
+                    client,
+                    lines,
+                }))
+            }
+            .await;
+            finish_tx(tx, result).await
+        })
+    }
+
+    ///
+    /// The stored invoice with the number, with its client and lines.
+    ///
+    pub async fn invoice(&mut self, invoice_number: i64) -> Result<InvoiceDoc, Error> {
+        let invoice: Invoice = with_pool!(self, pool, DB => {
+            sqlx::query_as(r#"SELECT * FROM Invoices WHERE "InvoiceNumber" = $1"#)
+                .bind(invoice_number)
+                .fetch_one(pool)
+                .await
+                .context("Invoice", invoice_number.to_string())
+        })?;
+        let mut client = Client {
+            client_id: invoice.client_id,
+            ..Default::default()
+        };
+        self.fetch_one(&mut client).await?;
+        let lines = self.fetch_some::<InvoiceLine>(&invoice.invoice_id).await?;
+        Ok(InvoiceDoc {
+            invoice,
+            client,
+            lines,
+        })
+    }
+
+    ///
+    /// The TaskTime whose timer is running now, if any.
+    ///
+    pub async fn running_timer(&mut self) -> Result<Option<RunningTimer>, Error> {
+        with_pool!(self, pool, DB => {
+            sqlx::query_as(timer::RUNNING_SQL)
+                .fetch_optional(pool)
+                .await
+                .context("TaskTime", "")
+        })
+    }
+
+    ///
+    /// Start a timer on the project's task, now. See `start_timer_at`.
+    ///
+    pub async fn start_timer(
+        &mut self,
+        project_id: Uuid,
+        task_name: &str,
+    ) -> Result<TaskTime, Error> {
+        self.start_timer_at(project_id, task_name, now()).await
     }
 
-    pub async fn fetch_all(
+    ///
+    /// Start a timer on the project's task at `start`, as a TaskTime with
+    /// no EndTime. The timer carries on the project's latest task with the
+    /// name, or a new task is added. Only one timer may run at a time, so
+    /// this fails with a Validation error while another is running.
+    ///
+    pub async fn start_timer_at(
         &mut self,
-        data_object: &DataObject,
-    ) -> Result<Box<DataCollection>, Error> {
-        let results = match data_object {
-            DataObject::Project(project) => Box::new(DataCollection::Projects(
-                Project::retrieve_all(&self.pool).await?,
-            )),
-            DataObject::ProjectTask(project) => Box::new(DataCollection::ProjectTasks(
-                ProjectTask::retrieve_all(&self.pool).await?,
-            )),
-            DataObject::TaskTime(project) => Box::new(DataCollection::TaskTimes(
-                TaskTime::retrieve_all(&self.pool).await?,
-            )),
-            #[cfg(test)]
-            DataObject::MyTable(value) => Box::new(DataCollection::MyTables(
-                <tests::MyTable>::retrieve_all(&self.pool).await?,
-            )),
+        project_id: Uuid,
+        task_name: &str,
+        start: NaiveDateTime,
+    ) -> Result<TaskTime, Error> {
+        let mut project = Project {
+            project_id,
+            ..Default::default()
+        };
+        self.fetch_one(&mut project).await?;
+        let rules = self.config.validation;
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                let running: Option<RunningTimer> = sqlx::query_as(timer::RUNNING_SQL)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .context("TaskTime", "")?;
+                if let Some(running) = running {
+                    return Err(Error::Validation {
+                        entity: "TaskTime",
+                        id: running.task_time_id.to_string(),
+                        message: format!(
+                            "A timer is already running on {} {} since {}",
+                            running.project_name, running.task_name, running.start_time
+                        ),
+                    });
+                }
+
+                let task: Option<ProjectTask> = sqlx::query_as(timer::TASK_SQL)
+                    .bind(Guid::from(project_id))
+                    .bind(task_name)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .context("ProjectTask", "")?;
+                let task = match task {
+                    Some(task) => task,
+                    None => {
+                        // Keyed the way mv_load_csv keys the tasks it reads
+                        let task = ProjectTask {
+                            task_id: utils::make_uuid(&format!(
+                                "{}{}{}",
+                                project_id,
+                                task_name,
+                                start.format("%H:%M:%S")
+                            )),
+                            project_id,
+                            task_name: task_name.to_string(),
+                            task_duration: 0,
+                            task_date_time: start,
+                            notes: None,
+                        };
+                        <ProjectTask as DbObject<DB, _>>::insert_in_tx(&mut tx, &task).await?;
+                        task
+                    }
+                };
+
+                let mut task_time = TaskTime {
+                    task_id: task.task_id,
+                    start_time: start,
+                    ..Default::default()
+                };
+                task_time.validate(&rules)?;
+                if let Some(span) = task_time.time_span() {
+                    if let Some(query) = OverlapQuery::new::<TaskTime>(&span, rules.overlap) {
+                        let others = query
+                            .find(&mut *tx, &span, task_time.page_key())
+                            .await
+                            .context(span.entity, &span.id)?;
+                        overlap_error(span, others)?;
+                    }
+                }
+                task_time.task_time_id =
+                    <TaskTime as DbObject<DB, _>>::insert_in_tx(&mut tx, &task_time).await?;
+                Ok::<_, Error>(task_time)
+            }
+            .await;
+            finish_tx(tx, result).await
+        })
+    }
+
+    ///
+    /// Stop the running timer now. See `stop_timer_at`.
+    ///
+    pub async fn stop_timer(&mut self) -> Result<Option<TaskTime>, Error> {
+        self.stop_timer_at(now()).await
+    }
+
+    ///
+    /// Stop the running timer at `end`, adding its time to the task's and
+    /// project's durations and the project's pay. Projects with a billing
+    /// rule are billed again. Returns the stopped TaskTime, or None when
+    /// no timer was running.
+    ///
+    /// The time must pass the configured `max_task_minutes`; a timer left
+    /// running for longer can only be thrown away with `cancel_timer`.
+    ///
+    pub async fn stop_timer_at(&mut self, end: NaiveDateTime) -> Result<Option<TaskTime>, Error> {
+        let Some(running) = self.running_timer().await? else {
+            return Ok(None);
+        };
+        let mut project = Project {
+            project_id: running.project_id,
+            ..Default::default()
+        };
+        self.fetch_one(&mut project).await?;
+        let pay_rate = self.pay_rate(&project).await?;
+        let task_time = TaskTime {
+            task_time_id: running.task_time_id as u64,
+            task_id: running.task_id,
+            start_time: running.start_time,
+            end_time: Some(end),
+            invoice_id: None,
+        };
+        self.validate(&task_time).await?;
+
+        let duration = (end - running.start_time).num_milliseconds();
+        let id = running.task_time_id.to_string();
+        let stopped = with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                let result = sqlx::query(timer::STOP_SQL)
+                    .bind(end)
+                    .bind(running.task_time_id)
+                    .execute(&mut *tx)
+                    .await
+                    .context("TaskTime", &id)?;
+                // Stopped by someone else in the meantime
+                if result.rows_affected() == 0 {
+                    return Ok(None);
+                }
+                sqlx::query(timer::ADD_TASK_DURATION_SQL)
+                    .bind(duration)
+                    .bind(Guid::from(running.task_id))
+                    .execute(&mut *tx)
+                    .await
+                    .context("ProjectTask", running.task_id)?;
+                let project_duration: i64 = sqlx::query_scalar(timer::ADD_PROJECT_DURATION_SQL)
+                    .bind(duration)
+                    .bind(Guid::from(running.project_id))
+                    .fetch_one(&mut *tx)
+                    .await
+                    .context("Project", running.project_id)?;
+                sqlx::query(reconcile::UPDATE_PAY_SQL)
+                    .bind(utils::total_pay(project_duration, pay_rate, self.config.rounding))
+                    .bind(Guid::from(running.project_id))
+                    .execute(&mut *tx)
+                    .await
+                    .context("Project", running.project_id)?;
+                Ok::<_, Error>(Some(task_time))
+            }
+            .await;
+            finish_tx(tx, result).await
+        })?;
+        self.bill().await?;
+        Ok(stopped)
+    }
+
+    ///
+    /// Throw away the running timer, as when it was left running by
+    /// mistake. Its task is kept. Returns the cancelled timer, or None when
+    /// no timer was running.
+    ///
+    pub async fn cancel_timer(&mut self) -> Result<Option<RunningTimer>, Error> {
+        let Some(running) = self.running_timer().await? else {
+            return Ok(None);
+        };
+        let cancelled = with_pool!(self, pool, DB => {
+            sqlx::query(timer::CANCEL_SQL)
+                .bind(running.task_time_id)
+                .execute(pool)
+                .await
+                .map(|result| result.rows_affected())
+        })
+        .context("TaskTime", running.task_time_id)?;
+        // Stopped or cancelled by someone else in the meantime
+        if cancelled == 0 {
+            return Ok(None);
+        }
+        Ok(Some(running))
+    }
+
+    ///
+    /// Put the named tags on the task, storing any that are new. Names
+    /// are trimmed and lower cased first. Returns how many of the tags the
+    /// task did not have already.
+    ///
+    /// ```ignore
+    /// db.tag_task(task.task_id, &["Meetings", "travel"]).await?;
+    /// let august = ReportRange::month(2024, 8).unwrap().tag("meetings");
+    /// let hours = db.project_hours(Period::Month, &august).await?;
+    /// ```
+    pub async fn tag_task(&mut self, task_id: Uuid, names: &[&str]) -> Result<u64, Error> {
+        let tags: Vec<Tag> = names.iter().map(|name| Tag::new(name)).collect();
+        for tag in &tags {
+            tag.validate(&self.config.validation)?;
+        }
+        with_pool!(self, pool, DB => {
+            let mut tx = pool.begin().await?;
+            let result = async {
+                let added = tag_in_tx::<DB>(&mut tx, task_id, &tags).await?;
+                Ok::<_, Error>(added)
+            }
+            .await;
+            finish_tx(tx, result).await
+        })
+    }
+
+    ///
+    /// Take the named tag off the task. The tag itself is kept. Returns 0
+    /// when the task did not have it.
+    ///
+    pub async fn untag_task(&mut self, task_id: Uuid, name: &str) -> Result<u64, Error> {
+        with_pool!(self, pool, DB => {
+            let result = sqlx::query(tag::DELETE_TASK_TAG_SQL)
+                .bind(Guid::from(task_id))
+                .bind(Guid::from(Tag::id_for(name)))
+                .execute(pool)
+                .await
+                .context("ProjectTask", task_id)?;
+            Ok(result.rows_affected())
+        })
+    }
+
+    ///
+    /// The task's tags, by name.
+    ///
+    pub async fn task_tags(&mut self, task_id: Uuid) -> Result<Vec<Tag>, Error> {
+        with_pool!(self, pool, DB => {
+            sqlx::query_as(tag::TASK_TAGS_SQL)
+                .bind(Guid::from(task_id))
+                .fetch_all(pool)
+                .await
+                .context("ProjectTask", task_id)
+        })
+    }
+
+    ///
+    /// Hours and pay for each tag, longest first. A time whose task has
+    /// several tags is counted under each of them.
+    ///
+    pub async fn tag_hours(&mut self, range: &ReportRange) -> Result<Vec<TagHours>, Error> {
+        let mut rows: Vec<TagHours> = with_pool!(self, pool, DB => {
+            let mut qb = report::tag_hours_query::<DB>(range);
+            qb.build_query_as().fetch_all(pool).await.context("TagHours", "")
+        })?;
+        report::round_pay(&mut rows, self.config.rounding, |r| &mut r.pay);
+        Ok(rows)
+    }
+
+    ///
+    /// Projects and ProjectTasks whose names or notes match the query,
+    /// best match first. Words are matched on their stems, so "collate"
+    /// finds "collated". An empty query finds nothing.
+    ///
+    /// ```ignore
+    /// for hit in db.search("collate ruby").await? {
+    ///     println!("{} {:?}: {}", hit.project_name, hit.task_name, hit.snippet);
+    /// }
+    /// ```
+    pub async fn search(&mut self, query: &str) -> Result<Vec<SearchHit>, Error> {
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        with_pool!(self, pool, DB => {
+            sqlx::query_as(<DB as SearchDialect>::SEARCH_SQL)
+                .bind(query)
+                .fetch_all(pool)
+                .await
+                .map_err(|error| search::search_error(error, query))
+        })
+    }
+
+    ///
+    /// Write a compacted copy of an SQLite database to `path` with
+    /// `VACUUM INTO`. The database stays open for reading and writing
+    /// while the copy is taken, and the copy is consistent as of the
+    /// moment it started. `path` must not already exist.
+    ///
+    pub async fn backup_to(&mut self, path: &Path) -> Result<(), Error> {
+        let pool = self.sqlite_pool("backup")?;
+        if path.exists() {
+            return Err(Error::Maintenance(format!(
+                "{} already exists",
+                path.display()
+            )));
+        }
+        sqlx::query(maintenance::VACUUM_INTO_SQL)
+            .bind(path.to_string_lossy())
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    ///
+    /// Back up into `dir` under a timestamped name, then delete the oldest
+    /// backups of this database in `dir` until `keep` are left. The
+    /// directory is created if needed. Returns the new backup's path.
+    ///
+    /// ```ignore
+    /// // Keeps projects-20241025-090507-042.db3 and the six before it
+    /// db.backup_rotating(Path::new("backups"), 7).await?;
+    /// ```
+    ///
+    pub async fn backup_rotating(&mut self, dir: &Path, keep: usize) -> Result<PathBuf, Error> {
+        let stem = match self.database_file() {
+            Ok(file) => file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Err(_) => "memory".to_string(),
         };
-        // Err(Error::Protocol("Unexpected error".to_string()))
-        Ok(results)
+        std::fs::create_dir_all(dir).map_err(|e| maintenance::io_error(dir, e))?;
+        let path = dir.join(maintenance::backup_name(&stem, Local::now().naive_local()));
+        self.backup_to(&path).await?;
+        maintenance::prune_backups(dir, &stem, keep.max(1))?;
+        Ok(path)
     }
 
-    pub async fn fetch_one(&mut self, data_object: &mut DataObject) -> Result<(), Error> {
-        match data_object {
-            DataObject::Project(project) => project.retrieve_one(&self.pool).await,
-            DataObject::ProjectTask(task) => task.retrieve_one(&self.pool).await,
-            DataObject::TaskTime(task_time) => task_time.retrieve_one(&self.pool).await,
-            #[cfg(test)]
-            DataObject::MyTable(table) => table.retrieve_one(&self.pool).await,
+    ///
+    /// Replace the database with the backup at `backup`. The backup is
+    /// checked first, and nothing is changed unless it passes both
+    /// integrity checks and holds no migration this build does not know.
+    /// A backup from an older build has its schema brought up to date
+    /// when the database is reopened.
+    ///
+    /// The database as it was is first backed up beside its file, and the
+    /// path of that copy is returned. No other program may have the
+    /// database open while it is restored.
+    ///
+    pub async fn restore_from(&mut self, backup: &Path) -> Result<PathBuf, Error> {
+        let file = self.database_file()?;
+        maintenance::verify_backup(backup, schema::migrator(Backend::Sqlite)).await?;
+
+        let stem = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let dir = file.parent().unwrap_or(Path::new("."));
+        let safety = dir.join(maintenance::backup_name(
+            &format!("{}-pre-restore", stem),
+            Local::now().naive_local(),
+        ));
+        self.backup_to(&safety).await?;
+
+        self.sqlite_pool("restore")?.close().await;
+        let replaced = maintenance::replace_file(backup, &file);
+        // Reopen even if the copy failed, so the database stays usable
+        self.pool = DbPool::Sqlite(Self::open_sqlite(&self.config).await?);
+        replaced?;
+        Ok(safety)
+    }
+
+    ///
+    /// Run SQLite's `PRAGMA integrity_check` and `PRAGMA foreign_key_check`
+    /// and report what they found.
+    ///
+    /// ```ignore
+    /// let report = db.check_integrity().await?;
+    /// if !report.is_ok() {
+    ///     eprintln!("{}", report);
+    /// }
+    /// ```
+    ///
+    pub async fn check_integrity(&mut self) -> Result<IntegrityReport, Error> {
+        maintenance::integrity_report(self.sqlite_pool("integrity check")?).await
+    }
+
+    ///
+    /// Rebuild the database file to give back the space left by deleted
+    /// rows. On PostgreSQL this runs a plain VACUUM of every table.
+    ///
+    pub async fn compact(&mut self) -> Result<(), Error> {
+        with_pool!(self, pool, DB => {
+            sqlx::query(maintenance::VACUUM_SQL).execute(pool).await.map(|_| ())
+        })?;
+        Ok(())
+    }
+
+    // The pool, for the operations only SQLite supports
+    fn sqlite_pool(&self, operation: &str) -> Result<&Pool<Sqlite>, Error> {
+        match &self.pool {
+            DbPool::Sqlite(pool) => Ok(pool),
+            DbPool::Postgres(_) => Err(Error::Maintenance(format!(
+                "{} needs an SQLite database",
+                operation
+            ))),
+        }
+    }
+
+    // The file an SQLite database is stored in
+    fn database_file(&self) -> Result<PathBuf, Error> {
+        self.sqlite_pool("restore")?;
+        let url = &self.config.url;
+        if url.contains(":memory:") || url.contains("mode=memory") {
+            return Err(Error::Maintenance(
+                "an in-memory database has no file".to_string(),
+            ));
+        }
+        let options = SqliteConnectOptions::from_str(url)?;
+        Ok(options.get_filename().to_path_buf())
+    }
+
+    // What each project with a billing rule should be paid
+    async fn billed_pay(&mut self) -> Result<HashMap<Uuid, Money>, Error> {
+        let book = self.rule_book().await?;
+        if book.is_empty() {
+            return Ok(HashMap::new());
         }
+        let charges = self.charges(&ReportRange::all()).await?;
+        let projects = self.fetch_all::<Project>().await?;
+        Ok(projects
+            .iter()
+            .filter(|p| book.rule_for(p.project_id, p.client_id).is_some())
+            .map(|p| {
+                let pay = charges.iter().map(|c| c.project_total(p.project_id)).sum();
+                (p.project_id, pay)
+            })
+            .collect())
+    }
+}
+
+// The local time to the second, as the TaskTimes read from CSV are
+fn now() -> NaiveDateTime {
+    Local::now().naive_local().with_nanosecond(0).unwrap()
+}
+
+fn overlap_error(span: TimeSpan, others: Vec<String>) -> Result<(), Error> {
+    if others.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Overlap {
+            entity: span.entity,
+            id: span.id,
+            others,
+        })
     }
 }
 
@@ -127,13 +1783,14 @@ mod tests {
     use super::*;
     use chrono::NaiveDate;
     use database::query::DbObject;
+    use futures::{StreamExt, TryStreamExt};
     use serde::{Deserialize, Serialize};
     use sqlx::pool;
     use sqlx::query::Query;
-    use sqlx::sqlite::{SqliteArguments, SqliteRow};
-    use sqlx::{Column, Error, FromRow, Pool, Row};
+    use sqlx::{Column, Database, FromRow, Pool, Row};
 
     #[tokio::test]
+    #[allow(clippy::field_reassign_with_default)]
     async fn test_database_select_one() -> Result<(), Error> {
         let config = DbConfig::new("sqlite::memory:");
         let mut db = DbiDatabase::new(config).await?;
@@ -146,11 +1803,9 @@ mod tests {
 
         let mut mytable = MyTable::default();
         mytable.id = inserted.id;
-        let mut dao = DataObject::MyTable(mytable.clone());
-        let result = db.fetch_one(&mut dao).await?;
-        if let DataObject::MyTable(mytable) = dao {
-            assert_eq!(&mytable, &inserted);
-        }
+        #[allow(clippy::let_unit_value)]
+        let result = db.fetch_one(&mut mytable).await?;
+        assert_eq!(&mytable, &inserted);
 
         assert_eq!(result, ());
         Ok(())
@@ -170,15 +1825,7 @@ mod tests {
             };
         }
 
-        let mut mytable = MyTable::default();
-        let dao = DataObject::MyTable(mytable);
-        let result = db.fetch_all(&dao).await?;
-
-        let tables = match *result {
-            DataCollection::MyTables(tables) => tables,
-            _ => todo!(),
-        };
-
+        let tables = db.fetch_all::<MyTable>().await?;
         assert_eq!(tables, expected);
         Ok(())
     }
@@ -193,23 +1840,297 @@ mod tests {
             data: "Testing Insert".to_string(),
             created_at: NaiveDate::MAX,
         };
-        let mut dao = DataObject::MyTable(mt.clone());
-        let result = db.do_insert(&dao).await?;
+        let result = db.insert(&mt).await?;
+        assert_eq!(result, 1);
+        mt.id = result;
+        assert_eq!(db.fetch_all::<MyTable>().await?, vec![mt]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dbobject_update_and_delete() -> Result<(), Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        let mut db = DbiDatabase::new(config).await?;
+        create_table(&mut db).await?;
+        let mut inserted = setup(&mut db).await?;
+
+        inserted.data = "An updated object".to_string();
+        let result = db.update(&inserted).await?;
         assert_eq!(result, 1);
-        mt.id = 1;
-        if let DataObject::MyTable(mut mytable) = dao {
-            mytable.id = result;
-            assert_eq!(&mytable, &mt);
+
+        let mut mytable = MyTable {
+            id: inserted.id,
+            ..Default::default()
+        };
+        db.fetch_one(&mut mytable).await?;
+        assert_eq!(mytable, inserted);
+
+        let result = db.delete(&mytable).await?;
+        assert_eq!(
+            result,
+            DeleteCount {
+                rows: 1,
+                children: 0
+            }
+        );
+        let result = db.delete(&mytable).await?;
+        assert_eq!(result.rows, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_wal_then_read_only() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("mv_dbi_{}.db3", Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+
+        let config = DbConfig::new(&url)
+            .journal_mode(config::JournalMode::Wal)
+            .max_connections(2);
+        let mut db = DbiDatabase::new(config).await?;
+        let mode: (String,) = sqlx::query_as("PRAGMA journal_mode")
+            .fetch_one(db.pool.sqlite())
+            .await?;
+        assert_eq!(mode.0, "wal");
+        create_table(&mut db).await?;
+        setup(&mut db).await?;
+        db.pool.sqlite().close().await;
+
+        let config = DbConfig::new(&url).read_only(true);
+        let mut db = DbiDatabase::new(config).await?;
+        let tables = db.fetch_all::<MyTable>().await?;
+        assert_eq!(tables.len(), 1);
+        assert!(db.insert(&MyTable::default()).await.is_err());
+        db.pool.sqlite().close().await;
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
         }
         Ok(())
     }
 
+    #[tokio::test]
+    #[ignore = "needs a PostgreSQL server in TEST_POSTGRES_URL"]
+    async fn test_postgres_round_trip() -> Result<(), Error> {
+        let url = test_postgres_url();
+        let mut db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        assert_eq!(db.backend(), Backend::Postgres);
+
+        let project = Project {
+            project_id: Uuid::new_v4(),
+            project_name: "Postgres Round Trip".to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 8, 10).unwrap(),
+            pay_rate: Money::from(40),
+            ..Default::default()
+        };
+        let task = ProjectTask {
+            task_id: Uuid::new_v4(),
+            project_id: project.project_id,
+            task_name: "Task 1".to_string(),
+            task_date_time: project.project_date.and_hms_opt(12, 0, 0).unwrap(),
+            ..Default::default()
+        };
+        let task_time = TaskTime {
+            task_id: task.task_id,
+            start_time: task.task_date_time,
+            end_time: Some(task.task_date_time + chrono::TimeDelta::minutes(30)),
+            ..Default::default()
+        };
+        let tree = ProjectTree {
+            project: project.clone(),
+            tasks: vec![model::project_tree::TaskTree {
+                task,
+                task_times: vec![task_time],
+                tags: vec!["meetings".to_string()],
+            }],
+        };
+        let trees = vec![tree];
+        db.save_project_trees(&trees, SaveMode::Insert).await?;
+        db.save_project_trees(&trees, SaveMode::Replace).await?;
+
+        let filter = QueryFilter::new().project_id(project.project_id);
+        let times: Vec<TaskTime> = db.fetch_filtered(&filter).await?;
+        assert_eq!(times.len(), 1);
+        assert!(times[0].task_time_id > 0);
+
+        let streamed: Vec<TaskTime> = db.fetch_stream().try_collect().await?;
+        assert!(!streamed.is_empty());
+        let page = db.fetch_page::<TaskTime>(None, 1).await?;
+        assert!(page.next.is_some());
+
+        let range = ReportRange::new(project.project_date, project.project_date);
+        let hours = db.project_hours(Period::Week, &range).await?;
+        let row = hours.iter().find(|r| r.project_id == project.project_id);
+        assert_eq!(row.map(|r| (r.hours, r.pay)), Some((0.5, Money::from(20))));
+        assert!(!db.period_earnings(Period::Month, &range).await?.is_empty());
+        assert!(!db.task_name_hours(&range).await?.is_empty());
+        assert!(!db.top_projects(3, &range).await?.is_empty());
+        assert!(!db.client_earnings(&range).await?.is_empty());
+        let task_id = trees[0].tasks[0].task.task_id;
+        assert_eq!(db.tag_task(task_id, &["Meetings", "travel"]).await?, 1);
+        assert_eq!(db.task_tags(task_id).await?.len(), 2);
+        let tagged = db.project_hours(Period::Week, &range.tag("travel")).await?;
+        assert!(tagged.iter().any(|r| r.project_id == project.project_id));
+        let filter = QueryFilter::new()
+            .project_id(project.project_id)
+            .tag("travel");
+        assert_eq!(db.fetch_filtered::<ProjectTask>(&filter).await?.len(), 1);
+        assert!(!db.tag_hours(&range).await?.is_empty());
+        let hits = db.search("postgres round trip").await?;
+        assert!(hits.iter().any(|h| h.project_id == project.project_id));
+        assert!(hits.iter().all(|h| h.snippet.contains("[Postgres]")));
+        assert_eq!(db.untag_task(task_id, "travel").await?, 1);
+        let charges = db.charges(&range).await?;
+        let billed: Money = charges
+            .iter()
+            .map(|c| c.project_total(project.project_id))
+            .sum();
+        assert_eq!(billed, Money::from(20));
+
+        let stale = db.verify().await?;
+        assert!(stale
+            .projects
+            .iter()
+            .any(|p| p.project_id == project.project_id));
+        db.reconcile().await?;
+        assert!(db.verify().await?.is_empty());
+
+        let mut fetched = Project {
+            project_id: project.project_id,
+            ..Default::default()
+        };
+        db.fetch_one(&mut fetched).await?;
+        assert_eq!(fetched.project_duration, 30 * 60 * 1000);
+        assert_eq!(fetched.total_pay, Money::from(20));
+
+        // A rule billing the task at 60.00 for a whole hour
+        let rule = BillingRule::for_project(project.project_id).task_increment(60);
+        db.insert(&rule).await?;
+        db.insert(&TaskRate::new(rule.rule_id, "Task 1", Money::from(60)))
+            .await?;
+        assert!(db.fetch_all::<BillingRule>().await?.contains(&rule));
+        assert_eq!(db.bill().await?, 1);
+        db.fetch_one(&mut fetched).await?;
+        assert_eq!(fetched.total_pay, Money::from(60));
+
+        // Half an hour on a timer, billed as a whole hour at 40.00
+        let start = project.project_date.and_hms_opt(14, 0, 0).unwrap();
+        let started = db
+            .start_timer_at(project.project_id, "Task 2", start)
+            .await?;
+        let running = db.running_timer().await?.expect("Expected a timer");
+        assert_eq!(running.task_time_id as u64, started.task_time_id);
+        let stopped = db
+            .stop_timer_at(start + chrono::TimeDelta::minutes(30))
+            .await?;
+        assert!(stopped.is_some());
+        db.fetch_one(&mut fetched).await?;
+        assert_eq!(fetched.project_duration, 60 * 60 * 1000);
+        assert_eq!(fetched.total_pay, Money::from(100));
+
+        let result = db.delete(&fetched).await?;
+        assert_eq!(
+            result,
+            DeleteCount {
+                rows: 1,
+                children: 4
+            }
+        );
+
+        // Invoices cannot be deleted, so these rows stay behind
+        let client_id = Uuid::new_v4();
+        let client = Client {
+            client_id,
+            client_name: format!("Postgres Invoice {}", client_id),
+            pay_rate: Some(Money::from(60)),
+        };
+        db.insert(&client).await?;
+        let project = Project {
+            project_id: Uuid::new_v4(),
+            client_id: Some(client.client_id),
+            pay_rate: Money::ZERO,
+            project_duration: 30 * 60 * 1000,
+            total_pay: Money::from(30),
+            ..project
+        };
+        let task = ProjectTask {
+            task_id: Uuid::new_v4(),
+            project_id: project.project_id,
+            task_name: "Invoiced".to_string(),
+            task_duration: 30 * 60 * 1000,
+            task_date_time: project.project_date.and_hms_opt(12, 0, 0).unwrap(),
+            notes: None,
+        };
+        let mut task_time = TaskTime {
+            task_id: task.task_id,
+            start_time: task.task_date_time,
+            end_time: Some(task.task_date_time + chrono::TimeDelta::minutes(30)),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        db.insert(&task).await?;
+        task_time.task_time_id = db.insert(&task_time).await?;
+        let issued = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+        let doc = db
+            .generate_invoice(client.client_id, &range, issued)
+            .await?
+            .expect("Expected an invoice");
+        assert_eq!(doc.invoice.total, Money::from(30));
+        assert_eq!(db.invoice(doc.invoice.invoice_number).await?, doc);
+        assert!(db
+            .generate_invoice(client.client_id, &range, issued)
+            .await?
+            .is_none());
+        task_time.invoice_id = Some(doc.invoice.invoice_id);
+        assert!(matches!(
+            db.delete(&task_time).await,
+            Err(Error::Locked { .. })
+        ));
+        assert!(matches!(
+            db.update(&doc.invoice).await,
+            Err(Error::Locked { .. })
+        ));
+
+        // A copy of the project keeps the TaskTimeId it is saved with, and
+        // times added after it are numbered after it
+        let mut copy = db.fetch_project_tree(project).await?;
+        assert_eq!(copy.tasks[0].task_times, vec![task_time.clone()]);
+        let last_id = db
+            .fetch_all::<TaskTime>()
+            .await?
+            .iter()
+            .map(|t| t.task_time_id)
+            .max()
+            .unwrap_or_default();
+        copy.project.project_id = Uuid::new_v4();
+        copy.project.client_id = None;
+        let task_tree = &mut copy.tasks[0];
+        task_tree.task.task_id = Uuid::new_v4();
+        task_tree.task.project_id = copy.project.project_id;
+        task_tree.task_times[0].task_id = task_tree.task.task_id;
+        task_tree.task_times[0].task_time_id = last_id + 10;
+        task_tree.task_times[0].invoice_id = None;
+        db.config.validation.overlap = config::OverlapScope::Off;
+        db.save_project_trees(std::slice::from_ref(&copy), SaveMode::Insert)
+            .await?;
+        let next = TaskTime {
+            task_time_id: 0,
+            start_time: task_time.start_time + chrono::TimeDelta::hours(1),
+            end_time: task_time
+                .end_time
+                .map(|end| end + chrono::TimeDelta::hours(1)),
+            ..copy.tasks[0].task_times[0].clone()
+        };
+        assert_eq!(db.insert(&next).await?, last_id + 11);
+        Ok(())
+    }
+
+    #[allow(clippy::field_reassign_with_default)]
     async fn setup(db: &mut DbiDatabase) -> Result<MyTable, Error> {
         let mut my_table = MyTable::default();
         my_table.data = "A setup object".to_string();
         let sql = "INSERT INTO MyTable (Data, CreatedAt) VALUES (?, ?)";
 
-        let mut tx = db.pool.begin().await?;
+        let mut tx = db.pool.sqlite().begin().await?;
         let result = sqlx::query(sql)
             .bind(my_table.data.clone())
             .bind(my_table.created_at)
@@ -232,65 +2153,19 @@ mod tests {
                 CreatedAt DATE NOT NULL
             )",
         )
-        .execute(&db.pool)
+        .execute(db.pool.sqlite())
         .await?;
         Ok(())
     }
 
-    #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, FromRow)]
+    #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, FromRow, DbObject)]
     #[sqlx(rename_all = "PascalCase")]
+    #[dbobject(table = "MyTable")]
     pub struct MyTable {
-        id: u64,
+        #[dbobject(autoincrement)]
+        #[sqlx(try_from = "i64")]
+        pub id: u64,
         data: String,
         created_at: NaiveDate,
     }
-
-    impl DbObject<Sqlite, MyTable> for MyTable {
-        async fn insert_one(pool: &Pool<Sqlite>, dbo: &MyTable) -> Result<u64, Error> {
-            let query_str = "INSERT INTO MyTable (Data, CreatedAt) VALUES (?, ?)";
-            let mut tx = pool.begin().await?;
-
-            sqlx::query(query_str)
-                .bind(dbo.data.clone())
-                .bind(dbo.created_at)
-                .execute(&mut *tx)
-                .await?;
-
-            let row: (i64,) = sqlx::query_as("SELECT last_insert_rowid()")
-                .fetch_one(&mut *tx)
-                .await?;
-            let row_id = row.0 as u64;
-
-            tx.commit().await?;
-
-            Ok(row_id)
-        }
-
-        async fn retrieve_all(pool: &Pool<Sqlite>) -> Result<Vec<MyTable>, Error> {
-            let sql = "SELECT Id, Data, CreatedAt FROM MyTable ORDER BY Id ASC";
-            let records: Vec<MyTable> = sqlx::query_as(&sql).fetch_all(pool).await?;
-            Ok(records)
-        }
-
-        async fn retrieve_some(
-            pool: &Pool<Sqlite>,
-            uuid: &uuid::Uuid,
-        ) -> Result<Vec<MyTable>, Error> {
-            todo!()
-        }
-
-        async fn retrieve_one(&mut self, pool: &Pool<Sqlite>) -> Result<(), Error> {
-            let sql = "SELECT Id, Data, CreatedAt FROM MyTable WHERE Id = ?";
-
-            let row: SqliteRow = sqlx::query(&sql)
-                .bind(self.id as i64)
-                .fetch_one(pool)
-                .await?;
-
-            let tbl: MyTable = MyTable::from_row(&row)?;
-            self.data = tbl.data;
-            self.created_at = tbl.created_at;
-            Ok(())
-        }
-    }
 }
@@ -0,0 +1,395 @@
+// maintenance.rs
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{FromRow, Pool, Sqlite};
+
+use crate::error::Error;
+
+/// How backup file names are timestamped, so sorting the names sorts the
+/// backups from oldest to newest
+pub const BACKUP_TIME_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+/// The extension given to every backup file
+pub const BACKUP_EXTENSION: &str = "db3";
+
+// VACUUM INTO writes a compacted copy and refuses to overwrite a file
+// that is not empty
+pub(crate) const VACUUM_INTO_SQL: &str = "VACUUM INTO $1";
+pub(crate) const VACUUM_SQL: &str = "VACUUM";
+const INTEGRITY_CHECK_SQL: &str = "PRAGMA integrity_check";
+const FOREIGN_KEY_CHECK_SQL: &str = "PRAGMA foreign_key_check";
+const MIGRATIONS_SQL: &str = "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version";
+
+// What integrity_check returns for a sound database
+const INTEGRITY_OK: &str = "ok";
+
+///
+/// A row that refers to a parent row that is not stored, as reported by
+/// `PRAGMA foreign_key_check`. `constraint` numbers the table's foreign
+/// keys from 0.
+///
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Deserialize, Serialize)]
+pub struct ForeignKeyProblem {
+    pub table: String,
+    #[sqlx(rename = "rowid")]
+    pub row_id: Option<i64>,
+    pub parent: String,
+    #[sqlx(rename = "fkid")]
+    pub constraint: i64,
+}
+
+impl fmt::Display for ForeignKeyProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.row_id {
+            Some(row_id) => write!(f, "{} row {}", self.table, row_id)?,
+            None => write!(f, "A {} row", self.table)?,
+        }
+        write!(
+            f,
+            " refers to a missing {} row (foreign key {})",
+            self.parent, self.constraint
+        )
+    }
+}
+
+///
+/// The result of `DbiDatabase::check_integrity`. `problems` holds what
+/// `PRAGMA integrity_check` found wrong with the file itself, and
+/// `foreign_keys` the rows whose parents are missing. Both are empty for
+/// a sound database.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct IntegrityReport {
+    pub problems: Vec<String>,
+    pub foreign_keys: Vec<ForeignKeyProblem>,
+}
+
+impl IntegrityReport {
+    /// True when neither check found anything wrong
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty() && self.foreign_keys.is_empty()
+    }
+}
+
+impl fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "ok");
+        }
+        let lines: Vec<String> = self
+            .problems
+            .iter()
+            .cloned()
+            .chain(self.foreign_keys.iter().map(ToString::to_string))
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+///
+/// Run both integrity checks against an SQLite pool.
+///
+pub(crate) async fn integrity_report(pool: &Pool<Sqlite>) -> Result<IntegrityReport, Error> {
+    let problems: Vec<String> = sqlx::query_scalar(INTEGRITY_CHECK_SQL)
+        .fetch_all(pool)
+        .await?;
+    let foreign_keys = sqlx::query_as(FOREIGN_KEY_CHECK_SQL)
+        .fetch_all(pool)
+        .await?;
+    Ok(IntegrityReport {
+        problems: problems.into_iter().filter(|p| p != INTEGRITY_OK).collect(),
+        foreign_keys,
+    })
+}
+
+///
+/// Check that a backup can be restored: it passes both integrity checks
+/// and every migration applied to it is one this build knows, so opening
+/// it afterwards can only bring its schema up to date.
+///
+pub(crate) async fn verify_backup(backup: &Path, migrator: &Migrator) -> Result<(), Error> {
+    if !backup.is_file() {
+        return Err(Error::Maintenance(format!(
+            "{} is not a file",
+            backup.display()
+        )));
+    }
+    // Not read-only: FTS5 writes while it checks the SearchIndex, though
+    // nothing in the backup is changed
+    let options = SqliteConnectOptions::new().filename(backup);
+    let pool = Pool::<Sqlite>::connect_with(options).await?;
+    let result = verify_pool(&pool, backup, migrator).await;
+    pool.close().await;
+    result
+}
+
+async fn verify_pool(pool: &Pool<Sqlite>, backup: &Path, migrator: &Migrator) -> Result<(), Error> {
+    let report = integrity_report(pool).await?;
+    if !report.is_ok() {
+        return Err(Error::Maintenance(format!(
+            "{} failed its integrity check:\n{}",
+            backup.display(),
+            report
+        )));
+    }
+    let versions: Vec<i64> = sqlx::query_scalar(MIGRATIONS_SQL)
+        .fetch_all(pool)
+        .await
+        .map_err(|_| {
+            Error::Maintenance(format!("{} is not an mv_dbi database", backup.display()))
+        })?;
+    if let Some(version) = versions
+        .iter()
+        .find(|v| !migrator.iter().any(|m| m.version == **v))
+    {
+        return Err(Error::Maintenance(format!(
+            "{} has migration {}, which this build does not know",
+            backup.display(),
+            version
+        )));
+    }
+    Ok(())
+}
+
+///
+/// The name of a backup of the database called `stem`, taken at `at`.
+///
+pub fn backup_name(stem: &str, at: NaiveDateTime) -> String {
+    format!(
+        "{}-{}.{}",
+        stem,
+        at.format(BACKUP_TIME_FORMAT),
+        BACKUP_EXTENSION
+    )
+}
+
+///
+/// When the backup called `name` was taken, or None if it is not a
+/// backup of the database called `stem`.
+///
+pub fn backup_time(stem: &str, name: &str) -> Option<NaiveDateTime> {
+    let time = name
+        .strip_prefix(stem)?
+        .strip_prefix('-')?
+        .strip_suffix(BACKUP_EXTENSION)?
+        .strip_suffix('.')?;
+    NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT).ok()
+}
+
+///
+/// Delete the oldest backups of `stem` in `dir` until only `keep` are
+/// left, returning the deleted paths. Other files are left alone.
+///
+pub(crate) fn prune_backups(dir: &Path, stem: &str, keep: usize) -> Result<Vec<PathBuf>, Error> {
+    let entries = fs::read_dir(dir).map_err(|e| io_error(dir, e))?;
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| io_error(dir, e))?;
+        let name = entry.file_name();
+        if let Some(time) = name.to_str().and_then(|name| backup_time(stem, name)) {
+            backups.push((time, entry.path()));
+        }
+    }
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    let mut deleted = Vec::with_capacity(excess);
+    for (_, path) in backups.into_iter().take(excess) {
+        fs::remove_file(&path).map_err(|e| io_error(&path, e))?;
+        deleted.push(path);
+    }
+    Ok(deleted)
+}
+
+///
+/// Copy `backup` over `target`. The copy is written beside the target
+/// and renamed over it, so a failed copy leaves the target as it was.
+/// The old journal files are removed so SQLite does not replay them onto
+/// the restored file.
+///
+pub(crate) fn replace_file(backup: &Path, target: &Path) -> Result<(), Error> {
+    let partial = sibling(target, "-restoring");
+    fs::copy(backup, &partial).map_err(|e| io_error(&partial, e))?;
+    fs::File::open(&partial)
+        .and_then(|file| file.sync_all())
+        .map_err(|e| io_error(&partial, e))?;
+    fs::rename(&partial, target).map_err(|e| io_error(target, e))?;
+    for suffix in ["-wal", "-shm", "-journal"] {
+        let journal = sibling(target, suffix);
+        if journal.exists() {
+            fs::remove_file(&journal).map_err(|e| io_error(&journal, e))?;
+        }
+    }
+    Ok(())
+}
+
+// The file SQLite would name by adding `suffix` to `path`, as it does
+// for database.db3-wal
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub(crate) fn io_error(path: &Path, error: std::io::Error) -> Error {
+    Error::Maintenance(format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::model::project::Project;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase};
+
+    // A directory of its own for each test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("mv_dbi-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn project(name: &str) -> Project {
+        Project {
+            project_id: make_uuid(&name.to_string()),
+            project_name: name.to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    async fn setup(dir: &TempDir) -> Result<DbiDatabase, Error> {
+        let url = format!("sqlite://{}", dir.0.join("projects.db3").display());
+        let mut db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        db.insert(&project("Diamond")).await?;
+        Ok(db)
+    }
+
+    #[test]
+    fn test_backup_names() {
+        let at = NaiveDate::from_ymd_opt(2024, 10, 25)
+            .unwrap()
+            .and_hms_milli_opt(9, 5, 7, 42)
+            .unwrap();
+        let name = backup_name("projects", at);
+        assert_eq!(name, "projects-20241025-090507-042.db3");
+        assert_eq!(backup_time("projects", &name), Some(at));
+        assert_eq!(backup_time("other", &name), None);
+        assert_eq!(backup_time("projects", "projects.db3"), None);
+        assert_eq!(backup_time("projects", "projects-notes.db3"), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_integrity() -> Result<(), Error> {
+        // One connection: integrity_check can be left waiting forever on
+        // the locks of the others sharing a memory database's cache
+        let config = DbConfig::new("sqlite::memory:").max_connections(1);
+        let mut db = DbiDatabase::new(config).await?;
+        let report = db.check_integrity().await?;
+        assert!(report.is_ok());
+        assert_eq!(report.to_string(), "ok");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() -> Result<(), Error> {
+        let dir = TempDir::new();
+        let mut db = setup(&dir).await?;
+        let backup = dir.0.join("backup.db3");
+        db.backup_to(&backup).await?;
+        assert!(matches!(
+            db.backup_to(&backup).await,
+            Err(Error::Maintenance(_))
+        ));
+
+        db.insert(&project("Ruby")).await?;
+        assert_eq!(db.fetch_all::<Project>().await?.len(), 2);
+
+        let safety = db.restore_from(&backup).await?;
+        let projects = db.fetch_all::<Project>().await?;
+        assert_eq!(projects, vec![project("Diamond")]);
+        assert!(db.check_integrity().await?.is_ok());
+
+        // The copy taken before restoring still has both projects
+        let url = format!("sqlite://{}", safety.display());
+        let mut before = DbiDatabase::new(DbConfig::new(&url)).await?;
+        assert_eq!(before.fetch_all::<Project>().await?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_bad_backup() -> Result<(), Error> {
+        let dir = TempDir::new();
+        let mut db = setup(&dir).await?;
+        let missing = dir.0.join("missing.db3");
+        assert!(matches!(
+            db.restore_from(&missing).await,
+            Err(Error::Maintenance(_))
+        ));
+
+        let garbage = dir.0.join("garbage.db3");
+        fs::write(&garbage, b"not a database").unwrap();
+        assert!(db.restore_from(&garbage).await.is_err());
+
+        // The database is still open and unchanged
+        assert_eq!(db.fetch_all::<Project>().await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backup_rotating() -> Result<(), Error> {
+        let dir = TempDir::new();
+        let mut db = setup(&dir).await?;
+        let backups = dir.0.join("backups");
+        let unrelated = backups.join("notes.txt");
+        let mut taken = Vec::new();
+        for _ in 0..4 {
+            taken.push(db.backup_rotating(&backups, 2).await?);
+            if taken.len() == 1 {
+                fs::write(&unrelated, b"keep me").unwrap();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        let mut left: Vec<PathBuf> = fs::read_dir(&backups)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        left.sort();
+        assert_eq!(left, vec![unrelated, taken[2].clone(), taken[3].clone()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_database_cannot_restore() -> Result<(), Error> {
+        let dir = TempDir::new();
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        let backup = dir.0.join("memory.db3");
+        db.backup_to(&backup).await?;
+        assert!(matches!(
+            db.restore_from(&backup).await,
+            Err(Error::Maintenance(_))
+        ));
+        db.compact().await?;
+        Ok(())
+    }
+}
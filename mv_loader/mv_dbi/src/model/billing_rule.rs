@@ -0,0 +1,242 @@
+// billing_rule.rs
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::config::ValidationRules;
+use crate::database::query::DbObject;
+use crate::database::validate::Validate;
+use crate::error::Error;
+use crate::guid::{Guid, OptionalGuid};
+use crate::model::task_rate::TaskRate;
+use crate::money::RoundingMode;
+use crate::utils::make_uuid;
+
+///
+/// How the time on a project, or on every project of a client, is billed.
+/// A project's own rule is used before its client's. Times are in minutes
+/// and 0 turns that part of the rule off; see `billing::charge` for the
+/// order the parts are applied in.
+///
+#[derive(Debug, Clone, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
+#[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "BillingRules", order_by = "RuleId")]
+#[dbobject(children(TaskRate), validate)]
+pub struct BillingRule {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
+    pub rule_id: Uuid,
+    #[sqlx(try_from = "OptionalGuid")]
+    pub project_id: Option<Uuid>,
+    #[sqlx(try_from = "OptionalGuid")]
+    pub client_id: Option<Uuid>,
+    /// Round each TaskTime to a multiple of this
+    pub time_increment: i32,
+    /// Round the total of each task to a multiple of this
+    pub task_increment: i32,
+    /// Which way the increments round
+    pub increment_rounding: RoundingMode,
+    /// The least time billed for a day with any work in it
+    pub daily_minimum: i32,
+    /// Time past this in a day is overtime
+    pub daily_overtime: i32,
+    /// Time past this in an ISO week is overtime
+    pub weekly_overtime: i32,
+    /// The share of the rate paid for overtime, 150 for time and a half
+    pub overtime_percent: i32,
+}
+
+impl Default for BillingRule {
+    fn default() -> Self {
+        Self {
+            rule_id: Uuid::default(),
+            project_id: None,
+            client_id: None,
+            time_increment: 0,
+            task_increment: 0,
+            increment_rounding: RoundingMode::Up,
+            daily_minimum: 0,
+            daily_overtime: 0,
+            weekly_overtime: 0,
+            overtime_percent: 150,
+        }
+    }
+}
+
+impl BillingRule {
+    /// A rule for one project, with everything turned off
+    pub fn for_project(project_id: Uuid) -> Self {
+        Self {
+            rule_id: make_uuid(&format!("BillingRule{}", project_id)),
+            project_id: Some(project_id),
+            ..Default::default()
+        }
+    }
+
+    /// A rule for the projects of a client that have no rule of their own
+    pub fn for_client(client_id: Uuid) -> Self {
+        Self {
+            rule_id: make_uuid(&format!("BillingRule{}", client_id)),
+            client_id: Some(client_id),
+            ..Default::default()
+        }
+    }
+
+    pub fn time_increment(mut self, minutes: i32) -> Self {
+        self.time_increment = minutes;
+        self
+    }
+
+    pub fn task_increment(mut self, minutes: i32) -> Self {
+        self.task_increment = minutes;
+        self
+    }
+
+    pub fn increment_rounding(mut self, mode: RoundingMode) -> Self {
+        self.increment_rounding = mode;
+        self
+    }
+
+    pub fn daily_minimum(mut self, minutes: i32) -> Self {
+        self.daily_minimum = minutes;
+        self
+    }
+
+    pub fn daily_overtime(mut self, minutes: i32) -> Self {
+        self.daily_overtime = minutes;
+        self
+    }
+
+    pub fn weekly_overtime(mut self, minutes: i32) -> Self {
+        self.weekly_overtime = minutes;
+        self
+    }
+
+    pub fn overtime_percent(mut self, percent: i32) -> Self {
+        self.overtime_percent = percent;
+        self
+    }
+}
+
+impl Validate for BillingRule {
+    fn validate(&self, _rules: &ValidationRules) -> Result<(), Error> {
+        let invalid = |message: String| Error::Validation {
+            entity: "BillingRule",
+            id: self.rule_id.to_string(),
+            message,
+        };
+        if self.project_id.is_some() == self.client_id.is_some() {
+            return Err(invalid(
+                "a rule needs either a ProjectId or a ClientId".to_string(),
+            ));
+        }
+        for (name, minutes) in [
+            ("TimeIncrement", self.time_increment),
+            ("TaskIncrement", self.task_increment),
+            ("DailyMinimum", self.daily_minimum),
+            ("DailyOvertime", self.daily_overtime),
+            ("WeeklyOvertime", self.weekly_overtime),
+        ] {
+            if minutes < 0 {
+                return Err(invalid(format!("{} of {} is negative", name, minutes)));
+            }
+        }
+        if self.overtime_percent < 100 {
+            return Err(invalid(format!(
+                "OvertimePercent of {} is less than 100",
+                self.overtime_percent
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::*;
+    use crate::DbConfig;
+    use crate::DbiDatabase;
+    use crate::Error;
+    use crate::Money;
+    use chrono::NaiveDate;
+
+    use super::BillingRule;
+    use crate::database::query::DeleteCount;
+    use crate::model::project::Project;
+    use crate::model::task_rate::TaskRate;
+
+    async fn setup() -> Result<(DbiDatabase, Project), Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        let mut db = DbiDatabase::new(config).await?;
+        let project = Project {
+            project_id: make_uuid(&"Billed".to_string()),
+            project_name: "Billed".to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 8, 10).unwrap(),
+            pay_rate: Money::from(40),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        Ok((db, project))
+    }
+
+    #[tokio::test]
+    async fn test_insert_rule_with_task_rates() -> Result<(), Error> {
+        let (mut db, project) = setup().await?;
+        let rule = BillingRule::for_project(project.project_id)
+            .time_increment(15)
+            .daily_overtime(8 * 60);
+        db.insert(&rule).await?;
+        let rate = TaskRate::new(rule.rule_id, "Review", Money::from(60));
+        db.insert(&rate).await?;
+
+        assert_eq!(db.fetch_all::<BillingRule>().await?, vec![rule.clone()]);
+        assert_eq!(
+            db.fetch_some::<TaskRate>(&rule.rule_id).await?,
+            vec![rate.clone()]
+        );
+
+        let result = db
+            .insert(&TaskRate::new(rule.rule_id, "Review", Money::from(70)))
+            .await;
+        assert!(matches!(result, Err(Error::Duplicate { .. })));
+
+        let deleted = db.delete(&rule).await?;
+        assert_eq!(
+            deleted,
+            DeleteCount {
+                rows: 1,
+                children: 1
+            }
+        );
+        assert!(db.fetch_all::<TaskRate>().await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rule() -> Result<(), Error> {
+        let (mut db, project) = setup().await?;
+        let mut rule = BillingRule::for_project(project.project_id);
+        rule.client_id = Some(make_uuid(&"Acme".to_string()));
+        let result = db.insert(&rule).await;
+        assert!(matches!(
+            result,
+            Err(Error::Validation {
+                entity: "BillingRule",
+                ..
+            })
+        ));
+
+        let rule = BillingRule::for_project(project.project_id).overtime_percent(50);
+        assert!(matches!(
+            db.insert(&rule).await,
+            Err(Error::Validation { .. })
+        ));
+
+        // Removing the project removes its rule
+        db.insert(&BillingRule::for_project(project.project_id))
+            .await?;
+        db.delete(&project).await?;
+        assert!(db.fetch_all::<BillingRule>().await?.is_empty());
+        Ok(())
+    }
+}
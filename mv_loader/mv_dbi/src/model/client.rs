@@ -0,0 +1,106 @@
+// client.rs
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::query::DbObject;
+use crate::guid::Guid;
+use crate::money::Money;
+
+///
+/// A customer that projects are billed to. `pay_rate` is used for the
+/// client's projects that do not set a rate of their own.
+///
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
+#[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "Clients", order_by = "ClientName")]
+pub struct Client {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
+    pub client_id: Uuid,
+    pub client_name: String,
+    pub pay_rate: Option<Money>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::*;
+    use crate::DbConfig;
+    use crate::DbiDatabase;
+    use crate::Error;
+    use chrono::NaiveDate;
+
+    use super::Client;
+    use crate::model::project::Project;
+    use crate::money::Money;
+
+    fn make_client(name: &str, pay_rate: Option<i64>) -> Client {
+        Client {
+            client_id: make_uuid(&name.to_string()),
+            client_name: name.to_string(),
+            pay_rate: pay_rate.map(Money::from),
+        }
+    }
+
+    fn make_project(name: &str, pay_rate: i64, client: &Client) -> Project {
+        Project {
+            project_id: make_uuid(&name.to_string()),
+            project_name: name.to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 8, 10).unwrap(),
+            pay_rate: Money::from(pay_rate),
+            client_id: Some(client.client_id),
+            ..Default::default()
+        }
+    }
+
+    async fn setup() -> Result<DbiDatabase, Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        let db = DbiDatabase::new(config).await?;
+        Ok(db)
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_update_client() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut client = make_client("Acme", Some(50));
+        assert_eq!(db.insert(&client).await?, 1);
+
+        let result = db.insert(&make_client("Acme", None)).await;
+        assert!(matches!(
+            result,
+            Err(Error::Duplicate {
+                entity: "Client",
+                ..
+            })
+        ));
+
+        client.pay_rate = Some(Money::from(55));
+        db.update(&client).await?;
+        assert_eq!(db.fetch_all::<Client>().await?, vec![client]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pay_rate_falls_back_to_client() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let client = make_client("Acme", Some(50));
+        db.insert(&client).await?;
+        let own_rate = make_project("Own Rate", 40, &client);
+        let no_rate = make_project("No Rate", 0, &client);
+        db.insert(&own_rate).await?;
+        db.insert(&no_rate).await?;
+
+        assert_eq!(db.pay_rate(&own_rate).await?, Money::from(40));
+        assert_eq!(db.pay_rate(&no_rate).await?, Money::from(50));
+        assert_eq!(no_rate.pay_rate_for(Some(&client)), Money::from(50));
+        assert_eq!(no_rate.pay_rate_for(None), Money::ZERO);
+
+        // Removing the client leaves its projects without one
+        db.delete(&client).await?;
+        let projects = db.fetch_all::<Project>().await?;
+        assert!(projects.iter().all(|p| p.client_id.is_none()));
+        let no_rate = projects.iter().find(|p| p.pay_rate.is_zero()).unwrap();
+        assert_eq!(db.pay_rate(no_rate).await?, Money::ZERO);
+        Ok(())
+    }
+}
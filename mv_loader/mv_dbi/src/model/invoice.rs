@@ -0,0 +1,46 @@
+// invoice.rs
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::query::DbObject;
+use crate::guid::Guid;
+use crate::model::invoice_line::InvoiceLine;
+use crate::money::Money;
+use crate::utils::make_uuid;
+
+///
+/// A bill sent to a client for the TaskTimes in a period. Invoices are
+/// numbered in sequence by `DbiDatabase::generate_invoice` and the
+/// database refuses to change or delete one once it is written.
+///
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
+#[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "Invoices", order_by = "InvoiceNumber")]
+#[dbobject(children(InvoiceLine))]
+pub struct Invoice {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
+    pub invoice_id: Uuid,
+    pub invoice_number: i64,
+    #[sqlx(try_from = "Guid")]
+    pub client_id: Uuid,
+    pub issue_date: NaiveDate,
+    pub period_start: Option<NaiveDate>,
+    pub period_end: Option<NaiveDate>,
+    pub currency: String,
+    pub total: Money,
+}
+
+impl Invoice {
+    pub fn new(invoice_number: i64, client_id: Uuid, issue_date: NaiveDate) -> Self {
+        Self {
+            invoice_id: make_uuid(&format!("Invoice{}", invoice_number)),
+            invoice_number,
+            client_id,
+            issue_date,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,57 @@
+// invoice_line.rs
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::billing::{ChargeKind, ChargeLine};
+use crate::database::query::DbObject;
+use crate::guid::Guid;
+use crate::money::Money;
+use crate::utils::make_uuid;
+
+///
+/// One charge line as it was billed on an invoice. The project and task
+/// are kept by name so the invoice reads the same whatever happens to
+/// them later.
+///
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
+#[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "InvoiceLines", order_by = "InvoiceId, LineNumber")]
+pub struct InvoiceLine {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
+    pub invoice_line_id: Uuid,
+    #[dbobject(parent)]
+    #[sqlx(try_from = "Guid")]
+    pub invoice_id: Uuid,
+    pub line_number: i32,
+    pub line_date: NaiveDate,
+    pub project_name: String,
+    pub task_name: String,
+    pub kind: ChargeKind,
+    pub minutes: i64,
+    pub pay_rate: Money,
+    pub percent: i32,
+    pub amount: Money,
+    pub description: String,
+}
+
+impl InvoiceLine {
+    pub fn new(invoice_id: Uuid, line_number: i32, line: &ChargeLine) -> Self {
+        Self {
+            invoice_line_id: make_uuid(&format!("{}{}", invoice_id, line_number)),
+            invoice_id,
+            line_number,
+            line_date: line.date,
+            project_name: line.project_name.clone(),
+            task_name: line.task_name.clone(),
+            kind: line.kind,
+            minutes: line.minutes,
+            pay_rate: line.pay_rate,
+            percent: line.percent,
+            amount: line.amount,
+            description: line.reason.clone(),
+        }
+    }
+}
@@ -1,4 +1,11 @@
 // model/mod.rs
+pub mod billing_rule;
+pub mod client;
+pub mod invoice;
+pub mod invoice_line;
 pub mod project;
 pub mod project_task;
+pub mod project_tree;
+pub mod tag;
+pub mod task_rate;
 pub mod task_time;
@@ -10,114 +10,90 @@ use sqlx::sqlite::SqliteRow;
 use sqlx::sqlite::SqliteValueRef;
 use sqlx::Column;
 use sqlx::Decode;
-use sqlx::Error;
 use sqlx::FromRow;
+use sqlx::QueryBuilder;
 use sqlx::Row;
 use uuid::Uuid;
 
-use crate::database::query::DbObject;
-use crate::DataObject;
+use crate::database::filter::{FilterTarget, QueryFilter};
+use crate::database::query::{DbObject, DeleteCount};
+use crate::guid::{Guid, OptionalGuid};
+use crate::model::client::Client;
+use crate::model::project_task::ProjectTask;
+use crate::money::{Money, DEFAULT_CURRENCY};
 
-#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
 #[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "Projects", order_by = "ProjectDate")]
+#[dbobject(filter = "Projects", children(ProjectTask))]
 pub struct Project {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
     pub project_id: Uuid,
     pub project_name: String,
     pub project_date: NaiveDate,
-    pub pay_rate: f64,
+    pub pay_rate: Money,
     pub project_duration: i64,
-    pub total_pay: f64,
+    pub total_pay: Money,
+    /// The ISO 4217 code pay_rate and total_pay are in
+    pub currency: String,
+    #[sqlx(try_from = "OptionalGuid")]
+    pub client_id: Option<Uuid>,
 }
 
-impl DbObject<Sqlite, Project> for Project {
-    async fn insert_one(pool: &sqlx::Pool<Sqlite>, dbo: &Project) -> Result<u64, Error> {
-        let mut tx = pool.begin().await?;
-        let sql = "INSERT INTO Projects (
-            ProjectId,
-            ProjectName,
-            ProjectDate,
-            PayRate,
-            ProjectDuration,
-            TotalPay
-        ) VALUES (
-            $1, $2, $3, $4, $5, $6
-        )";
-
-        let query = sqlx::query(sql)
-            .bind::<Uuid>(dbo.project_id)
-            .bind::<String>(dbo.project_name.clone())
-            .bind::<NaiveDate>(dbo.project_date)
-            .bind::<f64>(dbo.pay_rate)
-            .bind::<i64>(dbo.project_duration)
-            .bind::<f64>(dbo.total_pay)
-            .execute(&mut *tx)
-            .await?;
-
-        tx.commit().await?;
-
-        Ok(query.rows_affected())
-    }
-
-    async fn retrieve_all(pool: &sqlx::Pool<Sqlite>) -> Result<Vec<Project>, Error> {
-        let sql = "SELECT
-            ProjectId,
-            ProjectName,
-            ProjectDate,
-            PayRate,
-            ProjectDuration,
-            TotalPay
-        FROM Projects
-        ORDER BY ProjectDate ASC";
-        let records: Vec<Project> = sqlx::query_as(&sql).fetch_all(pool).await?;
-        Ok(records)
-    }
-
-    async fn retrieve_some(pool: &sqlx::Pool<Sqlite>, uuid: &Uuid) -> Result<Vec<Project>, Error> {
-        todo!()
-    }
-
-    async fn retrieve_one(&mut self, pool: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
-        let sql = "SELECT
-            ProjectId,
-            ProjectName,
-            ProjectDate,
-            PayRate,
-            ProjectDuration,
-            TotalPay
-        FROM Projects
-        WHERE ProjectId = ?
-        ORDER BY ProjectDate ASC";
-
-        let row: SqliteRow = sqlx::query(&sql)
-            .bind(self.project_id)
-            .fetch_one(pool)
-            .await?;
-        let temp_project = Project::from_row(&row)?;
-
-        self.project_id = temp_project.project_id;
-        self.project_name = temp_project.project_name;
-        self.project_date = temp_project.project_date;
-        self.pay_rate = temp_project.pay_rate;
-        self.project_duration = temp_project.project_duration;
-        self.total_pay = temp_project.total_pay;
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            project_id: Uuid::default(),
+            project_name: String::default(),
+            project_date: NaiveDate::default(),
+            pay_rate: Money::ZERO,
+            project_duration: 0,
+            total_pay: Money::ZERO,
+            currency: DEFAULT_CURRENCY.to_string(),
+            client_id: None,
+        }
+    }
+}
 
-        Ok(())
+impl Project {
+    ///
+    /// The project's own rate, or the client's when the project's is 0.
+    /// Pass the project's client, if it has one.
+    ///
+    pub fn pay_rate_for(&self, client: Option<&Client>) -> Money {
+        if self.pay_rate > Money::ZERO {
+            return self.pay_rate;
+        }
+        client.and_then(|c| c.pay_rate).unwrap_or(Money::ZERO)
     }
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::field_reassign_with_default,
+    clippy::clone_on_copy,
+    clippy::op_ref,
+    clippy::needless_borrow,
+    clippy::explicit_counter_loop,
+    clippy::unnecessary_mut_passed
+)]
 mod tests {
     use crate::utils::*;
-    use crate::DataCollection;
     use crate::DbConfig;
     use crate::DbObject;
     use crate::DbiDatabase;
-    use chrono::NaiveDate;
+    use crate::Error;
+    use crate::Money;
+    use chrono::{NaiveDate, NaiveDateTime};
     use sqlx::migrate::Migrator;
-    use sqlx::Error;
     use uuid::Uuid;
 
     use super::Project;
+    use crate::database::filter::{QueryFilter, SortOrder};
+    use crate::database::query::{DbTable, DeleteCount};
+    use crate::model::project_task::ProjectTask;
+    use crate::model::task_time::TaskTime;
 
     // Uuid:OID = 6ba7b812-9dad-11d1-80b4-00c04fd430c8
     fn make_project() -> Project {
@@ -128,8 +104,8 @@ mod tests {
         project.project_date =
             NaiveDate::from_ymd_opt(2024, 8, 10).expect("Tried to create an invalid date");
         project.project_duration = 0;
-        project.pay_rate = 40.0;
-        project.total_pay = 0.0;
+        project.pay_rate = Money::from(40);
+        project.total_pay = Money::ZERO;
 
         let dt = project.project_date.format("%a %b %-d %C%y").to_string();
         let value = format!("{}{}", &project.project_name, &dt);
@@ -147,25 +123,84 @@ mod tests {
     async fn test_insert_one_project() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_failed_insert_leaves_database_writable() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("mv_dbi_project_{}.db3", Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        // Without waiting, a lock left behind fails the next write at once
+        let config = DbConfig::new(&url).busy_timeout(std::time::Duration::ZERO);
+        let db = DbiDatabase::new(config).await?;
+        let pool = db.pool.sqlite();
+        let mut project = make_project();
+        Project::insert_one(pool, &project).await?;
+        for _ in 0..3 {
+            let result = Project::insert_one(pool, &project).await;
+            assert!(matches!(result, Err(Error::Duplicate { .. })), "{result:?}");
+        }
+
+        project.project_name = "Another project".to_string();
+        project.project_id = make_uuid(&project.project_name);
+        assert_eq!(Project::upsert_one(pool, &project).await?, 1);
+        let deleted = Project::delete_one(pool, &project).await?;
+        assert_eq!(deleted.rows, 1);
+        pool.close().await;
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_select_one_project() -> Result<(), Error> {
         let mut db = setup().await?;
         let mut expected = make_project();
-        let num_rows = Project::insert_one(&db.pool, &mut expected).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &mut expected).await?;
         assert_eq!(num_rows, 1);
 
         let mut actual = Project::default();
         actual.project_id = expected.project_id.clone();
-        actual.retrieve_one(&db.pool).await?;
+        actual.retrieve_one(db.pool.sqlite()).await?;
         assert_eq!(actual, expected);
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_insert_duplicate_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let result = Project::insert_one(db.pool.sqlite(), &project).await;
+        match result {
+            Err(Error::Duplicate { entity, id, .. }) => {
+                assert_eq!(entity, "Project");
+                assert_eq!(id, project.project_id.to_string());
+            }
+            other => panic!("Expected a Duplicate error, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_select_missing_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut project = make_project();
+
+        let result = project.retrieve_one(db.pool.sqlite()).await;
+        assert!(matches!(
+            result,
+            Err(Error::NotFound {
+                entity: "Project",
+                ..
+            })
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_select_all_project() -> Result<(), Error> {
         let mut db = setup().await?;
@@ -181,12 +216,152 @@ mod tests {
             let dt = &n.project_date.format("%a %b %-d %C%y").to_string();
             let value = format!("{}{}", &n.project_name, &dt);
             n.project_id = make_uuid(&value);
-            Project::insert_one(&db.pool, &mut n).await?;
+            Project::insert_one(db.pool.sqlite(), &mut n).await?;
         }
 
-        let actual = Project::retrieve_all(&db.pool).await?;
+        let actual = Project::retrieve_all(db.pool.sqlite()).await?;
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_one_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut expected = make_project();
+        Project::insert_one(db.pool.sqlite(), &expected).await?;
+
+        expected.project_name = "A corrected project name".to_string();
+        expected.pay_rate = Money::from(45);
+        let num_rows = Project::update_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(num_rows, 1);
+
+        let mut actual = Project {
+            project_id: expected.project_id,
+            ..Default::default()
+        };
+        actual.retrieve_one(db.pool.sqlite()).await?;
         assert_eq!(actual, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_one_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut expected = make_project();
+        let num_rows = Project::upsert_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(num_rows, 1);
+
+        expected.project_duration = 90 * 60 * 1000;
+        expected.total_pay = Money::from(60);
+        let num_rows = Project::upsert_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(num_rows, 1);
+
+        let actual = Project::retrieve_all(db.pool.sqlite()).await?;
+        assert_eq!(actual, vec![expected]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_one_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let task = ProjectTask {
+            task_id: make_uuid(&format!("{}{}", project.project_id, "Task 01")),
+            project_id: project.project_id,
+            task_name: "Task 01".to_string(),
+            ..Default::default()
+        };
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+        for _ in 0..2 {
+            // Stopped, since only one timer may run at a time
+            let task_time = TaskTime {
+                task_id: task.task_id,
+                end_time: Some(NaiveDateTime::default()),
+                ..Default::default()
+            };
+            TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
+        }
+
+        let deleted = Project::delete_one(db.pool.sqlite(), &project).await?;
+        assert_eq!(
+            deleted,
+            DeleteCount {
+                rows: 1,
+                children: 3
+            }
+        );
+        assert!(Project::retrieve_all(db.pool.sqlite()).await?.is_empty());
+        assert!(ProjectTask::retrieve_all(db.pool.sqlite())
+            .await?
+            .is_empty());
+        assert!(TaskTime::retrieve_all(db.pool.sqlite()).await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_dependents_sql() {
+        let sql = <Project as DbTable>::dependents_sql("$1");
+        assert_eq!(
+            sql,
+            vec![
+                r#"SELECT COUNT(*) FROM ProjectTasks WHERE "ProjectId" IN ($1)"#,
+                r#"SELECT COUNT(*) FROM TaskTimes WHERE "TaskId" IN (SELECT "TaskId" FROM ProjectTasks WHERE "ProjectId" IN ($1))"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_select_some_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let expected = make_project();
+        Project::insert_one(db.pool.sqlite(), &expected).await?;
+
+        let actual = Project::retrieve_some(db.pool.sqlite(), &expected.project_id).await?;
+        assert_eq!(actual, vec![expected]);
+        let actual = Project::retrieve_some(db.pool.sqlite(), &Uuid::nil()).await?;
+        assert!(actual.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_select_filtered_project() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut projects: Vec<Project> = Vec::with_capacity(4);
+        for (i, (name, rate)) in [("Diamond", 35), ("Ruby", 45), ("Diamond", 35), ("Opal", 50)]
+            .into_iter()
+            .enumerate()
+        {
+            let mut project = make_project();
+            project.project_name = name.to_string();
+            project.pay_rate = Money::from(rate);
+            project.project_date = NaiveDate::from_ymd_opt(2024, 7, 30)
+                .unwrap()
+                .checked_add_days(chrono::Days::new(i as u64))
+                .unwrap();
+            project.project_id = make_uuid(&format!("{}{}", name, project.project_date));
+            Project::insert_one(db.pool.sqlite(), &project).await?;
+            projects.push(project);
+        }
+
+        let filter = QueryFilter::new().project_name("Dia%");
+        let actual = Project::retrieve_filtered(db.pool.sqlite(), &filter).await?;
+        assert_eq!(actual, vec![projects[0].clone(), projects[2].clone()]);
+
+        let filter = QueryFilter::new()
+            .min_pay_rate(Money::from(40))
+            .order(SortOrder::Descending);
+        let actual = Project::retrieve_filtered(db.pool.sqlite(), &filter).await?;
+        assert_eq!(actual, vec![projects[3].clone(), projects[1].clone()]);
 
+        let filter = QueryFilter::new()
+            .date_range(projects[1].project_date, projects[2].project_date)
+            .limit(1)
+            .offset(1);
+        let actual = Project::retrieve_filtered(db.pool.sqlite(), &filter).await?;
+        assert_eq!(actual, vec![projects[2].clone()]);
         Ok(())
     }
 }
@@ -10,118 +10,55 @@ use sqlx::sqlite::SqliteRow;
 use sqlx::sqlite::SqliteValueRef;
 use sqlx::Column;
 use sqlx::Decode;
-use sqlx::Error;
 use sqlx::FromRow;
+use sqlx::QueryBuilder;
 use sqlx::Row;
 use uuid::Uuid;
 
-use crate::database::query::DbObject;
-use crate::DataObject;
+use crate::database::filter::{FilterTarget, QueryFilter};
+use crate::database::query::{DbObject, DeleteCount};
+use crate::guid::Guid;
+use crate::model::task_time::TaskTime;
 
-#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
 #[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "ProjectTasks", order_by = "ProjectId, TaskDateTime")]
+#[dbobject(filter = "ProjectTasks", children(TaskTime))]
 pub struct ProjectTask {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
     pub task_id: Uuid,
+    #[dbobject(parent)]
+    #[sqlx(try_from = "Guid")]
     pub project_id: Uuid,
     pub task_name: String,
     pub task_duration: i64,
     pub task_date_time: NaiveDateTime,
-}
-
-impl DbObject<Sqlite, ProjectTask> for ProjectTask {
-    async fn insert_one(pool: &sqlx::Pool<Sqlite>, dbo: &ProjectTask) -> Result<u64, Error> {
-        let mut tx = pool.begin().await?;
-        let sql = "INSERT INTO ProjectTasks (
-            TaskId,
-            ProjectId,
-            TaskName,
-            TaskDuration,
-            TaskDateTime
-        ) VALUES (
-            $1, $2, $3, $4, $5
-        )";
-
-        let query = sqlx::query(sql)
-            .bind::<Uuid>(dbo.task_id)
-            .bind::<Uuid>(dbo.project_id)
-            .bind::<String>(dbo.task_name.clone())
-            .bind::<i64>(dbo.task_duration)
-            .bind::<NaiveDateTime>(dbo.task_date_time)
-            .execute(&mut *tx)
-            .await?;
-
-        tx.commit().await?;
-
-        Ok(query.rows_affected())
-    }
-
-    async fn retrieve_all(pool: &sqlx::Pool<Sqlite>) -> Result<Vec<ProjectTask>, Error> {
-        let sql = "SELECT
-            TaskId,
-            ProjectId,
-            TaskName,
-            TaskDuration,
-            TaskDateTime
-        FROM ProjectTasks
-        ORDER BY ProjectId, TaskDateTime ASC";
-        let records: Vec<ProjectTask> = sqlx::query_as(&sql).fetch_all(pool).await?;
-        Ok(records)
-    }
-
-    async fn retrieve_some(
-        pool: &sqlx::Pool<Sqlite>,
-        uuid: &Uuid,
-    ) -> Result<Vec<ProjectTask>, Error> {
-        let sql = "SELECT
-            TaskId,
-            ProjectId,
-            TaskName,
-            TaskDuration,
-            TaskDateTime
-        FROM ProjectTasks
-        WHERE ProjectId = ?
-        ORDER BY ProjectId, TaskDateTime ASC";
-        let records: Vec<ProjectTask> = sqlx::query_as(&sql).bind(&uuid).fetch_all(pool).await?;
-        Ok(records)
-    }
-
-    async fn retrieve_one(&mut self, pool: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
-        let sql = "SELECT
-            TaskId,
-            ProjectId,
-            TaskName,
-            TaskDuration,
-            TaskDateTime
-        FROM ProjectTasks
-        WHERE TaskId = ?";
-
-        let row: SqliteRow = sqlx::query(&sql).bind(self.task_id).fetch_one(pool).await?;
-        let temp_project = ProjectTask::from_row(&row)?;
-
-        self.task_id = temp_project.task_id;
-        self.project_id = temp_project.project_id;
-        self.task_name = temp_project.task_name;
-        self.task_date_time = temp_project.task_date_time;
-        self.task_duration = temp_project.task_duration;
-
-        Ok(())
-    }
+    pub notes: Option<String>,
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::field_reassign_with_default,
+    clippy::clone_on_copy,
+    clippy::op_ref,
+    clippy::needless_borrow
+)]
 mod tests {
     use crate::utils::*;
-    use crate::DataCollection;
     use crate::DbConfig;
     use crate::DbObject;
     use crate::DbiDatabase;
+    use crate::Error;
+    use crate::Money;
     use chrono::NaiveDate;
     use chrono::NaiveDateTime;
     use sqlx::migrate::Migrator;
-    use sqlx::Error;
     use uuid::Uuid;
 
     use super::ProjectTask;
+    use crate::database::query::DeleteCount;
+    use crate::model::task_time::TaskTime;
     use crate::Project;
 
     fn make_project() -> Project {
@@ -132,8 +69,8 @@ mod tests {
         project.project_date =
             NaiveDate::from_ymd_opt(2024, 8, 10).expect("Tried to create an invalid date");
         project.project_duration = 0;
-        project.pay_rate = 40.0;
-        project.total_pay = 0.0;
+        project.pay_rate = Money::from(40);
+        project.total_pay = Money::ZERO;
 
         let dt = project.project_date.format("%a %b %-d %C%y").to_string();
         let value = format!("{}{}", &project.project_name, &dt);
@@ -173,12 +110,12 @@ mod tests {
     async fn test_insert_one_task() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let expected = make_task(project.project_id.clone(), 0);
 
-        let num_rows = ProjectTask::insert_one(&db.pool, &expected).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &expected).await?;
         assert_eq!(num_rows, 1);
         Ok(())
     }
@@ -187,17 +124,17 @@ mod tests {
     async fn test_select_one_task() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let expected = make_task(project.project_id.clone(), 0);
         let mut actual = ProjectTask::default();
         actual.task_id = expected.task_id.clone();
 
-        let num_rows = ProjectTask::insert_one(&db.pool, &expected).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &expected).await?;
         assert_eq!(num_rows, 1);
 
-        actual.retrieve_one(&db.pool).await?;
+        actual.retrieve_one(db.pool.sqlite()).await?;
         assert_eq!(actual, expected);
 
         Ok(())
@@ -207,19 +144,19 @@ mod tests {
     async fn test_select_all_task() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let mut expected: Vec<ProjectTask> = Vec::with_capacity(3);
 
         for i in 0..3 {
             let a_task = make_task(project.project_id.clone(), i);
-            let res = ProjectTask::insert_one(&db.pool, &a_task).await?;
+            let res = ProjectTask::insert_one(db.pool.sqlite(), &a_task).await?;
             assert_eq!(res, 1);
             expected.push(a_task);
         }
 
-        let actual = ProjectTask::retrieve_all(&db.pool).await?;
+        let actual = ProjectTask::retrieve_all(db.pool.sqlite()).await?;
         assert_eq!(actual, expected);
 
         Ok(())
@@ -233,29 +170,85 @@ mod tests {
         modify_project(&mut p1, 1);
         modify_project(&mut p2, 2);
 
-        let num_rows = Project::insert_one(&db.pool, &p1).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &p1).await?;
         assert_eq!(num_rows, 1);
-        let num_rows = Project::insert_one(&db.pool, &p2).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &p2).await?;
         assert_eq!(num_rows, 1);
 
         let mut expected: Vec<ProjectTask> = Vec::with_capacity(3);
 
         for i in 0..3 {
             let a_task = make_task(p1.project_id.clone(), i);
-            let res = ProjectTask::insert_one(&db.pool, &a_task).await?;
+            let res = ProjectTask::insert_one(db.pool.sqlite(), &a_task).await?;
             assert_eq!(res, 1);
             expected.push(a_task);
         }
 
         for i in 3..6 {
             let a_task = make_task(p2.project_id.clone(), i);
-            let res = ProjectTask::insert_one(&db.pool, &a_task).await?;
+            let res = ProjectTask::insert_one(db.pool.sqlite(), &a_task).await?;
             assert_eq!(res, 1);
         }
 
-        let actual = ProjectTask::retrieve_some(&db.pool, &p1.project_id).await?;
+        let actual = ProjectTask::retrieve_some(db.pool.sqlite(), &p1.project_id).await?;
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_update_one_task() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let mut expected = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &expected).await?;
+
+        expected.task_name = "Task 01 renamed".to_string();
+        expected.task_duration = 90 * 60 * 1000;
+        let num_rows = ProjectTask::update_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(num_rows, 1);
+
+        let mut actual = ProjectTask {
+            task_id: expected.task_id,
+            ..Default::default()
+        };
+        actual.retrieve_one(db.pool.sqlite()).await?;
         assert_eq!(actual, expected);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_delete_one_task() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let task1 = make_task(project.project_id, 0);
+        let task2 = make_task(project.project_id, 1);
+        ProjectTask::insert_one(db.pool.sqlite(), &task1).await?;
+        ProjectTask::insert_one(db.pool.sqlite(), &task2).await?;
+        let task_time = TaskTime {
+            task_id: task1.task_id,
+            ..Default::default()
+        };
+        TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
+
+        let deleted = ProjectTask::delete_one(db.pool.sqlite(), &task1).await?;
+        assert_eq!(
+            deleted,
+            DeleteCount {
+                rows: 1,
+                children: 1
+            }
+        );
+
+        let actual = ProjectTask::retrieve_all(db.pool.sqlite()).await?;
+        assert_eq!(actual, vec![task2]);
+        assert!(TaskTime::retrieve_all(db.pool.sqlite()).await?.is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,405 @@
+// model/project_tree.rs
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::model::project::Project;
+use crate::model::project_task::ProjectTask;
+use crate::model::task_time::TaskTime;
+
+///
+/// A Project together with all of its tasks and their times, so the
+/// whole hierarchy can be written in one transaction.
+///
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ProjectTree {
+    pub project: Project,
+    pub tasks: Vec<TaskTree>,
+}
+
+///
+/// A ProjectTask, the TaskTimes recorded against it and the names of its
+/// tags.
+///
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TaskTree {
+    pub task: ProjectTask,
+    pub task_times: Vec<TaskTime>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+///
+/// What `DbiDatabase::save_project_trees` does with a project that is
+/// already in the database.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SaveMode {
+    /// Fail, leaving the stored project as it was
+    #[default]
+    Insert,
+    /// Update the project and bring its tasks and times into line with
+    /// the saved ones. TaskTimes that have not changed keep their
+    /// TaskTimeId, and invoiced ones are never changed or removed.
+    Replace,
+}
+
+// The stored tasks of a project, for SaveMode::Replace to compare
+pub(crate) const PROJECT_TASK_IDS_SQL: &str =
+    r#"SELECT "TaskId" FROM ProjectTasks WHERE "ProjectId" = $1"#;
+
+// Remove a task the saved project no longer has, unless it still has
+// invoiced times
+pub(crate) const DELETE_EMPTY_TASK_SQL: &str = r#"DELETE FROM ProjectTasks WHERE "TaskId" = $1
+    AND NOT EXISTS (SELECT 1 FROM TaskTimes WHERE TaskTimes."TaskId" = ProjectTasks."TaskId")"#;
+
+///
+/// An invoiced TaskTime that `SaveMode::Replace` left as it was, though
+/// the saved project changes it or no longer has it.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockedTime {
+    pub stored: TaskTime,
+    /// The saved copy, None when the saved project does not have it
+    pub saved: Option<TaskTime>,
+}
+
+impl fmt::Display for LockedTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "TaskTime {} from {} has been invoiced and was kept, ",
+            self.stored.task_time_id, self.stored.start_time
+        )?;
+        match &self.saved {
+            Some(saved) => match saved.end_time {
+                Some(end_time) => write!(f, "though it now ends at {}", end_time),
+                None => write!(f, "though it is now still running"),
+            },
+            None => write!(f, "though it is no longer there"),
+        }
+    }
+}
+
+///
+/// What `DbiDatabase::save_project_trees_with_report` did: the number of
+/// projects saved, and the invoiced TaskTimes a replace could not change.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SaveReport {
+    pub saved: u64,
+    pub locked: Vec<LockedTime>,
+}
+
+impl SaveReport {
+    /// Add the results of saving another batch
+    pub fn add(&mut self, other: SaveReport) {
+        self.saved += other.saved;
+        self.locked.extend(other.locked);
+    }
+}
+
+///
+/// How `SaveMode::Replace` brings a project's stored TaskTimes into line
+/// with the saved ones, matching them on `TaskTime::stable_id`.
+///
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct ReplacePlan<'a> {
+    /// Unbilled TaskTimes the saved project no longer has
+    pub delete: Vec<u64>,
+    /// Stored TaskTimes to change to match the saved ones, by TaskTimeId
+    pub update: Vec<(u64, &'a TaskTime)>,
+    /// Saved TaskTimes that are not stored yet
+    pub insert: Vec<&'a TaskTime>,
+    pub locked: Vec<LockedTime>,
+}
+
+impl<'a> ReplacePlan<'a> {
+    pub fn new(stored: Vec<TaskTime>, saved: impl IntoIterator<Item = &'a TaskTime>) -> Self {
+        let mut plan = ReplacePlan::default();
+        let mut stored: HashMap<Uuid, TaskTime> =
+            stored.into_iter().map(|t| (t.stable_id(), t)).collect();
+        for task_time in saved {
+            let Some(old) = stored.remove(&task_time.stable_id()) else {
+                plan.insert.push(task_time);
+                continue;
+            };
+            if old.end_time == task_time.end_time && old.invoice_id == task_time.invoice_id {
+                continue;
+            }
+            if old.invoice_id.is_some() {
+                plan.locked.push(LockedTime {
+                    stored: old,
+                    saved: Some(task_time.clone()),
+                });
+            } else {
+                plan.update.push((old.task_time_id, task_time));
+            }
+        }
+        let mut gone: Vec<TaskTime> = stored.into_values().collect();
+        gone.sort_by_key(|t| t.task_time_id);
+        for old in gone {
+            if old.invoice_id.is_some() {
+                plan.locked.push(LockedTime {
+                    stored: old,
+                    saved: None,
+                });
+            } else {
+                plan.delete.push(old.task_time_id);
+            }
+        }
+        plan.locked.sort_by_key(|l| l.stored.task_time_id);
+        plan
+    }
+
+    /// The stored tasks the saved project no longer has
+    pub fn removed_tasks(stored: &[Uuid], tree: &ProjectTree) -> Vec<Uuid> {
+        let saved: HashSet<Uuid> = tree.tasks.iter().map(|t| t.task.task_id).collect();
+        stored
+            .iter()
+            .filter(|id| !saved.contains(id))
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::*;
+    use crate::DbConfig;
+    use crate::DbObject;
+    use crate::DbiDatabase;
+    use crate::Error;
+    use crate::Money;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{ProjectTree, SaveMode, TaskTree};
+    use crate::database::report::ReportRange;
+    use crate::model::client::Client;
+    use crate::model::project::Project;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::tag::Tag;
+    use crate::model::task_time::TaskTime;
+
+    fn make_tree(name: &str, num_tasks: i64) -> ProjectTree {
+        let mut project = Project {
+            project_name: name.to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 8, 10)
+                .expect("Tried to create an invalid date"),
+            pay_rate: Money::from(40),
+            ..Default::default()
+        };
+        let dt = project.project_date.format("%a %b %-d %C%y").to_string();
+        project.project_id = make_uuid(&format!("{}{}", &project.project_name, &dt));
+
+        let mut tasks: Vec<TaskTree> = Vec::with_capacity(num_tasks as usize);
+        for i in 0..num_tasks {
+            let dt_str = format!("2024-08-10 {}:30:00", 12 + i);
+            let start_time = NaiveDateTime::parse_from_str(&dt_str, "%Y-%m-%d %H:%M:%S").unwrap();
+            let mut task = ProjectTask {
+                project_id: project.project_id,
+                task_name: format!("Task {:2}", i + 1),
+                task_date_time: start_time,
+                ..Default::default()
+            };
+            task.task_id = make_uuid(&format!("{}{}", task.project_id, task.task_name));
+            let task_time = TaskTime {
+                task_id: task.task_id,
+                start_time,
+                end_time: Some(start_time + chrono::TimeDelta::minutes(30)),
+                ..Default::default()
+            };
+            tasks.push(TaskTree {
+                task,
+                task_times: vec![task_time],
+                tags: vec!["Gems ".to_string()],
+            });
+        }
+        ProjectTree { project, tasks }
+    }
+
+    async fn setup() -> Result<DbiDatabase, Error> {
+        let config = DbConfig::new("sqlite::memory:");
+        DbiDatabase::new(config).await
+    }
+
+    #[tokio::test]
+    async fn test_save_project_trees() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let trees = vec![make_tree("Diamond", 2), make_tree("Ruby", 3)];
+
+        let saved = db.save_project_trees(&trees, SaveMode::Insert).await?;
+        assert_eq!(saved, 2);
+        assert_eq!(Project::retrieve_all(db.pool.sqlite()).await?.len(), 2);
+        assert_eq!(ProjectTask::retrieve_all(db.pool.sqlite()).await?.len(), 5);
+        assert_eq!(TaskTime::retrieve_all(db.pool.sqlite()).await?.len(), 5);
+        let tags = db.fetch_all::<Tag>().await?;
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].tag_name, "gems");
+        let task_id = trees[1].tasks[2].task.task_id;
+        assert_eq!(db.task_tags(task_id).await?, tags);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_project_trees_rolls_back() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let good = make_tree("Diamond", 2);
+        let mut bad = make_tree("Ruby", 2);
+        // A time pointing at a task that does not exist violates fk_TimesTasks
+        bad.tasks[1].task_times[0].task_id = uuid::Uuid::nil();
+
+        let result = db.save_project_trees(&[good, bad], SaveMode::Insert).await;
+        assert!(matches!(
+            result,
+            Err(Error::ForeignKey {
+                entity: "TaskTime",
+                ..
+            })
+        ));
+        assert!(Project::retrieve_all(db.pool.sqlite()).await?.is_empty());
+        assert!(ProjectTask::retrieve_all(db.pool.sqlite())
+            .await?
+            .is_empty());
+        assert!(TaskTime::retrieve_all(db.pool.sqlite()).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_project_trees_replace() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let first = vec![make_tree("Diamond", 3)];
+        db.save_project_trees(&first, SaveMode::Insert).await?;
+
+        let result = db.save_project_trees(&first, SaveMode::Insert).await;
+        assert!(matches!(
+            result,
+            Err(Error::Duplicate {
+                entity: "Project",
+                ..
+            })
+        ));
+
+        // Re-importing a corrected project drops the task that went away
+        let mut tree = make_tree("Diamond", 2);
+        tree.project.pay_rate = Money::from(45);
+        let second = vec![tree];
+        let saved = db.save_project_trees(&second, SaveMode::Replace).await?;
+        assert_eq!(saved, 1);
+        let saved = db.save_project_trees(&second, SaveMode::Replace).await?;
+        assert_eq!(saved, 1);
+
+        assert_eq!(
+            Project::retrieve_all(db.pool.sqlite()).await?,
+            vec![second[0].project.clone()]
+        );
+        let tasks: Vec<ProjectTask> = second[0].tasks.iter().map(|t| t.task.clone()).collect();
+        assert_eq!(ProjectTask::retrieve_all(db.pool.sqlite()).await?, tasks);
+        assert_eq!(TaskTime::retrieve_all(db.pool.sqlite()).await?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_again_on_file_database() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("mv_dbi_tree_{}.db3", uuid::Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        // Without waiting, a lock left behind fails the next write at once
+        let config = DbConfig::new(&url).busy_timeout(std::time::Duration::ZERO);
+        let mut db = DbiDatabase::new(config).await?;
+        let names = ["Diamond", "Ruby", "Emerald", "Sapphire"];
+        for name in names {
+            db.save_project_trees(&[make_tree(name, 2)], SaveMode::Insert)
+                .await?;
+        }
+
+        // Re-running an import a project at a time without replace: each
+        // project that failed leaves nothing locked for the next one
+        for name in names {
+            let result = db
+                .save_project_trees(&[make_tree(name, 2)], SaveMode::Insert)
+                .await;
+            assert!(matches!(result, Err(Error::Duplicate { .. })), "{result:?}");
+        }
+        let saved = db
+            .save_project_trees(&[make_tree("Topaz", 2)], SaveMode::Insert)
+            .await?;
+        assert_eq!(saved, 1);
+        assert_eq!(Project::retrieve_all(db.pool.sqlite()).await?.len(), 5);
+        db.pool.sqlite().close().await;
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replace_keeps_invoiced_times() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let client = Client {
+            client_id: make_uuid(&"Acme".to_string()),
+            client_name: "Acme".to_string(),
+            pay_rate: Some(Money::from(60)),
+        };
+        db.insert(&client).await?;
+        let mut first = make_tree("Diamond", 2);
+        first.project.client_id = Some(client.client_id);
+        db.save_project_trees(&[first.clone()], SaveMode::Insert)
+            .await?;
+        let range = ReportRange::month(2024, 8).unwrap();
+        let issued = NaiveDate::from_ymd_opt(2024, 9, 1).unwrap();
+        db.generate_invoice(client.client_id, &range, issued)
+            .await?
+            .expect("Expected an invoice");
+        let mut invoiced = TaskTime::retrieve_all(db.pool.sqlite()).await?;
+        invoiced.sort_by_key(|t| t.task_time_id);
+        assert!(invoiced.iter().all(|t| t.invoice_id.is_some()));
+
+        // The re-imported project lengthens the first time, drops the
+        // second task and adds a third
+        let mut second = make_tree("Diamond", 3);
+        second.project.client_id = Some(client.client_id);
+        second.tasks.remove(1);
+        let first_time = &mut second.tasks[0].task_times[0];
+        first_time.end_time = Some(first_time.start_time + chrono::TimeDelta::minutes(45));
+        let report = db
+            .save_project_trees_with_report(&[second.clone()], SaveMode::Replace)
+            .await?;
+        assert_eq!(report.saved, 1);
+        let locked: Vec<(u64, bool)> = report
+            .locked
+            .iter()
+            .map(|l| (l.stored.task_time_id, l.saved.is_some()))
+            .collect();
+        assert_eq!(locked, vec![(1, true), (2, false)]);
+        assert_eq!(
+            report.locked[1].to_string(),
+            "TaskTime 2 from 2024-08-10 13:30:00 has been invoiced and was kept, \
+             though it is no longer there"
+        );
+
+        // The invoiced times and their task are as they were, and the new
+        // time is added alongside them
+        let mut times = TaskTime::retrieve_all(db.pool.sqlite()).await?;
+        times.sort_by_key(|t| t.task_time_id);
+        assert_eq!(times[..2], invoiced[..]);
+        assert_eq!(times.len(), 3);
+        assert_eq!(times[2].task_time_id, 3);
+        assert_eq!(times[2].invoice_id, None);
+        assert_eq!(ProjectTask::retrieve_all(db.pool.sqlite()).await?.len(), 3);
+
+        // An unbilled time is changed in place, keeping its TaskTimeId
+        let new_time = &mut second.tasks[1].task_times[0];
+        new_time.end_time = Some(new_time.start_time + chrono::TimeDelta::minutes(50));
+        let report = db
+            .save_project_trees_with_report(&[second.clone()], SaveMode::Replace)
+            .await?;
+        assert_eq!(report.locked.len(), 2);
+        let mut times = TaskTime::retrieve_all(db.pool.sqlite()).await?;
+        times.sort_by_key(|t| t.task_time_id);
+        assert_eq!(times.len(), 3);
+        assert_eq!(times[2].task_time_id, 3);
+        assert_eq!(times[2].end_time, second.tasks[1].task_times[0].end_time);
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+// tag.rs
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Encode, Executor, FromRow, IntoArguments, Type};
+use uuid::Uuid;
+
+use crate::config::ValidationRules;
+use crate::database::query::DbObject;
+use crate::database::validate::Validate;
+use crate::error::{DbContext, Error};
+use crate::guid::Guid;
+use crate::utils::make_uuid;
+
+/// The longest tag name the TagName column holds
+pub const MAX_TAG_LENGTH: usize = 64;
+
+///
+/// A label for classifying ProjectTasks, such as "meetings". A task can
+/// have any number of tags; see `DbiDatabase::tag_task`. Names are kept
+/// trimmed and in lower case, so "Meetings " and "meetings" are one tag.
+///
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
+#[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "Tags", order_by = "TagName", validate)]
+pub struct Tag {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
+    pub tag_id: Uuid,
+    pub tag_name: String,
+}
+
+impl Tag {
+    pub fn new(name: &str) -> Self {
+        let tag_name = Self::normalize(name);
+        Self {
+            tag_id: Self::id_for(&tag_name),
+            tag_name,
+        }
+    }
+
+    /// The name as it is stored
+    pub fn normalize(name: &str) -> String {
+        name.trim().to_lowercase()
+    }
+
+    /// The key of the tag with the name
+    pub fn id_for(name: &str) -> Uuid {
+        make_uuid(&format!("Tag{}", Self::normalize(name)))
+    }
+}
+
+impl Validate for Tag {
+    fn validate(&self, _rules: &ValidationRules) -> Result<(), Error> {
+        let invalid = |message: String| Error::Validation {
+            entity: "Tag",
+            id: self.tag_id.to_string(),
+            message,
+        };
+        if self.tag_name.is_empty() {
+            return Err(invalid("a tag needs a name".to_string()));
+        }
+        if self.tag_name.chars().count() > MAX_TAG_LENGTH {
+            return Err(invalid(format!(
+                "'{}' is longer than {} characters",
+                self.tag_name, MAX_TAG_LENGTH
+            )));
+        }
+        if self.tag_name != Self::normalize(&self.tag_name) {
+            return Err(invalid(format!(
+                "'{}' is not trimmed and in lower case",
+                self.tag_name
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) const INSERT_TAG_SQL: &str =
+    r#"INSERT INTO Tags ("TagId", "TagName") VALUES ($1, $2) ON CONFLICT DO NOTHING"#;
+
+pub(crate) const INSERT_TASK_TAG_SQL: &str = r#"INSERT INTO TaskTags ("TaskId", "TagId") VALUES ($1, $2) ON CONFLICT DO NOTHING
+    RETURNING "TaskId""#;
+
+pub(crate) const DELETE_TASK_TAG_SQL: &str =
+    r#"DELETE FROM TaskTags WHERE "TaskId" = $1 AND "TagId" = $2"#;
+
+pub(crate) const DELETE_TASK_TAGS_SQL: &str = r#"DELETE FROM TaskTags WHERE "TaskId" = $1"#;
+
+pub(crate) const TASK_TAGS_SQL: &str = r#"SELECT g."TagId", g."TagName" FROM Tags g
+    JOIN TaskTags x ON x."TagId" = g."TagId"
+    WHERE x."TaskId" = $1
+    ORDER BY g."TagName""#;
+
+///
+/// Store the tags, those not stored already, and put them on the task.
+/// Returns how many of them the task did not have before.
+///
+pub(crate) async fn tag_in_tx<DB>(
+    conn: &mut DB::Connection,
+    task_id: Uuid,
+    tags: &[Tag],
+) -> Result<u64, Error>
+where
+    DB: Database,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    for<'q> Guid: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+{
+    let mut added = 0;
+    for tag in tags {
+        sqlx::query::<DB>(INSERT_TAG_SQL)
+            .bind(Guid::from(tag.tag_id))
+            .bind(tag.tag_name.clone())
+            .execute(&mut *conn)
+            .await
+            .context("Tag", tag.tag_id)?;
+        // A row comes back only when the task did not have the tag
+        let inserted = sqlx::query::<DB>(INSERT_TASK_TAG_SQL)
+            .bind(Guid::from(task_id))
+            .bind(Guid::from(tag.tag_id))
+            .fetch_optional(&mut *conn)
+            .await
+            .context("ProjectTask", task_id)?;
+        added += inserted.is_some() as u64;
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::database::filter::QueryFilter;
+    use crate::model::project_task::ProjectTask;
+    use crate::{DbConfig, DbiDatabase, Project};
+
+    async fn setup() -> Result<(DbiDatabase, Vec<ProjectTask>), Error> {
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        let project = Project {
+            project_id: make_uuid(&"Kiln".to_string()),
+            project_name: "Kiln".to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 8, 12).unwrap(),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        let mut tasks = Vec::new();
+        for name in ["Standup", "Firing"] {
+            let task = ProjectTask {
+                task_id: make_uuid(&format!("Kiln{}", name)),
+                project_id: project.project_id,
+                task_name: name.to_string(),
+                ..Default::default()
+            };
+            db.insert(&task).await?;
+            tasks.push(task);
+        }
+        Ok((db, tasks))
+    }
+
+    #[test]
+    fn test_tag_names() {
+        let rules = ValidationRules::default();
+        let tag = Tag::new("  Meetings ");
+        assert_eq!(tag.tag_name, "meetings");
+        assert_eq!(tag.tag_id, Tag::id_for("MEETINGS"));
+        assert!(tag.validate(&rules).is_ok());
+        assert!(Tag::new(" ").validate(&rules).is_err());
+        assert!(Tag::new(&"x".repeat(MAX_TAG_LENGTH + 1))
+            .validate(&rules)
+            .is_err());
+        let unnormalized = Tag {
+            tag_name: "Meetings".to_string(),
+            ..tag
+        };
+        assert!(matches!(
+            unnormalized.validate(&rules),
+            Err(Error::Validation { entity: "Tag", .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tag_and_untag() -> Result<(), Error> {
+        let (mut db, tasks) = setup().await?;
+        let standup = tasks[0].task_id;
+        assert_eq!(db.tag_task(standup, &["Meetings", "daily"]).await?, 2);
+        // Tagging again changes nothing
+        assert_eq!(db.tag_task(standup, &["meetings"]).await?, 0);
+        assert_eq!(db.tag_task(tasks[1].task_id, &["meetings"]).await?, 1);
+        let names: Vec<String> = db
+            .task_tags(standup)
+            .await?
+            .into_iter()
+            .map(|tag| tag.tag_name)
+            .collect();
+        assert_eq!(names, vec!["daily", "meetings"]);
+        assert_eq!(db.fetch_all::<Tag>().await?.len(), 2);
+
+        assert_eq!(db.untag_task(standup, "Daily").await?, 1);
+        assert_eq!(db.untag_task(standup, "daily").await?, 0);
+        assert_eq!(db.task_tags(standup).await?.len(), 1);
+        // The tag outlives its last task
+        assert_eq!(db.fetch_all::<Tag>().await?.len(), 2);
+
+        let result = db.tag_task(standup, &[""]).await;
+        assert!(matches!(result, Err(Error::Validation { .. })));
+        let result = db.tag_task(Uuid::nil(), &["meetings"]).await;
+        assert!(matches!(result, Err(Error::ForeignKey { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_cascade() -> Result<(), Error> {
+        let (mut db, tasks) = setup().await?;
+        db.tag_task(tasks[0].task_id, &["meetings"]).await?;
+
+        let filter = QueryFilter::new().tag("Meetings");
+        let tagged: Vec<ProjectTask> = db.fetch_filtered(&filter).await?;
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].task_name, "Standup");
+        let projects: Vec<Project> = db.fetch_filtered(&filter).await?;
+        assert_eq!(projects.len(), 1);
+        let none: Vec<Project> = db.fetch_filtered(&QueryFilter::new().tag("travel")).await?;
+        assert!(none.is_empty());
+
+        // Deleting a task takes its tags off it, a deleted tag leaves its tasks
+        db.delete(&tasks[0]).await?;
+        assert!(db.fetch_filtered::<ProjectTask>(&filter).await?.is_empty());
+        db.tag_task(tasks[1].task_id, &["meetings"]).await?;
+        db.delete(&Tag::new("meetings")).await?;
+        assert!(db.task_tags(tasks[1].task_id).await?.is_empty());
+        assert_eq!(db.fetch_all::<ProjectTask>().await?.len(), 1);
+        Ok(())
+    }
+}
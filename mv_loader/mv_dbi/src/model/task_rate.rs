@@ -0,0 +1,38 @@
+// task_rate.rs
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::query::DbObject;
+use crate::guid::Guid;
+use crate::money::Money;
+use crate::utils::make_uuid;
+
+///
+/// The rate a billing rule pays for tasks named `task_name`, in place of
+/// the project's rate.
+///
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
+#[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "TaskRates", order_by = "RuleId, TaskName")]
+pub struct TaskRate {
+    #[dbobject(key)]
+    #[sqlx(try_from = "Guid")]
+    pub task_rate_id: Uuid,
+    #[dbobject(parent)]
+    #[sqlx(try_from = "Guid")]
+    pub rule_id: Uuid,
+    pub task_name: String,
+    pub pay_rate: Money,
+}
+
+impl TaskRate {
+    pub fn new(rule_id: Uuid, task_name: &str, pay_rate: Money) -> Self {
+        Self {
+            task_rate_id: make_uuid(&format!("{}{}", rule_id, task_name)),
+            rule_id,
+            task_name: task_name.to_string(),
+            pay_rate,
+        }
+    }
+}
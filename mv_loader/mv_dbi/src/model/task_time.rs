@@ -10,116 +10,145 @@ use sqlx::sqlite::SqliteRow;
 use sqlx::sqlite::SqliteValueRef;
 use sqlx::Column;
 use sqlx::Decode;
-use sqlx::Error;
 use sqlx::FromRow;
+use sqlx::QueryBuilder;
 use sqlx::Row;
 use uuid::Uuid;
 
-use crate::database::query::DbObject;
-use crate::DataObject;
+use crate::config::ValidationRules;
+use crate::database::filter::{FilterTarget, QueryFilter};
+use crate::database::query::{DbObject, DeleteCount};
+use crate::database::validate::{TimeSpan, Validate, OPEN_END};
+use crate::error::Error;
+use crate::guid::{Guid, OptionalGuid};
+use crate::utils::make_uuid;
 
-#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, FromRow, PartialEq, Deserialize, Serialize, DbObject)]
 #[sqlx(rename_all = "PascalCase")]
+#[dbobject(table = "TaskTimes", order_by = "TaskId, StartTime")]
+#[dbobject(filter = "TaskTimes", validate)]
 pub struct TaskTime {
+    #[dbobject(autoincrement)]
+    #[sqlx(try_from = "i64")]
     pub task_time_id: u64,
+    #[dbobject(parent)]
+    #[sqlx(try_from = "Guid")]
     pub task_id: Uuid,
     pub start_time: NaiveDateTime,
-    pub end_time: NaiveDateTime,
+    /// None while the TaskTime's timer is running
+    pub end_time: Option<NaiveDateTime>,
+    /// The invoice the time was billed on. A billed TaskTime is locked.
+    #[sqlx(try_from = "OptionalGuid")]
+    pub invoice_id: Option<Uuid>,
 }
 
-impl DbObject<Sqlite, TaskTime> for TaskTime {
-    async fn insert_one(pool: &sqlx::Pool<Sqlite>, dbo: &TaskTime) -> Result<u64, Error> {
-        let mut tx = pool.begin().await?;
-        let sql = "INSERT INTO TaskTimes (
-            TaskId,
-            StartTime,
-            EndTime
-        ) VALUES (
-            $1, $2, $3
-        )";
-
-        let query = sqlx::query(sql)
-            .bind::<Uuid>(dbo.task_id)
-            .bind::<NaiveDateTime>(dbo.start_time)
-            .bind::<NaiveDateTime>(dbo.end_time)
-            .execute(&mut *tx)
-            .await?;
-
-        let row: (i64,) = sqlx::query_as("SELECT last_insert_rowid()")
-            .fetch_one(&mut *tx)
-            .await?;
-        let row_id = row.0 as u64;
-
-        tx.commit().await?;
-
-        Ok(row_id)
+// Insert a TaskTime keeping the TaskTimeId it already has, as an import does
+pub(crate) const INSERT_WITH_ID_SQL: &str = r#"INSERT INTO TaskTimes
+    ("TaskTimeId", "TaskId", "StartTime", "EndTime", "InvoiceId") VALUES ($1, $2, $3, $4, $5)"#;
+
+// Move PostgreSQL's TaskTimeId sequence past the ids written by
+// INSERT_WITH_ID_SQL. SQLite's AUTOINCREMENT keeps up by itself.
+pub(crate) const PG_RESTART_IDS_SQL: &str = r#"SELECT setval(pg_get_serial_sequence('tasktimes', 'TaskTimeId'),
+    (SELECT MAX("TaskTimeId") FROM TaskTimes))"#;
+
+// Every stored TaskTime of a project, for SaveMode::Replace to compare
+pub(crate) const PROJECT_TIMES_SQL: &str = r#"SELECT TaskTimes.* FROM TaskTimes
+    JOIN ProjectTasks ON ProjectTasks."TaskId" = TaskTimes."TaskId"
+    WHERE ProjectTasks."ProjectId" = $1"#;
+
+// Remove an unbilled TaskTime a replaced project no longer has
+pub(crate) const DELETE_BY_ID_SQL: &str = r#"DELETE FROM TaskTimes WHERE "TaskTimeId" = $1"#;
+
+// Change a stored TaskTime to match the copy in another database
+pub(crate) const UPDATE_SYNCED_SQL: &str =
+    r#"UPDATE TaskTimes SET "EndTime" = $1, "InvoiceId" = $2 WHERE "TaskTimeId" = $3"#;
+
+impl TaskTime {
+    ///
+    /// An id that is the same in every database the TaskTime is copied
+    /// to, made like the ProjectId and TaskId from the task and the
+    /// start time. The TaskTimeId is given out by each database and
+    /// cannot be compared across them.
+    ///
+    pub fn stable_id(&self) -> Uuid {
+        make_uuid(&format!("{}{}", self.task_id, self.start_time))
     }
 
-    async fn retrieve_all(pool: &sqlx::Pool<Sqlite>) -> Result<Vec<TaskTime>, Error> {
-        let sql = "SELECT
-            TaskTimeId,
-            TaskId,
-            StartTime,
-            EndTime
-        FROM TaskTimes
-        ORDER BY TaskId, StartTime ASC";
-        let records: Vec<TaskTime> = sqlx::query_as(&sql).fetch_all(pool).await?;
-        Ok(records)
+    // The key once stored, the task before then, as in insert errors
+    fn error_id(&self) -> String {
+        if self.task_time_id == 0 {
+            self.task_id.to_string()
+        } else {
+            self.task_time_id.to_string()
+        }
     }
+}
 
-    async fn retrieve_some(pool: &sqlx::Pool<Sqlite>, uuid: &Uuid) -> Result<Vec<TaskTime>, Error> {
-        let sql = "SELECT
-            TaskTimeId,
-            TaskId,
-            StartTime,
-            EndTime
-        FROM TaskTimes
-        WHERE TaskId = ?
-        ORDER BY TaskId, StartTime ASC";
-        let records: Vec<TaskTime> = sqlx::query_as(&sql).bind(&uuid).fetch_all(pool).await?;
-        Ok(records)
+impl Validate for TaskTime {
+    fn validate(&self, rules: &ValidationRules) -> Result<(), Error> {
+        let invalid = |message: String| Error::Validation {
+            entity: "TaskTime",
+            id: self.error_id(),
+            message,
+        };
+        let Some(end_time) = self.end_time else {
+            return Ok(());
+        };
+        if end_time < self.start_time {
+            return Err(invalid(format!(
+                "EndTime {} is before StartTime {}",
+                end_time, self.start_time
+            )));
+        }
+        if let Some(max) = rules.max_task_duration() {
+            let length = end_time - self.start_time;
+            if length > max {
+                return Err(invalid(format!(
+                    "{} minutes is longer than the {} allowed",
+                    length.num_minutes(),
+                    max.num_minutes()
+                )));
+            }
+        }
+        Ok(())
     }
 
-    async fn retrieve_one(&mut self, pool: &sqlx::Pool<Sqlite>) -> Result<(), Error> {
-        let sql = "SELECT
-            TaskTimeId,
-            TaskId,
-            StartTime,
-            EndTime
-        FROM TaskTimes
-        WHERE TaskTimeId = ?";
-
-        let row: SqliteRow = sqlx::query(&sql)
-            .bind(self.task_time_id as i64)
-            .fetch_one(pool)
-            .await?;
-        let temp = TaskTime::from_row(&row)?;
-
-        self.task_time_id = temp.task_time_id;
-        self.task_id = temp.task_id;
-        self.start_time = temp.start_time;
-        self.end_time = temp.end_time;
-
-        Ok(())
+    fn time_span(&self) -> Option<TimeSpan> {
+        Some(TimeSpan {
+            entity: "TaskTime",
+            id: self.error_id(),
+            group: self.task_id,
+            start_column: "StartTime",
+            end_column: "EndTime",
+            start: self.start_time,
+            end: self.end_time.unwrap_or(OPEN_END),
+        })
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default, clippy::clone_on_copy)]
 mod tests {
     use crate::model::project_task::ProjectTask;
     use crate::utils::*;
-    use crate::DataCollection;
     use crate::DbConfig;
     use crate::DbObject;
     use crate::DbiDatabase;
+    use crate::Error;
+    use crate::Money;
     use chrono::NaiveDate;
     use chrono::NaiveDateTime;
     use sqlx::migrate::Migrator;
-    use sqlx::Error;
     use uuid::Uuid;
 
     use super::TaskTime;
+    use crate::config::{OverlapScope, ValidationRules};
+    use crate::database::filter::QueryFilter;
+    use crate::database::query::{DeleteCount, PageKey};
+    use crate::model::project_tree::{ProjectTree, SaveMode, TaskTree};
     use crate::Project;
+    use chrono::TimeDelta;
+    use futures::TryStreamExt;
 
     fn make_project() -> Project {
         let mut project = Project::default();
@@ -129,8 +158,8 @@ mod tests {
         project.project_date =
             NaiveDate::from_ymd_opt(2024, 8, 10).expect("Tried to create an invalid date");
         project.project_duration = 0;
-        project.pay_rate = 40.0;
-        project.total_pay = 0.0;
+        project.pay_rate = Money::from(40);
+        project.total_pay = Money::ZERO;
 
         let dt = project.project_date.format("%a %b %-d %C%y").to_string();
         let value = format!("{}{}", &project.project_name, &dt);
@@ -165,7 +194,8 @@ mod tests {
         task_time.start_time = NaiveDateTime::parse_from_str(&dt_str, "%Y-%m-%d %H:%M:%S").unwrap();
         hour += time_diff;
         let dt_str = format!("{}-{}-{} {}:{}:{}", 2024, "08", 10, hour, 30, 30);
-        task_time.end_time = NaiveDateTime::parse_from_str(&dt_str, "%Y-%m-%d %H:%M:%S").unwrap();
+        task_time.end_time =
+            Some(NaiveDateTime::parse_from_str(&dt_str, "%Y-%m-%d %H:%M:%S").unwrap());
 
         task_time
     }
@@ -180,15 +210,15 @@ mod tests {
     async fn test_insert_one_task_time() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let task = make_task(project.project_id.clone(), 0);
-        let num_rows = ProjectTask::insert_one(&db.pool, &task).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
         assert_eq!(num_rows, 1);
 
         let task_time = make_task_time(task.task_id.clone(), 0);
-        let row_id = TaskTime::insert_one(&db.pool, &task_time).await?;
+        let row_id = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
         assert_eq!(row_id, 1);
 
         Ok(())
@@ -198,22 +228,22 @@ mod tests {
     async fn test_select_one_task_time() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let task = make_task(project.project_id.clone(), 0);
-        let num_rows = ProjectTask::insert_one(&db.pool, &task).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
         assert_eq!(num_rows, 1);
 
         let mut task_time = make_task_time(task.task_id.clone(), 0);
-        let row_id = TaskTime::insert_one(&db.pool, &task_time).await?;
+        let row_id = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
         assert_eq!(row_id, 1);
         task_time.task_time_id = row_id;
 
         let mut actual = TaskTime::default();
         actual.task_time_id = task_time.task_time_id;
 
-        actual.retrieve_one(&db.pool).await?;
+        actual.retrieve_one(db.pool.sqlite()).await?;
         assert_eq!(actual, task_time);
 
         Ok(())
@@ -223,24 +253,24 @@ mod tests {
     async fn test_select_all_task_times() -> Result<(), Error> {
         let mut db = setup().await?;
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let task = make_task(project.project_id.clone(), 0);
-        let num_rows = ProjectTask::insert_one(&db.pool, &task).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
         assert_eq!(num_rows, 1);
 
         let mut expected: Vec<TaskTime> = Vec::with_capacity(3);
 
         for i in 0..3 {
             let mut task_time = make_task_time(task.task_id.clone(), i);
-            let res = TaskTime::insert_one(&db.pool, &task_time).await?;
+            let res = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
             assert_eq!(res, (i + 1) as u64);
             task_time.task_time_id = res;
             expected.push(task_time);
         }
 
-        let actual = TaskTime::retrieve_all(&db.pool).await?;
+        let actual = TaskTime::retrieve_all(db.pool.sqlite()).await?;
 
         assert_eq!(actual, expected);
 
@@ -252,22 +282,22 @@ mod tests {
         let mut db = setup().await?;
 
         let project = make_project();
-        let num_rows = Project::insert_one(&db.pool, &project).await?;
+        let num_rows = Project::insert_one(db.pool.sqlite(), &project).await?;
         assert_eq!(num_rows, 1);
 
         let task1 = make_task(project.project_id.clone(), 0);
-        let num_rows = ProjectTask::insert_one(&db.pool, &task1).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &task1).await?;
         assert_eq!(num_rows, 1);
 
         let task2 = make_task(project.project_id.clone(), 1);
-        let num_rows = ProjectTask::insert_one(&db.pool, &task2).await?;
+        let num_rows = ProjectTask::insert_one(db.pool.sqlite(), &task2).await?;
         assert_eq!(num_rows, 1);
 
         let mut expected: Vec<TaskTime> = Vec::with_capacity(3);
 
         for i in 0..3 {
             let mut task_time = make_task_time(task1.task_id.clone(), i);
-            let res = TaskTime::insert_one(&db.pool, &task_time).await?;
+            let res = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
             assert_eq!(res, (i + 1) as u64);
             task_time.task_time_id = res;
             expected.push(task_time);
@@ -275,14 +305,278 @@ mod tests {
 
         for i in 3..6 {
             let mut task_time = make_task_time(task2.task_id.clone(), i);
-            let res = TaskTime::insert_one(&db.pool, &task_time).await?;
+            let res = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
             assert_eq!(res, (i + 1) as u64);
             task_time.task_time_id = res;
         }
 
-        let actual = TaskTime::retrieve_some(&db.pool, &task1.task_id).await?;
+        let actual = TaskTime::retrieve_some(db.pool.sqlite(), &task1.task_id).await?;
         assert_eq!(actual, expected);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_update_one_task_time() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let task = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+
+        let mut expected = make_task_time(task.task_id, 1);
+        expected.task_time_id = TaskTime::insert_one(db.pool.sqlite(), &expected).await?;
+
+        expected.end_time = make_task_time(task.task_id, 2).end_time;
+        let num_rows = TaskTime::update_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(num_rows, 1);
+
+        let mut actual = TaskTime {
+            task_time_id: expected.task_time_id,
+            ..Default::default()
+        };
+        actual.retrieve_one(db.pool.sqlite()).await?;
+        assert_eq!(actual, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upsert_one_task_time() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let task = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+
+        let mut expected = make_task_time(task.task_id, 1);
+        let row_id = TaskTime::upsert_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(row_id, 1);
+        expected.task_time_id = row_id;
+
+        expected.end_time = make_task_time(task.task_id, 2).end_time;
+        let row_id = TaskTime::upsert_one(db.pool.sqlite(), &expected).await?;
+        assert_eq!(row_id, 1);
+
+        let actual = TaskTime::retrieve_all(db.pool.sqlite()).await?;
+        assert_eq!(actual, vec![expected]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_one_task_time() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let task = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+
+        let mut expected: Vec<TaskTime> = Vec::with_capacity(3);
+        for i in 0..3 {
+            let mut task_time = make_task_time(task.task_id, i);
+            task_time.task_time_id = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
+            expected.push(task_time);
+        }
+
+        let removed = expected.remove(1);
+        let deleted = TaskTime::delete_one(db.pool.sqlite(), &removed).await?;
+        assert_eq!(
+            deleted,
+            DeleteCount {
+                rows: 1,
+                children: 0
+            }
+        );
+
+        let actual = TaskTime::retrieve_all(db.pool.sqlite()).await?;
+        assert_eq!(actual, expected);
+        assert_eq!(ProjectTask::retrieve_all(db.pool.sqlite()).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_select_filtered_task_times() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+
+        let task = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+
+        // One entry on each of Jul 31, Aug 1 and Aug 10
+        let mut all: Vec<TaskTime> = Vec::with_capacity(3);
+        for day in [
+            NaiveDate::from_ymd_opt(2024, 7, 31).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 8, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 8, 10).unwrap(),
+        ] {
+            let mut task_time = make_task_time(task.task_id, 1);
+            task_time.start_time = day.and_time(task_time.start_time.time());
+            task_time.end_time = task_time.end_time.map(|end| day.and_time(end.time()));
+            task_time.task_time_id = TaskTime::insert_one(db.pool.sqlite(), &task_time).await?;
+            all.push(task_time);
+        }
+
+        let august = QueryFilter::new()
+            .project_id(project.project_id)
+            .date_range(
+                NaiveDate::from_ymd_opt(2024, 8, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 8, 31).unwrap(),
+            );
+        let actual: Vec<TaskTime> = db.fetch_filtered(&august).await?;
+        assert_eq!(actual, all[1..].to_vec());
+
+        let filter = QueryFilter::new().task_name("Nothing like this");
+        let actual = TaskTime::retrieve_filtered(db.pool.sqlite(), &filter).await?;
+        assert!(actual.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_page_task_times() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+        let task = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+        for i in 0..5 {
+            TaskTime::insert_one(db.pool.sqlite(), &make_task_time(task.task_id, i)).await?;
+        }
+
+        let mut after = None;
+        let mut pages: Vec<Vec<u64>> = Vec::new();
+        loop {
+            let page = db.fetch_page::<TaskTime>(after, 2).await?;
+            pages.push(page.items.iter().map(|t| t.task_time_id).collect());
+            match page.next {
+                Some(key) => after = Some(key),
+                None => break,
+            }
+        }
+        assert_eq!(pages, vec![vec![1, 2], vec![3, 4], vec![5]]);
+
+        let result = db
+            .fetch_page::<TaskTime>(Some(PageKey::Uuid(task.task_id)), 2)
+            .await;
+        assert!(matches!(result, Err(Error::Validation { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_task_times() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        Project::insert_one(db.pool.sqlite(), &project).await?;
+        let task = make_task(project.project_id, 0);
+        ProjectTask::insert_one(db.pool.sqlite(), &task).await?;
+        for i in 0..3 {
+            TaskTime::insert_one(db.pool.sqlite(), &make_task_time(task.task_id, i)).await?;
+        }
+
+        let mut ids: Vec<u64> = Vec::new();
+        let mut stream = db.fetch_stream::<TaskTime>();
+        while let Some(task_time) = stream.try_next().await? {
+            ids.push(task_time.task_time_id);
+        }
+        assert_eq!(ids, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    async fn setup_rules(rules: ValidationRules) -> Result<(DbiDatabase, ProjectTask), Error> {
+        let config = DbConfig::new("sqlite::memory:").validation(rules);
+        let mut db = DbiDatabase::new(config).await?;
+        let project = make_project();
+        db.insert(&project).await?;
+        let task = make_task(project.project_id, 0);
+        db.insert(&task).await?;
+        Ok((db, task))
+    }
+
+    #[tokio::test]
+    async fn test_validate_task_time_range() -> Result<(), Error> {
+        let (mut db, task) = setup_rules(ValidationRules::default()).await?;
+        let mut task_time = make_task_time(task.task_id, 1);
+        let end_time = task_time.end_time.replace(task_time.start_time);
+        task_time.start_time = end_time.unwrap();
+        let result = db.insert(&task_time).await;
+        assert!(matches!(
+            result,
+            Err(Error::Validation { entity: "TaskTime", ref id, .. }) if *id == task.task_id.to_string()
+        ));
+
+        let mut task_time = make_task_time(task.task_id, 1);
+        task_time.end_time = Some(task_time.start_time + TimeDelta::hours(25));
+        let result = db.insert(&task_time).await;
+        assert!(matches!(result, Err(Error::Validation { .. })));
+
+        let (mut db, task) = setup_rules(ValidationRules::default().max_task_minutes(0)).await?;
+        task_time.task_id = task.task_id;
+        assert_eq!(db.insert(&task_time).await?, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_task_time_overlaps() -> Result<(), Error> {
+        let (mut db, task) = setup_rules(ValidationRules::default()).await?;
+        let other_task = make_task(task.project_id, 1);
+        db.insert(&other_task).await?;
+
+        // 14:30 to 16:30, then 15:30 to 18:30
+        let mut first = make_task_time(task.task_id, 2);
+        first.task_time_id = db.insert(&first).await?;
+        let second = make_task_time(task.task_id, 3);
+        let result = db.insert(&second).await;
+        match result {
+            Err(Error::Overlap { entity, id, others }) => {
+                assert_eq!(entity, "TaskTime");
+                assert_eq!(id, task.task_id.to_string());
+                assert_eq!(others, vec!["1".to_string()]);
+            }
+            _ => panic!("Expected an Overlap error, got {:?}", result),
+        }
+
+        // A row never overlaps itself, and touching ends do not overlap
+        first.end_time = first.end_time.map(|end| end - TimeDelta::minutes(30));
+        assert_eq!(db.update(&first).await?, 1);
+        assert!(db.insert(&make_task_time(task.task_id, 1)).await.is_ok());
+
+        // Another task's time only overlaps with the global scope
+        let third = make_task_time(other_task.task_id, 3);
+        assert!(db.insert(&third).await.is_ok());
+        let (mut db, task) =
+            setup_rules(ValidationRules::default().overlap(OverlapScope::Global)).await?;
+        db.insert(&other_task).await?;
+        db.insert(&make_task_time(task.task_id, 2)).await?;
+        let result = db.insert(&make_task_time(other_task.task_id, 3)).await;
+        assert!(matches!(result, Err(Error::Overlap { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validate_project_tree_times() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let project = make_project();
+        let task = make_task(project.project_id, 0);
+        let tree = ProjectTree {
+            project,
+            tasks: vec![TaskTree {
+                task_times: vec![
+                    make_task_time(task.task_id, 2),
+                    make_task_time(task.task_id, 3),
+                ],
+                task,
+                tags: Vec::new(),
+            }],
+        };
+        let result = db.save_project_trees(&[tree], SaveMode::Insert).await;
+        assert!(matches!(result, Err(Error::Overlap { .. })));
+        assert!(db.fetch_all::<Project>().await?.is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,286 @@
+// money.rs
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Sub};
+use std::str::FromStr;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
+use sqlx::sqlite::{SqliteArgumentValue, SqliteTypeInfo, SqliteValueRef};
+use sqlx::{Decode, Encode, Postgres, Sqlite, Type, TypeInfo, ValueRef};
+
+use crate::database::text_type::text_type;
+use crate::error::Error;
+
+/// The places money is rounded to
+pub const MONEY_PLACES: u32 = 2;
+
+/// The currency used when none is given
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+///
+/// How an amount is rounded to whole cents.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Halves go away from zero: 0.125 becomes 0.13
+    #[default]
+    HalfUp,
+    /// Halves go to the even cent: 0.125 becomes 0.12
+    HalfEven,
+    /// Toward zero: 0.129 becomes 0.12
+    Down,
+    /// Away from zero: 0.121 becomes 0.13
+    Up,
+}
+
+impl RoundingMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundingMode::HalfUp => "half_up",
+            RoundingMode::HalfEven => "half_even",
+            RoundingMode::Down => "down",
+            RoundingMode::Up => "up",
+        }
+    }
+
+    pub(crate) fn strategy(self) -> RoundingStrategy {
+        match self {
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::Down => RoundingStrategy::ToZero,
+            RoundingMode::Up => RoundingStrategy::AwayFromZero,
+        }
+    }
+}
+
+impl fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "half_up" => Ok(RoundingMode::HalfUp),
+            "half_even" => Ok(RoundingMode::HalfEven),
+            "down" => Ok(RoundingMode::Down),
+            "up" => Ok(RoundingMode::Up),
+            _ => Err(Error::Configuration(format!(
+                "Unknown rounding mode '{s}', expected 'half_up', 'half_even', 'down' or 'up'"
+            ))),
+        }
+    }
+}
+
+///
+/// An exact decimal amount of money, or a pay rate per hour.
+///
+/// PostgreSQL stores it as NUMERIC. SQLite has no decimal type, so it is
+/// stored as TEXT, which keeps every digit. SQL that does arithmetic or
+/// comparisons on a money column should `CAST(... AS NUMERIC)` it first,
+/// so SQLite compares numbers rather than strings.
+///
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct Money(pub Decimal);
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    pub fn new(amount: Decimal) -> Self {
+        Money(amount)
+    }
+
+    /// Round to whole cents
+    pub fn round(self, mode: RoundingMode) -> Self {
+        Money(self.0.round_dp_with_strategy(MONEY_PLACES, mode.strategy()))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.2}", self.0)
+    }
+}
+
+impl FromStr for Money {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Decimal::from_str(s.trim()).map(Money)
+    }
+}
+
+impl From<Decimal> for Money {
+    fn from(amount: Decimal) -> Self {
+        Money(amount)
+    }
+}
+
+impl From<i64> for Money {
+    fn from(amount: i64) -> Self {
+        Money(Decimal::from(amount))
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Mul<Decimal> for Money {
+    type Output = Money;
+
+    fn mul(self, factor: Decimal) -> Money {
+        Money(self.0 * factor)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl Type<Sqlite> for Money {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    // Sums and other arithmetic in SQL come back as REAL or INTEGER
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as Type<Sqlite>>::compatible(ty)
+            || <f64 as Type<Sqlite>>::compatible(ty)
+            || <i64 as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for Money {
+    fn encode_by_ref(
+        &self,
+        args: &mut Vec<SqliteArgumentValue<'q>>,
+    ) -> Result<IsNull, BoxDynError> {
+        <String as Encode<Sqlite>>::encode(self.0.to_string(), args)
+    }
+}
+
+impl<'r> Decode<'r, Sqlite> for Money {
+    fn decode(value: SqliteValueRef<'r>) -> Result<Self, BoxDynError> {
+        let type_name = value.type_info().name().to_owned();
+        match type_name.as_str() {
+            "INTEGER" => Ok(Money::from(<i64 as Decode<Sqlite>>::decode(value)?)),
+            // The shortest text that reads back as the same double, so a
+            // REAL of 79.33 is 79.33 and not 79.3299999...
+            "REAL" => {
+                let amount = <f64 as Decode<Sqlite>>::decode(value)?;
+                Ok(Decimal::from_str(&amount.to_string())?.into())
+            }
+            _ => Ok(Decimal::from_str(<&str as Decode<Sqlite>>::decode(value)?)?.into()),
+        }
+    }
+}
+
+impl Type<Postgres> for Money {
+    fn type_info() -> PgTypeInfo {
+        <Decimal as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <Decimal as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Money {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <Decimal as Encode<Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Money {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(Money(<Decimal as Decode<Postgres>>::decode(value)?))
+    }
+}
+
+// A RoundingMode is stored as its name, "half_up" and so on
+text_type!(RoundingMode);
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use rust_decimal_macros::dec;
+
+    use super::*;
+    use crate::utils::total_pay;
+
+    #[test]
+    fn test_money_rounding() {
+        let amount = Money(dec!(0.125));
+        assert_eq!(amount.round(RoundingMode::HalfUp), Money(dec!(0.13)));
+        assert_eq!(amount.round(RoundingMode::HalfEven), Money(dec!(0.12)));
+        assert_eq!(
+            Money(dec!(0.129)).round(RoundingMode::Down),
+            Money(dec!(0.12))
+        );
+        assert_eq!(
+            Money(dec!(0.121)).round(RoundingMode::Up),
+            Money(dec!(0.13))
+        );
+        assert_eq!(
+            "half_even".parse::<RoundingMode>().unwrap(),
+            RoundingMode::HalfEven
+        );
+        assert!("nearest".parse::<RoundingMode>().is_err());
+    }
+
+    #[test]
+    fn test_money_total_pay() {
+        // 1:59:30 is paid as 119 minutes, which at 35.00 is 69.41666...
+        let duration = TimeDelta::seconds(119 * 60 + 30).num_milliseconds();
+        let rate = Money(dec!(35));
+        assert_eq!(
+            total_pay(duration, rate, RoundingMode::HalfUp),
+            Money(dec!(69.42))
+        );
+        assert_eq!(
+            total_pay(duration, rate, RoundingMode::Down),
+            Money(dec!(69.41))
+        );
+
+        // Summing the cents never drifts the way f64 does
+        let total: Money = std::iter::repeat_n(Money(dec!(0.1)), 10).sum();
+        assert_eq!(total, Money::from(1));
+        assert_eq!(total.to_string(), "1.00");
+    }
+}
@@ -0,0 +1,891 @@
+// schema.rs
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sqlx::migrate::{Migrate, MigrateError, Migration, Migrator};
+use sqlx::{Database, Executor, FromRow, IntoArguments, Pool, Postgres, Sqlite};
+
+use crate::config::{Backend, UuidStorage};
+use crate::error::Error;
+
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations_pg");
+
+// sqlx records migrations in this table, creating it on the first run
+const APPLIED_SQL: &str =
+    "SELECT version, checksum, success FROM _sqlx_migrations ORDER BY version";
+
+pub(crate) trait SchemaDialect {
+    /// Counts 1 when the migrations table exists and 0 when it does not
+    const MIGRATIONS_TABLE_SQL: &'static str;
+    /// Run on the migrating connection before the first migration and
+    /// after the last one
+    const BEFORE_MIGRATING_SQL: Option<&'static str>;
+    const AFTER_MIGRATING_SQL: Option<&'static str>;
+}
+
+impl SchemaDialect for Sqlite {
+    const MIGRATIONS_TABLE_SQL: &'static str =
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'";
+    // A migration that rebuilds a table drops the old one, which would take
+    // the rows of every table referring to it along while foreign keys are
+    // on. They cannot be turned off inside the migration's transaction.
+    const BEFORE_MIGRATING_SQL: Option<&'static str> = Some("PRAGMA foreign_keys = OFF");
+    const AFTER_MIGRATING_SQL: Option<&'static str> = Some("PRAGMA foreign_keys = ON");
+}
+
+impl SchemaDialect for Postgres {
+    const MIGRATIONS_TABLE_SQL: &'static str =
+        "SELECT COUNT(*) FROM (SELECT to_regclass('_sqlx_migrations') AS t) m WHERE t IS NOT NULL";
+    const BEFORE_MIGRATING_SQL: Option<&'static str> = None;
+    const AFTER_MIGRATING_SQL: Option<&'static str> = None;
+}
+
+///
+/// The migrations built into mv_dbi for a backend.
+///
+pub fn migrator(backend: Backend) -> &'static Migrator {
+    match backend {
+        Backend::Sqlite => &SQLITE_MIGRATOR,
+        Backend::Postgres => &POSTGRES_MIGRATOR,
+    }
+}
+
+///
+/// The newest schema version this build of mv_dbi knows for a backend.
+/// Databases at a later version are refused when they are opened.
+///
+pub fn latest_version(backend: Backend) -> i64 {
+    latest(migrator(backend))
+}
+
+fn latest(migrator: &Migrator) -> i64 {
+    up_migrations(migrator)
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default()
+}
+
+fn up_migrations(migrator: &Migrator) -> impl DoubleEndedIterator<Item = &Migration> {
+    migrator
+        .iter()
+        .filter(|m| m.migration_type.is_up_migration())
+}
+
+///
+/// One migration this build knows, and whether the database has it.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MigrationInfo {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    /// Whether there is a down script, so `migrate_to` can roll it back
+    pub reversible: bool,
+}
+
+///
+/// Which version a database's schema is at, and the migrations between it
+/// and the latest one this build knows.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SchemaStatus {
+    /// The newest migration applied, None before the first one
+    pub version: Option<i64>,
+    pub latest: i64,
+    pub migrations: Vec<MigrationInfo>,
+}
+
+impl SchemaStatus {
+    /// The migrations that have not been applied, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &MigrationInfo> {
+        self.migrations.iter().filter(|m| !m.applied)
+    }
+
+    /// Whether every migration has been applied
+    pub fn is_current(&self) -> bool {
+        self.pending().next().is_none()
+    }
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for migration in &self.migrations {
+            writeln!(
+                f,
+                "{} {:<8} {}{}",
+                migration.version,
+                if migration.applied {
+                    "applied"
+                } else {
+                    "pending"
+                },
+                migration.description,
+                if migration.reversible {
+                    ""
+                } else {
+                    " (irreversible)"
+                }
+            )?;
+        }
+        match self.version {
+            Some(version) => write!(f, "Schema version {}", version)?,
+            None => write!(f, "No migrations applied")?,
+        }
+        write!(f, ", latest {}", self.latest)
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub(crate) struct AppliedMigration {
+    version: i64,
+    checksum: Vec<u8>,
+    success: bool,
+}
+
+// What the database has recorded, empty when no migration has run. The
+// table is looked for first so a read-only database is never written to.
+async fn applied_migrations<DB>(conn: &mut DB::Connection) -> Result<Vec<AppliedMigration>, Error>
+where
+    DB: Database + SchemaDialect,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+    AppliedMigration: for<'r> FromRow<'r, DB::Row>,
+{
+    let (tables,): (i64,) = sqlx::query_as(DB::MIGRATIONS_TABLE_SQL)
+        .fetch_one(&mut *conn)
+        .await?;
+    if tables == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(sqlx::query_as(APPLIED_SQL).fetch_all(conn).await?)
+}
+
+fn check_not_newer(applied: &[AppliedMigration], migrator: &Migrator) -> Result<(), Error> {
+    let latest = latest(migrator);
+    match applied.iter().map(|m| m.version).max() {
+        Some(version) if version > latest => Err(Error::SchemaTooNew { version, latest }),
+        _ => Ok(()),
+    }
+}
+
+// The same checks sqlx makes before it runs migrations
+fn check_applied(applied: &[AppliedMigration], migrator: &Migrator) -> Result<(), Error> {
+    check_not_newer(applied, migrator)?;
+    let known: HashMap<i64, &Migration> = up_migrations(migrator).map(|m| (m.version, m)).collect();
+    for migration in applied {
+        if !migration.success {
+            return Err(MigrateError::Dirty(migration.version).into());
+        }
+        match known.get(&migration.version) {
+            None => return Err(MigrateError::VersionMissing(migration.version).into()),
+            Some(m) if *m.checksum != *migration.checksum => {
+                return Err(MigrateError::VersionMismatch(migration.version).into())
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+///
+/// Refuse a database that a newer build of mv_dbi has migrated, since
+/// this build cannot know what its schema looks like.
+///
+pub(crate) async fn ensure_supported<DB>(pool: &Pool<DB>, migrator: &Migrator) -> Result<(), Error>
+where
+    DB: Database + SchemaDialect,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+    AppliedMigration: for<'r> FromRow<'r, DB::Row>,
+{
+    let mut conn = pool.acquire().await?;
+    check_not_newer(&applied_migrations::<DB>(&mut conn).await?, migrator)
+}
+
+///
+/// Apply every migration the database does not have yet, as opening it
+/// does.
+///
+pub(crate) async fn run<DB>(pool: &Pool<DB>, migrator: &Migrator) -> Result<(), Error>
+where
+    DB: Database + SchemaDialect,
+    DB::Connection: Migrate,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+{
+    let mut conn = pool.acquire().await?;
+    set_migrating::<DB>(&mut conn, DB::BEFORE_MIGRATING_SQL).await?;
+    let result = migrator.run_direct(&mut *conn).await;
+    set_migrating::<DB>(&mut conn, DB::AFTER_MIGRATING_SQL).await?;
+    Ok(result?)
+}
+
+async fn set_migrating<DB>(conn: &mut DB::Connection, sql: Option<&str>) -> Result<(), Error>
+where
+    DB: Database,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+{
+    if let Some(sql) = sql {
+        conn.execute(sql).await?;
+    }
+    Ok(())
+}
+
+///
+/// List the migrations this build knows and which of them the database
+/// has had applied.
+///
+pub(crate) async fn status<DB>(pool: &Pool<DB>, migrator: &Migrator) -> Result<SchemaStatus, Error>
+where
+    DB: Database + SchemaDialect,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+    AppliedMigration: for<'r> FromRow<'r, DB::Row>,
+{
+    let mut conn = pool.acquire().await?;
+    let applied = applied_migrations::<DB>(&mut conn).await?;
+    check_not_newer(&applied, migrator)?;
+    let migrations = up_migrations(migrator)
+        .map(|m| MigrationInfo {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.iter().any(|a| a.version == m.version),
+            reversible: m.migration_type.is_reversible(),
+        })
+        .collect();
+    Ok(SchemaStatus {
+        version: applied.iter().map(|m| m.version).max(),
+        latest: latest(migrator),
+        migrations,
+    })
+}
+
+///
+/// Bring the schema to `target`, applying the migrations up to it that
+/// are missing and rolling back the ones after it, newest first. A target
+/// of 0 rolls every migration back. Each migration runs in its own
+/// transaction, so a failure leaves the schema at the last one that
+/// worked.
+///
+pub(crate) async fn migrate_to<DB>(
+    pool: &Pool<DB>,
+    migrator: &Migrator,
+    target: i64,
+) -> Result<(), Error>
+where
+    DB: Database + SchemaDialect,
+    DB::Connection: Migrate,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+    AppliedMigration: for<'r> FromRow<'r, DB::Row>,
+{
+    if target != 0 && !up_migrations(migrator).any(|m| m.version == target) {
+        return Err(Error::Configuration(format!(
+            "There is no migration {}, expected 0 or one of the versions listed by the schema status",
+            target
+        )));
+    }
+    // Read what is applied only once the lock is held, so another process
+    // migrating at the same time cannot change it underneath
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    set_migrating::<DB>(&mut conn, DB::BEFORE_MIGRATING_SQL).await?;
+    let result = run_migrations::<DB>(&mut conn, migrator, target).await;
+    set_migrating::<DB>(&mut conn, DB::AFTER_MIGRATING_SQL).await?;
+    conn.unlock().await?;
+    result
+}
+
+async fn run_migrations<DB>(
+    conn: &mut DB::Connection,
+    migrator: &Migrator,
+    target: i64,
+) -> Result<(), Error>
+where
+    DB: Database + SchemaDialect,
+    DB::Connection: Migrate,
+    for<'e> &'e mut DB::Connection: Executor<'e, Database = DB>,
+    for<'q> <DB as Database>::Arguments<'q>: IntoArguments<'q, DB>,
+    (i64,): for<'r> FromRow<'r, DB::Row>,
+    AppliedMigration: for<'r> FromRow<'r, DB::Row>,
+{
+    let applied = applied_migrations::<DB>(conn).await?;
+    check_applied(&applied, migrator)?;
+
+    let reverts: Vec<&Migration> = migrator
+        .iter()
+        .rev()
+        .filter(|m| m.migration_type.is_down_migration() && m.version > target)
+        .filter(|m| applied.iter().any(|a| a.version == m.version))
+        .collect();
+    if let Some(migration) = applied
+        .iter()
+        .filter(|a| a.version > target)
+        .find(|a| !reverts.iter().any(|m| m.version == a.version))
+    {
+        return Err(Error::Configuration(format!(
+            "Migration {} has no down script and cannot be rolled back",
+            migration.version
+        )));
+    }
+
+    conn.ensure_migrations_table().await?;
+    for migration in up_migrations(migrator)
+        .filter(|m| m.version <= target && !applied.iter().any(|a| a.version == m.version))
+    {
+        conn.apply(migration).await?;
+    }
+    for migration in reverts {
+        conn.revert(migration).await?;
+    }
+    Ok(())
+}
+
+// The SQLite columns that hold keys, whichever migrations created them
+const KEY_COLUMNS: &[(&str, &str)] = &[
+    ("Clients", "ClientId"),
+    ("Projects", "ProjectId"),
+    ("Projects", "ClientId"),
+    ("ProjectTasks", "TaskId"),
+    ("ProjectTasks", "ProjectId"),
+    ("TaskTimes", "TaskId"),
+    ("TaskTimes", "InvoiceId"),
+    ("Invoices", "InvoiceId"),
+    ("Invoices", "ClientId"),
+    ("InvoiceLines", "InvoiceLineId"),
+    ("InvoiceLines", "InvoiceId"),
+    ("BillingRules", "RuleId"),
+    ("BillingRules", "ProjectId"),
+    ("BillingRules", "ClientId"),
+    ("TaskRates", "TaskRateId"),
+    ("TaskRates", "RuleId"),
+    ("Tags", "TagId"),
+    ("TaskTags", "TaskId"),
+    ("TaskTags", "TagId"),
+    ("SearchIndex", "ProjectId"),
+    ("SearchIndex", "TaskId"),
+    ("SearchKeys", "ProjectId"),
+    ("SearchKeys", "TaskId"),
+];
+
+const TABLES_SQL: &str = "SELECT name FROM sqlite_master WHERE type = 'table'";
+const TRIGGERS_SQL: &str =
+    "SELECT name, sql FROM sqlite_master WHERE type = 'trigger' AND sql IS NOT NULL";
+
+// The type keys are kept as in `storage`, and how one kept the other way
+// is rewritten into it
+fn key_conversion(storage: UuidStorage, column: &str) -> (&'static str, String) {
+    match storage {
+        UuidStorage::Text => (
+            "text",
+            format!(
+                "lower(substr(hex({c}), 1, 8) || '-' || substr(hex({c}), 9, 4) || '-' \
+                 || substr(hex({c}), 13, 4) || '-' || substr(hex({c}), 17, 4) || '-' \
+                 || substr(hex({c}), 21, 12))",
+                c = column
+            ),
+        ),
+        UuidStorage::Blob => ("blob", format!("unhex(replace({}, '-', ''))", column)),
+    }
+}
+
+// The key columns of the tables the database has, with the condition that
+// finds the keys not kept as in `storage`
+async fn stored_key_columns(
+    conn: &mut sqlx::SqliteConnection,
+    storage: UuidStorage,
+) -> Result<Vec<(&'static str, &'static str, String)>, Error> {
+    let tables: Vec<String> = sqlx::query_scalar(TABLES_SQL).fetch_all(&mut *conn).await?;
+    let (kind, _) = key_conversion(storage, "");
+    Ok(KEY_COLUMNS
+        .iter()
+        .filter(|(table, _)| tables.iter().any(|t| t == table))
+        .map(|&(table, column)| {
+            let other = format!("typeof({}) NOT IN ('{}', 'null')", column, kind);
+            (table, column, other)
+        })
+        .collect())
+}
+
+///
+/// Whether any key is kept other than as `storage` says
+///
+pub(crate) async fn keys_need_converting(
+    pool: &Pool<Sqlite>,
+    storage: UuidStorage,
+) -> Result<bool, Error> {
+    let mut conn = pool.acquire().await?;
+    let checks: Vec<String> = stored_key_columns(&mut conn, storage)
+        .await?
+        .iter()
+        .map(|(table, _, other)| format!("EXISTS (SELECT 1 FROM {} WHERE {})", table, other))
+        .collect();
+    if checks.is_empty() {
+        return Ok(false);
+    }
+    // Read to the end, as a statement left part way through holds its read
+    // lock, which blocks writers on a shared in-memory database
+    let found: Vec<bool> = sqlx::query_scalar(&format!("SELECT {}", checks.join(" OR ")))
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(found.contains(&true))
+}
+
+///
+/// Rewrite the keys kept other than as `storage` says, in one transaction,
+/// returning how many values changed. The triggers, which keep invoiced
+/// rows from being updated, are dropped while it runs and put back, and
+/// foreign keys are only checked at the commit, once parents and children
+/// match again.
+///
+pub(crate) async fn convert_keys(pool: &Pool<Sqlite>, storage: UuidStorage) -> Result<u64, Error> {
+    if !keys_need_converting(pool, storage).await? {
+        return Ok(0);
+    }
+    let mut tx = pool.begin().await?;
+    let result = async {
+        sqlx::query("PRAGMA defer_foreign_keys = ON")
+            .execute(&mut *tx)
+            .await?;
+        let triggers: Vec<(String, String)> =
+            sqlx::query_as(TRIGGERS_SQL).fetch_all(&mut *tx).await?;
+        for (name, _) in &triggers {
+            sqlx::query(&format!("DROP TRIGGER \"{}\"", name))
+                .execute(&mut *tx)
+                .await?;
+        }
+        let mut converted = 0;
+        for (table, column, other) in stored_key_columns(&mut tx, storage).await? {
+            let (_, value) = key_conversion(storage, column);
+            let sql = format!(
+                "UPDATE {} SET {} = {} WHERE {}",
+                table, column, value, other
+            );
+            converted += sqlx::query(&sql).execute(&mut *tx).await?.rows_affected();
+        }
+        for (_, sql) in &triggers {
+            sqlx::query(sql).execute(&mut *tx).await?;
+        }
+        Ok::<_, Error>(converted)
+    }
+    .await;
+    crate::finish_tx(tx, result).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DbConfig;
+    use crate::model::project::Project;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::project_tree::{ProjectTree, SaveMode, TaskTree};
+    use crate::model::task_time::TaskTime;
+    use crate::money::Money;
+    use crate::DbiDatabase;
+    use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+    use uuid::Uuid;
+
+    // The last migration that still left TaskDateTime nullable
+    const BEFORE_TASK_DATE_TIME_REQUIRED: i64 = 20241024120000;
+    // Before tags, search, timers and invoices
+    const BEFORE_INVOICES: i64 = 20241020120000;
+    // Before Projects were linked to Clients
+    const BEFORE_PROJECT_CLIENTS: i64 = 20241018120000;
+    // The last migration that still stored keys as BLOBs
+    const BEFORE_TEXT_UUIDS: i64 = 20241025120000;
+    // The schema the loader had before mv_dbi kept its own migrations
+    const BASELINE: i64 = 20240811190310;
+
+    // One connection: the other connections to a memory database share
+    // its cache, and can be left waiting on locks forever once migrations
+    // change the schema under them
+    async fn memory_db() -> DbiDatabase {
+        DbiDatabase::new(DbConfig::new("sqlite::memory:").max_connections(1))
+            .await
+            .unwrap()
+    }
+
+    fn sample_tree() -> ProjectTree {
+        let project = Project {
+            project_id: Uuid::new_v4(),
+            project_name: "Migrations".to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 10, 25).unwrap(),
+            pay_rate: Money::from(60),
+            ..Default::default()
+        };
+        let task = ProjectTask {
+            task_id: Uuid::new_v4(),
+            project_id: project.project_id,
+            task_name: "Write the down scripts".to_string(),
+            task_date_time: project.project_date.and_hms_opt(9, 0, 0).unwrap(),
+            ..Default::default()
+        };
+        let task_time = TaskTime {
+            task_id: task.task_id,
+            start_time: task.task_date_time,
+            end_time: Some(task.task_date_time + TimeDelta::minutes(90)),
+            ..Default::default()
+        };
+        ProjectTree {
+            project,
+            tasks: vec![TaskTree {
+                task,
+                task_times: vec![task_time],
+                tags: Vec::new(),
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_database_is_current() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        let status = db.schema_status().await?;
+        assert_eq!(status.version, Some(latest_version(Backend::Sqlite)));
+        assert!(status.is_current());
+        assert!(status.migrations.iter().all(|m| m.reversible));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_migrate_down_and_up_again() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        let latest = latest_version(Backend::Sqlite);
+
+        db.migrate_to(0).await?;
+        let status = db.schema_status().await?;
+        assert_eq!(status.version, None);
+        assert_eq!(status.pending().count(), status.migrations.len());
+
+        db.migrate_to(latest).await?;
+        assert!(db.schema_status().await?.is_current());
+        let tree = sample_tree();
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+        assert!(db.check_integrity().await?.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rolled_back_schema_keeps_rows() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        let tree = sample_tree();
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+        // As if later TaskTimes had been deleted, whose ids stay used
+        let sequence_sql = "SELECT seq FROM sqlite_sequence WHERE name = 'TaskTimes'";
+        sqlx::query("UPDATE sqlite_sequence SET seq = 41 WHERE name = 'TaskTimes'")
+            .execute(db.pool.sqlite())
+            .await?;
+
+        db.migrate_to(BEFORE_INVOICES).await?;
+        let status = db.schema_status().await?;
+        assert_eq!(status.version, Some(BEFORE_INVOICES));
+        assert!(db.check_integrity().await?.is_ok());
+
+        // The tables rebuilt on the way down keep the rows referring to them
+        db.migrate_to(BEFORE_PROJECT_CLIENTS).await?;
+        let pool = db.pool.sqlite();
+        let times: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM TaskTimes")
+            .fetch_one(pool)
+            .await?;
+        assert_eq!(times, 1);
+        let sequence: i64 = sqlx::query_scalar(sequence_sql).fetch_one(pool).await?;
+        assert_eq!(sequence, 41);
+        assert!(db.check_integrity().await?.is_ok());
+
+        db.migrate_to(latest_version(Backend::Sqlite)).await?;
+        let projects = db.fetch_all::<Project>().await?;
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].pay_rate, Money::from(60));
+        let stored = db.fetch_project_tree(projects[0].clone()).await?;
+        assert_eq!(stored.tasks.len(), 1);
+        let (stored, saved) = (&stored.tasks[0].task_times, &tree.tasks[0].task_times);
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].start_time, saved[0].start_time);
+        assert_eq!(stored[0].end_time, saved[0].end_time);
+        assert!(db.check_integrity().await?.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_date_time_is_backfilled_and_required() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        db.migrate_to(BEFORE_TASK_DATE_TIME_REQUIRED).await?;
+        let tree = sample_tree();
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+        let task_id = tree.tasks[0].task.task_id;
+        assert_eq!(db.tag_task(task_id, &["schema"]).await?, 1);
+        let pool = db.pool.sqlite();
+        sqlx::query(r#"UPDATE ProjectTasks SET "TaskDateTime" = NULL"#)
+            .execute(pool)
+            .await?;
+
+        db.migrate_to(latest_version(Backend::Sqlite)).await?;
+        let pool = db.pool.sqlite();
+        let task_date_time: NaiveDateTime =
+            sqlx::query_scalar(r#"SELECT "TaskDateTime" FROM ProjectTasks"#)
+                .fetch_one(pool)
+                .await?;
+        assert_eq!(task_date_time, tree.tasks[0].task_times[0].start_time);
+        let cleared = sqlx::query(r#"UPDATE ProjectTasks SET "TaskDateTime" = NULL"#)
+            .execute(pool)
+            .await;
+        assert!(cleared.is_err());
+
+        // Rebuilding ProjectTasks either way keeps the rows that refer to it
+        db.migrate_to(BEFORE_TASK_DATE_TIME_REQUIRED).await?;
+        db.migrate_to(latest_version(Backend::Sqlite)).await?;
+        assert_eq!(db.task_tags(task_id).await?.len(), 1);
+        let stored = db.fetch_project_tree(tree.project.clone()).await?;
+        assert_eq!(stored.tasks[0].task_times.len(), 1);
+        let hits = db.search("down scripts").await?;
+        assert_eq!(hits[0].task_id, Some(task_id));
+        assert!(db.check_integrity().await?.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_task_date_time_stops_migration() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        db.migrate_to(BEFORE_TASK_DATE_TIME_REQUIRED).await?;
+        let tree = sample_tree();
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+        // A task left behind by its project, with no TaskTimes, has nothing
+        // to take a TaskDateTime from
+        let pool = db.pool.sqlite();
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(pool).await?;
+        sqlx::query(r#"UPDATE ProjectTasks SET "TaskDateTime" = NULL"#)
+            .execute(pool)
+            .await?;
+        sqlx::query("DELETE FROM TaskTimes").execute(pool).await?;
+        sqlx::query("DELETE FROM Projects").execute(pool).await?;
+
+        let result = db.migrate_to(latest_version(Backend::Sqlite)).await;
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("no TaskDateTime"), "{}", message);
+        let status = db.schema_status().await?;
+        assert_eq!(status.version, Some(BEFORE_TASK_DATE_TIME_REQUIRED));
+        let tasks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ProjectTasks")
+            .fetch_one(db.pool.sqlite())
+            .await?;
+        assert_eq!(tasks, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blob_keys_are_converted_to_text() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        let tree = sample_tree();
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+
+        db.migrate_to(BEFORE_TEXT_UUIDS).await?;
+        let pool = db.pool.sqlite();
+        let kinds: Vec<String> = sqlx::query_scalar(
+            "SELECT typeof(ProjectId) FROM Projects
+            UNION SELECT typeof(TaskId) FROM TaskTimes
+            UNION SELECT typeof(ProjectId) FROM SearchIndex",
+        )
+        .fetch_all(pool)
+        .await?;
+        assert_eq!(kinds, vec!["blob"]);
+
+        db.migrate_to(latest_version(Backend::Sqlite)).await?;
+        let pool = db.pool.sqlite();
+        let project_id: String = sqlx::query_scalar("SELECT ProjectId FROM Projects")
+            .fetch_one(pool)
+            .await?;
+        assert_eq!(project_id, tree.project.project_id.to_string());
+        let hits = db.search("down scripts").await?;
+        assert_eq!(hits[0].task_id, Some(tree.tasks[0].task.task_id));
+        let stored = db.fetch_project_tree(tree.project.clone()).await?;
+        assert_eq!(stored.tasks[0].task_times.len(), 1);
+        assert!(db.check_integrity().await?.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keys_follow_uuid_storage() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("mv_dbi_schema_{}.db3", Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        let tree = sample_tree();
+        let task_id = tree.tasks[0].task.task_id;
+        let kinds_sql = "SELECT typeof(ProjectId) FROM Projects
+            UNION SELECT typeof(TaskId) FROM TaskTimes
+            UNION SELECT typeof(TaskId) FROM TaskTags
+            UNION SELECT typeof(ProjectId) FROM SearchKeys";
+        let triggers_sql = "SELECT COUNT(*) FROM sqlite_master WHERE type = 'trigger'";
+
+        let blob = DbConfig::new(&url).uuid_storage(UuidStorage::Blob);
+        let mut db = DbiDatabase::new(blob).await?;
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+        assert_eq!(db.tag_task(task_id, &["schema"]).await?, 1);
+        let pool = db.pool.sqlite();
+        let kinds: Vec<String> = sqlx::query_scalar(kinds_sql).fetch_all(pool).await?;
+        assert_eq!(kinds, vec!["blob"]);
+        let triggers: i64 = sqlx::query_scalar(triggers_sql).fetch_one(pool).await?;
+        let project_id: String = sqlx::query_scalar("SELECT ProjectId FROM v_Projects")
+            .fetch_one(pool)
+            .await?;
+        assert_eq!(project_id, tree.project.project_id.to_string());
+
+        // Keys bound as BLOBs find the rows they were stored with
+        let mut changed = tree.clone();
+        changed.tasks[0].task.task_name = "Write the up scripts".to_string();
+        changed.tasks[0].task_times[0].task_time_id = 1;
+        changed.tasks[0].tags = vec!["schema".to_string()];
+        db.save_project_trees(std::slice::from_ref(&changed), SaveMode::Replace)
+            .await?;
+        let stored = db.fetch_project_tree(tree.project.clone()).await?;
+        assert_eq!(stored, changed);
+        let hits = db.search("up scripts").await?;
+        assert_eq!(hits[0].task_id, Some(task_id));
+        db.pool.sqlite().close().await;
+
+        // Bound as text they would match nothing, and a read-only database
+        // cannot be converted
+        let result = DbiDatabase::new(DbConfig::new(&url).read_only(true)).await;
+        assert!(matches!(result, Err(Error::Configuration(_))));
+
+        let mut db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        let pool = db.pool.sqlite();
+        let kinds: Vec<String> = sqlx::query_scalar(kinds_sql).fetch_all(pool).await?;
+        assert_eq!(kinds, vec!["text"]);
+        let after: i64 = sqlx::query_scalar(triggers_sql).fetch_one(pool).await?;
+        assert_eq!(after, triggers);
+        assert_eq!(db.task_tags(task_id).await?.len(), 1);
+        assert_eq!(db.fetch_project_tree(tree.project.clone()).await?, changed);
+        assert!(db.check_integrity().await?.is_ok());
+        db.pool.sqlite().close().await;
+
+        let blob = DbConfig::new(&url).uuid_storage(UuidStorage::Blob);
+        let mut db = DbiDatabase::new(blob).await?;
+        let kinds: Vec<String> = sqlx::query_scalar(kinds_sql)
+            .fetch_all(db.pool.sqlite())
+            .await?;
+        assert_eq!(kinds, vec!["blob"]);
+        assert_eq!(db.task_tags(task_id).await?.len(), 1);
+        assert!(db.check_integrity().await?.is_ok());
+        db.pool.sqlite().close().await;
+        std::fs::remove_file(&path).ok();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_views() -> Result<(), Error> {
+        let mut db = memory_db().await;
+        let mut tree = sample_tree();
+        tree.project.project_duration = 90 * 60 * 1000;
+        tree.tasks[0].task.task_duration = 90 * 60 * 1000;
+        db.save_project_trees(std::slice::from_ref(&tree), SaveMode::Insert)
+            .await?;
+        let pool = db.pool.sqlite();
+
+        let (project_id, duration): (String, String) =
+            sqlx::query_as("SELECT ProjectId, Duration FROM v_Projects")
+                .fetch_one(pool)
+                .await?;
+        assert_eq!(project_id, tree.project.project_id.to_string());
+        assert_eq!(duration, "01:30:00");
+        let (project_name, duration): (String, String) =
+            sqlx::query_as("SELECT ProjectName, Duration FROM v_ProjectTasks")
+                .fetch_one(pool)
+                .await?;
+        assert_eq!(project_name, "Migrations");
+        assert_eq!(duration, "01:30:00");
+        let (task_name, duration): (String, Option<String>) =
+            sqlx::query_as("SELECT TaskName, Duration FROM v_TaskTimes")
+                .fetch_one(pool)
+                .await?;
+        assert_eq!(task_name, "Write the down scripts");
+        assert_eq!(duration.as_deref(), Some("01:30:00"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_baseline_database_opens_without_migrating() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("mv_dbi_schema_{}.db3", Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        let mut db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        db.migrate_to(BASELINE).await?;
+        db.pool.sqlite().close().await;
+
+        for config in [
+            DbConfig::new(&url).skip_migrations(true),
+            DbConfig::new(&url).read_only(true),
+        ] {
+            let mut db = DbiDatabase::new(config).await?;
+            let status = db.schema_status().await?;
+            assert_eq!(status.version, Some(BASELINE));
+            assert!(status.pending().any(|m| m.version > BASELINE));
+            db.pool.sqlite().close().await;
+        }
+
+        let mut db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        assert!(db.schema_status().await?.is_current());
+        db.pool.sqlite().close().await;
+        std::fs::remove_file(&path).ok();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_target_is_refused() {
+        let mut db = memory_db().await;
+        let result = db.migrate_to(20240101000000).await;
+        assert!(matches!(result, Err(Error::Configuration(_))));
+    }
+
+    #[tokio::test]
+    async fn test_newer_database_is_refused() -> Result<(), Error> {
+        let path = std::env::temp_dir().join(format!("mv_dbi_schema_{}.db3", Uuid::new_v4()));
+        let url = format!("sqlite://{}", path.display());
+        let db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        let newer = latest_version(Backend::Sqlite) + 1;
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+            VALUES ($1, 'From the future', TRUE, x'00', 0)",
+        )
+        .bind(newer)
+        .execute(db.pool.sqlite())
+        .await?;
+        db.pool.sqlite().close().await;
+
+        for config in [
+            DbConfig::new(&url),
+            DbConfig::new(&url).read_only(true),
+            DbConfig::new(&url).skip_migrations(true),
+        ] {
+            let result = DbiDatabase::new(config).await;
+            assert!(matches!(
+                result,
+                Err(Error::SchemaTooNew { version, .. }) if version == newer
+            ));
+        }
+        std::fs::remove_file(&path).ok();
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a PostgreSQL server in TEST_POSTGRES_URL"]
+    async fn test_postgres_migrate_down_and_up_again() -> Result<(), Error> {
+        let url = crate::test_postgres_url();
+        let mut db = DbiDatabase::new(DbConfig::new(&url)).await?;
+        let latest = latest_version(Backend::Postgres);
+        db.migrate_to(BEFORE_TASK_DATE_TIME_REQUIRED).await?;
+        assert_eq!(
+            db.schema_status().await?.version,
+            Some(BEFORE_TASK_DATE_TIME_REQUIRED)
+        );
+        db.migrate_to(latest).await?;
+        assert!(db.schema_status().await?.is_current());
+        Ok(())
+    }
+}
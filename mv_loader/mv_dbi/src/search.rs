@@ -0,0 +1,229 @@
+// search.rs
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Postgres, Sqlite};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::guid::{Guid, OptionalGuid};
+
+///
+/// A Project or ProjectTask matching a `DbiDatabase::search` query. A
+/// task's hit has its task_id and task_name; a project's has None in
+/// both. The snippet shows the matched words in [brackets], and hits
+/// with a higher rank match better.
+///
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct SearchHit {
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub project_name: String,
+    #[sqlx(try_from = "OptionalGuid")]
+    pub task_id: Option<Uuid>,
+    pub task_name: Option<String>,
+    pub snippet: String,
+    pub rank: f64,
+}
+
+impl SearchHit {
+    /// True when the hit is a ProjectTask rather than a Project
+    pub fn is_task(&self) -> bool {
+        self.task_id.is_some()
+    }
+}
+
+///
+/// How each backend searches the SearchIndex. SQLite uses FTS5 and its
+/// query syntax; PostgreSQL uses `websearch_to_tsquery`. Plain words,
+/// "quoted phrases" and OR mean the same on both.
+///
+pub(crate) trait SearchDialect {
+    const SEARCH_SQL: &'static str;
+}
+
+impl SearchDialect for Sqlite {
+    // bm25 is lower for better matches
+    const SEARCH_SQL: &'static str = r#"SELECT p."ProjectId", p."ProjectName",
+        t."TaskId", t."TaskName",
+        snippet(SearchIndex, -1, '[', ']', '...', 12) AS "Snippet",
+        -bm25(SearchIndex) AS "Rank"
+        FROM SearchIndex
+        JOIN Projects p ON p."ProjectId" = SearchIndex."ProjectId"
+        LEFT JOIN ProjectTasks t ON t."TaskId" = SearchIndex."TaskId"
+        WHERE SearchIndex MATCH $1
+        ORDER BY "Rank" DESC, p."ProjectDate" DESC, t."TaskDateTime""#;
+}
+
+impl SearchDialect for Postgres {
+    const SEARCH_SQL: &'static str = r#"SELECT p."ProjectId", p."ProjectName",
+        t."TaskId", t."TaskName",
+        ts_headline('english', s."Name" || ' ' || COALESCE(s."Notes", ''), q,
+            'StartSel=[, StopSel=], MinWords=4, MaxWords=12') AS "Snippet",
+        CAST(ts_rank(s."Document", q) AS DOUBLE PRECISION) AS "Rank"
+        FROM SearchIndex s
+        CROSS JOIN websearch_to_tsquery('english', $1) q
+        JOIN Projects p ON p."ProjectId" = s."ProjectId"
+        LEFT JOIN ProjectTasks t ON t."TaskId" = s."TaskId"
+        WHERE s."Document" @@ q
+        ORDER BY "Rank" DESC, p."ProjectDate" DESC, t."TaskDateTime""#;
+}
+
+// SQLITE_ERROR, which FTS5 fails with when it cannot parse the query
+const BAD_QUERY_CODE: &str = "1";
+
+///
+/// Sort a failed search into an Error. FTS5 reports a query it cannot
+/// parse as an ordinary error, so that is made a Validation error.
+/// PostgreSQL's `websearch_to_tsquery` accepts any text.
+///
+pub(crate) fn search_error(error: sqlx::Error, query: &str) -> Error {
+    match &error {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(BAD_QUERY_CODE) => {
+            Error::Validation {
+                entity: "SearchHit",
+                id: query.to_string(),
+                message: format!("'{}' is not a valid search: {}", query, e.message()),
+            }
+        }
+        _ => Error::classify(error, "SearchHit", query.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::model::project_task::ProjectTask;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Project};
+
+    async fn add_project(
+        db: &mut DbiDatabase,
+        name: &str,
+        day: u32,
+        tasks: &[(&str, Option<&str>)],
+    ) -> Result<Project, Error> {
+        let project = Project {
+            project_id: make_uuid(&name.to_string()),
+            project_name: name.to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 8, day).unwrap(),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        for (task_name, notes) in tasks {
+            let task = ProjectTask {
+                task_id: make_uuid(&format!("{}{}", name, task_name)),
+                project_id: project.project_id,
+                task_name: task_name.to_string(),
+                notes: notes.map(str::to_string),
+                ..Default::default()
+            };
+            db.insert(&task).await?;
+        }
+        Ok(project)
+    }
+
+    async fn setup() -> Result<DbiDatabase, Error> {
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        add_project(
+            &mut db,
+            "Diamond",
+            1,
+            &[
+                ("Task 01", Some("Collated the Ruby files for the report")),
+                ("Task 02", None),
+            ],
+        )
+        .await?;
+        add_project(
+            &mut db,
+            "Ruby",
+            2,
+            &[("Cutting", Some("Polished the edges"))],
+        )
+        .await?;
+        Ok(db)
+    }
+
+    #[tokio::test]
+    async fn test_search() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let hits = db.search("collate ruby").await?;
+        assert_eq!(hits.len(), 1);
+        let hit = &hits[0];
+        assert!(hit.is_task());
+        assert_eq!(hit.project_name, "Diamond");
+        assert_eq!(hit.task_name.as_deref(), Some("Task 01"));
+        assert_eq!(hit.task_id, Some(make_uuid(&"DiamondTask 01".to_string())));
+        assert!(hit.snippet.contains("[Collated]"), "{}", hit.snippet);
+        assert!(hit.snippet.contains("[Ruby]"), "{}", hit.snippet);
+
+        // The project named Ruby matches better than a task that mentions it
+        let hits = db.search("ruby").await?;
+        assert_eq!(hits.len(), 2);
+        assert!(!hits[0].is_task());
+        assert_eq!(hits[0].project_name, "Ruby");
+        assert!(hits[0].rank > hits[1].rank);
+
+        let hits = db.search("polish OR \"task 02\"").await?;
+        let mut names: Vec<&str> = hits.iter().filter_map(|h| h.task_name.as_deref()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Cutting", "Task 02"]);
+        assert!(db.search("  ").await?.is_empty());
+        assert!(db.search("emerald").await?.is_empty());
+
+        let result = db.search("\"ruby").await;
+        assert!(matches!(
+            result,
+            Err(Error::Validation {
+                entity: "SearchHit",
+                ..
+            })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_follows_changes() -> Result<(), Error> {
+        let mut db = setup().await?;
+        let mut task = ProjectTask {
+            task_id: make_uuid(&"RubyCutting".to_string()),
+            ..Default::default()
+        };
+        db.fetch_one(&mut task).await?;
+        task.task_name = "Setting".to_string();
+        task.notes = None;
+        db.update(&task).await?;
+        assert!(db.search("polished").await?.is_empty());
+        assert_eq!(db.search("setting").await?.len(), 1);
+
+        let mut project = Project {
+            project_id: make_uuid(&"Diamond".to_string()),
+            ..Default::default()
+        };
+        db.fetch_one(&mut project).await?;
+        project.project_name = "Sapphire".to_string();
+        db.update(&project).await?;
+        assert!(db.search("diamond").await?.is_empty());
+        assert_eq!(
+            db.search("sapphire").await?[0].project_id,
+            project.project_id
+        );
+
+        // Deleting the project takes its tasks out of the index too
+        db.delete(&project).await?;
+        assert!(db.search("sapphire OR collated").await?.is_empty());
+        assert_eq!(db.search("ruby").await?.len(), 1);
+
+        // The triggers find index rows through SearchKeys, which keeps one
+        // key for each row
+        let counts: (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM SearchKeys), (SELECT COUNT(*) FROM SearchIndex)",
+        )
+        .fetch_one(db.pool.sqlite())
+        .await?;
+        assert_eq!(counts, (2, 2));
+        Ok(())
+    }
+}
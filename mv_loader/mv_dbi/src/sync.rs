@@ -0,0 +1,738 @@
+// sync.rs
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::export::InvoiceTree;
+use crate::model::client::Client;
+use crate::model::project::Project;
+use crate::model::project_tree::{ProjectTree, TaskTree};
+use crate::model::task_time::TaskTime;
+
+///
+/// What `DbiDatabase::merge_from` does with a row that both databases
+/// store, but differently.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the row in the database being merged into
+    #[default]
+    Ours,
+    /// Take the row from the database being merged from
+    Theirs,
+    /// Write nothing at all if any row conflicts
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Ours => "ours",
+            ConflictPolicy::Theirs => "theirs",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ours" => Ok(ConflictPolicy::Ours),
+            "theirs" => Ok(ConflictPolicy::Theirs),
+            "fail" => Ok(ConflictPolicy::Fail),
+            _ => Err(Error::Configuration(format!(
+                "Unknown conflict policy '{}', expected ours, theirs or fail",
+                s
+            ))),
+        }
+    }
+}
+
+/// How a row in the other database compares with this one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    /// Only the other database has it
+    New,
+    /// Both databases store it the same
+    Identical,
+    /// Both databases store it, differently
+    Conflict,
+}
+
+/// What the merge did with a row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    /// Copied into this database
+    Added,
+    /// Already here as it is there
+    Unchanged,
+    /// This database's row was kept
+    Kept,
+    /// This database's row was replaced by the other's
+    Replaced,
+}
+
+///
+/// One row of the other database and what the merge made of it. `id` is
+/// the row's key; TaskTimes use `TaskTime::stable_id`. `reason` says how
+/// a conflicting row differs, and why it was kept when the policy could
+/// not be followed.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncItem {
+    pub entity: &'static str,
+    pub id: String,
+    pub name: String,
+    pub status: SyncStatus,
+    pub action: SyncAction,
+    pub reason: Option<String>,
+}
+
+///
+/// Everything `DbiDatabase::merge_from` compared. `applied` is false when
+/// nothing was written, because the policy is Fail and rows conflict.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+    pub policy: ConflictPolicy,
+    pub applied: bool,
+    pub items: Vec<SyncItem>,
+}
+
+impl MergeReport {
+    pub(crate) fn new(policy: ConflictPolicy) -> Self {
+        Self {
+            policy,
+            applied: false,
+            items: Vec::new(),
+        }
+    }
+
+    /// How many rows have the status
+    pub fn count(&self, status: SyncStatus) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.status == status)
+            .count()
+    }
+
+    /// The rows stored differently in the two databases
+    pub fn conflicts(&self) -> impl Iterator<Item = &SyncItem> {
+        self.items
+            .iter()
+            .filter(|item| item.status == SyncStatus::Conflict)
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts().next().is_some()
+    }
+
+    /// How many rows were added or replaced
+    pub fn written(&self) -> usize {
+        if !self.applied {
+            return 0;
+        }
+        self.items
+            .iter()
+            .filter(|item| matches!(item.action, SyncAction::Added | SyncAction::Replaced))
+            .count()
+    }
+}
+
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} new, {} identical, {} conflicting (policy {})",
+            self.count(SyncStatus::New),
+            self.count(SyncStatus::Identical),
+            self.count(SyncStatus::Conflict),
+            self.policy.as_str()
+        )?;
+        if !self.applied {
+            writeln!(f, "Nothing was written")?;
+        }
+        for item in self
+            .items
+            .iter()
+            .filter(|i| i.status != SyncStatus::Identical)
+        {
+            let action = match item.action {
+                SyncAction::Added => "added",
+                SyncAction::Unchanged => "unchanged",
+                SyncAction::Kept => "kept",
+                SyncAction::Replaced => "replaced",
+            };
+            write!(
+                f,
+                "  {:<9} {} {} ({})",
+                action, item.entity, item.name, item.id
+            )?;
+            match &item.reason {
+                Some(reason) => writeln!(f, ": {}", reason)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// The two merges `DbiDatabase::sync_with` makes: the other database's
+/// rows into this one, then the result back into the other.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    pub pulled: MergeReport,
+    pub pushed: MergeReport,
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pulled: {}Pushed: {}", self.pulled, self.pushed)
+    }
+}
+
+///
+/// Every row of a database a merge compares.
+///
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Snapshot {
+    pub clients: Vec<Client>,
+    pub invoices: Vec<InvoiceTree>,
+    pub projects: Vec<ProjectTree>,
+}
+
+///
+/// The writes that bring one database's rows into another, with the
+/// report of how each row compared.
+///
+pub(crate) struct MergePlan {
+    pub report: MergeReport,
+    /// Upserted
+    pub clients: Vec<Client>,
+    /// Inserted with their lines
+    pub invoices: Vec<InvoiceTree>,
+    /// Upserted
+    pub projects: Vec<Project>,
+    /// Upserted with their tags replaced; the task_times are empty
+    pub tasks: Vec<TaskTree>,
+    /// Inserted, each with the index of its item in the report, so an
+    /// overlap found while inserting can be reported
+    pub new_times: Vec<(usize, TaskTime)>,
+    /// Changed to match the other database, keeping the TaskTimeId here
+    pub changed_times: Vec<TaskTime>,
+}
+
+///
+/// Compare the rows of `theirs` with `ours` and decide what to write.
+///
+/// Rows are matched on their keys, which `make_uuid` makes the same in
+/// every database, and TaskTimes on `TaskTime::stable_id`. ProjectDuration,
+/// TotalPay and TaskDuration are left out of the comparison, since they
+/// follow from the TaskTimes and are reconciled after the merge. A row
+/// deleted from one database is new to it again in the next merge.
+///
+pub(crate) fn plan(ours: &Snapshot, theirs: &Snapshot, policy: ConflictPolicy) -> MergePlan {
+    let mut plan = MergePlan {
+        report: MergeReport::new(policy),
+        clients: Vec::new(),
+        invoices: Vec::new(),
+        projects: Vec::new(),
+        tasks: Vec::new(),
+        new_times: Vec::new(),
+        changed_times: Vec::new(),
+    };
+
+    let our_clients: HashMap<Uuid, &Client> =
+        ours.clients.iter().map(|c| (c.client_id, c)).collect();
+    for client in &theirs.clients {
+        let differs = our_clients.get(&client.client_id).map(|our| {
+            differing(&[
+                ("ClientName", our.client_name != client.client_name),
+                ("PayRate", our.pay_rate != client.pay_rate),
+            ])
+        });
+        if plan.compare("Client", client.client_id, &client.client_name, differs) {
+            plan.clients.push(client.clone());
+        }
+    }
+
+    // Invoices never change, so one that differs is always kept
+    let our_invoices: HashMap<Uuid, &InvoiceTree> = ours
+        .invoices
+        .iter()
+        .map(|i| (i.invoice.invoice_id, i))
+        .collect();
+    let our_numbers: HashSet<i64> = ours
+        .invoices
+        .iter()
+        .map(|i| i.invoice.invoice_number)
+        .collect();
+    let mut invoice_ids: HashSet<Uuid> = our_invoices.keys().copied().collect();
+    for tree in &theirs.invoices {
+        let invoice = &tree.invoice;
+        let name = format!("#{}", invoice.invoice_number);
+        let (status, reason) = match our_invoices.get(&invoice.invoice_id) {
+            Some(our) if *our == tree => (SyncStatus::Identical, None),
+            Some(_) => (
+                SyncStatus::Conflict,
+                Some("an invoice cannot be changed".to_string()),
+            ),
+            None if our_numbers.contains(&invoice.invoice_number) => (
+                SyncStatus::Conflict,
+                Some(format!(
+                    "invoice number {} is already used",
+                    invoice.invoice_number
+                )),
+            ),
+            None => (SyncStatus::New, None),
+        };
+        let action = match status {
+            SyncStatus::New => SyncAction::Added,
+            SyncStatus::Identical => SyncAction::Unchanged,
+            SyncStatus::Conflict => SyncAction::Kept,
+        };
+        plan.push("Invoice", invoice.invoice_id, name, status, action, reason);
+        if status == SyncStatus::New {
+            invoice_ids.insert(invoice.invoice_id);
+            plan.invoices.push(tree.clone());
+        }
+    }
+
+    let our_projects: HashMap<Uuid, &ProjectTree> = ours
+        .projects
+        .iter()
+        .map(|t| (t.project.project_id, t))
+        .collect();
+    for tree in &theirs.projects {
+        let project = &tree.project;
+        let our_tree = our_projects.get(&project.project_id);
+        let differs = our_tree.map(|our| {
+            let our = &our.project;
+            differing(&[
+                ("ProjectName", our.project_name != project.project_name),
+                ("ProjectDate", our.project_date != project.project_date),
+                ("PayRate", our.pay_rate != project.pay_rate),
+                ("Currency", our.currency != project.currency),
+                ("ClientId", our.client_id != project.client_id),
+            ])
+        });
+        let name = format!("{} {}", project.project_name, project.project_date);
+        if plan.compare("Project", project.project_id, &name, differs) {
+            plan.projects.push(project.clone());
+        }
+
+        let our_tasks: HashMap<Uuid, &TaskTree> = our_tree
+            .map(|our| our.tasks.iter().map(|t| (t.task.task_id, t)).collect())
+            .unwrap_or_default();
+        for task_tree in &tree.tasks {
+            let task = &task_tree.task;
+            let our_task = our_tasks.get(&task.task_id);
+            let differs = our_task.map(|our| {
+                differing(&[
+                    ("TaskName", our.task.task_name != task.task_name),
+                    (
+                        "TaskDateTime",
+                        our.task.task_date_time != task.task_date_time,
+                    ),
+                    ("Notes", our.task.notes != task.notes),
+                    ("Tags", sorted(&our.tags) != sorted(&task_tree.tags)),
+                ])
+            });
+            if plan.compare("ProjectTask", task.task_id, &task.task_name, differs) {
+                plan.tasks.push(TaskTree {
+                    task: task.clone(),
+                    task_times: Vec::new(),
+                    tags: task_tree.tags.clone(),
+                });
+            }
+
+            let our_times: HashMap<Uuid, &TaskTime> = our_task
+                .map(|our| our.task_times.iter().map(|t| (t.stable_id(), t)).collect())
+                .unwrap_or_default();
+            for task_time in &task_tree.task_times {
+                let name = format!("{} {}", task.task_name, task_time.start_time);
+                let our_time = our_times.get(&task_time.stable_id()).copied();
+                plan.compare_time(our_time, task_time, name, &invoice_ids);
+            }
+        }
+    }
+    plan
+}
+
+impl MergePlan {
+    fn push(
+        &mut self,
+        entity: &'static str,
+        id: impl fmt::Display,
+        name: String,
+        status: SyncStatus,
+        action: SyncAction,
+        reason: Option<String>,
+    ) -> usize {
+        self.report.items.push(SyncItem {
+            entity,
+            id: id.to_string(),
+            name,
+            status,
+            action,
+            reason,
+        });
+        self.report.items.len() - 1
+    }
+
+    // Record a row compared on its fields: `differs` is None when only
+    // they have it, and names the differing fields when both do. Returns
+    // true when their row is to be written.
+    fn compare(
+        &mut self,
+        entity: &'static str,
+        id: Uuid,
+        name: &str,
+        differs: Option<Option<String>>,
+    ) -> bool {
+        let replace = self.report.policy == ConflictPolicy::Theirs;
+        let (status, action, reason) = match differs {
+            None => (SyncStatus::New, SyncAction::Added, None),
+            Some(None) => (SyncStatus::Identical, SyncAction::Unchanged, None),
+            Some(reason) if replace => (SyncStatus::Conflict, SyncAction::Replaced, reason),
+            Some(reason) => (SyncStatus::Conflict, SyncAction::Kept, reason),
+        };
+        self.push(entity, id, name.to_string(), status, action, reason);
+        matches!(action, SyncAction::Added | SyncAction::Replaced)
+    }
+
+    fn compare_time(
+        &mut self,
+        ours: Option<&TaskTime>,
+        theirs: &TaskTime,
+        name: String,
+        invoice_ids: &HashSet<Uuid>,
+    ) {
+        let id = theirs.stable_id();
+        let differs = ours.and_then(|our| {
+            differing(&[
+                ("EndTime", our.end_time != theirs.end_time),
+                ("InvoiceId", our.invoice_id != theirs.invoice_id),
+            ])
+        });
+        if ours.is_some() && differs.is_none() {
+            self.push(
+                "TaskTime",
+                id,
+                name,
+                SyncStatus::Identical,
+                SyncAction::Unchanged,
+                None,
+            );
+            return;
+        }
+        let status = match ours {
+            Some(_) => SyncStatus::Conflict,
+            None => SyncStatus::New,
+        };
+        // Reasons their row cannot be written whatever the policy
+        let blocked = if theirs.end_time.is_none() {
+            Some("its timer is still running".to_string())
+        } else if let Some(invoice_id) = theirs.invoice_id.filter(|i| !invoice_ids.contains(i)) {
+            Some(format!("invoice {} was not copied", invoice_id))
+        } else if ours.is_some_and(|our| our.invoice_id.is_some()) {
+            Some("it has been invoiced here".to_string())
+        } else {
+            None
+        };
+        let writable = blocked.is_none();
+        let reason = match (differs, blocked) {
+            (Some(differs), Some(blocked)) => Some(format!("{}, and {}", differs, blocked)),
+            (differs, blocked) => differs.or(blocked),
+        };
+        let index = self.report.items.len();
+        let action = match ours {
+            None if writable => {
+                self.new_times.push((
+                    index,
+                    TaskTime {
+                        task_time_id: 0,
+                        ..theirs.clone()
+                    },
+                ));
+                SyncAction::Added
+            }
+            Some(our) if writable && self.report.policy == ConflictPolicy::Theirs => {
+                self.changed_times.push(TaskTime {
+                    task_time_id: our.task_time_id,
+                    ..theirs.clone()
+                });
+                SyncAction::Replaced
+            }
+            _ => SyncAction::Kept,
+        };
+        // A new row that cannot be copied is reported as a conflict
+        let status = if action == SyncAction::Kept {
+            SyncStatus::Conflict
+        } else {
+            status
+        };
+        self.push("TaskTime", id, name, status, action, reason);
+    }
+}
+
+// "ProjectName, PayRate differ", or None when every field is the same
+fn differing(fields: &[(&str, bool)]) -> Option<String> {
+    let names: Vec<&str> = fields
+        .iter()
+        .filter(|(_, differs)| *differs)
+        .map(|(name, _)| *name)
+        .collect();
+    match names.len() {
+        0 => None,
+        1 => Some(format!("{} differs", names[0])),
+        _ => Some(format!("{} differ", names.join(", "))),
+    }
+}
+
+fn sorted(tags: &[String]) -> Vec<&String> {
+    let mut tags: Vec<&String> = tags.iter().collect();
+    tags.sort();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::*;
+    use crate::model::project_task::ProjectTask;
+    use crate::model::project_tree::SaveMode;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Money};
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 10, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    // A project with one task, "Collate", and a time for each (start, end)
+    fn make_tree(name: &str, times: &[(NaiveDateTime, NaiveDateTime)]) -> ProjectTree {
+        let project = Project {
+            project_id: make_uuid(&name.to_string()),
+            project_name: name.to_string(),
+            project_date: NaiveDate::from_ymd_opt(2024, 10, 1).unwrap(),
+            pay_rate: Money::from(60),
+            ..Default::default()
+        };
+        let task = ProjectTask {
+            task_id: make_uuid(&format!("{}Collate", name)),
+            project_id: project.project_id,
+            task_name: "Collate".to_string(),
+            task_date_time: at(9, 0),
+            ..Default::default()
+        };
+        let task_times = times
+            .iter()
+            .map(|(start, end)| TaskTime {
+                task_id: task.task_id,
+                start_time: *start,
+                end_time: Some(*end),
+                ..Default::default()
+            })
+            .collect();
+        ProjectTree {
+            project,
+            tasks: vec![TaskTree {
+                task,
+                task_times,
+                tags: vec!["print".to_string()],
+            }],
+        }
+    }
+
+    async fn setup(trees: &[ProjectTree]) -> Result<DbiDatabase, Error> {
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        db.save_project_trees(trees, SaveMode::Insert).await?;
+        db.reconcile().await?;
+        Ok(db)
+    }
+
+    // Each time's stable id and end, which match across databases
+    async fn times(db: &mut DbiDatabase) -> Result<Vec<(Uuid, Option<NaiveDateTime>)>, Error> {
+        let mut times: Vec<_> = db
+            .fetch_all::<TaskTime>()
+            .await?
+            .iter()
+            .map(|t| (t.stable_id(), t.end_time))
+            .collect();
+        times.sort();
+        Ok(times)
+    }
+
+    #[test]
+    fn test_conflict_policy_from_str() {
+        assert_eq!(
+            "Theirs".parse::<ConflictPolicy>().unwrap(),
+            ConflictPolicy::Theirs
+        );
+        assert_eq!(
+            "fail".parse::<ConflictPolicy>().unwrap(),
+            ConflictPolicy::Fail
+        );
+        assert!(matches!(
+            "mine".parse::<ConflictPolicy>(),
+            Err(Error::Configuration(_))
+        ));
+    }
+
+    #[test]
+    fn test_stable_id() {
+        let tree = make_tree("Diamond", &[(at(9, 0), at(10, 0))]);
+        let mut task_time = tree.tasks[0].task_times[0].clone();
+        let id = task_time.stable_id();
+        task_time.task_time_id = 42;
+        task_time.end_time = Some(at(11, 0));
+        assert_eq!(task_time.stable_id(), id);
+        task_time.start_time = at(9, 1);
+        assert_ne!(task_time.stable_id(), id);
+    }
+
+    #[tokio::test]
+    async fn test_merge_copies_new_rows() -> Result<(), Error> {
+        let mut laptop = setup(&[make_tree("Diamond", &[(at(9, 0), at(10, 0))])]).await?;
+        let mut desktop = setup(&[
+            make_tree("Diamond", &[(at(9, 0), at(10, 0)), (at(11, 0), at(11, 30))]),
+            make_tree("Ruby", &[(at(13, 0), at(14, 0))]),
+        ])
+        .await?;
+
+        let report = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Ours)
+            .await?;
+        assert!(report.applied);
+        assert!(!report.has_conflicts());
+        // Ruby, its task and time, and Diamond's second time
+        assert_eq!(report.count(SyncStatus::New), 4);
+        assert_eq!(report.count(SyncStatus::Identical), 3);
+        assert_eq!(times(&mut laptop).await?, times(&mut desktop).await?);
+
+        // The totals follow the copied times
+        let diamond = laptop
+            .fetch_all::<Project>()
+            .await?
+            .into_iter()
+            .find(|p| p.project_name == "Diamond")
+            .unwrap();
+        assert_eq!(diamond.project_duration, 90 * 60 * 1000);
+        assert_eq!(diamond.total_pay, Money::from(90));
+
+        let again = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Ours)
+            .await?;
+        assert_eq!(again.count(SyncStatus::Identical), again.items.len());
+        assert_eq!(again.written(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_conflicts() -> Result<(), Error> {
+        let ours = make_tree("Diamond", &[(at(9, 0), at(10, 0))]);
+        let mut theirs = make_tree("Diamond", &[(at(9, 0), at(10, 15))]);
+        theirs.tasks[0].task.notes = Some("Both sides".to_string());
+
+        let mut laptop = setup(&[ours]).await?;
+        let mut desktop = setup(&[theirs]).await?;
+
+        let report = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Fail)
+            .await?;
+        assert!(!report.applied);
+        assert_eq!(report.count(SyncStatus::Conflict), 2);
+        let reasons: Vec<_> = report
+            .conflicts()
+            .map(|c| c.reason.clone().unwrap())
+            .collect();
+        assert_eq!(reasons, vec!["Notes differs", "EndTime differs"]);
+
+        let report = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Ours)
+            .await?;
+        assert!(report.conflicts().all(|c| c.action == SyncAction::Kept));
+        assert_eq!(times(&mut laptop).await?[0].1, Some(at(10, 0)));
+
+        let report = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Theirs)
+            .await?;
+        assert!(report.conflicts().all(|c| c.action == SyncAction::Replaced));
+        assert_eq!(times(&mut laptop).await?, times(&mut desktop).await?);
+        let tasks = laptop.fetch_all::<ProjectTask>().await?;
+        assert_eq!(tasks[0].notes.as_deref(), Some("Both sides"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_overlap_is_conflict() -> Result<(), Error> {
+        let mut laptop = setup(&[make_tree("Diamond", &[(at(9, 0), at(10, 0))])]).await?;
+        let mut desktop = setup(&[make_tree("Diamond", &[(at(9, 30), at(10, 30))])]).await?;
+
+        let report = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Theirs)
+            .await?;
+        assert!(report.applied);
+        let conflict = report.conflicts().next().unwrap();
+        assert_eq!(conflict.entity, "TaskTime");
+        assert_eq!(conflict.action, SyncAction::Kept);
+        assert!(conflict.reason.as_deref().unwrap().starts_with("overlaps"));
+        assert_eq!(times(&mut laptop).await?.len(), 1);
+
+        let report = laptop
+            .merge_from(&mut desktop, ConflictPolicy::Fail)
+            .await?;
+        assert!(!report.applied);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_with() -> Result<(), Error> {
+        let mut ours = make_tree("Diamond", &[(at(9, 0), at(10, 0))]);
+        ours.tasks[0].tags.push("rush".to_string());
+        let mut laptop = setup(&[ours, make_tree("Ruby", &[(at(13, 0), at(14, 0))])]).await?;
+        let mut desktop = setup(&[make_tree(
+            "Diamond",
+            &[(at(9, 0), at(10, 0)), (at(11, 0), at(12, 0))],
+        )])
+        .await?;
+
+        let report = laptop.sync_with(&mut desktop, ConflictPolicy::Ours).await?;
+        assert!(report.pulled.applied && report.pushed.applied);
+        assert_eq!(report.pulled.count(SyncStatus::Conflict), 1);
+        assert_eq!(report.pushed.count(SyncStatus::Conflict), 1);
+
+        assert_eq!(times(&mut laptop).await?, times(&mut desktop).await?);
+        assert_eq!(
+            laptop.fetch_all::<Project>().await?,
+            desktop.fetch_all::<Project>().await?
+        );
+        let task_id = make_uuid(&"DiamondCollate".to_string());
+        let tags: Vec<_> = desktop
+            .task_tags(task_id)
+            .await?
+            .into_iter()
+            .map(|t| t.tag_name)
+            .collect();
+        assert_eq!(tags, vec!["print", "rush"]);
+
+        let again = laptop.sync_with(&mut desktop, ConflictPolicy::Ours).await?;
+        assert_eq!(again.pulled.written() + again.pushed.written(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+// timer.rs
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::guid::Guid;
+
+///
+/// The TaskTime whose timer is running, with the task and project it is
+/// for. At most one timer runs at a time.
+///
+#[derive(Debug, Clone, PartialEq, FromRow, Deserialize, Serialize)]
+#[sqlx(rename_all = "PascalCase")]
+pub struct RunningTimer {
+    pub task_time_id: i64,
+    #[sqlx(try_from = "Guid")]
+    pub project_id: Uuid,
+    pub project_name: String,
+    #[sqlx(try_from = "Guid")]
+    pub task_id: Uuid,
+    pub task_name: String,
+    pub start_time: NaiveDateTime,
+}
+
+impl RunningTimer {
+    /// How long the timer has run at `now`
+    pub fn elapsed(&self, now: NaiveDateTime) -> TimeDelta {
+        now - self.start_time
+    }
+}
+
+pub(crate) const RUNNING_SQL: &str = r#"SELECT tt."TaskTimeId", p."ProjectId", p."ProjectName",
+    t."TaskId", t."TaskName", tt."StartTime"
+    FROM TaskTimes tt
+    JOIN ProjectTasks t ON t."TaskId" = tt."TaskId"
+    JOIN Projects p ON p."ProjectId" = t."ProjectId"
+    WHERE tt."EndTime" IS NULL"#;
+
+// The project's latest task with the name, which a timer carries on
+pub(crate) const TASK_SQL: &str = r#"SELECT * FROM ProjectTasks
+    WHERE "ProjectId" = $1 AND "TaskName" = $2
+    ORDER BY "TaskDateTime" DESC LIMIT 1"#;
+
+pub(crate) const STOP_SQL: &str =
+    r#"UPDATE TaskTimes SET "EndTime" = $1 WHERE "TaskTimeId" = $2 AND "EndTime" IS NULL"#;
+
+// A running timer adds nothing to the durations until it stops, so
+// cancelling it only removes its TaskTime
+pub(crate) const CANCEL_SQL: &str =
+    r#"DELETE FROM TaskTimes WHERE "TaskTimeId" = $1 AND "EndTime" IS NULL"#;
+
+pub(crate) const ADD_TASK_DURATION_SQL: &str =
+    r#"UPDATE ProjectTasks SET "TaskDuration" = "TaskDuration" + $1 WHERE "TaskId" = $2"#;
+
+pub(crate) const ADD_PROJECT_DURATION_SQL: &str = r#"UPDATE Projects
+    SET "ProjectDuration" = "ProjectDuration" + $1 WHERE "ProjectId" = $2
+    RETURNING "ProjectDuration""#;
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::database::report::{Period, ReportRange};
+    use crate::model::project_task::ProjectTask;
+    use crate::model::task_time::TaskTime;
+    use crate::utils::make_uuid;
+    use crate::{DbConfig, DbiDatabase, Error, Money, Project};
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, 10)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    async fn setup() -> Result<(DbiDatabase, Project), Error> {
+        let mut db = DbiDatabase::new(DbConfig::new("sqlite::memory:")).await?;
+        let project = Project {
+            project_id: make_uuid(&"Shed".to_string()),
+            project_name: "Shed".to_string(),
+            project_date: at(9, 0).date(),
+            pay_rate: Money::from(60),
+            ..Default::default()
+        };
+        db.insert(&project).await?;
+        Ok((db, project))
+    }
+
+    #[tokio::test]
+    async fn test_start_and_stop_timer() -> Result<(), Error> {
+        let (mut db, project) = setup().await?;
+        assert!(db.running_timer().await?.is_none());
+        assert!(db.stop_timer_at(at(9, 0)).await?.is_none());
+
+        let started = db
+            .start_timer_at(project.project_id, "Roof", at(9, 0))
+            .await?;
+        assert_eq!(started.end_time, None);
+        let running = db.running_timer().await?.expect("Expected a timer");
+        assert_eq!(running.task_time_id as u64, started.task_time_id);
+        assert_eq!(running.task_name, "Roof");
+        assert_eq!(running.elapsed(at(9, 45)), TimeDelta::minutes(45));
+
+        // Running time is left out of the reports until it stops
+        let range = ReportRange::all();
+        assert!(db.project_hours(Period::Day, &range).await?.is_empty());
+
+        let result = db
+            .start_timer_at(project.project_id, "Walls", at(9, 30))
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::Validation {
+                entity: "TaskTime",
+                ..
+            })
+        ));
+
+        let stopped = db
+            .stop_timer_at(at(10, 30))
+            .await?
+            .expect("Expected a timer");
+        assert_eq!(stopped.end_time, Some(at(10, 30)));
+        assert!(db.running_timer().await?.is_none());
+        assert!(db.verify().await?.is_empty());
+        let mut fetched = project.clone();
+        db.fetch_one(&mut fetched).await?;
+        assert_eq!(
+            fetched.project_duration,
+            TimeDelta::minutes(90).num_milliseconds()
+        );
+        assert_eq!(fetched.total_pay, Money::from(90));
+
+        // The next timer on the task carries it on
+        let again = db
+            .start_timer_at(project.project_id, "Roof", at(11, 0))
+            .await?;
+        assert_eq!(again.task_id, started.task_id);
+        db.stop_timer_at(at(11, 30)).await?;
+        assert_eq!(db.fetch_all::<ProjectTask>().await?.len(), 1);
+        let hours = db.project_hours(Period::Day, &range).await?;
+        assert_eq!(hours[0].hours, 2.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timer_guards() -> Result<(), Error> {
+        let (mut db, project) = setup().await?;
+        let result = db.start_timer_at(Uuid::nil(), "Roof", at(9, 0)).await;
+        assert!(matches!(
+            result,
+            Err(Error::NotFound {
+                entity: "Project",
+                ..
+            })
+        ));
+
+        let started = db
+            .start_timer_at(project.project_id, "Roof", at(9, 0))
+            .await?;
+
+        // A running timer overlaps everything after its start
+        let later = TaskTime {
+            task_id: started.task_id,
+            start_time: at(12, 0),
+            end_time: Some(at(13, 0)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            db.insert(&later).await,
+            Err(Error::Overlap { .. })
+        ));
+
+        // The database allows one running timer even when overlaps are
+        let other = TaskTime {
+            task_id: started.task_id,
+            start_time: at(8, 0),
+            ..Default::default()
+        };
+        db.config.validation.overlap = crate::config::OverlapScope::Off;
+        assert!(matches!(
+            db.insert(&other).await,
+            Err(Error::Duplicate { .. })
+        ));
+
+        // A timer cannot stop before it started
+        let result = db.stop_timer_at(at(8, 0)).await;
+        assert!(matches!(result, Err(Error::Validation { .. })));
+        assert!(db.running_timer().await?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_timer_left_over_weekend() -> Result<(), Error> {
+        let (mut db, project) = setup().await?;
+        assert!(db.cancel_timer().await?.is_none());
+
+        // Friday evening to Monday morning is longer than a task may be
+        let started = db
+            .start_timer_at(project.project_id, "Roof", at(17, 0))
+            .await?;
+        let monday = at(17, 0) + TimeDelta::hours(64);
+        let result = db.stop_timer_at(monday).await;
+        assert!(matches!(result, Err(Error::Validation { .. })));
+
+        let cancelled = db.cancel_timer().await?.expect("Expected a timer");
+        assert_eq!(cancelled.task_time_id as u64, started.task_time_id);
+        assert!(db.running_timer().await?.is_none());
+        assert!(db.fetch_all::<TaskTime>().await?.is_empty());
+        assert_eq!(db.fetch_all::<ProjectTask>().await?.len(), 1);
+        assert!(db.verify().await?.is_empty());
+
+        // A new timer can start once it is gone
+        db.start_timer_at(project.project_id, "Roof", monday)
+            .await?;
+        db.stop_timer_at(monday + TimeDelta::minutes(30)).await?;
+        let mut fetched = project.clone();
+        db.fetch_one(&mut fetched).await?;
+        assert_eq!(
+            fetched.project_duration,
+            TimeDelta::minutes(30).num_milliseconds()
+        );
+        Ok(())
+    }
+}
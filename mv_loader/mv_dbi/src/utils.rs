@@ -1,6 +1,10 @@
+use chrono::TimeDelta;
+use rust_decimal::Decimal;
 use uuid;
 use uuid::Uuid;
 
+use crate::money::{Money, RoundingMode};
+
 ///
 /// Make a UUID based on encoding the provided String referenc
 ///
@@ -9,6 +13,15 @@ use uuid::Uuid;
 ///
 /// OID namespace = {6ba7b812-9dad-11d1-80b4-00c04fd430c8}
 pub fn make_uuid(value: &String) -> Uuid {
-    let uuid = Uuid::new_v5(&Uuid::NAMESPACE_OID, value.as_bytes());
-    uuid
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, value.as_bytes())
+}
+
+///
+/// The pay for a duration in milliseconds. Only whole minutes are paid,
+/// as when mv_load_csv first works out a project's TotalPay, and the pay
+/// is rounded to cents with `mode`.
+///
+pub fn total_pay(duration_ms: i64, pay_rate: Money, mode: RoundingMode) -> Money {
+    let minutes = Decimal::from(TimeDelta::milliseconds(duration_ms).num_minutes());
+    Money(minutes * pay_rate.0 / Decimal::from(60)).round(mode)
 }
@@ -0,0 +1,957 @@
+// mv_dbi_derive/src/lib.rs
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, Path};
+use syn::{PathArguments, Type};
+
+///
+/// Derive `DbObject` for SQLite and PostgreSQL, and `DbTable`, from a
+/// model struct whose fields map onto columns.
+///
+/// Column names follow the struct's `sqlx` attributes, so they are the
+/// ones `FromRow` decodes: `#[sqlx(rename_all = "...")]` on the struct,
+/// and `#[sqlx(rename = "...")]` and `#[sqlx(skip)]` on fields. Fields
+/// marked `#[sqlx(flatten)]` or `#[sqlx(json)]` are not supported.
+///
+/// Struct attributes:
+///
+/// * `table = "Projects"`: the table name (required)
+/// * `order_by = "ProjectId, TaskDateTime"`: columns for `retrieve_all`
+///   and `retrieve_some`, defaulting to the key
+/// * `filter = "Projects"`: the `FilterTarget` used by `retrieve_filtered`.
+///   Without it `retrieve_filtered` returns a Validation error.
+/// * `children(ProjectTask)`: models removed with this one by
+///   `ON DELETE CASCADE`, counted by `delete_one`
+/// * `validate`: the model implements `Validate` itself. Without it an
+///   empty `Validate` impl, which accepts every row, is derived.
+///
+/// `Uuid` and `Option<Uuid>` fields are bound through `mv_dbi::Guid`, so
+/// they are stored the way the database's `uuid_storage` says. FromRow
+/// reads them back with `#[sqlx(try_from = "Guid")]` and
+/// `#[sqlx(try_from = "OptionalGuid")]`.
+///
+/// Field attributes:
+///
+/// * `key`: the primary key column
+/// * `autoincrement`: the primary key is assigned by the database
+/// * `parent`: the column `retrieve_some` selects by
+///
+/// ```ignore
+/// #[derive(DbObject, FromRow)]
+/// #[sqlx(rename_all = "PascalCase")]
+/// #[dbobject(table = "ProjectTasks", order_by = "ProjectId, TaskDateTime")]
+/// #[dbobject(filter = "ProjectTasks", children(TaskTime))]
+/// pub struct ProjectTask {
+///     #[dbobject(key)]
+///     #[sqlx(try_from = "Guid")]
+///     pub task_id: Uuid,
+///     #[dbobject(parent)]
+///     #[sqlx(try_from = "Guid")]
+///     pub project_id: Uuid,
+///     ...
+/// }
+/// ```
+#[proc_macro_derive(DbObject, attributes(dbobject))]
+pub fn derive_db_object(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UuidField {
+    No,
+    Plain,
+    Optional,
+}
+
+struct Column {
+    ident: Ident,
+    name: String,
+    key: bool,
+    autoincrement: bool,
+    parent: bool,
+    uuid: UuidField,
+}
+
+impl Column {
+    // The value bound for this column. Database assigned keys are u64 in
+    // the models but stored as signed 64 bit integers.
+    fn bind_value(&self, owner: &TokenStream2) -> TokenStream2 {
+        let ident = &self.ident;
+        if self.autoincrement {
+            return quote! { #owner.#ident as i64 };
+        }
+        match self.uuid {
+            UuidField::No => quote! { #owner.#ident.clone() },
+            UuidField::Plain => quote! { ::mv_dbi::Guid::from(#owner.#ident) },
+            UuidField::Optional => quote! { #owner.#ident.map(::mv_dbi::Guid::from) },
+        }
+    }
+}
+
+// The generic arguments of `ty` when it is a type called `name`, whether
+// written as `Uuid` or `uuid::Uuid`
+fn last_segment<'a>(ty: &'a Type, name: &str) -> Option<&'a PathArguments> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .filter(|segment| segment.ident == name)
+            .map(|segment| &segment.arguments),
+        _ => None,
+    }
+}
+
+fn uuid_field(ty: &Type) -> UuidField {
+    if last_segment(ty, "Uuid").is_some() {
+        return UuidField::Plain;
+    }
+    if let Some(PathArguments::AngleBracketed(args)) = last_segment(ty, "Option") {
+        if let Some(GenericArgument::Type(inner)) = args.args.first() {
+            if last_segment(inner, "Uuid").is_some() {
+                return UuidField::Optional;
+            }
+        }
+    }
+    UuidField::No
+}
+
+struct Model {
+    table: String,
+    order_by: Vec<String>,
+    filter: Option<Ident>,
+    children: Vec<Path>,
+    validate: bool,
+    columns: Vec<Column>,
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(capitalized)
+        .collect()
+}
+
+///
+/// The `#[sqlx(rename_all = "...")]` conventions `FromRow` understands,
+/// applied to a snake_case field name
+///
+fn rename_all(name: &str, convention: &LitStr) -> syn::Result<String> {
+    let words: Vec<&str> = name.split('_').filter(|part| !part.is_empty()).collect();
+    Ok(match convention.value().as_str() {
+        "snake_case" => words.join("_"),
+        "lowercase" => words.concat().to_lowercase(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "PascalCase" => pascal_case(name),
+        "camelCase" => {
+            let pascal = pascal_case(name);
+            let mut chars = pascal.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                convention,
+                "unknown rename_all convention",
+            ))
+        }
+    })
+}
+
+// Skip the value or list of an sqlx attribute that does not change
+// which columns the model has
+fn skip_meta(meta: &syn::meta::ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip_meta(&nested))?;
+    }
+    Ok(())
+}
+
+// Identifiers are quoted so PostgreSQL keeps their case, and parameters
+// are numbered, so the same SQL runs on SQLite and PostgreSQL
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name)
+}
+
+fn parse_model(input: &DeriveInput) -> syn::Result<Model> {
+    let mut table = None;
+    let mut order_by = Vec::new();
+    let mut filter = None;
+    let mut children = Vec::new();
+    let mut validate = false;
+    let mut convention: Option<LitStr> = None;
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("sqlx")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                convention = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                skip_meta(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("dbobject")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("order_by") {
+                let value = meta.value()?.parse::<LitStr>()?.value();
+                order_by = value.split(',').map(|c| c.trim().to_string()).collect();
+            } else if meta.path.is_ident("filter") {
+                let value = meta.value()?.parse::<LitStr>()?;
+                filter = Some(Ident::new(&value.value(), value.span()));
+            } else if meta.path.is_ident("children") {
+                meta.parse_nested_meta(|child| {
+                    children.push(child.path);
+                    Ok(())
+                })?;
+            } else if meta.path.is_ident("validate") {
+                validate = true;
+            } else {
+                return Err(meta.error("unknown dbobject attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "DbObject needs a struct with named fields",
+                ))
+            }
+        },
+        _ => return Err(syn::Error::new_spanned(input, "DbObject needs a struct")),
+    };
+
+    let mut columns = Vec::new();
+    for field in fields {
+        let ident = field.ident.clone().expect("named field");
+        let field_name = ident.to_string().trim_start_matches("r#").to_string();
+        let mut rename = None;
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("sqlx")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else if meta.path.is_ident("flatten") || meta.path.is_ident("json") {
+                    return Err(meta.error("DbObject does not support this sqlx attribute"));
+                } else {
+                    skip_meta(&meta)?;
+                }
+                Ok(())
+            })?;
+        }
+
+        let name = match (rename, &convention) {
+            (Some(name), _) => name,
+            (None, Some(convention)) => rename_all(&field_name, convention)?,
+            (None, None) => field_name,
+        };
+        let mut column = Column {
+            name,
+            ident,
+            key: false,
+            autoincrement: false,
+            parent: false,
+            uuid: uuid_field(&field.ty),
+        };
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("dbobject")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("key") {
+                    column.key = true;
+                } else if meta.path.is_ident("autoincrement") {
+                    column.key = true;
+                    column.autoincrement = true;
+                } else if meta.path.is_ident("parent") {
+                    column.parent = true;
+                } else {
+                    return Err(meta.error("unknown dbobject field attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if skip {
+            // FromRow fills a skipped field with its default, so it is
+            // not a column and cannot be the key or the parent
+            if column.key || column.parent {
+                return Err(syn::Error::new_spanned(
+                    &column.ident,
+                    "the key or parent field cannot be #[sqlx(skip)]",
+                ));
+            }
+            continue;
+        }
+        columns.push(column);
+    }
+
+    let table = table.ok_or_else(|| {
+        syn::Error::new_spanned(&input.ident, "missing #[dbobject(table = \"...\")]")
+    })?;
+    match columns.iter().filter(|c| c.key).count() {
+        1 => {}
+        0 => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "one field needs #[dbobject(key)] or #[dbobject(autoincrement)]",
+            ))
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only one field can be the key",
+            ))
+        }
+    }
+    if columns.iter().filter(|c| c.parent).count() > 1 {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "only one field can be the parent",
+        ));
+    }
+
+    Ok(Model {
+        table,
+        order_by,
+        filter,
+        children,
+        validate,
+        columns,
+    })
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let model = parse_model(input)?;
+    let name = &input.ident;
+    let entity = name.to_string();
+    let table = &model.table;
+
+    let key = model.columns.iter().find(|c| c.key).expect("checked key");
+    let key_ident = &key.ident;
+    let key_name = &key.name;
+    let parent_name = match model.columns.iter().find(|c| c.parent) {
+        Some(parent) => {
+            let name = &parent.name;
+            quote! { Some(#name) }
+        }
+        None => quote! { None },
+    };
+
+    let all_columns: Vec<String> = model.columns.iter().map(|c| quoted(&c.name)).collect();
+    let select_list = all_columns.join(", ");
+    let order_by = if model.order_by.is_empty() {
+        quoted(key_name)
+    } else {
+        model
+            .order_by
+            .iter()
+            .map(|c| quoted(c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // INSERT, leaving out a database assigned key
+    let insert_columns: Vec<&Column> = model.columns.iter().filter(|c| !c.autoincrement).collect();
+    let insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}){}",
+        table,
+        insert_columns
+            .iter()
+            .map(|c| quoted(&c.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        (1..=insert_columns.len())
+            .map(|n| format!("${}", n))
+            .collect::<Vec<_>>()
+            .join(", "),
+        if key.autoincrement {
+            format!(" RETURNING {}", quoted(key_name))
+        } else {
+            String::new()
+        }
+    );
+    let dbo = quote! { dbo };
+    let insert_binds: Vec<TokenStream2> = insert_columns
+        .iter()
+        .map(|c| {
+            let value = c.bind_value(&dbo);
+            quote! { .bind(#value) }
+        })
+        .collect();
+
+    // UPSERT and UPDATE write every non-key column
+    let value_columns: Vec<&Column> = model.columns.iter().filter(|c| !c.key).collect();
+    let upsert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT({}) {}",
+        table,
+        select_list,
+        (1..=model.columns.len())
+            .map(|n| format!("${}", n))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quoted(key_name),
+        if value_columns.is_empty() {
+            "DO NOTHING".to_string()
+        } else {
+            format!(
+                "DO UPDATE SET {}",
+                value_columns
+                    .iter()
+                    .map(|c| format!("{0} = excluded.{0}", quoted(&c.name)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    );
+    let upsert_binds: Vec<TokenStream2> = model
+        .columns
+        .iter()
+        .map(|c| {
+            let value = c.bind_value(&dbo);
+            quote! { .bind(#value) }
+        })
+        .collect();
+
+    let update_sql = format!(
+        "UPDATE {} SET {} WHERE {} = $1",
+        table,
+        value_columns
+            .iter()
+            .enumerate()
+            .map(|(n, c)| format!("{} = ${}", quoted(&c.name), n + 2))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quoted(key_name)
+    );
+    let key_bind = key.bind_value(&dbo);
+    let self_key_bind = key.bind_value(&quote! { self });
+    let update_binds: Vec<TokenStream2> = value_columns
+        .iter()
+        .map(|c| {
+            let value = c.bind_value(&dbo);
+            quote! { .bind(#value) }
+        })
+        .collect();
+
+    let delete_sql = format!("DELETE FROM {} WHERE {} = $1", table, quoted(key_name));
+    let select_all_sql = format!(
+        "SELECT {} FROM {} ORDER BY {} ASC",
+        select_list, table, order_by
+    );
+    let some_column = model
+        .columns
+        .iter()
+        .find(|c| c.parent)
+        .map(|c| &c.name)
+        .unwrap_or(key_name);
+    let select_some_sql = format!(
+        "SELECT {} FROM {} WHERE {} = $1 ORDER BY {} ASC",
+        select_list,
+        table,
+        quoted(some_column),
+        order_by
+    );
+    let select_one_sql = format!(
+        "SELECT {} FROM {} WHERE {} = $1",
+        select_list,
+        table,
+        quoted(key_name)
+    );
+    let select_by_key_sql = format!(
+        "SELECT {} FROM {} ORDER BY {} ASC",
+        select_list,
+        table,
+        quoted(key_name)
+    );
+    let page_select_sql = format!("SELECT {} FROM {}", select_list, table);
+    let page_where_sql = format!(" WHERE {} > ", quoted(key_name));
+    let page_order_sql = format!(" ORDER BY {} ASC LIMIT ", quoted(key_name));
+    let page_key_value = if key.autoincrement {
+        quote! { ::mv_dbi::database::query::PageKey::Id(self.#key_ident) }
+    } else {
+        quote! { ::mv_dbi::database::query::PageKey::Uuid(self.#key_ident) }
+    };
+    let page_key = if key.autoincrement {
+        quote! { key.id(#entity)? }
+    } else {
+        quote! { ::mv_dbi::Guid::from(key.uuid(#entity)?) }
+    };
+
+    let filter_body = match &model.filter {
+        Some(target) => {
+            let alias = match target.to_string().as_str() {
+                "Projects" => "p",
+                "ProjectTasks" => "t",
+                "TaskTimes" => "tt",
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        target,
+                        "filter must be Projects, ProjectTasks or TaskTimes",
+                    ))
+                }
+            };
+            let filter_select = format!(
+                "SELECT {}",
+                model
+                    .columns
+                    .iter()
+                    .map(|c| format!("{}.{}", alias, quoted(&c.name)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            quote! {
+                let mut qb: ::mv_dbi::__private::sqlx::QueryBuilder<DB> =
+                    ::mv_dbi::__private::sqlx::QueryBuilder::new(#filter_select);
+                filter.push_clauses(&mut qb, ::mv_dbi::database::filter::FilterTarget::#target);
+                let records: Vec<#name> = qb.build_query_as().fetch_all(pool).await?;
+                Ok(records)
+            }
+        }
+        None => quote! {
+            Err(::mv_dbi::Error::Validation {
+                entity: #entity,
+                id: String::new(),
+                message: "This model cannot be filtered".to_string(),
+            })
+        },
+    };
+
+    // Errors name the row by its key. A new row with a database assigned
+    // key has none yet, so it is named by its parent instead.
+    let insert_context = match (key.autoincrement, model.columns.iter().find(|c| c.parent)) {
+        (true, Some(parent)) => {
+            let ident = &parent.ident;
+            quote! { dbo.#ident.to_string() }
+        }
+        (true, None) => quote! { String::new() },
+        (false, _) => quote! { dbo.#key_ident.to_string() },
+    };
+
+    let (insert_in_tx_body, upsert_in_tx_body) = if key.autoincrement {
+        (
+            quote! {
+                let row: (i64,) = ::mv_dbi::__private::sqlx::query_as(#insert_sql)
+                    #(#insert_binds)*
+                    .fetch_one(&mut *conn)
+                    .await
+                    .map_err(|e| ::mv_dbi::Error::classify(e, #entity, #insert_context))?;
+                Ok(row.0 as u64)
+            },
+            quote! {
+                if dbo.#key_ident == 0 {
+                    return <Self as ::mv_dbi::database::query::DbObject<DB, Self>>::insert_in_tx(conn, dbo).await;
+                }
+                ::mv_dbi::__private::sqlx::query(#upsert_sql)
+                    #(#upsert_binds)*
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| ::mv_dbi::Error::classify(e, #entity, dbo.#key_ident.to_string()))?;
+                Ok(dbo.#key_ident)
+            },
+        )
+    } else {
+        (
+            quote! {
+                let result = ::mv_dbi::__private::sqlx::query(#insert_sql)
+                    #(#insert_binds)*
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| ::mv_dbi::Error::classify(e, #entity, #insert_context))?;
+                Ok(result.rows_affected())
+            },
+            quote! {
+                let result = ::mv_dbi::__private::sqlx::query(#upsert_sql)
+                    #(#upsert_binds)*
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| ::mv_dbi::Error::classify(e, #entity, dbo.#key_ident.to_string()))?;
+                Ok(result.rows_affected())
+            },
+        )
+    };
+
+    let backends = [
+        quote! { ::mv_dbi::__private::sqlx::Sqlite },
+        quote! { ::mv_dbi::__private::sqlx::Postgres },
+    ];
+    let impls = backends.iter().map(|backend| {
+        quote! {
+            impl ::mv_dbi::database::query::DbObject<#backend, #name> for #name {
+                async fn insert_one(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    dbo: &#name,
+                ) -> Result<u64, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        type DB = #backend;
+                        let mut tx = pool.begin().await?;
+                        let result = <Self as ::mv_dbi::database::query::DbObject<DB, Self>>::insert_in_tx(&mut tx, dbo).await;
+                        ::mv_dbi::__private::finish_tx(tx, result).await
+                    })
+                    .await
+                }
+
+                async fn insert_in_tx(
+                    conn: &mut <#backend as ::mv_dbi::__private::sqlx::Database>::Connection,
+                    dbo: &#name,
+                ) -> Result<u64, ::mv_dbi::Error> {
+                    #insert_in_tx_body
+                }
+
+                async fn update_one(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    dbo: &#name,
+                ) -> Result<u64, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        let result = ::mv_dbi::__private::sqlx::query(#update_sql)
+                            .bind(#key_bind)
+                            #(#update_binds)*
+                            .execute(pool)
+                            .await
+                            .map_err(|e| ::mv_dbi::Error::classify(e, #entity, dbo.#key_ident.to_string()))?;
+                        Ok(result.rows_affected())
+                    })
+                    .await
+                }
+
+                async fn upsert_one(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    dbo: &#name,
+                ) -> Result<u64, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        type DB = #backend;
+                        let mut tx = pool.begin().await?;
+                        let result = <Self as ::mv_dbi::database::query::DbObject<DB, Self>>::upsert_in_tx(&mut tx, dbo).await;
+                        ::mv_dbi::__private::finish_tx(tx, result).await
+                    })
+                    .await
+                }
+
+                async fn upsert_in_tx(
+                    conn: &mut <#backend as ::mv_dbi::__private::sqlx::Database>::Connection,
+                    dbo: &#name,
+                ) -> Result<u64, ::mv_dbi::Error> {
+                    type DB = #backend;
+                    #upsert_in_tx_body
+                }
+
+                async fn delete_one(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    dbo: &#name,
+                ) -> Result<::mv_dbi::database::query::DeleteCount, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        let mut tx = pool.begin().await?;
+                        let result: Result<_, ::mv_dbi::Error> = async {
+                            // Dependent rows go with this one through the ON DELETE
+                            // CASCADE keys, so count them before they disappear
+                            let mut children: i64 = 0;
+                            for sql in <#name as ::mv_dbi::database::query::DbTable>::dependents_sql("$1") {
+                                let count: (i64,) = ::mv_dbi::__private::sqlx::query_as(&sql)
+                                    .bind(#key_bind)
+                                    .fetch_one(&mut *tx)
+                                    .await?;
+                                children += count.0;
+                            }
+
+                            let result = ::mv_dbi::__private::sqlx::query(#delete_sql)
+                                .bind(#key_bind)
+                                .execute(&mut *tx)
+                                .await
+                                .map_err(|e| ::mv_dbi::Error::classify(e, #entity, dbo.#key_ident.to_string()))?;
+
+                            let rows = result.rows_affected();
+                            let children = if rows > 0 { children as u64 } else { 0 };
+                            Ok(::mv_dbi::database::query::DeleteCount { rows, children })
+                        }
+                        .await;
+                        ::mv_dbi::__private::finish_tx(tx, result).await
+                    })
+                    .await
+                }
+
+                async fn retrieve_all(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                ) -> Result<Vec<#name>, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        let records: Vec<#name> = ::mv_dbi::__private::sqlx::query_as(#select_all_sql)
+                            .fetch_all(pool)
+                            .await?;
+                        Ok(records)
+                    })
+                    .await
+                }
+
+                fn retrieve_stream(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                ) -> ::mv_dbi::__private::futures::stream::BoxStream<'_, Result<#name, ::mv_dbi::Error>> {
+                    use ::mv_dbi::__private::futures::{StreamExt, TryStreamExt};
+                    ::mv_dbi::__private::sqlx::query_as(#select_by_key_sql)
+                        .fetch(pool)
+                        .map_err(::mv_dbi::Error::from)
+                        .boxed()
+                }
+
+                async fn retrieve_page(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    after: Option<&::mv_dbi::database::query::PageKey>,
+                    limit: i64,
+                ) -> Result<Vec<#name>, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        let mut qb: ::mv_dbi::__private::sqlx::QueryBuilder<#backend> =
+                            ::mv_dbi::__private::sqlx::QueryBuilder::new(#page_select_sql);
+                        if let Some(key) = after {
+                            qb.push(#page_where_sql);
+                            qb.push_bind(#page_key);
+                        }
+                        qb.push(#page_order_sql);
+                        qb.push_bind(limit);
+                        let records: Vec<#name> = qb.build_query_as().fetch_all(pool).await?;
+                        Ok(records)
+                    })
+                    .await
+                }
+
+                async fn retrieve_some(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    uuid: &::mv_dbi::__private::uuid::Uuid,
+                ) -> Result<Vec<#name>, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        let records: Vec<#name> = ::mv_dbi::__private::sqlx::query_as(#select_some_sql)
+                            .bind(::mv_dbi::Guid::from(*uuid))
+                            .fetch_all(pool)
+                            .await?;
+                        Ok(records)
+                    })
+                    .await
+                }
+
+                async fn retrieve_filtered(
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                    filter: &::mv_dbi::database::filter::QueryFilter,
+                ) -> Result<Vec<#name>, ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        type DB = #backend;
+                        #filter_body
+                    })
+                    .await
+                }
+
+                async fn retrieve_one(
+                    &mut self,
+                    pool: &::mv_dbi::__private::sqlx::Pool<#backend>,
+                ) -> Result<(), ::mv_dbi::Error> {
+                    ::mv_dbi::__private::with_pool_storage(pool, async {
+                        *self = ::mv_dbi::__private::sqlx::query_as(#select_one_sql)
+                            .bind(#self_key_bind)
+                            .fetch_one(pool)
+                            .await
+                            .map_err(|e| ::mv_dbi::Error::classify(e, #entity, self.#key_ident.to_string()))?;
+                        Ok(())
+                    })
+                    .await
+                }
+            }
+        }
+    });
+
+    let child_sql = model.children.iter().map(|child| {
+        quote! {
+            let parent = <#child as ::mv_dbi::database::query::DbTable>::PARENT
+                .expect("a child model needs a #[dbobject(parent)] field");
+            let table = <#child as ::mv_dbi::database::query::DbTable>::TABLE;
+            let key = <#child as ::mv_dbi::database::query::DbTable>::KEY;
+            sql.push(format!("SELECT COUNT(*) FROM {} WHERE \"{}\" IN ({})", table, parent, keys));
+            let child_keys = format!("SELECT \"{}\" FROM {} WHERE \"{}\" IN ({})", key, table, parent, keys);
+            sql.extend(<#child as ::mv_dbi::database::query::DbTable>::dependents_sql(&child_keys));
+        }
+    });
+
+    let validate_impl = if model.validate {
+        quote! {}
+    } else {
+        quote! { impl ::mv_dbi::database::validate::Validate for #name {} }
+    };
+
+    Ok(quote! {
+        impl ::mv_dbi::database::query::DbTable for #name {
+            const TABLE: &'static str = #table;
+            const KEY: &'static str = #key_name;
+            const PARENT: Option<&'static str> = #parent_name;
+
+            fn dependents_sql(keys: &str) -> Vec<String> {
+                let mut sql: Vec<String> = Vec::new();
+                #(#child_sql)*
+                sql
+            }
+
+            fn page_key(&self) -> ::mv_dbi::database::query::PageKey {
+                #page_key_value
+            }
+        }
+
+        #validate_impl
+
+        #(#impls)*
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pascal_case() {
+        assert_eq!(pascal_case("project_id"), "ProjectId");
+        assert_eq!(pascal_case("task_date_time"), "TaskDateTime");
+        assert_eq!(pascal_case("id"), "Id");
+    }
+
+    #[test]
+    fn test_rename_all() {
+        let convention = |value: &str| LitStr::new(value, proc_macro2::Span::call_site());
+        let name = "task_date_time";
+        assert_eq!(
+            rename_all(name, &convention("PascalCase")).unwrap(),
+            "TaskDateTime"
+        );
+        assert_eq!(
+            rename_all(name, &convention("camelCase")).unwrap(),
+            "taskDateTime"
+        );
+        assert_eq!(
+            rename_all(name, &convention("lowercase")).unwrap(),
+            "taskdatetime"
+        );
+        assert_eq!(
+            rename_all(name, &convention("UPPERCASE")).unwrap(),
+            "TASKDATETIME"
+        );
+        assert_eq!(
+            rename_all(name, &convention("SCREAMING_SNAKE_CASE")).unwrap(),
+            "TASK_DATE_TIME"
+        );
+        assert_eq!(
+            rename_all(name, &convention("kebab-case")).unwrap(),
+            "task-date-time"
+        );
+        assert_eq!(rename_all(name, &convention("snake_case")).unwrap(), name);
+        assert!(rename_all(name, &convention("Title Case")).is_err());
+    }
+
+    #[test]
+    fn test_parse_model() {
+        let input: DeriveInput = syn::parse_quote! {
+            #[sqlx(rename_all = "PascalCase")]
+            #[dbobject(table = "TaskTimes", order_by = "TaskId, StartTime")]
+            #[dbobject(filter = "TaskTimes", validate)]
+            struct TaskTime {
+                #[dbobject(autoincrement)]
+                task_time_id: u64,
+                #[dbobject(parent)]
+                #[sqlx(try_from = "Guid")]
+                task_id: Uuid,
+                start_time: NaiveDateTime,
+                #[sqlx(try_from = "OptionalGuid")]
+                invoice_id: Option<uuid::Uuid>,
+            }
+        };
+        let model = parse_model(&input).unwrap();
+        assert_eq!(model.table, "TaskTimes");
+        assert_eq!(model.order_by, vec!["TaskId", "StartTime"]);
+        assert!(model.validate);
+        assert!(model.columns[0].key && model.columns[0].autoincrement);
+        assert!(model.columns[1].parent);
+        assert_eq!(model.columns[2].name, "StartTime");
+        let uuids: Vec<UuidField> = model.columns.iter().map(|c| c.uuid).collect();
+        assert_eq!(
+            uuids,
+            vec![
+                UuidField::No,
+                UuidField::Plain,
+                UuidField::No,
+                UuidField::Optional
+            ]
+        );
+
+        let input: DeriveInput = syn::parse_quote! {
+            #[derive(FromRow)]
+            #[sqlx(rename_all = "PascalCase", default)]
+            #[dbobject(table = "Things")]
+            struct Thing {
+                #[dbobject(autoincrement)]
+                #[sqlx(rename = "rowid", try_from = "i64")]
+                id: u64,
+                thing_name: String,
+                #[sqlx(skip)]
+                cached: Vec<String>,
+            }
+        };
+        let model = parse_model(&input).unwrap();
+        let names: Vec<&str> = model.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["rowid", "ThingName"]);
+
+        // Without rename_all FromRow reads the field names as they are
+        let input: DeriveInput = syn::parse_quote! {
+            #[dbobject(table = "Things")]
+            struct Thing {
+                #[dbobject(key)]
+                thing_id: Uuid,
+            }
+        };
+        assert_eq!(parse_model(&input).unwrap().columns[0].name, "thing_id");
+
+        let input: DeriveInput = syn::parse_quote! {
+            #[dbobject(table = "Things")]
+            struct Thing {
+                #[dbobject(key)]
+                #[sqlx(skip)]
+                thing_id: Uuid,
+            }
+        };
+        assert!(parse_model(&input).is_err());
+
+        let input: DeriveInput = syn::parse_quote! {
+            #[dbobject(table = "Things")]
+            struct Thing {
+                #[dbobject(key)]
+                thing_id: Uuid,
+                #[sqlx(json)]
+                details: Details,
+            }
+        };
+        assert!(parse_model(&input).is_err());
+
+        let input: DeriveInput = syn::parse_quote! {
+            #[dbobject(table = "Things")]
+            struct Thing {
+                name: String,
+            }
+        };
+        assert!(parse_model(&input).is_err());
+    }
+}
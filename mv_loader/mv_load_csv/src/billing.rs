@@ -0,0 +1,132 @@
+use mv_dbi::{
+    model::{billing_rule::BillingRule, task_rate::TaskRate},
+    utils::make_uuid,
+    DbiDatabase, Money, RoundingMode,
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, error::Error, fs};
+
+use crate::models::Project;
+
+// One [[rule]] table
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleRecord {
+    client: Option<String>,
+    project: Option<String>,
+    #[serde(default)]
+    time_increment: i32,
+    #[serde(default)]
+    task_increment: i32,
+    #[serde(default)]
+    increment_rounding: Option<RoundingMode>,
+    #[serde(default)]
+    daily_minimum: i32,
+    #[serde(default)]
+    daily_overtime: i32,
+    #[serde(default)]
+    weekly_overtime: i32,
+    overtime_percent: Option<i32>,
+    #[serde(default)]
+    task_rates: BTreeMap<String, Money>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<RuleRecord>,
+}
+
+///
+/// Billing rules read from a TOML file, each for a client or for every
+/// project with a given name. Times are in minutes:
+///
+/// ```toml
+/// [[rule]]
+/// client = "Gemstone Ltd"
+/// time_increment = 15
+/// daily_overtime = 480
+/// task_rates = { "Task 01" = "60.00" }
+/// ```
+#[derive(Debug, Default)]
+pub struct BillingFile {
+    rules: Vec<RuleRecord>,
+}
+
+impl BillingFile {
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let file: RuleFile = toml::from_str(&text)?;
+        for record in &file.rule {
+            if record.client.is_some() == record.project.is_some() {
+                return Err(
+                    format!("Each rule in {path} needs either a client or a project").into(),
+                );
+            }
+        }
+        Ok(Self { rules: file.rule })
+    }
+
+    ///
+    /// Store the rules, replacing any saved before for the same client or
+    /// project. Rules for a project are stored for each of the projects
+    /// with that name.
+    ///
+    pub async fn save(
+        &self,
+        projects: &[Project],
+        db: &mut DbiDatabase,
+    ) -> Result<(), mv_dbi::Error> {
+        for record in &self.rules {
+            let rules: Vec<BillingRule> = match (&record.client, &record.project) {
+                (Some(client), _) => vec![BillingRule::for_client(make_uuid(client))],
+                (_, Some(name)) => projects
+                    .iter()
+                    .filter(|p| &p.project_name == name)
+                    .map(|p| BillingRule::for_project(p.project_id))
+                    .collect(),
+                (None, None) => Vec::new(),
+            };
+            for rule in rules {
+                let rule = record.settings(rule);
+                db.upsert(&rule).await?;
+                save_task_rates(&rule, &record.task_rates, db).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RuleRecord {
+    fn settings(&self, rule: BillingRule) -> BillingRule {
+        let defaults = BillingRule::default();
+        rule.time_increment(self.time_increment)
+            .task_increment(self.task_increment)
+            .increment_rounding(
+                self.increment_rounding
+                    .unwrap_or(defaults.increment_rounding),
+            )
+            .daily_minimum(self.daily_minimum)
+            .daily_overtime(self.daily_overtime)
+            .weekly_overtime(self.weekly_overtime)
+            .overtime_percent(self.overtime_percent.unwrap_or(defaults.overtime_percent))
+    }
+}
+
+// Make the rule's task rates the ones in the file
+async fn save_task_rates(
+    rule: &BillingRule,
+    rates: &BTreeMap<String, Money>,
+    db: &mut DbiDatabase,
+) -> Result<(), mv_dbi::Error> {
+    for stored in db.fetch_some::<TaskRate>(&rule.rule_id).await? {
+        if !rates.contains_key(&stored.task_name) {
+            db.delete(&stored).await?;
+        }
+    }
+    for (task_name, pay_rate) in rates {
+        db.upsert(&TaskRate::new(rule.rule_id, task_name, *pay_rate))
+            .await?;
+    }
+    Ok(())
+}
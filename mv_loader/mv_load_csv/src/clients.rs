@@ -0,0 +1,92 @@
+use csv::ReaderBuilder;
+use mv_dbi::{
+    model::client::Client, utils::make_uuid, utils::total_pay, DbiDatabase, Money, RoundingMode,
+};
+use serde::Deserialize;
+use std::{collections::HashMap, error::Error, fs::File};
+
+use crate::from_money_string;
+use crate::models::Project;
+
+// Project,Client,Pay Rate
+#[derive(Debug, Clone, Deserialize)]
+struct ClientRecord {
+    #[serde(rename = "Project")]
+    project: String,
+    #[serde(rename = "Client")]
+    client: String,
+    #[serde(rename = "Pay Rate", default, deserialize_with = "from_money_string")]
+    pay_rate: Option<Money>,
+}
+
+///
+/// Which client each project name belongs to, read from a CSV file with
+/// the columns Project, Client and an optional Pay Rate for the client
+///
+#[derive(Debug, Default)]
+pub struct ClientMap {
+    clients: Vec<Client>,
+    by_project: HashMap<String, usize>,
+}
+
+impl ClientMap {
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut reader = ReaderBuilder::new().trim(csv::Trim::All).from_reader(file);
+
+        let mut map = ClientMap::default();
+        for result in reader.deserialize() {
+            let record: ClientRecord = result?;
+            let index = match map
+                .clients
+                .iter()
+                .position(|c| c.client_name == record.client)
+            {
+                Some(index) => index,
+                None => {
+                    map.clients.push(Client {
+                        client_id: make_uuid(&record.client),
+                        client_name: record.client.clone(),
+                        pay_rate: None,
+                    });
+                    map.clients.len() - 1
+                }
+            };
+            // The first rate given for a client is the one it keeps
+            let client = &mut map.clients[index];
+            client.pay_rate = client.pay_rate.or(record.pay_rate);
+            map.by_project.insert(record.project, index);
+        }
+        Ok(map)
+    }
+
+    ///
+    /// Link the projects to their clients. A project without a rate of its
+    /// own keeps a PayRate of 0, so it follows the client's rate, and its
+    /// TotalPay is worked out at the client's rate, rounded with `rounding`.
+    ///
+    pub fn apply(&self, projects: &mut [Project], rounding: RoundingMode) {
+        for project in projects {
+            let Some(&index) = self.by_project.get(&project.project_name) else {
+                continue;
+            };
+            let client = &self.clients[index];
+            project.client_id = Some(client.client_id);
+            if project.pay_rate <= Money::ZERO {
+                if let Some(rate) = client.pay_rate {
+                    project.total_pay = total_pay(project.project_duration, rate, rounding);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Store the clients, updating the name and rate of ones saved before
+    ///
+    pub async fn save(&self, db: &mut DbiDatabase) -> Result<(), mv_dbi::Error> {
+        for client in &self.clients {
+            db.upsert(client).await?;
+        }
+        Ok(())
+    }
+}
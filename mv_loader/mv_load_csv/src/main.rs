@@ -1,23 +1,72 @@
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
 use csv::ReaderBuilder;
 use getopts::Options;
-use mv_dbi::{utils::make_uuid, DbConfig, DbiDatabase};
+use mv_dbi::{
+    model::{
+        project_tree::{SaveMode, SaveReport},
+        tag::Tag,
+    },
+    utils::{make_uuid, total_pay},
+    DbConfig, DbiDatabase, Error as DbError, Money, RoundingMode,
+};
 use serde::{Deserialize, Deserializer};
-use std::{env, error::Error, fs::File, process};
+use dotenv::dotenv;
+use std::{env, error::Error, fs::File, path::Path, process};
 use time::macros::format_description;
 use time::Time;
 
+mod billing;
+mod clients;
+mod maintenance;
 mod models;
+mod schema;
+mod sync;
+mod timer;
+mod transfer;
+use billing::BillingFile;
+use clients::ClientMap;
+use maintenance::MaintenanceCommand;
 use models::{combine_like_projects, Project, ProjectTask, TaskTime};
+use schema::SchemaCommand;
+use sync::SyncCommand;
+use timer::TimerCommand;
+use transfer::TransferCommand;
+
+///
+/// How much of the import is written in a single transaction
+///
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Atomicity {
+    /// Each project, with its tasks and times, is saved or rolled back on its own
+    #[default]
+    Project,
+    /// The whole file is saved or rolled back as one unit
+    File,
+}
 
 #[derive(Debug, Default)]
 pub struct AppOptions {
     pub file: String,
-    pub db_name: String,
+    pub db_name: Option<String>,
+    pub config_file: Option<String>,
+    pub clients_file: Option<String>,
+    pub billing_file: Option<String>,
     pub has_headers: bool,
+    pub atomicity: Atomicity,
+    pub save_mode: SaveMode,
+    /// Drive the live timer instead of importing a file
+    pub timer: Option<TimerCommand>,
+    /// Export the database to, or import it from, a JSON or NDJSON file
+    pub transfer: Option<TransferCommand>,
+    /// Back up, restore, check or compact the database
+    pub maintenance: Option<MaintenanceCommand>,
+    /// Merge another database into this one, or both ways
+    pub sync: Option<SyncCommand>,
+    /// List the schema migrations or migrate to a version
+    pub schema: Option<SchemaCommand>,
 }
 
-// Date,Project,Pay Rate,Task ID,Start Time,End Time,Duration
+// Date,Project,Pay Rate,Task ID,Start Time,End Time,Duration[,Tags][,Notes]
 #[allow(dead_code)]
 #[derive(Debug, Default, Clone, Deserialize)]
 struct Record {
@@ -25,8 +74,8 @@ struct Record {
     date: Option<NaiveDate>,
     #[serde(rename = "Project", deserialize_with = "csv::invalid_option")]
     project: Option<String>,
-    #[serde(rename = "Pay Rate", deserialize_with = "csv::invalid_option")]
-    pay_rate: Option<f64>,
+    #[serde(rename = "Pay Rate", deserialize_with = "from_money_string")]
+    pay_rate: Option<Money>,
     #[serde(rename = "Task ID", deserialize_with = "csv::invalid_option")]
     task_name: Option<String>,
     #[serde(rename = "Start Time", deserialize_with = "parse_time")]
@@ -35,10 +84,15 @@ struct Record {
     end_time: NaiveTime,
     #[serde(rename = "Duration", deserialize_with = "from_time_string")]
     duration: TimeDelta,
+    #[serde(rename = "Tags", default, deserialize_with = "from_tag_list")]
+    tags: Vec<String>,
+    #[serde(rename = "Notes", default, deserialize_with = "csv::invalid_option")]
+    notes: Option<String>,
 }
 
 #[tokio::main]
 async fn main() {
+    dotenv().ok();
     let opts = process_options();
 
     if let Err(err) = run(&opts).await {
@@ -48,6 +102,31 @@ async fn main() {
 }
 
 async fn run(opts: &AppOptions) -> Result<(), Box<dyn Error>> {
+    let mut config = DbConfig::load(opts.config_file.as_deref().map(Path::new))?;
+    if let Some(db_name) = &opts.db_name {
+        config.url = db_name.clone();
+    }
+    if let Some(command) = &opts.timer {
+        let mut db = DbiDatabase::new(config).await?;
+        return timer::run_timer(command, &mut db).await;
+    }
+    if let Some(command) = &opts.transfer {
+        let mut db = DbiDatabase::new(config).await?;
+        return transfer::run_transfer(command, &mut db, opts.save_mode).await;
+    }
+    if let Some(command) = &opts.maintenance {
+        let mut db = DbiDatabase::new(config).await?;
+        return maintenance::run_maintenance(command, &mut db).await;
+    }
+    if let Some(command) = opts.schema {
+        let mut db = DbiDatabase::new(config.skip_migrations(true)).await?;
+        return schema::run_schema(command, &mut db).await;
+    }
+    if let Some(command) = &opts.sync {
+        let mut db = DbiDatabase::new(config.clone()).await?;
+        return sync::run_sync(command, &mut db, &config).await;
+    }
+
     let file = File::open(&opts.file)?;
     let mut reader = ReaderBuilder::new()
         .has_headers(opts.has_headers)
@@ -59,15 +138,36 @@ async fn run(opts: &AppOptions) -> Result<(), Box<dyn Error>> {
         // println!("{:?}", &record);
         records.push(record);
     }
-    let converted = convert_records(records)?;
-    save_records_to_database(converted, opts).await?;
+
+    let mut converted = convert_records(records, config.rounding)?;
+    let clients = match &opts.clients_file {
+        Some(path) => {
+            let clients = ClientMap::from_file(path)?;
+            clients.apply(&mut converted, config.rounding);
+            Some(clients)
+        }
+        None => None,
+    };
+    let billing = match &opts.billing_file {
+        Some(path) => Some(BillingFile::from_file(path)?),
+        None => None,
+    };
+    save_records_to_database(converted, clients.as_ref(), billing.as_ref(), config, opts).await?;
     Ok(())
 }
 
 /// Convert the CSV data into a hierarchy ready to put into the
-/// database.
+/// database. TotalPay is rounded to cents with `rounding`.
 ///
-fn convert_records(records: Vec<Record>) -> Result<Vec<Project>, Box<dyn Error>> {
+/// The Start and End Times are to the minute, so a record's Duration may
+/// be up to a minute either side of them. One that is further off is a
+/// Validation error naming the record, and nothing is converted.
+///
+#[allow(clippy::single_match, clippy::useless_conversion, clippy::clone_on_copy)]
+fn convert_records(
+    records: Vec<Record>,
+    rounding: RoundingMode,
+) -> Result<Vec<Project>, Box<dyn Error>> {
     let rec_iter = records.iter();
 
     let mut project: Project = Project::default();
@@ -77,12 +177,11 @@ fn convert_records(records: Vec<Record>) -> Result<Vec<Project>, Box<dyn Error>>
     let mut tflag = false;
     let mut all_projects: Vec<Project> = Vec::with_capacity(rec_iter.len());
 
-    for rec in rec_iter {
+    for (number, rec) in rec_iter.enumerate() {
         match &rec.project {
             Some(project_name) => {
                 if pflag {
-                    let pd = TimeDelta::milliseconds(project.project_duration);
-                    project.total_pay = (pd.num_minutes() as f64/60.0) * project.pay_rate;
+                    project.total_pay = total_pay(project.project_duration, project.pay_rate, rounding);
                     project.tasks.push(task);
                     all_projects.push(project);
                     task = ProjectTask::default();
@@ -93,7 +192,7 @@ fn convert_records(records: Vec<Record>) -> Result<Vec<Project>, Box<dyn Error>>
                 project = Project::default();
                 project.project_name = project_name.clone();
                 project.project_date = rec.date.unwrap().into();
-                project.pay_rate = rec.pay_rate.unwrap_or(0.0);
+                project.pay_rate = rec.pay_rate.unwrap_or_default();
                 let dt = project.project_date.format("%a %b %-d %C%y").to_string();
                 let value = format!("{}{}", &project.project_name, &dt);
                 project.project_id = make_uuid(&value);
@@ -110,7 +209,20 @@ fn convert_records(records: Vec<Record>) -> Result<Vec<Project>, Box<dyn Error>>
         let start_time = NaiveDateTime::new(project.project_date.into(), rec.start_time.clone());
         let end_time = NaiveDateTime::new(project.project_date.into(), rec.end_time.clone());
         let duration = end_time - start_time;
-        assert_eq!(duration.num_milliseconds(), rec.duration.num_milliseconds());
+        if (duration - rec.duration).num_seconds().abs() >= 60 {
+            return Err(DbError::Validation {
+                entity: "TaskTime",
+                id: format!("in CSV record {}", number + 1),
+                message: format!(
+                    "{} to {} is {} minutes, but its Duration is {}",
+                    rec.start_time.format("%-I:%M %p"),
+                    rec.end_time.format("%-I:%M %p"),
+                    duration.num_minutes(),
+                    format_duration(rec.duration)
+                ),
+            }
+            .into());
+        }
         match &rec.task_name {
             Some(task_name) => {
                 if tflag {
@@ -136,13 +248,24 @@ fn convert_records(records: Vec<Record>) -> Result<Vec<Project>, Box<dyn Error>>
         task_time.end_time = end_time;
         task_time.task_id = task.task_id.clone();
         task.task_times.push(task_time);
+        if let Some(notes) = rec.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            // The notes of the task's rows, one per line
+            task.notes = Some(match task.notes.take() {
+                Some(earlier) => format!("{}\n{}", earlier, notes),
+                None => notes.to_string(),
+            });
+        }
+        for tag in &rec.tags {
+            if !task.tags.contains(tag) {
+                task.tags.push(tag.clone());
+            }
+        }
     }
-    let pd = TimeDelta::milliseconds(project.project_duration);
-    project.total_pay = (pd.num_minutes() as f64/60.0) * project.pay_rate;
+    project.total_pay = total_pay(project.project_duration, project.pay_rate, rounding);
     project.tasks.push(task);
     all_projects.push(project);
 
-    all_projects = combine_like_projects(all_projects);
+    all_projects = combine_like_projects(all_projects, rounding);
 
     // println!("+++++++++++++++++++++++++++++++++++++++++++++++");
     // println!("{:#?}", &all_projects);
@@ -155,23 +278,58 @@ fn convert_records(records: Vec<Record>) -> Result<Vec<Project>, Box<dyn Error>>
 ///
 async fn save_records_to_database(
     projects: Vec<Project>,
+    clients: Option<&ClientMap>,
+    billing: Option<&BillingFile>,
+    config: DbConfig,
     opts: &AppOptions,
 ) -> Result<(), Box<dyn Error>> {
-    let mut inserted: u64 = 0;
+    let mut report = SaveReport::default();
+
+    let mut db = DbiDatabase::new(config).await?;
+    if let Some(clients) = clients {
+        clients.save(&mut db).await?;
+    }
+    match opts.atomicity {
+        Atomicity::File => {
+            report = models::add_projects(&projects, &mut db, opts.save_mode).await?;
+        }
+        Atomicity::Project => {
+            for project in &projects {
+                match models::add_project(project, &mut db, opts.save_mode).await {
+                    Ok(saved) => report.add(saved),
+                    Err(DbError::Duplicate { .. }) => println!(
+                        "Project {} on {} was already imported, use --replace to update it",
+                        project.project_name, project.project_date
+                    ),
+                    Err(error) => println!(
+                        "Project {} on {} was not saved: {}",
+                        project.project_name, project.project_date, error
+                    ),
+                }
+            }
+        }
+    }
+    for locked in &report.locked {
+        println!("{locked}");
+    }
+    println!("Read and inserted {} projects", report.saved);
 
-    let config = DbConfig::new(&opts.db_name);
-    let mut db = DbiDatabase::new(config).await.unwrap();
-    for project in projects {
-        let x = models::add_project(&project, &mut db).await.unwrap();
-        inserted += x;
+    if let Some(billing) = billing {
+        billing.save(&projects, &mut db).await?;
+    }
+    // Projects with a billing rule, from this run or an earlier one, are
+    // paid what the rule charges
+    let billed = db.bill().await?;
+    if billed > 0 {
+        println!("Billed {billed} projects by their billing rules");
     }
-    println!("Read and inserted {inserted} projects");
     Ok(())
 }
 
 ///
 /// Parse the options from the command line arguments
 ///
+#[allow(clippy::field_reassign_with_default)]
 pub fn process_options() -> AppOptions {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
@@ -185,11 +343,40 @@ pub fn process_options() -> AppOptions {
     );
     opts.optflag("n", "no-headers", "Indicate the file does not have headers");
     opts.optflag("h", "help", "print this help menu");
+    opts.optflag(
+        "r",
+        "replace",
+        "Replace the tasks and times of projects that were imported before",
+    );
+    opts.optopt(
+        "a",
+        "atomic",
+        "Save each project in its own transaction, or the whole file in one (default: project)",
+        "project|file",
+    );
     opts.optopt(
         "d",
         "database",
-        "The path and name of the sqlite3 database file",
-        "<database>",
+        "The sqlite3 database file, or a postgres:// URL",
+        "<database>|<url>",
+    );
+    opts.optopt(
+        "c",
+        "config",
+        "A TOML file with a [database] table; MV_DB_* variables override it",
+        "<file>",
+    );
+    opts.optopt(
+        "b",
+        "billing",
+        "A TOML file of billing rules for clients or projects",
+        "<file>",
+    );
+    opts.optopt(
+        "m",
+        "clients",
+        "A CSV file mapping project names to clients: Project,Client,Pay Rate",
+        "<file>",
     );
 
     let matches = match opts.parse(&args[1..]) {
@@ -208,7 +395,64 @@ pub fn process_options() -> AppOptions {
     app_opts.has_headers = true;
     let file = matches.opt_str("f");
 
-    if let Some(fname) = file {
+    if matches.free.first().map(String::as_str) == Some("timer") {
+        match TimerCommand::parse(&matches.free[1..]) {
+            Ok(command) => app_opts.timer = Some(command),
+            Err(message) => {
+                println!("{message}");
+                print_usage(&program, opts);
+                std::process::exit(1);
+            }
+        }
+    } else if matches!(
+        matches.free.first().map(String::as_str),
+        Some("export") | Some("import")
+    ) {
+        match TransferCommand::parse(&matches.free) {
+            Ok(command) => app_opts.transfer = Some(command),
+            Err(message) => {
+                println!("{message}");
+                print_usage(&program, opts);
+                std::process::exit(1);
+            }
+        }
+    } else if matches!(
+        matches.free.first().map(String::as_str),
+        Some("backup") | Some("restore") | Some("check") | Some("compact")
+    ) {
+        match MaintenanceCommand::parse(&matches.free) {
+            Ok(command) => app_opts.maintenance = Some(command),
+            Err(message) => {
+                println!("{message}");
+                print_usage(&program, opts);
+                std::process::exit(1);
+            }
+        }
+    } else if matches!(
+        matches.free.first().map(String::as_str),
+        Some("merge") | Some("sync")
+    ) {
+        match SyncCommand::parse(&matches.free) {
+            Ok(command) => app_opts.sync = Some(command),
+            Err(message) => {
+                println!("{message}");
+                print_usage(&program, opts);
+                std::process::exit(1);
+            }
+        }
+    } else if matches!(
+        matches.free.first().map(String::as_str),
+        Some("migrations") | Some("migrate")
+    ) {
+        match SchemaCommand::parse(&matches.free) {
+            Ok(command) => app_opts.schema = Some(command),
+            Err(message) => {
+                println!("{message}");
+                print_usage(&program, opts);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(fname) = file {
         app_opts.file = fname;
     } else if !matches.free.is_empty() {
         app_opts.file = matches.free[0].clone();
@@ -217,27 +461,43 @@ pub fn process_options() -> AppOptions {
         std::process::exit(1);
     }
 
-    let db = matches.opt_str("d");
-    if let Some(db_file) = db {
-        app_opts.db_name = db_file;
-    } else {
-        app_opts.db_name = "sqlite::memory:".to_string();
-    }
+    app_opts.db_name = matches.opt_str("d");
+    app_opts.config_file = matches.opt_str("c");
+    app_opts.clients_file = matches.opt_str("m");
+    app_opts.billing_file = matches.opt_str("b");
 
     if matches.opt_present("n") {
         app_opts.has_headers = false;
     }
 
+    if matches.opt_present("r") {
+        app_opts.save_mode = SaveMode::Replace;
+    }
+
+    match matches.opt_str("a").as_deref() {
+        None | Some("project") => app_opts.atomicity = Atomicity::Project,
+        Some("file") => app_opts.atomicity = Atomicity::File,
+        Some(other) => {
+            println!("Unknown atomicity '{other}', expected 'project' or 'file'");
+            print_usage(&program, opts);
+            std::process::exit(1);
+        }
+    }
+
     app_opts
 }
 
 fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} FILE [options]", program);
+    let brief = format!(
+        "Usage: {0} FILE [options]\n       {0} timer start <project> <task> | stop | cancel | status [options]\n       {0} export|import <file.json|file.ndjson> [options]\n       {0} backup <dir> [<keep>] | restore <file> | check | compact [options]\n       {0} merge|sync <database> [ours|theirs|fail] [<report.json>] [options]\n       {0} migrations | migrate [<version>|latest] [options]",
+        program
+    );
     print!("{}", opts.usage(&brief));
 }
 
 ///
-/// Convert a string in "00:11:00" (HH:MM:SS) format to a TimeDelta
+/// Convert a string in "00:11:00" (HH:MM:SS) format to a TimeDelta. The
+/// seconds are kept; rounding billed time is left to the billing rules.
 ///
 fn from_time_string<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
 where
@@ -250,16 +510,26 @@ where
     let mm: i64 = dur[1].parse().unwrap_or_default();
     let ss: i64 = dur[2].parse().unwrap_or_default();
 
-    let mut minutes = (hh * 60) + mm;
-    if ss > 0 {
-        minutes += 1;
-    }
-    Ok(TimeDelta::minutes(minutes))
+    Ok(TimeDelta::seconds((hh * 60 + mm) * 60 + ss))
+}
+
+///
+/// A duration as the CSV writes it, H:MM:SS
+///
+fn format_duration(duration: TimeDelta) -> String {
+    let seconds = duration.num_seconds();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
 }
 
 ///
 /// Convert a string in "MM/DD/YYYY" format to a TimeDelta
 ///
+#[allow(clippy::expect_fun_call, clippy::needless_borrow)]
 fn from_date_string<'de, D>(deserializer: D) -> Result<Option<NaiveDate>, D::Error>
 where
     D: Deserializer<'de>,
@@ -275,6 +545,38 @@ where
     Ok(Some(date_value))
 }
 
+///
+/// Convert a pay rate such as "35" or "42.50" to Money, keeping every
+/// digit. An empty field is None.
+///
+pub(crate) fn from_money_string<'de, D>(deserializer: D) -> Result<Option<Money>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: &str = Deserialize::deserialize(deserializer)?;
+    if s.trim().is_empty() {
+        return Ok(None);
+    }
+    s.parse()
+        .map(Some)
+        .map_err(|_| serde::de::Error::custom(format!("Unable to parse pay rate {s}")))
+}
+
+///
+/// Split a list of tags such as "meetings; Travel" on semicolons. The
+/// names are trimmed and lower cased, and empty ones are dropped.
+///
+fn from_tag_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: &str = Deserialize::deserialize(deserializer)?;
+    Ok(s.split(';')
+        .map(Tag::normalize)
+        .filter(|tag| !tag.is_empty())
+        .collect())
+}
+
 fn parse_time<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
 where
     D: Deserializer<'de>,
@@ -286,3 +588,49 @@ where
         NaiveTime::from_hms_opt(t.hour().into(), t.minute().into(), t.second().into()).unwrap();
     Ok(nt)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_records(csv: &str) -> Vec<Record> {
+        ReaderBuilder::new()
+            .from_reader(csv.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_duration_with_seconds() {
+        let records = read_records(
+            "Date,Project,Pay Rate,Task ID,Start Time,End Time,Duration\n\
+             10/1/2024,Diamond,35,Design,9:30 AM,11:00 AM,1:29:30\n",
+        );
+        assert_eq!(records[0].duration, TimeDelta::seconds(5370));
+
+        let projects = convert_records(records, RoundingMode::default()).unwrap();
+        let task = &projects[0].tasks[0];
+        assert_eq!(
+            task.task_duration,
+            TimeDelta::minutes(90).num_milliseconds()
+        );
+        assert_eq!(
+            task.task_times[0].end_time - task.task_times[0].start_time,
+            TimeDelta::minutes(90)
+        );
+    }
+
+    #[test]
+    fn test_duration_not_matching_times() {
+        let records = read_records(
+            "Date,Project,Pay Rate,Task ID,Start Time,End Time,Duration\n\
+             10/1/2024,Diamond,35,Design,9:30 AM,11:00 AM,1:30:00\n\
+             ,,,,11:00 AM,11:30 AM,0:45:00\n",
+        );
+        let error = convert_records(records, RoundingMode::default()).unwrap_err();
+        let error = error.downcast_ref::<DbError>().unwrap();
+        assert!(matches!(error, DbError::Validation { .. }));
+        assert_eq!(error.id(), Some("in CSV record 2"));
+    }
+}
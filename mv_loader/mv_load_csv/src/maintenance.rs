@@ -0,0 +1,88 @@
+use mv_dbi::DbiDatabase;
+use std::error::Error;
+use std::path::PathBuf;
+
+/// How many backups `backup <dir>` keeps when no count is given
+const DEFAULT_KEEP: usize = 7;
+
+///
+/// What `mv_load_csv backup|restore|check|compact` was asked to do. These
+/// work on SQLite databases only.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaintenanceCommand {
+    /// Add a timestamped backup to the directory, keeping the newest `keep`
+    Backup { dir: PathBuf, keep: usize },
+    /// Replace the database with a backup, once it has been checked
+    Restore(PathBuf),
+    /// Run the integrity and foreign key checks
+    Check,
+    /// Give back the space left by deleted rows
+    Compact,
+}
+
+impl MaintenanceCommand {
+    /// Parse `backup <dir> [<keep>]`, `restore <file>`, `check` or `compact`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["backup", dir] => Ok(MaintenanceCommand::Backup {
+                dir: dir.into(),
+                keep: DEFAULT_KEEP,
+            }),
+            ["backup", dir, keep] => match keep.parse() {
+                Ok(keep) if keep > 0 => Ok(MaintenanceCommand::Backup {
+                    dir: dir.into(),
+                    keep,
+                }),
+                _ => Err(format!(
+                    "Bad backup count '{keep}', expected a number above 0"
+                )),
+            },
+            ["restore", file] => Ok(MaintenanceCommand::Restore(file.into())),
+            ["check"] => Ok(MaintenanceCommand::Check),
+            ["compact"] => Ok(MaintenanceCommand::Compact),
+            _ => Err(format!(
+                "Unknown command '{}', expected 'backup <dir> [<keep>]', 'restore <file>', 'check' or 'compact'",
+                args.join(" ")
+            )),
+        }
+    }
+}
+
+pub async fn run_maintenance(
+    command: &MaintenanceCommand,
+    db: &mut DbiDatabase,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        MaintenanceCommand::Backup { dir, keep } => {
+            let path = db.backup_rotating(dir, *keep).await?;
+            println!("Backed up to {}", path.display());
+        }
+        MaintenanceCommand::Restore(file) => {
+            let safety = db.restore_from(file).await?;
+            println!(
+                "Restored from {}, the database as it was is in {}",
+                file.display(),
+                safety.display()
+            );
+        }
+        MaintenanceCommand::Check => {
+            let report = db.check_integrity().await?;
+            println!("{}", report);
+            if !report.is_ok() {
+                return Err(format!(
+                    "Found {} problems and {} missing parents",
+                    report.problems.len(),
+                    report.foreign_keys.len()
+                )
+                .into());
+            }
+        }
+        MaintenanceCommand::Compact => {
+            db.compact().await?;
+            println!("Compacted the database");
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,13 @@
-use chrono::{NaiveDateTime, TimeDelta};
-use mv_dbi::{model::{project, project_task, task_time}, DataObject, DbiDatabase};
+use chrono::NaiveDateTime;
+use mv_dbi::{
+    model::{
+        project, project_task,
+        project_tree::{ProjectTree, SaveMode, SaveReport, TaskTree},
+        task_time,
+    },
+    utils::total_pay,
+    DbiDatabase, Money, RoundingMode,
+};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use std::collections::HashMap;
@@ -9,9 +17,10 @@ pub struct Project {
     pub project_id: Uuid,
     pub project_name: String,
     pub project_date: NaiveDateTime,
-    pub pay_rate: f64,
+    pub pay_rate: Money,
     pub project_duration: i64,
-    pub total_pay: f64,
+    pub total_pay: Money,
+    pub client_id: Option<Uuid>,
     pub tasks: Vec<ProjectTask>,
 }
 
@@ -23,6 +32,8 @@ pub struct ProjectTask {
     pub task_duration: i64,
     pub task_date_time: NaiveDateTime,
     pub task_times: Vec<TaskTime>,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
@@ -34,64 +45,83 @@ pub struct TaskTime {
 }
 
 ///
-/// Prepare and save a Project to the database
+/// Convert a CSV Project, with its tasks and times, into the database
+/// hierarchy
 ///
-pub async fn add_project(
-    csv_project: &Project,
-    dbi: &mut DbiDatabase,
-) -> Result<u64, anyhow::Error> {
-    let mut db_project: project::Project = project::Project::default();
+pub fn to_project_tree(csv_project: &Project) -> ProjectTree {
+    let project = project::Project {
+        project_id: csv_project.project_id,
+        project_name: csv_project.project_name.clone(),
+        project_date: csv_project.project_date.into(),
+        pay_rate: csv_project.pay_rate,
+        project_duration: csv_project.project_duration,
+        total_pay: csv_project.total_pay,
+        client_id: csv_project.client_id,
+        ..Default::default()
+    };
 
-    db_project.project_id = csv_project.project_id;
-    db_project.project_date = csv_project.project_date.into();
-    db_project.project_name = csv_project.project_name.clone();
-    db_project.project_duration = csv_project.project_duration;
-    db_project.pay_rate = csv_project.pay_rate;
-    db_project.total_pay = csv_project.total_pay;
+    let tasks = csv_project
+        .tasks
+        .iter()
+        .map(|csv_task| TaskTree {
+            task: project_task::ProjectTask {
+                task_id: csv_task.task_id,
+                project_id: csv_task.project_id,
+                task_name: csv_task.task_name.clone(),
+                task_duration: csv_task.task_duration,
+                task_date_time: csv_task.task_date_time,
+                notes: csv_task.notes.clone(),
+            },
+            task_times: csv_task
+                .task_times
+                .iter()
+                .map(|csv_time| task_time::TaskTime {
+                    task_time_id: 0,
+                    task_id: csv_time.task_id,
+                    start_time: csv_time.start_time,
+                    end_time: Some(csv_time.end_time),
+                    invoice_id: None,
+                })
+                .collect(),
+            tags: csv_task.tags.clone(),
+        })
+        .collect();
 
-    let mut dao = DataObject::Project(db_project.clone());
-    let result = dbi.do_insert(&mut dao).await;
-    match result {
-        Ok(code) => {
-            add_project_task(&csv_project.tasks, dbi).await?;
-            return Ok(code);
-        }
-        Err(error) => return Err(anyhow::Error::new(error)),
-    }
+    ProjectTree { project, tasks }
 }
 
-async fn add_project_task(tasks: &Vec<ProjectTask>, dbi: &mut DbiDatabase) -> Result<u64, anyhow::Error> {
-
-    for csv_task in tasks {
-        let mut task = project_task::ProjectTask::default();
-        task.task_id = csv_task.task_id;
-        task.project_id = csv_task.project_id;
-        task.task_name = csv_task.task_name.clone();
-        task.task_date_time = csv_task.task_date_time;
-        task.task_duration = csv_task.task_duration;
-
-        let mut dao = DataObject::ProjectTask(task);
-        let _result = dbi.do_insert(&mut dao).await?;
-        let _result = add_task_times(&csv_task.task_times, dbi).await?;
-    }
-    Ok(0)
+///
+/// Prepare and save a Project, its tasks and their times to the database
+/// in one transaction
+///
+pub async fn add_project(
+    csv_project: &Project,
+    dbi: &mut DbiDatabase,
+    mode: SaveMode,
+) -> Result<SaveReport, mv_dbi::Error> {
+    add_projects(std::slice::from_ref(csv_project), dbi, mode).await
 }
 
-async fn add_task_times(times: &Vec<TaskTime>, dbi: &mut DbiDatabase) -> Result<u64, anyhow::Error> {
-
-    for csv_time in times {
-        let mut task_time = task_time::TaskTime::default();
-        task_time.task_time_id = 0;
-        task_time.task_id = csv_time.task_id;
-        task_time.start_time = csv_time.start_time;
-        task_time.end_time = csv_time.end_time;
-        let mut dao = DataObject::TaskTime(task_time);
-        let _result = dbi.do_insert(&mut dao).await?;
-    }
-    Ok(0)
+///
+/// Prepare and save several Projects in one transaction. Either all of
+/// them are saved or none are.
+///
+/// With `SaveMode::Replace` a project that was imported before has its
+/// tasks and times replaced, so re-running an import is harmless. Times
+/// that have been invoiced are kept, and returned in the report if the
+/// CSV changes them.
+///
+pub async fn add_projects(
+    csv_projects: &[Project],
+    dbi: &mut DbiDatabase,
+    mode: SaveMode,
+) -> Result<SaveReport, mv_dbi::Error> {
+    let trees: Vec<ProjectTree> = csv_projects.iter().map(to_project_tree).collect();
+    dbi.save_project_trees_with_report(&trees, mode).await
 }
 
-pub fn combine_like_projects(all_projects: Vec<Project>) -> Vec<Project>{
+#[allow(clippy::iter_kv_map, clippy::unnecessary_sort_by)]
+pub fn combine_like_projects(all_projects: Vec<Project>, rounding: RoundingMode) -> Vec<Project>{
     let mut projects_map: HashMap<Uuid, Project> = HashMap::with_capacity(all_projects.capacity());
 
     for project in all_projects {
@@ -102,8 +132,7 @@ pub fn combine_like_projects(all_projects: Vec<Project>) -> Vec<Project>{
                 saved.project_duration += task.task_duration;
                 saved.tasks.push(task.clone());
             }
-            let pd = TimeDelta::milliseconds(saved.project_duration);
-            saved.total_pay = (pd.num_minutes() as f64/60.0) * saved.pay_rate;
+            saved.total_pay = total_pay(saved.project_duration, saved.pay_rate, rounding);
             continue;
         }
         projects_map.insert(key, project);
@@ -0,0 +1,50 @@
+use mv_dbi::{schema, DbiDatabase};
+use std::error::Error;
+
+///
+/// What `mv_load_csv migrations|migrate` was asked to do. The database is
+/// opened without running migrations, so these see it as it is.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaCommand {
+    /// List the applied and pending migrations
+    Status,
+    /// Apply or roll back migrations to a version, or to the latest one
+    MigrateTo(Option<i64>),
+}
+
+impl SchemaCommand {
+    /// Parse `migrations` or `migrate [<version>]`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["migrations"] => Ok(SchemaCommand::Status),
+            ["migrate"] | ["migrate", "latest"] => Ok(SchemaCommand::MigrateTo(None)),
+            ["migrate", version] => match version.parse() {
+                Ok(version) if version >= 0 => Ok(SchemaCommand::MigrateTo(Some(version))),
+                _ => Err(format!(
+                    "Bad schema version '{version}', expected 0, 'latest' or a migration version"
+                )),
+            },
+            _ => Err(format!(
+                "Unknown command '{}', expected 'migrations' or 'migrate [<version>]'",
+                args.join(" ")
+            )),
+        }
+    }
+}
+
+pub async fn run_schema(
+    command: SchemaCommand,
+    db: &mut DbiDatabase,
+) -> Result<(), Box<dyn Error>> {
+    let status = match command {
+        SchemaCommand::Status => db.schema_status().await?,
+        SchemaCommand::MigrateTo(version) => {
+            let version = version.unwrap_or_else(|| schema::latest_version(db.backend()));
+            db.migrate_to(version).await?
+        }
+    };
+    println!("{}", status);
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use mv_dbi::{sync::ConflictPolicy, DbConfig, DbiDatabase};
+use serde::Serialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+///
+/// What `mv_load_csv merge|sync <database>` was asked to do. `other` is
+/// an SQLite file or a postgres:// URL, opened with the same settings as
+/// the database being merged into.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncCommand {
+    /// True to write the merged rows back into `other` as well
+    pub two_way: bool,
+    pub other: String,
+    pub policy: ConflictPolicy,
+    /// Where to write the report as JSON, besides printing it
+    pub report: Option<PathBuf>,
+}
+
+impl SyncCommand {
+    /// Parse `merge <database> [<policy>] [<report.json>]` or the same
+    /// with `sync`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let (two_way, rest) = match args.split_first() {
+            Some((cmd, rest)) if cmd == "merge" => (false, rest),
+            Some((cmd, rest)) if cmd == "sync" => (true, rest),
+            _ => return Err(Self::usage(args)),
+        };
+        let (other, policy, report) = match rest {
+            [other] => (other, None, None),
+            [other, policy] => (other, Some(policy), None),
+            [other, policy, report] => (other, Some(policy), Some(report)),
+            _ => return Err(Self::usage(args)),
+        };
+        let policy = match policy {
+            Some(policy) => policy.parse().map_err(|e: mv_dbi::Error| e.to_string())?,
+            None => ConflictPolicy::default(),
+        };
+        Ok(SyncCommand {
+            two_way,
+            other: other.clone(),
+            policy,
+            report: report.map(PathBuf::from),
+        })
+    }
+
+    fn usage(args: &[String]) -> String {
+        format!(
+            "Unknown command '{}', expected 'merge|sync <database> [ours|theirs|fail] [<report.json>]'",
+            args.join(" ")
+        )
+    }
+}
+
+pub async fn run_sync(
+    command: &SyncCommand,
+    db: &mut DbiDatabase,
+    config: &DbConfig,
+) -> Result<(), Box<dyn Error>> {
+    let mut other = DbiDatabase::new(DbConfig {
+        url: command.other.clone(),
+        ..config.clone()
+    })
+    .await?;
+    let applied = if command.two_way {
+        let report = db.sync_with(&mut other, command.policy).await?;
+        print!("{}", report);
+        write_report(command.report.as_deref(), &report)?;
+        report.pulled.applied
+    } else {
+        let report = db.merge_from(&mut other, command.policy).await?;
+        print!("{}", report);
+        write_report(command.report.as_deref(), &report)?;
+        report.applied
+    };
+    if !applied {
+        return Err("Rows conflict, nothing was merged".into());
+    }
+    Ok(())
+}
+
+fn write_report(path: Option<&Path>, report: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = path {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, report)?;
+        println!("Wrote the report to {}", path.display());
+    }
+    Ok(())
+}
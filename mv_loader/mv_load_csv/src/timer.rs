@@ -0,0 +1,145 @@
+use chrono::{Local, NaiveDateTime, TimeDelta, Timelike};
+use mv_dbi::{
+    database::filter::{QueryFilter, SortOrder},
+    model::project::Project,
+    utils::make_uuid,
+    DbiDatabase, Error as DbError,
+};
+use std::error::Error;
+
+///
+/// What `mv_load_csv timer ...` was asked to do
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimerCommand {
+    /// Start timing a task of today's project with the name
+    Start { project: String, task: String },
+    /// Stop the running timer
+    Stop,
+    /// Throw away the running timer
+    Cancel,
+    /// Show the running timer
+    Status,
+}
+
+impl TimerCommand {
+    /// Parse the words after `timer`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        match args {
+            [cmd, project, task] if cmd == "start" => Ok(TimerCommand::Start {
+                project: project.clone(),
+                task: task.clone(),
+            }),
+            [cmd] if cmd == "stop" => Ok(TimerCommand::Stop),
+            [cmd] if cmd == "cancel" => Ok(TimerCommand::Cancel),
+            [] => Ok(TimerCommand::Status),
+            [cmd] if cmd == "status" => Ok(TimerCommand::Status),
+            _ => Err(format!(
+                "Unknown timer command '{}', expected 'start <project> <task>', 'stop', 'cancel' or 'status'",
+                args.join(" ")
+            )),
+        }
+    }
+}
+
+pub async fn run_timer(command: &TimerCommand, db: &mut DbiDatabase) -> Result<(), Box<dyn Error>> {
+    // To the second, like the times read from the CSV files
+    let now = Local::now().naive_local().with_nanosecond(0).unwrap();
+    match command {
+        TimerCommand::Start { project, task } => {
+            if let Some(running) = db.running_timer().await? {
+                println!(
+                    "{} on {} is already running, stop it first",
+                    running.task_name, running.project_name
+                );
+                return Ok(());
+            }
+            let project = todays_project(project, now, db).await?;
+            let started = db.start_timer_at(project.project_id, task, now).await?;
+            println!(
+                "Started {} on {} at {}",
+                task,
+                project.project_name,
+                started.start_time.format("%H:%M")
+            );
+        }
+        TimerCommand::Stop => match db.running_timer().await? {
+            Some(running) => match db.stop_timer_at(now).await {
+                Ok(_) => println!(
+                    "Stopped {} on {} after {}",
+                    running.task_name,
+                    running.project_name,
+                    format_elapsed(running.elapsed(now))
+                ),
+                Err(DbError::Validation { message, .. }) => println!(
+                    "{} on {} was not stopped: {}. Use 'timer cancel' to throw it away",
+                    running.task_name, running.project_name, message
+                ),
+                Err(error) => return Err(error.into()),
+            },
+            None => println!("No timer is running"),
+        },
+        TimerCommand::Cancel => match db.cancel_timer().await? {
+            Some(running) => println!(
+                "Cancelled {} on {}, started {}",
+                running.task_name,
+                running.project_name,
+                running.start_time.format("%Y-%m-%d %H:%M")
+            ),
+            None => println!("No timer is running"),
+        },
+        TimerCommand::Status => match db.running_timer().await? {
+            Some(running) => println!(
+                "{} on {} running since {} ({})",
+                running.task_name,
+                running.project_name,
+                running.start_time.format("%Y-%m-%d %H:%M"),
+                format_elapsed(running.elapsed(now))
+            ),
+            None => println!("No timer is running"),
+        },
+    }
+    Ok(())
+}
+
+///
+/// Today's project with the name, keyed the way imported projects are.
+/// A new one takes its pay rate, client and currency from the latest
+/// project with the same name.
+///
+async fn todays_project(
+    name: &str,
+    now: NaiveDateTime,
+    db: &mut DbiDatabase,
+) -> Result<Project, Box<dyn Error>> {
+    let today = now.date();
+    let dt = today.format("%a %b %-d %C%y").to_string();
+    let mut project = Project {
+        project_id: make_uuid(&format!("{}{}", name, &dt)),
+        ..Default::default()
+    };
+    match db.fetch_one(&mut project).await {
+        Ok(()) => return Ok(project),
+        Err(DbError::NotFound { .. }) => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    let filter = QueryFilter::new()
+        .project_name(name)
+        .order(SortOrder::Descending);
+    let latest: Vec<Project> = db.fetch_filtered(&filter).await?;
+    let latest = latest.into_iter().find(|p| p.project_name == name);
+    project.project_name = name.to_string();
+    project.project_date = today;
+    if let Some(latest) = latest {
+        project.pay_rate = latest.pay_rate;
+        project.client_id = latest.client_id;
+        project.currency = latest.currency;
+    }
+    db.insert(&project).await?;
+    Ok(project)
+}
+
+fn format_elapsed(elapsed: TimeDelta) -> String {
+    format!("{}:{:02}", elapsed.num_hours(), elapsed.num_minutes() % 60)
+}
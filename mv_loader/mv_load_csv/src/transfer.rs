@@ -0,0 +1,68 @@
+use mv_dbi::{export::ExportFormat, model::project_tree::SaveMode, DbiDatabase};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+///
+/// What `mv_load_csv export|import <file>` was asked to do. Files ending
+/// in .ndjson or .jsonl are NDJSON, anything else is JSON.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferCommand {
+    /// Write the whole database to the file
+    Export(PathBuf),
+    /// Load the file written by an export
+    Import(PathBuf),
+}
+
+impl TransferCommand {
+    /// Parse `export <file>` or `import <file>`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        match args {
+            [cmd, path] if cmd == "export" => Ok(TransferCommand::Export(path.into())),
+            [cmd, path] if cmd == "import" => Ok(TransferCommand::Import(path.into())),
+            _ => Err(format!(
+                "Unknown command '{}', expected 'export <file>' or 'import <file>'",
+                args.join(" ")
+            )),
+        }
+    }
+}
+
+pub async fn run_transfer(
+    command: &TransferCommand,
+    db: &mut DbiDatabase,
+    mode: SaveMode,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        TransferCommand::Export(path) => {
+            let format = ExportFormat::for_path(path);
+            let writer = BufWriter::new(File::create(path)?);
+            let written = db.export(format, writer).await?;
+            println!(
+                "Exported {} projects to {}",
+                written,
+                describe(path, format)
+            );
+        }
+        TransferCommand::Import(path) => {
+            let format = ExportFormat::for_path(path);
+            let reader = BufReader::new(File::open(path)?);
+            let report = db.import(format, reader, mode).await?;
+            for locked in &report.locked {
+                println!("{}", locked);
+            }
+            println!(
+                "Imported {} projects from {}",
+                report.saved,
+                describe(path, format)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn describe(path: &Path, format: ExportFormat) -> String {
+    format!("{} ({})", path.display(), format.as_str())
+}